@@ -0,0 +1,183 @@
+use {
+    crate::error::Route53IpUpdateError,
+    schemars::JsonSchema,
+    serde::{Deserialize, Serialize},
+    std::{
+        fmt::{Display, Formatter, Result as FmtResult},
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        str::FromStr,
+    },
+};
+
+/// The class of addresses that may be published to a zone.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AddressClass {
+    /// Only globally routable addresses.
+    Routable,
+
+    /// Only non-routable addresses (RFC 1918, ULA, link-local, etc.), e.g. for a private hosted zone.
+    Nonroutable,
+
+    /// Any address.
+    Any,
+}
+
+impl AddressClass {
+    pub fn allows_address(&self, addr: &IpAddr) -> bool {
+        match self {
+            Self::Routable => is_routable(addr),
+            Self::Nonroutable => !is_routable(addr),
+            Self::Any => true,
+        }
+    }
+}
+
+/// IPv4 ranges that aren't globally reachable, from the IANA IPv4 Special-Purpose Address Registry, plus multicast.
+const NONROUTABLE_IPV4: [(Ipv4Addr, u8); 14] = [
+    (Ipv4Addr::new(0, 0, 0, 0), 8),       // "This network"
+    (Ipv4Addr::new(10, 0, 0, 0), 8),      // Private-use (RFC 1918)
+    (Ipv4Addr::new(100, 64, 0, 0), 10),   // Shared address space (carrier-grade NAT)
+    (Ipv4Addr::new(127, 0, 0, 0), 8),     // Loopback
+    (Ipv4Addr::new(169, 254, 0, 0), 16),  // Link-local
+    (Ipv4Addr::new(172, 16, 0, 0), 12),   // Private-use (RFC 1918)
+    (Ipv4Addr::new(192, 0, 0, 0), 24),    // IETF protocol assignments
+    (Ipv4Addr::new(192, 0, 2, 0), 24),    // Documentation (TEST-NET-1)
+    (Ipv4Addr::new(192, 168, 0, 0), 16),  // Private-use (RFC 1918)
+    (Ipv4Addr::new(198, 18, 0, 0), 15),   // Benchmarking
+    (Ipv4Addr::new(198, 51, 100, 0), 24), // Documentation (TEST-NET-2)
+    (Ipv4Addr::new(203, 0, 113, 0), 24),  // Documentation (TEST-NET-3)
+    (Ipv4Addr::new(224, 0, 0, 0), 4),     // Multicast
+    (Ipv4Addr::new(240, 0, 0, 0), 4),     // Reserved, including the limited broadcast address
+];
+
+/// IPv4 ranges within `NONROUTABLE_IPV4` that are globally reachable anyway.
+const ROUTABLE_IPV4_EXCEPTIONS: [(Ipv4Addr, u8); 2] = [
+    (Ipv4Addr::new(192, 0, 0, 9), 32),  // Port Control Protocol anycast
+    (Ipv4Addr::new(192, 0, 0, 10), 32), // TURN anycast
+];
+
+/// IPv6 ranges that aren't globally reachable, from the IANA IPv6 Special-Purpose Address Registry, plus multicast
+/// and the deprecated site-local range. IPv4-mapped addresses are handled separately.
+const NONROUTABLE_IPV6: [(Ipv6Addr, u8); 11] = [
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 128),         // Unspecified
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1), 128),         // Loopback
+    (Ipv6Addr::new(0x64, 0xff9b, 1, 0, 0, 0, 0, 0), 48),  // Local-use IPv4/IPv6 translation
+    (Ipv6Addr::new(0x100, 0, 0, 0, 0, 0, 0, 0), 64),      // Discard-only
+    (Ipv6Addr::new(0x2001, 0, 0, 0, 0, 0, 0, 0), 23),     // IETF protocol assignments
+    (Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), 32), // Documentation
+    (Ipv6Addr::new(0x3fff, 0, 0, 0, 0, 0, 0, 0), 20),     // Documentation
+    (Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 0), 7),      // Unique local
+    (Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), 10),     // Link-local
+    (Ipv6Addr::new(0xfec0, 0, 0, 0, 0, 0, 0, 0), 10),     // Site-local (deprecated)
+    (Ipv6Addr::new(0xff00, 0, 0, 0, 0, 0, 0, 0), 8),      // Multicast
+];
+
+/// IPv6 ranges within `NONROUTABLE_IPV6` that are globally reachable anyway.
+const ROUTABLE_IPV6_EXCEPTIONS: [(Ipv6Addr, u8); 6] = [
+    (Ipv6Addr::new(0x2001, 1, 0, 0, 0, 0, 0, 1), 128), // Port Control Protocol anycast
+    (Ipv6Addr::new(0x2001, 1, 0, 0, 0, 0, 0, 2), 128), // TURN anycast
+    (Ipv6Addr::new(0x2001, 3, 0, 0, 0, 0, 0, 0), 32),  // AMT
+    (Ipv6Addr::new(0x2001, 4, 0x112, 0, 0, 0, 0, 0), 48), // AS112-v6
+    (Ipv6Addr::new(0x2001, 0x20, 0, 0, 0, 0, 0, 0), 28), // ORCHIDv2
+    (Ipv6Addr::new(0x2001, 0x30, 0, 0, 0, 0, 0, 0), 28), // Drone remote ID protocol entity tags
+];
+
+/// Indicates whether an address is a globally routable unicast address, i.e. one that can usefully be published in a
+/// public zone.
+pub(crate) fn is_routable(addr: &IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(addr) => is_routable_ipv4(addr),
+        IpAddr::V6(addr) => match addr.to_ipv4_mapped() {
+            // IPv4-mapped addresses are routable if the IPv4 address is.
+            Some(addr) => is_routable_ipv4(&addr),
+            None => is_routable_ipv6(addr),
+        },
+    }
+}
+
+fn is_routable_ipv4(addr: &Ipv4Addr) -> bool {
+    let addr = u32::from(*addr);
+    let in_range = |(network, prefix_len): &(Ipv4Addr, u8)| {
+        let mask = u32::MAX.checked_shl(32 - u32::from(*prefix_len)).unwrap_or(0);
+        addr & mask == u32::from(*network)
+    };
+
+    !NONROUTABLE_IPV4.iter().any(in_range) || ROUTABLE_IPV4_EXCEPTIONS.iter().any(in_range)
+}
+
+fn is_routable_ipv6(addr: &Ipv6Addr) -> bool {
+    let addr = u128::from(*addr);
+    let in_range = |(network, prefix_len): &(Ipv6Addr, u8)| {
+        let mask = u128::MAX.checked_shl(128 - u32::from(*prefix_len)).unwrap_or(0);
+        addr & mask == u128::from(*network)
+    };
+
+    !NONROUTABLE_IPV6.iter().any(in_range) || ROUTABLE_IPV6_EXCEPTIONS.iter().any(in_range)
+}
+
+impl Display for AddressClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Routable => write!(f, "routable"),
+            Self::Nonroutable => write!(f, "nonroutable"),
+            Self::Any => write!(f, "any"),
+        }
+    }
+}
+
+impl FromStr for AddressClass {
+    type Err = Route53IpUpdateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "routable" => Ok(Self::Routable),
+            "nonroutable" => Ok(Self::Nonroutable),
+            "any" => Ok(Self::Any),
+            _ => Err(Route53IpUpdateError::InvalidAddressClass(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routable(addr: &str) -> bool {
+        is_routable(&addr.parse().unwrap())
+    }
+
+    #[test]
+    fn public_addresses_are_routable() {
+        for addr in ["1.1.1.1", "8.8.8.8", "100.128.0.1", "192.0.0.9", "2606:4700:4700::1111", "2001:4:112::1"] {
+            assert!(routable(addr), "{addr} should be routable");
+        }
+    }
+
+    #[test]
+    fn special_purpose_addresses_are_not_routable() {
+        for addr in [
+            "0.0.0.0",
+            "10.1.2.3",
+            "100.64.0.1",
+            "127.0.0.1",
+            "169.254.1.1",
+            "172.31.255.255",
+            "192.0.0.8",
+            "192.0.2.1",
+            "192.168.1.1",
+            "198.19.0.1",
+            "224.0.0.1",
+            "255.255.255.255",
+            "::",
+            "::1",
+            "::ffff:10.0.0.1",
+            "2001:db8::1",
+            "fd00::1",
+            "fe80::1",
+            "ff02::1",
+        ] {
+            assert!(!routable(addr), "{addr} should not be routable");
+        }
+    }
+}
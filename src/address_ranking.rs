@@ -0,0 +1,239 @@
+use {
+    crate::{
+        address_class::is_routable,
+        address_source::{AddressSource, DiscoveredAddress},
+        config::Config,
+        error::Route53IpUpdateError,
+        query_interfaces::get_address_lifetimes,
+    },
+    schemars::JsonSchema,
+    serde::{Deserialize, Serialize},
+    std::{
+        cmp::Reverse,
+        collections::{BTreeSet, HashMap, HashSet},
+        fmt::{Display, Formatter, Result as FmtResult},
+        net::IpAddr,
+        str::FromStr,
+        time::Duration,
+    },
+};
+
+/// How the address to publish is chosen when `single-address` is set and several addresses of a family are candidates.
+/// Whichever is chosen, routable addresses are preferred over non-routable ones, and ties are broken by taking the
+/// numerically lowest address.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum AddressRanking {
+    /// The address reported by the IP service (how the rest of the world sees us) over those on the interfaces.
+    #[default]
+    IpService,
+
+    /// The interface address with the longest preferred lifetime, e.g. a stable SLAAC address over a temporary one.
+    Lifetime,
+
+    /// The numerically lowest address.
+    Lowest,
+
+    /// The address last published to the zone, if it's still a candidate.
+    Previous,
+}
+
+impl Display for AddressRanking {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::IpService => write!(f, "ip-service"),
+            Self::Lifetime => write!(f, "lifetime"),
+            Self::Lowest => write!(f, "lowest"),
+            Self::Previous => write!(f, "previous"),
+        }
+    }
+}
+
+impl FromStr for AddressRanking {
+    type Err = Route53IpUpdateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ip-service" => Ok(Self::IpService),
+            "lifetime" => Ok(Self::Lifetime),
+            "lowest" => Ok(Self::Lowest),
+            "previous" => Ok(Self::Previous),
+            _ => Err(Route53IpUpdateError::InvalidAddressRanking(s.to_string())),
+        }
+    }
+}
+
+/// Chooses the addresses to publish to each zone from the candidates discovered for it.
+#[derive(Debug, Default)]
+pub(crate) struct AddressRanker {
+    /// Whether only the best address of each family is published.
+    single_address: bool,
+
+    ranking: AddressRanking,
+
+    /// Whether the address last published is kept while it's still a candidate, whatever the ranking.
+    sticky: bool,
+
+    /// The preferred lifetimes of the interface addresses, for the `lifetime` ranking.
+    lifetimes: HashMap<IpAddr, Duration>,
+}
+
+impl AddressRanker {
+    /// Returns the ranker for the configured `single-address`, `address-ranking`, and `sticky-address` settings,
+    /// looking up the interface address lifetimes if they're needed.
+    pub(crate) async fn new(config: &Config) -> Self {
+        let lifetimes = if config.single_address && config.address_ranking == AddressRanking::Lifetime {
+            get_address_lifetimes(config.interfaces_timeout()).await
+        } else {
+            HashMap::new()
+        };
+
+        Self {
+            single_address: config.single_address,
+            ranking: config.address_ranking,
+            sticky: config.sticky_address,
+            lifetimes,
+        }
+    }
+
+    /// Returns the addresses to publish from the candidates of one family: all of them, or only the best with
+    /// `single-address`. `previous` holds the addresses last published to the zone, if known.
+    pub(crate) fn select<'a>(
+        &self,
+        candidates: impl IntoIterator<Item = &'a DiscoveredAddress>,
+        previous: Option<&BTreeSet<IpAddr>>,
+    ) -> HashSet<IpAddr> {
+        let candidates = candidates.into_iter();
+        if self.single_address {
+            self.best(candidates, previous).into_iter().collect()
+        } else {
+            candidates.map(|addr| addr.address).collect()
+        }
+    }
+
+    /// Returns the best of the candidates according to the ranking. With `sticky`, an address published before beats
+    /// any other that's as routable, so the records don't churn between equally good addresses.
+    fn best<'a>(
+        &self,
+        candidates: impl Iterator<Item = &'a DiscoveredAddress>,
+        previous: Option<&BTreeSet<IpAddr>>,
+    ) -> Option<IpAddr> {
+        let was_published =
+            |addr: &DiscoveredAddress| previous.is_some_and(|previous| previous.contains(&addr.address));
+        candidates
+            .min_by_key(|addr| {
+                (
+                    !is_routable(&addr.address),
+                    !(self.sticky && was_published(addr)),
+                    self.rank(addr, previous),
+                    addr.address,
+                )
+            })
+            .map(|addr| addr.address)
+    }
+
+    /// Returns how a candidate ranks, lower being better.
+    fn rank(&self, addr: &DiscoveredAddress, previous: Option<&BTreeSet<IpAddr>>) -> Reverse<u64> {
+        let score = match self.ranking {
+            AddressRanking::IpService => u64::from(addr.source == AddressSource::IpService),
+            AddressRanking::Lifetime => self.lifetimes.get(&addr.address).map_or(0, Duration::as_secs),
+            AddressRanking::Lowest => 0,
+            AddressRanking::Previous => u64::from(previous.is_some_and(|previous| previous.contains(&addr.address))),
+        };
+
+        Reverse(score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{AddressRanker, AddressRanking},
+        crate::{
+            address_source::{AddressSource, DiscoveredAddress},
+            config::Config,
+        },
+        std::{
+            collections::{BTreeSet, HashMap, HashSet},
+            net::IpAddr,
+            time::Duration,
+        },
+    };
+
+    fn discovered(address: &str, source: AddressSource) -> DiscoveredAddress {
+        DiscoveredAddress {
+            address: address.parse().unwrap(),
+            source,
+        }
+    }
+
+    fn ranker(ranking: AddressRanking) -> AddressRanker {
+        AddressRanker {
+            single_address: true,
+            ranking,
+            sticky: false,
+            lifetimes: HashMap::from([
+                ("2606:4700::1".parse().unwrap(), Duration::from_secs(600)),
+                ("2606:4700::2".parse().unwrap(), Duration::from_secs(86400)),
+            ]),
+        }
+    }
+
+    fn best(ranking: AddressRanking, candidates: &[DiscoveredAddress], previous: &[&str]) -> HashSet<IpAddr> {
+        let previous: BTreeSet<IpAddr> = previous.iter().map(|addr| addr.parse().unwrap()).collect();
+        ranker(ranking).select(candidates, Some(&previous))
+    }
+
+    #[test]
+    fn each_ranking_picks_its_best_address() {
+        let candidates = [
+            discovered("fd00::9", AddressSource::Interfaces),
+            discovered("2606:4700::1", AddressSource::Interfaces),
+            discovered("2606:4700::2", AddressSource::Interfaces),
+            discovered("2606:4700::3", AddressSource::IpService),
+        ];
+        let one = |addr: &str| HashSet::from([addr.parse().unwrap()]);
+
+        assert_eq!(best(AddressRanking::IpService, &candidates, &[]), one("2606:4700::3"));
+        assert_eq!(best(AddressRanking::Lifetime, &candidates, &[]), one("2606:4700::2"));
+        assert_eq!(best(AddressRanking::Lowest, &candidates, &[]), one("2606:4700::1"));
+        assert_eq!(best(AddressRanking::Previous, &candidates, &["2606:4700::2"]), one("2606:4700::2"));
+        assert_eq!(best(AddressRanking::Previous, &candidates, &["fd00::9"]), one("2606:4700::1"));
+        assert_eq!(best(AddressRanking::Lowest, &candidates[..1], &[]), one("fd00::9"));
+    }
+
+    #[test]
+    fn sticky_keeps_the_previous_address_while_its_a_candidate() {
+        let candidates = [
+            discovered("fd00::9", AddressSource::Interfaces),
+            discovered("2606:4700::1", AddressSource::Interfaces),
+            discovered("2606:4700::3", AddressSource::IpService),
+        ];
+        let previous =
+            |addresses: &[&str]| -> BTreeSet<IpAddr> { addresses.iter().map(|a| a.parse().unwrap()).collect() };
+        let ranker = AddressRanker {
+            sticky: true,
+            ..ranker(AddressRanking::IpService)
+        };
+        let one = |addr: &str| HashSet::from([addr.parse().unwrap()]);
+
+        assert_eq!(ranker.select(&candidates, Some(&previous(&["2606:4700::1"]))), one("2606:4700::1"));
+        assert_eq!(ranker.select(&candidates, Some(&previous(&["2606:4700::2"]))), one("2606:4700::3"));
+        // A non-routable address isn't kept over a routable one.
+        assert_eq!(ranker.select(&candidates, Some(&previous(&["fd00::9"]))), one("2606:4700::3"));
+    }
+
+    #[tokio::test]
+    async fn every_address_is_published_without_single_address() {
+        let candidates =
+            [discovered("1.1.1.1", AddressSource::Interfaces), discovered("8.8.8.8", AddressSource::IpService)];
+        let config = Config {
+            single_address: false,
+            address_ranking: AddressRanking::Lifetime,
+            sticky_address: true,
+            ..Config::default()
+        };
+        let ranker = AddressRanker::new(&config).await;
+        assert_eq!(ranker.select(&candidates, None).len(), 2);
+    }
+}
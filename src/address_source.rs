@@ -0,0 +1,55 @@
+use {
+    crate::error::Route53IpUpdateError,
+    schemars::JsonSchema,
+    serde::{Deserialize, Serialize},
+    std::{
+        fmt::{Display, Formatter, Result as FmtResult},
+        net::IpAddr,
+        str::FromStr,
+    },
+};
+
+/// Where an address was discovered.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum AddressSource {
+    /// The addresses assigned to the local network interfaces.
+    Interfaces,
+
+    /// The address reported by the IP service.
+    IpService,
+}
+
+impl Display for AddressSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Interfaces => write!(f, "interfaces"),
+            Self::IpService => write!(f, "ip-service"),
+        }
+    }
+}
+
+impl FromStr for AddressSource {
+    type Err = Route53IpUpdateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "interfaces" => Ok(Self::Interfaces),
+            "ip-service" => Ok(Self::IpService),
+            _ => Err(Route53IpUpdateError::InvalidAddressSource(s.to_string())),
+        }
+    }
+}
+
+/// An address along with the source it was discovered from.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub(crate) struct DiscoveredAddress {
+    pub(crate) address: IpAddr,
+    pub(crate) source: AddressSource,
+}
+
+impl Display for DiscoveredAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} ({})", self.address, self.source)
+    }
+}
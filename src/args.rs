@@ -1,9 +1,24 @@
 use {
-    crate::{config::Config, error::Route53IpUpdateError, query_address_type::QueryAddressType, ttl::Ttl},
-    clap::{builder::ArgAction, Parser},
+    crate::{
+        address_ranking::AddressRanking,
+        config::{Config, HostnameConfig},
+        config_file::{load_config_files, ConfigFormat},
+        error::Route53IpUpdateError,
+        hostname_template::expand_hostname_placeholders,
+        query_address_type::QueryAddressType,
+        ttl::Ttl,
+    },
+    clap::{builder::ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand},
+    clap_complete::Shell,
     humantime::Duration,
-    std::path::Path,
-    tokio::{fs::File, io::copy},
+    std::{
+        env::args_os,
+        ffi::OsString,
+        fs,
+        io::{read_to_string, stdin},
+        net::SocketAddr,
+        path::{Path, PathBuf},
+    },
     tower::BoxError,
 };
 
@@ -15,78 +30,760 @@ pub(crate) struct Args {
     pub(crate) address_type: Option<QueryAddressType>,
 
     /// Whether non-routable addresses should be allowed to be used. If unspecified on the command-line and config file, defaults to false.
-    #[arg(short = 'n', long = "allow-nonroutable")]
+    #[arg(
+        short = 'n',
+        long = "allow-nonroutable",
+        num_args = 0,
+        default_missing_value = "true",
+        overrides_with = "no_allow_nonroutable"
+    )]
     pub(crate) allow_nonroutable: Option<bool>,
 
-    /// The config file to read, if any.
-    #[arg(short = 'c', long = "config-file")]
-    pub config_file: Option<String>,
+    /// Turns off --allow-nonroutable, overriding the config file.
+    #[arg(
+        long = "no-allow-nonroutable",
+        num_args = 0,
+        default_missing_value = "false",
+        overrides_with = "allow_nonroutable"
+    )]
+    pub(crate) no_allow_nonroutable: Option<bool>,
+
+    /// Whether to publish only the best IPv4 and IPv6 address to each zone instead of all of them, chosen according to --address-ranking. If unspecified on the command-line and config file, defaults to false.
+    #[arg(long = "single-address", num_args = 0, default_missing_value = "true", overrides_with = "no_single_address")]
+    pub(crate) single_address: Option<bool>,
+
+    /// Turns off --single-address, overriding the config file.
+    #[arg(
+        long = "no-single-address",
+        num_args = 0,
+        default_missing_value = "false",
+        overrides_with = "single_address"
+    )]
+    pub(crate) no_single_address: Option<bool>,
+
+    /// How the address published with --single-address is chosen: ip-service prefers the address reported by the IP service, lifetime the interface address with the longest preferred lifetime, lowest the numerically lowest address, and previous the address last published. Routable addresses are always preferred, and ties go to the lowest address. If unspecified on the command-line and config file, defaults to ip-service.
+    #[arg(long = "address-ranking")]
+    pub(crate) address_ranking: Option<AddressRanking>,
+
+    /// Whether --single-address keeps publishing the address published before while it's still a candidate, rather than switching to another one that's just as routable, avoiding needless record changes on hosts with several addresses. If unspecified on the command-line and config file, defaults to false.
+    #[arg(long = "sticky-address", num_args = 0, default_missing_value = "true", overrides_with = "no_sticky_address")]
+    pub(crate) sticky_address: Option<bool>,
+
+    /// Turns off --sticky-address, overriding the config file.
+    #[arg(
+        long = "no-sticky-address",
+        num_args = 0,
+        default_missing_value = "false",
+        overrides_with = "sticky_address"
+    )]
+    pub(crate) no_sticky_address: Option<bool>,
+
+    /// The config files or directories to read, if any. This may be repeated; later files override settings from earlier ones and add to their zones and hostnames. Directories are expanded to the config files they contain, in name order.
+    #[arg(short = 'c', long = "config-file", action = ArgAction::Append)]
+    pub config_files: Vec<String>,
+
+    /// An age identity file to decrypt config files encrypted with age (named e.g. config.yaml.age) or SOPS with. If unspecified, the file named by SOPS_AGE_KEY_FILE is used for age; SOPS also finds its other keys itself.
+    #[arg(long = "age-identity")]
+    pub(crate) age_identity: Option<PathBuf>,
+
+    /// Print the effective configuration (config files merged with command-line overrides and defaults) as json,
+    /// toml, or yaml, then exit. Defaults to yaml if no format is given.
+    #[arg(long = "print-config", value_name = "FORMAT", num_args = 0..=1, default_missing_value = "yaml")]
+    pub(crate) print_config: Option<ConfigFormat>,
+
+    /// Log less: once shows only warnings and errors, twice only errors, three times nothing. This overrides RUST_LOG.
+    /// There is no -q short form; -q is --query-interfaces.
+    #[arg(long = "quiet", action = ArgAction::Count, global = true)]
+    pub(crate) quiet: u8,
+
+    /// Log more: once adds debugging messages, twice adds tracing messages and debugging messages from libraries,
+    /// three times logs everything. This overrides RUST_LOG.
+    #[arg(short = 'v', long = "verbose", action = ArgAction::Count, global = true)]
+    pub(crate) verbose: u8,
 
     /// Whether interfaces should be queried for their addresses. If unspecified on the command-line and config file, defaults to false.
-    #[arg(short = 'q', long = "query-interfaces")]
+    #[arg(
+        short = 'q',
+        long = "query-interfaces",
+        num_args = 0,
+        default_missing_value = "true",
+        overrides_with = "no_query_interfaces"
+    )]
     pub(crate) query_interfaces: Option<bool>,
 
+    /// Turns off --query-interfaces, overriding the config file.
+    #[arg(
+        long = "no-query-interfaces",
+        num_args = 0,
+        default_missing_value = "false",
+        overrides_with = "query_interfaces"
+    )]
+    pub(crate) no_query_interfaces: Option<bool>,
+
     /// Whether the IP service should be queried for the current IP address. If unspecified on the command-line and config file, defaults to true.
-    #[arg(short = 'Q', long = "query-ip-service")]
+    #[arg(
+        short = 'Q',
+        long = "query-ip-service",
+        num_args = 0,
+        default_missing_value = "true",
+        overrides_with = "no_query_ip_service"
+    )]
     pub(crate) query_ip_service: Option<bool>,
 
-    /// Interfaces to ignore while querying.
-    #[arg(short = 'I', long = "ignore-interfaces", action = ArgAction::Append)]
+    /// Turns off --query-ip-service, overriding the config file.
+    #[arg(
+        long = "no-query-ip-service",
+        num_args = 0,
+        default_missing_value = "false",
+        overrides_with = "query_ip_service"
+    )]
+    pub(crate) no_query_ip_service: Option<bool>,
+
+    /// Interfaces to ignore while querying, separated by commas. This may be repeated.
+    #[arg(short = 'I', long = "ignore-interfaces", action = ArgAction::Append, value_delimiter = ',')]
     pub(crate) ignore_interfaces: Vec<String>,
 
     /// The service to query for the current IP address. If unspecified on the command-line and config file, defaults to https://api64.ipify.org.
     #[arg(short = 's', long = "ip-service")]
     pub(crate) ip_service: Option<String>,
 
+    /// How many more times to query the IP service if a query fails, before giving up on discovery. If unspecified on the command-line and config file, defaults to 2.
+    #[arg(long = "ip-service-retries")]
+    pub(crate) ip_service_retries: Option<u32>,
+
+    /// How long to wait before the first retry of the IP service, doubling with each retry after that, e.g. 1s. If unspecified on the command-line and config file, defaults to 1 second.
+    #[arg(long = "ip-service-backoff")]
+    pub(crate) ip_service_backoff: Option<Duration>,
+
+    /// Whether this host is IPv6-only behind a NAT64, so the IP service is queried for the public IPv4 address through the NAT64 and an A record can still be published. If unspecified on the command-line and config file, defaults to false.
+    #[arg(long = "nat64", num_args = 0, default_missing_value = "true", overrides_with = "no_nat64")]
+    pub(crate) nat64: Option<bool>,
+
+    /// Turns off --nat64, overriding the config file.
+    #[arg(long = "no-nat64", num_args = 0, default_missing_value = "false", overrides_with = "nat64")]
+    pub(crate) no_nat64: Option<bool>,
+
+    /// The NAT64 prefix to use with --nat64, e.g. 64:ff9b::/96, such as one advertised in router advertisements (the PREF64 option). If unspecified on the command-line and config file, it's discovered from the resolver by looking up ipv4only.arpa (RFC 7050).
+    #[arg(long = "nat64-prefix")]
+    pub(crate) nat64_prefix: Option<String>,
+
+    /// Whether to classify the NAT in front of this host (none, full cone, restricted cone, symmetric, or carrier-grade) with STUN, warning when the published IPv4 address may not reach this host. If unspecified on the command-line and config file, defaults to false.
+    #[arg(long = "detect-nat", num_args = 0, default_missing_value = "true", overrides_with = "no_detect_nat")]
+    pub(crate) detect_nat: Option<bool>,
+
+    /// Turns off --detect-nat, overriding the config file.
+    #[arg(long = "no-detect-nat", num_args = 0, default_missing_value = "false", overrides_with = "detect_nat")]
+    pub(crate) no_detect_nat: Option<bool>,
+
+    /// A STUN server (host:port) to use with --detect-nat. Several may be given separated by commas, and this may be repeated; two or more are needed to detect symmetric NAT. If unspecified on the command-line and config file, defaults to stun.l.google.com:19302 and stun.cloudflare.com:3478.
+    #[arg(long = "stun-server", action = ArgAction::Append, value_delimiter = ',')]
+    pub(crate) stun_servers: Vec<String>,
+
     /// The timeout to allow for the IP service to respond. If unspecified on the command-line and config file, defaults to 10 seconds. This may be specified as a duration with units, e.g. 10s, 1m, etc.
     #[arg(short = 't', long = "timeout")]
     pub(crate) timeout: Option<Duration>,
 
-    /// The time-to-live to apply to new records, in seconds.
+    /// The most Route 53 API calls to make per second, across all zones, so large configurations stay under Route 53's limit of five requests per second per account; 0 for no limit. If unspecified on the command-line and config file, defaults to 5.
+    #[arg(long = "route53-rate-limit")]
+    pub(crate) route53_rate_limit: Option<f64>,
+
+    /// The timeout to allow for listing the network interfaces' addresses. If unspecified on the command-line and config file, defaults to --timeout.
+    #[arg(long = "interfaces-timeout")]
+    pub(crate) interfaces_timeout: Option<Duration>,
+
+    /// The timeout to allow for each query of the IP service. If unspecified on the command-line and config file, defaults to --timeout.
+    #[arg(long = "ip-service-timeout")]
+    pub(crate) ip_service_timeout: Option<Duration>,
+
+    /// The timeout to allow for each Route 53 operation, including its retries. If unspecified on the command-line and config file, the AWS SDK's own limits apply.
+    #[arg(long = "route53-timeout")]
+    pub(crate) route53_timeout: Option<Duration>,
+
+    /// The timeout to allow for each STUN server to reply. If unspecified on the command-line and config file, defaults to --timeout.
+    #[arg(long = "stun-timeout")]
+    pub(crate) stun_timeout: Option<Duration>,
+
+    /// The time-to-live to apply to new records. This may be specified in seconds or as a duration with units, e.g. 300, 5m, 1h, etc.
     #[arg(short = 'T', long = "ttl")]
     pub(crate) ttl: Option<Ttl>,
 
-    /// The Route 53 zone to update. If you need to update more than one Route 53 zone, use the config file.
-    #[arg(short = 'r', long = "route53-zone")]
-    pub(crate) route53_zone: Option<String>,
+    /// The smallest TTL allowed in the configuration. If unspecified on the command-line and config file, defaults to 30 seconds.
+    #[arg(long = "min-ttl")]
+    pub(crate) min_ttl: Option<Ttl>,
+
+    /// The largest TTL allowed in the configuration. If unspecified on the command-line and config file, defaults to 7 days.
+    #[arg(long = "max-ttl")]
+    pub(crate) max_ttl: Option<Ttl>,
+
+    /// Whether TTLs outside of the allowed range should be clamped (with a warning) instead of rejected. If unspecified on the command-line and config file, defaults to false.
+    #[arg(long = "clamp-ttl", num_args = 0, default_missing_value = "true", overrides_with = "no_clamp_ttl")]
+    pub(crate) clamp_ttl: Option<bool>,
+
+    /// Turns off --clamp-ttl, overriding the config file.
+    #[arg(long = "no-clamp-ttl", num_args = 0, default_missing_value = "false", overrides_with = "clamp_ttl")]
+    pub(crate) no_clamp_ttl: Option<bool>,
+
+    /// The AWS profile to use for credentials. If unspecified on the command-line and config file, the AWS_PROFILE environment variable or the default profile is used.
+    #[arg(long = "profile")]
+    pub(crate) profile: Option<String>,
+
+    /// The AWS region to use. If unspecified on the command-line and config file, the region is taken from the environment or the AWS profile.
+    #[arg(long = "region")]
+    pub(crate) region: Option<String>,
+
+    /// The endpoint URL to use for Route 53 instead of the AWS endpoint, e.g. http://localhost:4566 for LocalStack.
+    #[arg(long = "aws-endpoint-url")]
+    pub(crate) aws_endpoint_url: Option<String>,
+
+    /// The MFA token code to use when assuming a role that requires MFA. If unspecified, the MFA token command is run or the code is prompted for on the terminal.
+    #[arg(long = "mfa-token")]
+    pub(crate) mfa_token: Option<String>,
+
+    /// A command whose output is the MFA token code to use when assuming a role that requires MFA. Roles requiring MFA, including those assumed by an AWS profile with `mfa_serial` set, are assumed again with a new code shortly before their credentials expire, so a long-running updater needs this or a terminal to keep working.
+    #[arg(long = "mfa-token-command")]
+    pub(crate) mfa_token_command: Option<String>,
+
+    /// Whether to use the FIPS Route 53 endpoint. If unspecified on the command-line and config file, defaults to false.
+    #[arg(long = "use-fips", num_args = 0, default_missing_value = "true", overrides_with = "no_use_fips")]
+    pub(crate) use_fips: Option<bool>,
+
+    /// Turns off --use-fips, overriding the config file.
+    #[arg(long = "no-use-fips", num_args = 0, default_missing_value = "false", overrides_with = "use_fips")]
+    pub(crate) no_use_fips: Option<bool>,
+
+    /// Whether to use the dual-stack (IPv4 and IPv6) Route 53 endpoint. If unspecified on the command-line and config file, defaults to false.
+    #[arg(long = "use-dual-stack", num_args = 0, default_missing_value = "true", overrides_with = "no_use_dual_stack")]
+    pub(crate) use_dual_stack: Option<bool>,
+
+    /// Turns off --use-dual-stack, overriding the config file.
+    #[arg(
+        long = "no-use-dual-stack",
+        num_args = 0,
+        default_missing_value = "false",
+        overrides_with = "use_dual_stack"
+    )]
+    pub(crate) no_use_dual_stack: Option<bool>,
+
+    /// If specified, records that are about to change first have their TTL lowered to this value, and the old TTL is allowed to expire before the new addresses are published.
+    #[arg(long = "prechange-ttl")]
+    pub(crate) prechange_ttl: Option<Ttl>,
+
+    /// The template for the comment attached to Route 53 changes. The placeholders {hostnames}, {zone}, {timestamp}, {machine}, and {version} are expanded. If unspecified on the command-line and config file, defaults to "Route 53 update for {hostnames}".
+    #[arg(long = "change-comment")]
+    pub(crate) change_comment: Option<String>,
+
+    /// Show the planned changes for each zone and ask for confirmation before applying them. This requires a terminal.
+    #[arg(long = "interactive")]
+    pub(crate) interactive: bool,
+
+    /// Keep running and repeat the update at this interval instead of exiting after one update, e.g. 5m.
+    #[arg(long = "interval")]
+    pub(crate) interval: Option<Duration>,
+
+    /// Keep running and update at the times matched by this cron expression (minute, hour, day of month, month, day of week, in local time) instead of at a fixed interval, e.g. "*/5 6-23 * * *".
+    #[arg(long = "schedule")]
+    pub(crate) schedule: Option<String>,
+
+    /// Delay each scheduled update by a random amount of up to this long, e.g. 30s. This keeps a fleet of machines started together from all updating at the same moment.
+    #[arg(long = "jitter")]
+    pub(crate) jitter: Option<Duration>,
+
+    /// Don't change a hostname again until this long after it was last changed, e.g. 15m. This protects against a flapping address using up the Route 53 quota.
+    #[arg(long = "cooldown")]
+    pub(crate) cooldown: Option<Duration>,
+
+    /// When the discovered addresses differ from those discovered on the previous update, wait this long and discover them again, only publishing them if they haven't changed, e.g. 30s. This keeps brief glitches, such as a DHCP renegotiation, from causing two updates.
+    #[arg(long = "debounce")]
+    pub(crate) debounce: Option<Duration>,
+
+    /// Give up with exit status 10 if a run takes longer than this, e.g. 5m, so a hung propagation wait or stuck connection can't block the next run. When running continuously, each update is limited to this long instead.
+    #[arg(long = "max-runtime")]
+    pub(crate) max_runtime: Option<Duration>,
+
+    /// A file to remember when hostnames were last changed and which addresses were last published in, so the cooldown and the previous address ranking also apply across separate runs.
+    #[arg(long = "state-file")]
+    pub(crate) state_file: Option<PathBuf>,
+
+    /// A directory to save the Route 53 record sets about to be changed to before each change, in a timestamped file per zone, so they can be put back with the restore command.
+    #[arg(long = "snapshot-dir")]
+    pub(crate) snapshot_dir: Option<PathBuf>,
+
+    /// Whether to only check that the published records match the discovered addresses, without changing them. If they don't match, the drift webhook is notified and the exit status is 2. If unspecified on the command-line and config file, defaults to false.
+    #[arg(long = "monitor-only", num_args = 0, default_missing_value = "true", overrides_with = "no_monitor_only")]
+    pub(crate) monitor_only: Option<bool>,
+
+    /// Turns off --monitor-only, overriding the config file.
+    #[arg(long = "no-monitor-only", num_args = 0, default_missing_value = "false", overrides_with = "monitor_only")]
+    pub(crate) no_monitor_only: Option<bool>,
+
+    /// A URL to POST a JSON report to when --monitor-only finds records that don't match the discovered addresses.
+    #[arg(long = "drift-webhook")]
+    pub(crate) drift_webhook: Option<String>,
+
+    /// The name or ARN of an EventBridge event bus to put an event onto for each hostname whose records are changed.
+    #[arg(long = "event-bus")]
+    pub(crate) event_bus: Option<String>,
+
+    /// Whether to also maintain a _r53ipupdate.<hostname> TXT record for each hostname changed, holding when it was updated, the machine that updated it, and the version of this tool. If unspecified on the command-line and config file, defaults to false.
+    #[arg(
+        long = "timestamp-records",
+        num_args = 0,
+        default_missing_value = "true",
+        overrides_with = "no_timestamp_records"
+    )]
+    pub(crate) timestamp_records: Option<bool>,
+
+    /// Turns off --timestamp-records, overriding the config file.
+    #[arg(
+        long = "no-timestamp-records",
+        num_args = 0,
+        default_missing_value = "false",
+        overrides_with = "timestamp_records"
+    )]
+    pub(crate) no_timestamp_records: Option<bool>,
+
+    /// Whether to refresh the _r53ipupdate.<hostname> TXT records of all hostnames on every update, even when their addresses haven't changed, so monitors can tell the updater is still running. Implies --timestamp-records. If unspecified on the command-line and config file, defaults to false.
+    #[arg(long = "heartbeat", num_args = 0, default_missing_value = "true", overrides_with = "no_heartbeat")]
+    pub(crate) heartbeat: Option<bool>,
+
+    /// Turns off --heartbeat, overriding the config file.
+    #[arg(long = "no-heartbeat", num_args = 0, default_missing_value = "false", overrides_with = "heartbeat")]
+    pub(crate) no_heartbeat: Option<bool>,
+
+    /// A CloudWatch Logs group to also send log messages to, in a log stream named after this machine's hostname unless cloudwatch-logs.log-stream is set in the config file.
+    #[arg(long = "cloudwatch-log-group")]
+    pub(crate) cloudwatch_log_group: Option<String>,
+
+    /// A statsd server to send metrics about each update to over UDP, as host:port, e.g. 127.0.0.1:8125. The metric prefix and DogStatsD tags are set with statsd in the config file.
+    #[arg(long = "statsd")]
+    pub(crate) statsd: Option<String>,
+
+    /// A DynamoDB table to take a lock in for each zone while it's being updated, so redundant updaters don't submit conflicting changes at the same time. The table's partition key must be a string named lock-id.
+    #[arg(long = "lock-table")]
+    pub(crate) lock_table: Option<String>,
+
+    /// A DynamoDB table holding a leader's lease, so only one of several redundant updaters publishes addresses while the others stand by. The table's partition key must be a string named lock-id.
+    #[arg(long = "leader-election-table")]
+    pub(crate) leader_election_table: Option<String>,
+
+    /// Whether to remove the managed records when shutting down after running with --interval or --schedule, e.g. when a laptop suspends or a container stops. If unspecified on the command-line and config file, defaults to false.
+    #[arg(long = "remove-on-exit", num_args = 0, default_missing_value = "true", overrides_with = "no_remove_on_exit")]
+    pub(crate) remove_on_exit: Option<bool>,
+
+    /// Turns off --remove-on-exit, overriding the config file.
+    #[arg(
+        long = "no-remove-on-exit",
+        num_args = 0,
+        default_missing_value = "false",
+        overrides_with = "remove_on_exit"
+    )]
+    pub(crate) no_remove_on_exit: Option<bool>,
+
+    /// How many more times to run a single update from the start (discovery included) if it fails in a way worth retrying, e.g. a network outage or AWS throttling, before giving up. This doesn't apply with --interval or --schedule. If unspecified on the command-line and config file, defaults to 0.
+    #[arg(long = "retries")]
+    pub(crate) retries: Option<u32>,
+
+    /// How long to wait before the first retry of a single update, doubling with each retry after that, e.g. 1m. If unspecified on the command-line and config file, defaults to 30 seconds.
+    #[arg(long = "retry-backoff")]
+    pub(crate) retry_backoff: Option<Duration>,
+
+    /// The number of updates in a row that may fail before exiting with the status of the last one, when running with --interval or --schedule, so a service manager can restart route53-ip-update or raise an alert. If unspecified on the command-line and config file, failed updates never cause an exit.
+    #[arg(long = "max-consecutive-failures")]
+    pub(crate) max_consecutive_failures: Option<u32>,
+
+    /// The address and port to serve the status of updates on as JSON, e.g. 127.0.0.1:8246, when running with --interval or --schedule.
+    #[arg(long = "status-listen")]
+    pub(crate) status_listen: Option<SocketAddr>,
+
+    /// The Route 53 zone to update, either as a hosted zone ID or a domain name (e.g. example.com.). This may be repeated to update several zones, each followed by its hostnames given with --hostname, e.g. -r Z1 -H a.example.com -r Z2 -H b.example.net.
+    #[arg(short = 'r', long = "route53-zone", action = ArgAction::Append)]
+    pub(crate) route53_zones: Vec<String>,
+
+    /// A hostname to update in the Route 53 zone given with the --route53-zone before it, optionally followed by a colon and its TTL (e.g. home.example.com:120). Hostnames given before any --route53-zone are updated in the hosted zone discovered for each. Several may be given separated by commas, and this may be repeated.
+    #[arg(short = 'H', long = "hostname", value_name = "HOSTNAME", action = ArgAction::Append, value_delimiter = ',', value_parser = HostnameConfig::from_arg)]
+    pub(crate) hostname_options: Vec<HostnameConfig>,
+
+    /// A file to read more hostnames to update from, one per line, or - to read them from stdin. They're updated like the positional hostnames, and may also be followed by a colon and their TTL. Blank lines and lines starting with # are ignored. A positional hostname of - also reads hostnames from stdin.
+    #[arg(long = "hostnames-from")]
+    pub(crate) hostnames_from: Option<PathBuf>,
+
+    /// The hostnames to update in the last Route 53 zone given with --route53-zone, each optionally followed by a colon and its TTL (e.g. home.example.com:120). If no zone is specified, the hosted zone for each hostname is discovered automatically.
+    #[arg(value_parser = HostnameConfig::from_arg)]
+    pub(crate) hostnames: Vec<HostnameConfig>,
+
+    /// The zones given with --route53-zone, each with its hostnames. Hostnames without a zone are grouped under `None`.
+    #[arg(skip)]
+    pub(crate) zone_hostnames: Vec<(Option<String>, Vec<HostnameConfig>)>,
+
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub(crate) enum Command {
+    /// Print a JSON Schema describing the configuration file format, for use by editors and CI pipelines.
+    Schema,
+
+    /// Interactively create a config file by choosing zones, hostnames, TTLs, and address sources.
+    Init {
+        /// The config file to write.
+        #[arg(short = 'o', long = "output", default_value = "route53-ip-update.yaml")]
+        output: PathBuf,
+
+        /// Overwrite the config file if it already exists.
+        #[arg(long = "force")]
+        force: bool,
+    },
+
+    /// Check the configuration without any network access and exit, e.g. in CI before deploying config changes.
+    Validate,
+
+    /// Convert a ddclient configuration file into an equivalent route53-ip-update configuration.
+    ImportDdclient {
+        /// The ddclient configuration file to read.
+        #[arg(default_value = "/etc/ddclient.conf")]
+        input: PathBuf,
+
+        /// The config file to write. If unspecified, the configuration is written to standard output.
+        #[arg(short = 'o', long = "output")]
+        output: Option<PathBuf>,
+    },
+
+    /// Run a DynDNS2-compatible server (the /nic/update endpoint) that applies updates sent by routers and other
+    /// clients to the configured zones.
+    Serve {
+        /// The address and port to listen on. If unspecified on the command-line and config file, defaults to
+        /// 0.0.0.0:8245.
+        #[arg(short = 'l', long = "listen")]
+        listen: Option<SocketAddr>,
+    },
+
+    /// Print a least-privilege IAM policy (JSON) allowing the configured zones to be updated. Zones given by name and
+    /// top-level hostnames are looked up in Route 53 first, so this needs credentials that can list hosted zones.
+    IamPolicy,
+
+    /// Remove the A and AAAA records of hostnames whose _r53ipupdate.<hostname> timestamp records show they haven't been
+    /// updated within --max-age, e.g. those of laptops that were retired. Only zones hosted on Route 53 are examined.
+    Gc {
+        /// How long since a hostname was last updated before its records are removed.
+        #[arg(long = "max-age", default_value = "30d")]
+        max_age: Duration,
+
+        /// Only print the records that would be removed.
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+    },
+
+    /// Put the record sets saved in a snapshot file (see --snapshot-dir) back the way they were: those that existed are
+    /// restored, and those created since are removed.
+    Restore {
+        /// The snapshot file to restore.
+        snapshot: PathBuf,
+
+        /// Only print the changes that would be made.
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+    },
+
+    /// Revert the most recent change to each hostname, using the snapshots in --snapshot-dir as the change history.
+    Undo {
+        /// The hostnames whose most recent change is reverted.
+        #[arg(required = true)]
+        hostnames: Vec<String>,
+
+        /// Only print the changes that would be made.
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+    },
+
+    /// Publish the addresses queued in --state-file by updates that couldn't reach AWS, without discovering the addresses
+    /// again.
+    Flush,
+
+    /// Exit successfully only if the daemon brought every zone up-to-date recently, for use as a container's
+    /// HEALTHCHECK command. The daemon's status endpoint (see --status-listen) is asked if one is configured; otherwise,
+    /// the state file (see --state-file) is read.
+    Healthcheck {
+        /// How recently the last successful sync must have been. If unspecified, defaults to three times --interval, or
+        /// three times the longest wait between the updates of the schedule, or 1h if neither is set.
+        #[arg(long = "max-age")]
+        max_age: Option<Duration>,
+
+        /// The URL of the status endpoint to ask. If unspecified, it's worked out from --status-listen.
+        #[arg(long = "url")]
+        url: Option<String>,
+    },
 
-    /// The hostnames to update in the Route 53 zone.
-    pub(crate) hostnames: Vec<String>,
+    /// Print a shell completion script. If config files are given with -c, the zones they configure are offered as
+    /// completions for --route53-zone.
+    Completions {
+        /// The shell to generate completions for.
+        shell: Shell,
+    },
+
+    /// Print a man page in roff format, covering the command-line options and the configuration file keys.
+    #[command(hide = true)]
+    Man,
 }
 
 impl Args {
-    pub async fn into_config(self) -> Result<Config, BoxError> {
-        let mut config = if let Some(config_file) = &self.config_file {
-            let config_path = Path::new(config_file);
-            let mut file = File::open(&config_path).await?;
-            let ext = match config_path.extension() {
-                None => None,
-                Some(ext) => ext.to_str(),
-            };
-            let Some(ext) = ext else {
-                return Err(Route53IpUpdateError::UnknownConfigFileExt(None).into());
-            };
-
-            match ext {
-                "toml" => {
-                    let mut file_contents = Vec::new();
-                    copy(&mut file, &mut file_contents).await?;
-                    toml::from_slice::<Config>(&file_contents)?
-                }
-                "json" | "yaml" | "yml" => {
-                    let mut file_contents = Vec::new();
-                    copy(&mut file, &mut file_contents).await?;
-                    serde_yaml::from_slice::<Config>(&file_contents)?
-                }
-                _ => {
-                    return Err(Route53IpUpdateError::UnknownConfigFileExt(Some(ext.to_string())).into());
-                }
+    /// Parses the command line, grouping each --hostname with the --route53-zone given before it.
+    pub(crate) fn parse_grouped() -> Self {
+        Self::parse_grouped_from(args_os())
+    }
+
+    fn parse_grouped_from<I, T>(itr: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Self::command().get_matches_from(itr);
+        let mut args = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.format(&mut Self::command()).exit());
+
+        // Clap keeps the values of each option apart, so their positions are needed to tell which zone a hostname
+        // follows.
+        fn indexed<T: Clone + Send + Sync + 'static>(matches: &ArgMatches, id: &str) -> Vec<(usize, T)> {
+            let indices = matches.indices_of(id).into_iter().flatten();
+            indices.zip(matches.get_many::<T>(id).into_iter().flatten().cloned()).collect()
+        }
+
+        args.zone_hostnames = group_zone_hostnames(
+            &indexed(&matches, "route53_zones"),
+            indexed(&matches, "hostname_options"),
+            args.hostnames.clone(),
+        );
+        args
+    }
+
+    /// Replaces a hostname of `-` with the hostnames read from stdin, and adds the hostnames read from --hostnames-from
+    /// to the positional hostnames. This is done once, at startup, since stdin can only be read once.
+    pub(crate) fn read_hostname_lists(&mut self) -> Result<(), BoxError> {
+        let stdin_path = Path::new("-");
+        let stdin_requested = self.zone_hostnames.iter().flat_map(|(_, hostnames)| hostnames).any(is_stdin);
+        let stdin_hostnames = if stdin_requested || self.hostnames_from.as_deref() == Some(stdin_path) {
+            let list = read_to_string(stdin()).map_err(|e| format!("Unable to read hostnames from stdin: {e}"))?;
+            parse_hostname_list(&list)?
+        } else {
+            Vec::new()
+        };
+
+        for (_, hostnames) in &mut self.zone_hostnames {
+            if hostnames.iter().any(is_stdin) {
+                *hostnames = hostnames
+                    .drain(..)
+                    .flat_map(|hostname| {
+                        if is_stdin(&hostname) {
+                            stdin_hostnames.clone()
+                        } else {
+                            vec![hostname]
+                        }
+                    })
+                    .collect();
             }
+        }
+
+        let Some(path) = &self.hostnames_from else {
+            return Ok(());
+        };
+
+        let hostnames = if path == stdin_path {
+            stdin_hostnames
         } else {
+            let list = fs::read_to_string(path)
+                .map_err(|e| format!("Unable to read hostnames from {}: {e}", path.display()))?;
+            parse_hostname_list(&list)?
+        };
+
+        // Like the positional hostnames, these belong to the last zone given, whose group is always last.
+        if self.zone_hostnames.is_empty() {
+            self.zone_hostnames.push((None, Vec::new()));
+        }
+        if let Some((_, last)) = self.zone_hostnames.last_mut() {
+            last.extend(hostnames);
+        }
+
+        Ok(())
+    }
+
+    pub async fn into_config(self) -> Result<Config, BoxError> {
+        let mut config = if self.config_files.is_empty() {
             Config::default()
+        } else {
+            load_config_files(&self.config_files, self.age_identity.as_deref()).await?
         };
 
         config.update_from_args(self);
+        expand_hostname_placeholders(&mut config)?;
         Ok(config)
     }
 }
+
+/// Indicates whether a hostname given on the command line stands for the hostnames read from stdin.
+fn is_stdin(hostname: &HostnameConfig) -> bool {
+    hostname.get_hostname() == "-"
+}
+
+/// Parses a list of hostnames, one per line, skipping blank lines and comments.
+fn parse_hostname_list(list: &str) -> Result<Vec<HostnameConfig>, Route53IpUpdateError> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(HostnameConfig::from_arg)
+        .collect()
+}
+
+/// Groups the hostnames given with --hostname under the zone given before each, or under `None` if they come before
+/// any zone. The positional hostnames belong to the last zone.
+fn group_zone_hostnames(
+    zones: &[(usize, String)],
+    hostname_options: Vec<(usize, HostnameConfig)>,
+    hostnames: Vec<HostnameConfig>,
+) -> Vec<(Option<String>, Vec<HostnameConfig>)> {
+    let mut groups = vec![(None, Vec::new())];
+    groups.extend(zones.iter().map(|(_, zone)| (Some(zone.clone()), Vec::new())));
+
+    for (index, hostname) in hostname_options {
+        let group = zones.iter().filter(|(zone_index, _)| *zone_index < index).count();
+        groups[group].1.push(hostname);
+    }
+
+    if let Some((_, last)) = groups.last_mut() {
+        last.extend(hostnames);
+    }
+
+    // Zones are kept even without hostnames, so their records are managed as configured.
+    groups.retain(|(zone, hostnames)| zone.is_some() || !hostnames.is_empty());
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{parse_hostname_list, Args},
+        crate::config::HostnameConfig,
+    };
+
+    /// Returns the zones and hostnames given on the command line, each hostname with its TTL, if any.
+    fn zone_hostnames(args: &Args) -> Vec<(Option<&str>, Vec<String>)> {
+        let hostname = |h: &HostnameConfig| match h.get_ttl() {
+            Some(ttl) => format!("{}:{ttl}", h.get_hostname()),
+            None => h.get_hostname().to_string(),
+        };
+
+        args.zone_hostnames
+            .iter()
+            .map(|(zone, hostnames)| (zone.as_deref(), hostnames.iter().map(hostname).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn groups_hostnames_by_zone() {
+        let args = Args::parse_grouped_from([
+            "route53-ip-update",
+            "-H",
+            "auto.example.org",
+            "-r",
+            "Z1",
+            "-H",
+            "a.example.com",
+            "--route53-zone",
+            "Z2",
+            "--hostname",
+            "b.example.net",
+            "-H",
+            "c.example.net",
+            "d.example.net",
+        ]);
+
+        assert_eq!(
+            zone_hostnames(&args),
+            vec![
+                (None, vec!["auto.example.org".to_string()]),
+                (Some("Z1"), vec!["a.example.com".to_string()]),
+                (
+                    Some("Z2"),
+                    vec!["b.example.net".to_string(), "c.example.net".to_string(), "d.example.net".to_string()]
+                ),
+            ]
+        );
+
+        let args = Args::parse_grouped_from(["route53-ip-update", "a.example.com", "b.example.com"]);
+        assert_eq!(zone_hostnames(&args), vec![(None, vec!["a.example.com".to_string(), "b.example.com".to_string()])]);
+    }
+
+    #[test]
+    fn parses_hostname_ttls() {
+        let args = Args::parse_grouped_from([
+            "route53-ip-update",
+            "-r",
+            "Z1",
+            "-H",
+            "a.example.com:120",
+            "-H",
+            "b.example.com",
+            "c.example.com:5m",
+        ]);
+
+        assert_eq!(
+            zone_hostnames(&args),
+            vec![(
+                Some("Z1"),
+                vec!["a.example.com:120".to_string(), "b.example.com".to_string(), "c.example.com:300".to_string()]
+            )]
+        );
+
+        assert!(HostnameConfig::from_arg("a.example.com:0").is_err());
+        assert!(HostnameConfig::from_arg("a.example.com:soon").is_err());
+    }
+
+    #[test]
+    fn reads_hostname_lists() {
+        let list = parse_hostname_list("# Generated\na.example.com\n\n  b.example.com:60\n").unwrap();
+        assert_eq!(
+            list.iter().map(HostnameConfig::get_hostname).collect::<Vec<_>>(),
+            ["a.example.com", "b.example.com"]
+        );
+        assert!(parse_hostname_list("a.example.com:never\n").is_err());
+    }
+
+    #[test]
+    fn negates_flags() {
+        let args = Args::parse_grouped_from(["route53-ip-update", "--query-interfaces", "--no-query-ip-service"]);
+        assert_eq!(args.query_interfaces.or(args.no_query_interfaces), Some(true));
+        assert_eq!(args.query_ip_service.or(args.no_query_ip_service), Some(false));
+        assert_eq!(args.heartbeat.or(args.no_heartbeat), None);
+
+        // The last of a flag and its negation wins.
+        let args = Args::parse_grouped_from(["route53-ip-update", "-n", "--no-allow-nonroutable", "--heartbeat"]);
+        assert_eq!(args.allow_nonroutable.or(args.no_allow_nonroutable), Some(false));
+        assert_eq!(args.heartbeat.or(args.no_heartbeat), Some(true));
+    }
+
+    #[test]
+    fn splits_lists_at_commas() {
+        let args = Args::parse_grouped_from([
+            "route53-ip-update",
+            "-I",
+            "eth0,docker0",
+            "--ignore-interfaces",
+            "lo",
+            "-r",
+            "Z1",
+            "-H",
+            "a.example.com:60,b.example.com",
+            "-r",
+            "Z2",
+            "-H",
+            "c.example.net",
+        ]);
+
+        assert_eq!(args.ignore_interfaces, ["eth0", "docker0", "lo"]);
+        assert_eq!(
+            zone_hostnames(&args),
+            vec![
+                (Some("Z1"), vec!["a.example.com:60".to_string(), "b.example.com".to_string()]),
+                (Some("Z2"), vec!["c.example.net".to_string()]),
+            ]
+        );
+    }
+}
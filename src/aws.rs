@@ -0,0 +1,645 @@
+use {
+    crate::{
+        config::{Config, Route53ZoneConfig},
+        error::{ClassifiedError, ErrorClass, Route53IpUpdateError},
+    },
+    aws_config::{
+        default_provider::{credentials::DefaultCredentialsChain, region::DefaultRegionChain},
+        profile::{load as load_profiles, profile_file::ProfileFiles},
+        sts::AssumeRoleProvider,
+        SdkConfig,
+    },
+    aws_sdk_route53::{config::Builder as Route53ConfigBuilder, Client as Route53Client, Endpoint, Region},
+    aws_sdk_sts::Client as StsClient,
+    aws_sigv4::http_request::{sign, SignableRequest, SigningParams, SigningSettings},
+    aws_smithy_types::timeout::TimeoutConfig,
+    aws_types::{
+        credentials::{future, CredentialsError, ProvideCredentials, SharedCredentialsProvider},
+        endpoint::{AwsEndpoint, BoxError as EndpointError, CredentialScope, ResolveAwsEndpoint},
+        os_shim_internal::{Env, Fs},
+        region::SigningRegion,
+        Credentials, SigningService,
+    },
+    hyper::{Method, Request, Uri},
+    log::{debug, info},
+    serde_json::Value,
+    std::{
+        collections::HashMap,
+        error::Error,
+        io::{stderr, stdin, BufRead, IsTerminal, Write},
+        process::Command,
+        sync::Arc,
+        time::{Duration, SystemTime},
+    },
+    tokio::{sync::Mutex as AsyncMutex, task::spawn_blocking},
+    tower::BoxError,
+};
+
+/// The role session name used when none is configured.
+const DEFAULT_ROLE_SESSION_NAME: &str = "route53-ip-update";
+
+/// How long before credentials for a role requiring MFA expire to assume the role again.
+const MFA_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Loads the AWS SDK configuration from the environment, optionally using a specific profile and/or region.
+pub(crate) async fn load_sdk_config(profile: Option<&str>, region: Option<&str>) -> SdkConfig {
+    let mut loader = aws_config::from_env();
+
+    if let Some(profile) = profile {
+        loader = loader.credentials_provider(DefaultCredentialsChain::builder().profile_name(profile).build().await);
+    }
+
+    match (region, profile) {
+        (Some(region), _) => loader = loader.region(Region::new(region.to_string())),
+        (None, Some(profile)) => loader = loader.region(DefaultRegionChain::builder().profile_name(profile).build()),
+        (None, None) => (),
+    }
+
+    loader.load().await
+}
+
+/// Returns the AWS partition (as used in ARNs) that a region belongs to.
+pub(crate) fn partition(region: &str) -> &'static str {
+    if region.starts_with("us-gov-") {
+        "aws-us-gov"
+    } else if region.starts_with("cn-") {
+        "aws-cn"
+    } else {
+        "aws"
+    }
+}
+
+/// A client for an AWS service with a JSON API that there's no SDK crate for here, such as EventBridge or CloudWatch
+/// Logs. Requests are signed with the SDK configuration's credentials.
+#[derive(Clone, Debug)]
+pub(crate) struct AwsJsonClient {
+    service: &'static str,
+    target_prefix: &'static str,
+    json_version: &'static str,
+    region: String,
+    endpoint: String,
+    credentials_provider: SharedCredentialsProvider,
+    http: reqwest::Client,
+}
+
+impl AwsJsonClient {
+    /// Creates a client for the service in the SDK configuration's region. `target_prefix` is prepended to operation
+    /// names in the `X-Amz-Target` header, e.g. `AWSEvents` for EventBridge, and `json_version` is the version of the
+    /// AWS JSON protocol the service uses (`1.0` or `1.1`).
+    pub(crate) fn new(
+        sdk_config: &SdkConfig,
+        service: &'static str,
+        target_prefix: &'static str,
+        json_version: &'static str,
+        timeout: Duration,
+    ) -> Result<Self, BoxError> {
+        let credentials_provider =
+            sdk_config.credentials_provider().cloned().ok_or(Route53IpUpdateError::MissingCredentials)?;
+        let (region, endpoint) = service_endpoint(sdk_config, service);
+
+        Ok(Self {
+            service,
+            target_prefix,
+            json_version,
+            endpoint,
+            region,
+            credentials_provider,
+            http: reqwest::Client::builder().timeout(timeout).user_agent(USER_AGENT).build()?,
+        })
+    }
+
+    /// Calls an operation with a JSON request, returning the JSON reply.
+    pub(crate) async fn call(&self, operation: &str, input: &Value) -> Result<Value, BoxError> {
+        // Credentials are fetched for each call so ones that expire while we're running are refreshed.
+        let credentials = self.credentials_provider.provide_credentials().await?;
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(&self.endpoint)
+            .header("content-type", format!("application/x-amz-json-{}", self.json_version))
+            .header("x-amz-target", format!("{}.{operation}", self.target_prefix))
+            .body(serde_json::to_vec(input)?)?;
+
+        let mut params = SigningParams::builder()
+            .access_key(credentials.access_key_id())
+            .secret_key(credentials.secret_access_key())
+            .region(&self.region)
+            .service_name(self.service)
+            .time(SystemTime::now())
+            .settings(SigningSettings::default());
+        params.set_security_token(credentials.session_token());
+        let params = params.build()?;
+        let (instructions, _) = sign(SignableRequest::from(&request), &params)?.into_parts();
+        instructions.apply_to_request(&mut request);
+
+        let (parts, body) = request.into_parts();
+        let response = self
+            .http
+            .post(&self.endpoint)
+            .headers(parts.headers)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| ClassifiedError::new(ErrorClass::AwsService, e))?;
+        let status = response.status();
+        let reply: Value = serde_json::from_str(&response.text().await.unwrap_or_default()).unwrap_or_default();
+        if !status.is_success() {
+            // Error types may be qualified with a namespace, e.g. `com.amazonaws.logs#ResourceNotFoundException`.
+            let code = reply["__type"].as_str().unwrap_or_default().rsplit('#').next().unwrap_or_default();
+            let message = reply["message"].as_str().or_else(|| reply["Message"].as_str()).unwrap_or_default();
+            return Err(ClassifiedError::from_aws_json(operation, status.as_u16(), code, message).into());
+        }
+
+        Ok(reply)
+    }
+}
+
+/// A client for an AWS service with a Query API that there's no SDK crate for here, such as EC2. Requests are signed
+/// with the SDK configuration's credentials, and replies are returned as XML.
+#[derive(Clone, Debug)]
+pub(crate) struct AwsQueryClient {
+    service: &'static str,
+    api_version: &'static str,
+    region: String,
+    endpoint: String,
+    credentials_provider: SharedCredentialsProvider,
+    http: reqwest::Client,
+}
+
+impl AwsQueryClient {
+    /// Creates a client for the service in the SDK configuration's region. `api_version` is the version of the API
+    /// sent with each request, e.g. `2016-11-15` for EC2.
+    pub(crate) fn new(
+        sdk_config: &SdkConfig,
+        service: &'static str,
+        api_version: &'static str,
+        timeout: Duration,
+    ) -> Result<Self, BoxError> {
+        let credentials_provider =
+            sdk_config.credentials_provider().cloned().ok_or(Route53IpUpdateError::MissingCredentials)?;
+        let (region, endpoint) = service_endpoint(sdk_config, service);
+
+        Ok(Self {
+            service,
+            api_version,
+            endpoint,
+            region,
+            credentials_provider,
+            http: reqwest::Client::builder().timeout(timeout).user_agent(USER_AGENT).build()?,
+        })
+    }
+
+    /// Calls an action with the given parameters, returning the XML reply.
+    pub(crate) async fn call(&self, action: &str, params: &[(String, String)]) -> Result<String, BoxError> {
+        let body = form_urlencoded::Serializer::new(String::new())
+            .append_pair("Action", action)
+            .append_pair("Version", self.api_version)
+            .extend_pairs(params)
+            .finish();
+
+        // Credentials are fetched for each call so ones that expire while we're running are refreshed.
+        let credentials = self.credentials_provider.provide_credentials().await?;
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(&self.endpoint)
+            .header("content-type", "application/x-www-form-urlencoded; charset=utf-8")
+            .body(body.into_bytes())?;
+
+        let mut params = SigningParams::builder()
+            .access_key(credentials.access_key_id())
+            .secret_key(credentials.secret_access_key())
+            .region(&self.region)
+            .service_name(self.service)
+            .time(SystemTime::now())
+            .settings(SigningSettings::default());
+        params.set_security_token(credentials.session_token());
+        let params = params.build()?;
+        let (instructions, _) = sign(SignableRequest::from(&request), &params)?.into_parts();
+        instructions.apply_to_request(&mut request);
+
+        let (parts, body) = request.into_parts();
+        let response = self
+            .http
+            .post(&self.endpoint)
+            .headers(parts.headers)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| ClassifiedError::new(ErrorClass::AwsService, e))?;
+        let status = response.status();
+        let reply = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            let document = roxmltree::Document::parse(&reply).ok();
+            let text = |name: &str| {
+                document
+                    .as_ref()
+                    .and_then(|document| document.descendants().find(|node| node.has_tag_name(name)))
+                    .and_then(|node| node.text())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            return Err(ClassifiedError::from_aws_json(action, status.as_u16(), &text("Code"), &text("Message")).into());
+        }
+
+        Ok(reply)
+    }
+}
+
+/// Returns the SDK configuration's region, defaulting to us-east-1, and the endpoint of the service there.
+fn service_endpoint(sdk_config: &SdkConfig, service: &str) -> (String, String) {
+    let region = sdk_config.region().map(|region| region.to_string()).unwrap_or_else(|| "us-east-1".to_string());
+    let domain = if partition(&region) == "aws-cn" {
+        "amazonaws.com.cn"
+    } else {
+        "amazonaws.com"
+    };
+
+    let endpoint = format!("https://{service}.{region}.{domain}/");
+    (region, endpoint)
+}
+
+/// Returns whether an error is an AWS JSON API error with the given code.
+pub(crate) fn is_aws_error_code(error: &(dyn Error + 'static), code: &str) -> bool {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if let Some(Route53IpUpdateError::AwsApiFailed(_, error_code, _)) = error.downcast_ref() {
+            return error_code == code;
+        }
+
+        current = error.source();
+    }
+
+    false
+}
+
+/// Resolves the Route 53 endpoint for a partition, optionally using the FIPS and/or dual-stack variants.
+///
+/// Route 53 is a global service, so the region only selects the partition; requests are always signed for the
+/// partition's global signing region.
+#[derive(Clone, Copy, Debug)]
+struct Route53EndpointResolver {
+    use_fips: bool,
+    use_dual_stack: bool,
+}
+
+impl ResolveAwsEndpoint for Route53EndpointResolver {
+    fn resolve_endpoint(&self, region: &Region) -> Result<AwsEndpoint, EndpointError> {
+        let region = region.as_ref();
+        let (url, signing_region) = if region.starts_with("us-gov-") {
+            // GovCloud's Route 53 endpoint is FIPS-validated already.
+            let url = if self.use_dual_stack {
+                "https://route53.us-gov.api.aws"
+            } else {
+                "https://route53.us-gov.amazonaws.com"
+            };
+            (url, "us-gov-west-1")
+        } else if region.starts_with("cn-") {
+            if self.use_fips || self.use_dual_stack {
+                return Err(Route53IpUpdateError::UnsupportedEndpointVariant(region.to_string()).into());
+            }
+            ("https://route53.amazonaws.com.cn", "cn-northwest-1")
+        } else {
+            let url = match (self.use_fips, self.use_dual_stack) {
+                (false, false) => "https://route53.amazonaws.com",
+                (true, false) => "https://route53-fips.amazonaws.com",
+                (false, true) => "https://route53.global.api.aws",
+                (true, true) => "https://route53-fips.global.api.aws",
+            };
+            (url, "us-east-1")
+        };
+
+        let credential_scope = CredentialScope::builder()
+            .region(SigningRegion::from_static(signing_region))
+            .service(SigningService::from_static("route53"))
+            .build();
+        Ok(AwsEndpoint::new(Endpoint::immutable(Uri::from_static(url)), credential_scope))
+    }
+}
+
+/// The credentials a zone's client is built from. Zones sharing the same key share a client.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+struct CredentialsKey {
+    profile: Option<String>,
+    region: Option<String>,
+    role_arn: Option<String>,
+    external_id: Option<String>,
+    role_session_name: Option<String>,
+    mfa_serial: Option<String>,
+}
+
+impl CredentialsKey {
+    fn for_zone(zone: &Route53ZoneConfig) -> Self {
+        Self {
+            profile: zone.profile.clone(),
+            region: zone.region.clone(),
+            role_arn: zone.role_arn.clone(),
+            external_id: zone.external_id.clone(),
+            role_session_name: zone.role_session_name.clone(),
+            mfa_serial: zone.mfa_serial.clone(),
+        }
+    }
+}
+
+/// Route 53 clients for each distinct set of credentials used by the configured zones.
+pub(crate) struct Route53Clients {
+    sdk_config: SdkConfig,
+    profile: Option<String>,
+    region: Option<String>,
+    endpoint_url: Option<Uri>,
+    use_fips: bool,
+    use_dual_stack: bool,
+    timeout: Option<Duration>,
+    mfa_tokens: Arc<MfaTokenSource>,
+    clients: HashMap<CredentialsKey, Route53Client>,
+}
+
+impl Route53Clients {
+    /// Creates the client set from the default SDK configuration, which was loaded using the profile and region from
+    /// the configuration.
+    pub(crate) fn new(sdk_config: SdkConfig, config: &Config) -> Result<Self, Route53IpUpdateError> {
+        let endpoint_url = match &config.aws_endpoint_url {
+            None => None,
+            Some(url) => Some(url.parse().map_err(|_| Route53IpUpdateError::InvalidEndpointUrl(url.clone()))?),
+        };
+
+        Ok(Self {
+            sdk_config,
+            profile: config.profile.clone(),
+            region: config.region.clone(),
+            endpoint_url,
+            use_fips: config.use_fips,
+            use_dual_stack: config.use_dual_stack,
+            timeout: config.timeouts.route53,
+            mfa_tokens: Arc::new(MfaTokenSource {
+                token: AsyncMutex::new(config.mfa_token.clone()),
+                command: config.mfa_token_command.clone(),
+            }),
+            clients: HashMap::new(),
+        })
+    }
+
+    /// Indicates whether the clients were created with the same AWS settings as the configuration uses. Clients can't
+    /// be recreated with different settings while running, so a reloaded configuration that changes them only takes
+    /// effect after a restart.
+    pub(crate) fn uses_settings_from(&self, config: &Config) -> bool {
+        let endpoint_url = config.aws_endpoint_url.as_ref().and_then(|url| url.parse::<Uri>().ok());
+        self.profile == config.profile
+            && self.region == config.region
+            && self.endpoint_url == endpoint_url
+            && self.use_fips == config.use_fips
+            && self.use_dual_stack == config.use_dual_stack
+            && self.timeout == config.timeouts.route53
+    }
+
+    /// Returns the client using the default credentials from the environment.
+    pub(crate) async fn default_client(&mut self) -> Result<Route53Client, Route53IpUpdateError> {
+        self.get_or_create(CredentialsKey::default()).await
+    }
+
+    /// Returns the client to use for the zone, creating it if necessary.
+    pub(crate) async fn for_zone(&mut self, zone: &Route53ZoneConfig) -> Result<Route53Client, Route53IpUpdateError> {
+        self.get_or_create(CredentialsKey::for_zone(zone)).await
+    }
+
+    async fn get_or_create(&mut self, key: CredentialsKey) -> Result<Route53Client, Route53IpUpdateError> {
+        if let Some(client) = self.clients.get(&key) {
+            return Ok(client.clone());
+        }
+
+        // Zones with their own profile or region get their own SDK configuration.
+        let mut sdk_config = if key.profile.is_some() || key.region.is_some() {
+            debug!("Loading AWS configuration for profile {:?} and region {:?}", key.profile, key.region);
+            let profile = key.profile.as_deref().or(self.profile.as_deref());
+            let region = key.region.as_deref().or(self.region.as_deref());
+            load_sdk_config(profile, region).await
+        } else {
+            self.sdk_config.clone()
+        };
+
+        let mut builder = Route53ConfigBuilder::from(&sdk_config);
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout_config(TimeoutConfig::builder().operation_timeout(timeout).build());
+        }
+
+        let mut mfa_role = key.role_arn.clone().zip(key.mfa_serial.clone()).map(|(role_arn, mfa_serial)| MfaRole {
+            role_arn,
+            mfa_serial,
+            external_id: key.external_id.clone(),
+            role_session_name: key.role_session_name.clone(),
+        });
+
+        // The SDK can't assume a role requiring MFA for a profile, so those roles are assumed here using the
+        // credentials of the profile's source profile.
+        if key.role_arn.is_none() {
+            let profile = key.profile.as_deref().or(self.profile.as_deref());
+            if let Some((role, source_profile)) = profile_mfa_role(profile).await {
+                debug!(
+                    "Profile {profile:?} assumes role {} with MFA; using source profile {source_profile:?}",
+                    role.role_arn
+                );
+                let region =
+                    key.region.clone().or(self.region.clone()).or(sdk_config.region().map(ToString::to_string));
+                sdk_config = load_sdk_config(source_profile.as_deref(), region.as_deref()).await;
+                mfa_role = Some(role);
+            }
+        }
+
+        if let Some(role) = mfa_role {
+            debug!("Creating Route 53 client assuming role {} with MFA device {}", role.role_arn, role.mfa_serial);
+            let provider = MfaAssumeRoleProvider {
+                sts: StsClient::new(&sdk_config),
+                role,
+                tokens: self.mfa_tokens.clone(),
+                cached: AsyncMutex::new(None),
+            };
+
+            // Assume the role straight away so a bad token code is reported before any zone is updated.
+            provider.credentials().await?;
+            builder = builder.credentials_provider(SharedCredentialsProvider::new(provider));
+        } else if let Some(role_arn) = &key.role_arn {
+            debug!("Creating Route 53 client assuming role {role_arn}");
+            let base_provider =
+                sdk_config.credentials_provider().cloned().ok_or(Route53IpUpdateError::MissingCredentials)?;
+
+            let mut assume_role = AssumeRoleProvider::builder(role_arn.clone());
+            if let Some(external_id) = &key.external_id {
+                assume_role = assume_role.external_id(external_id.clone());
+            }
+
+            if let Some(role_session_name) = &key.role_session_name {
+                assume_role = assume_role.session_name(role_session_name.clone());
+            }
+
+            if let Some(region) = sdk_config.region() {
+                assume_role = assume_role.region(region.clone());
+            }
+
+            builder = builder.credentials_provider(assume_role.build(base_provider));
+        }
+
+        if let Some(endpoint_url) = &self.endpoint_url {
+            debug!("Using Route 53 endpoint {endpoint_url}");
+            builder = builder.endpoint_resolver(Endpoint::immutable(endpoint_url.clone()));
+        } else if self.use_fips || self.use_dual_stack {
+            debug!("Using Route 53 endpoint variant fips={} dual-stack={}", self.use_fips, self.use_dual_stack);
+            builder = builder.endpoint_resolver(Route53EndpointResolver {
+                use_fips: self.use_fips,
+                use_dual_stack: self.use_dual_stack,
+            });
+        }
+
+        let client = Route53Client::from_conf(builder.build());
+        self.clients.insert(key, client.clone());
+        Ok(client)
+    }
+}
+
+/// A role requiring MFA to assume.
+#[derive(Debug)]
+struct MfaRole {
+    role_arn: String,
+    mfa_serial: String,
+    external_id: Option<String>,
+    role_session_name: Option<String>,
+}
+
+/// Returns the role requiring MFA that a profile in the AWS config file assumes, if it does, along with its source
+/// profile.
+async fn profile_mfa_role(profile: Option<&str>) -> Option<(MfaRole, Option<String>)> {
+    let profiles = load_profiles(&Fs::real(), &Env::real(), &ProfileFiles::default()).await.ok()?;
+    let profile = profiles.get_profile(profile.unwrap_or_else(|| profiles.selected_profile()))?;
+    let role = MfaRole {
+        role_arn: profile.get("role_arn")?.to_string(),
+        mfa_serial: profile.get("mfa_serial")?.to_string(),
+        external_id: profile.get("external_id").map(ToString::to_string),
+        role_session_name: profile.get("role_session_name").map(ToString::to_string),
+    };
+    Some((role, profile.get("source_profile").map(ToString::to_string)))
+}
+
+/// Where MFA token codes come from. It's shared by every role requiring MFA, so only one code is asked for at a time.
+#[derive(Debug)]
+struct MfaTokenSource {
+    /// A token code from the configuration. A token code can only be used once, so it's taken by the first role
+    /// assumed.
+    token: AsyncMutex<Option<String>>,
+
+    /// The command printing a token code, if any.
+    command: Option<String>,
+}
+
+impl MfaTokenSource {
+    /// Obtains a token code from the configuration, the command, or the terminal.
+    async fn token_code(&self, role_arn: &str, mfa_serial: &str) -> Result<String, Route53IpUpdateError> {
+        let mut token = self.token.lock().await;
+        if let Some(token_code) = token.take() {
+            return Ok(token_code);
+        }
+
+        // Running the command or reading from the terminal blocks, so it's kept off the runtime's worker threads.
+        let (command, role_arn, mfa_serial) = (self.command.clone(), role_arn.to_string(), mfa_serial.to_string());
+        spawn_blocking(move || read_mfa_token_code(command.as_deref(), &role_arn, &mfa_serial))
+            .await
+            .map_err(|e| Route53IpUpdateError::MfaTokenUnavailable(e.to_string()))?
+    }
+}
+
+/// Credentials for a role requiring MFA. The role is assumed again, with a new token code, shortly before the
+/// credentials expire, so a long-running updater keeps working.
+#[derive(Debug)]
+struct MfaAssumeRoleProvider {
+    sts: StsClient,
+    role: MfaRole,
+    tokens: Arc<MfaTokenSource>,
+    cached: AsyncMutex<Option<Credentials>>,
+}
+
+impl MfaAssumeRoleProvider {
+    /// Returns the cached credentials, assuming the role if they're missing or about to expire.
+    async fn credentials(&self) -> Result<Credentials, Route53IpUpdateError> {
+        let mut cached = self.cached.lock().await;
+        let refresh_at = SystemTime::now() + MFA_REFRESH_MARGIN;
+        if let Some(credentials) = cached.as_ref().filter(|c| c.expiry().is_none_or(|expiry| expiry > refresh_at)) {
+            return Ok(credentials.clone());
+        }
+
+        let MfaRole {
+            role_arn,
+            mfa_serial,
+            ..
+        } = &self.role;
+        let token_code = self.tokens.token_code(role_arn, mfa_serial).await?;
+        let result = self
+            .sts
+            .assume_role()
+            .role_arn(role_arn)
+            .role_session_name(self.role.role_session_name.as_deref().unwrap_or(DEFAULT_ROLE_SESSION_NAME))
+            .set_external_id(self.role.external_id.clone())
+            .serial_number(mfa_serial)
+            .token_code(token_code)
+            .send()
+            .await
+            .map_err(|e| Route53IpUpdateError::AssumeRoleFailed(role_arn.to_string(), e.to_string()))?;
+
+        let reply = result
+            .credentials()
+            .ok_or_else(|| Route53IpUpdateError::MissingExpectedAwsReplyField("Credentials".to_string()))?;
+        let expiration = reply.expiration().and_then(|e| SystemTime::try_from(*e).ok());
+        info!("Assumed role {role_arn} using MFA device {mfa_serial}");
+
+        let credentials = Credentials::new(
+            reply.access_key_id().unwrap_or_default(),
+            reply.secret_access_key().unwrap_or_default(),
+            reply.session_token().map(ToString::to_string),
+            expiration,
+            "MfaAssumeRole",
+        );
+        *cached = Some(credentials.clone());
+        Ok(credentials)
+    }
+}
+
+impl ProvideCredentials for MfaAssumeRoleProvider {
+    fn provide_credentials<'a>(&'a self) -> future::ProvideCredentials<'a>
+    where
+        Self: 'a,
+    {
+        future::ProvideCredentials::new(async { self.credentials().await.map_err(CredentialsError::provider_error) })
+    }
+}
+
+/// Obtains an MFA token code by running the command, if any, or by prompting on the terminal.
+fn read_mfa_token_code(
+    command: Option<&str>,
+    role_arn: &str,
+    mfa_serial: &str,
+) -> Result<String, Route53IpUpdateError> {
+    if let Some(command) = command {
+        debug!("Running MFA token command: {command}");
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .output()
+            .map_err(|e| Route53IpUpdateError::MfaTokenUnavailable(format!("{command}: {e}")))?;
+        if !output.status.success() {
+            return Err(Route53IpUpdateError::MfaTokenUnavailable(format!("{command}: {}", output.status)));
+        }
+
+        return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+    }
+
+    if !stdin().is_terminal() {
+        return Err(Route53IpUpdateError::MfaTokenUnavailable(format!(
+            "role {role_arn} requires an MFA token; use --mfa-token or mfa-token-command"
+        )));
+    }
+
+    let mut stderr = stderr();
+    let _ = write!(stderr, "Enter MFA code for {mfa_serial}: ");
+    let _ = stderr.flush();
+
+    let mut token_code = String::new();
+    stdin().lock().read_line(&mut token_code).map_err(|e| Route53IpUpdateError::MfaTokenUnavailable(e.to_string()))?;
+    Ok(token_code.trim().to_string())
+}
@@ -0,0 +1,378 @@
+use {
+    crate::{
+        config::{CloudflareConfig, Config, HostnameConfig, Route53ZoneConfig},
+        diff::{RecordSetDiff, RecordSetSummary},
+        error::Route53IpUpdateError,
+        provider::{
+            publish_targets, timestamp_record_hostnames, timestamp_record_name, timestamp_record_text, DnsProvider,
+        },
+        ttl::Ttl,
+        zones::normalize_domain_name,
+    },
+    async_trait::async_trait,
+    log::debug,
+    reqwest::{header::CONTENT_TYPE, Client, Method},
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
+    std::{collections::HashSet, net::IpAddr, time::Duration},
+    tower::BoxError,
+};
+
+const API_BASE: &str = "https://api.cloudflare.com/client/v4";
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+const DEFAULT_TTL: Ttl = Ttl::from_seconds(300);
+
+/// The most DNS records requested in one page; a hostname has far fewer address records than this.
+const MAX_PER_PAGE: usize = 5000;
+
+/// The envelope wrapped around every Cloudflare API reply.
+#[derive(Debug, Deserialize)]
+struct ApiReply<T> {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<ApiMessage>,
+    result: Option<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiMessage {
+    code: i64,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Zone {
+    id: String,
+}
+
+/// A DNS record as returned by the Cloudflare API.
+#[derive(Debug, Deserialize)]
+struct DnsRecord {
+    id: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    content: String,
+    ttl: i64,
+    #[serde(default)]
+    proxied: bool,
+}
+
+/// The body sent to create or replace a DNS record.
+#[derive(Debug, Serialize)]
+pub(crate) struct DnsRecordBody {
+    #[serde(rename = "type")]
+    record_type: &'static str,
+    name: String,
+    content: String,
+    ttl: i64,
+    proxied: bool,
+}
+
+/// A single Cloudflare API call needed to bring a hostname up-to-date.
+#[derive(Debug)]
+pub(crate) enum CloudflareChange {
+    Create(DnsRecordBody),
+    Replace(String, DnsRecordBody),
+    Delete(String),
+}
+
+/// The API calls needed to bring a zone up-to-date, in order.
+pub(crate) struct CloudflarePlan {
+    changes: Vec<CloudflareChange>,
+    diffs: Vec<RecordSetDiff>,
+}
+
+/// Publishes hostnames to a Cloudflare zone through the Cloudflare API.
+///
+/// Cloudflare has no way to change several records at once, so the changes for a zone are made one record at a time.
+pub(crate) struct CloudflareProvider {
+    client: Client,
+    api_token: String,
+    cloudflare_zone_id: String,
+    proxied: bool,
+}
+
+impl CloudflareProvider {
+    /// Creates a provider for the zone, looking up its Cloudflare zone ID by name if it isn't configured.
+    pub(crate) async fn new(
+        cloudflare: &CloudflareConfig,
+        zone_name: &str,
+        timeout: Duration,
+    ) -> Result<Self, BoxError> {
+        let client = Client::builder().timeout(timeout).user_agent(USER_AGENT).build()?;
+        let mut provider = Self {
+            client,
+            api_token: cloudflare.api_token.clone(),
+            cloudflare_zone_id: cloudflare.zone_id.clone().unwrap_or_default(),
+            proxied: cloudflare.proxied.unwrap_or(false),
+        };
+
+        if provider.cloudflare_zone_id.is_empty() {
+            let zone_name = cloudflare_name(zone_name);
+            let url = format!("{API_BASE}/zones?name={zone_name}");
+            let zones: Vec<Zone> = provider.call(Method::GET, &url, None).await?;
+            let Some(zone) = zones.into_iter().next() else {
+                return Err(Route53IpUpdateError::ZoneNotFound(zone_name).into());
+            };
+
+            debug!("Resolved Cloudflare zone {zone_name} to zone ID {}", zone.id);
+            provider.cloudflare_zone_id = zone.id;
+        }
+
+        Ok(provider)
+    }
+
+    /// Makes an API call, returning the result from the reply envelope.
+    async fn call<T: DeserializeOwned>(&self, method: Method, url: &str, body: Option<Vec<u8>>) -> Result<T, BoxError> {
+        let mut request = self.client.request(method, url).bearer_auth(&self.api_token);
+        if let Some(body) = body {
+            request = request.header(CONTENT_TYPE, "application/json").body(body);
+        }
+
+        let response = request.send().await?;
+        let reply: ApiReply<T> = serde_json::from_slice(&response.bytes().await?)?;
+        match reply.result {
+            Some(result) if reply.success => Ok(result),
+            _ => {
+                let errors: Vec<String> = reply.errors.iter().map(|e| format!("{} ({})", e.message, e.code)).collect();
+                Err(Route53IpUpdateError::DnsUpdateFailed(url.to_string(), errors.join(", ")).into())
+            }
+        }
+    }
+
+    /// Returns the address and CNAME records for a hostname.
+    async fn get_hostname_records(&self, hostname: &str) -> Result<Vec<DnsRecord>, BoxError> {
+        let url = format!(
+            "{API_BASE}/zones/{}/dns_records?name={}&per_page={MAX_PER_PAGE}",
+            self.cloudflare_zone_id,
+            cloudflare_name(hostname)
+        );
+        let records: Vec<DnsRecord> = self.call(Method::GET, &url, None).await?;
+        Ok(records.into_iter().filter(|r| matches!(r.record_type.as_str(), "A" | "AAAA" | "CNAME")).collect())
+    }
+
+    /// Returns the changes needed to bring a hostname up-to-date. Existing records are replaced in place where possible
+    /// so the hostname keeps resolving while the changes are made. A summary of each changed record set is added to
+    /// `diffs`. Address families whose desired addresses are `None` aren't managed and are left as they are.
+    async fn get_changes_for_hostname(
+        &self,
+        hostname_config: &HostnameConfig,
+        desired_ipv4: Option<&HashSet<IpAddr>>,
+        desired_ipv6: Option<&HashSet<IpAddr>>,
+        default_ttl: Ttl,
+        diffs: &mut Vec<RecordSetDiff>,
+    ) -> Result<Vec<CloudflareChange>, BoxError> {
+        let hostname = cloudflare_name(hostname_config.get_hostname());
+        let records = self.get_hostname_records(&hostname).await?;
+        debug!("Hostname {hostname} has {} Cloudflare records", records.len());
+
+        let summarize = |record_type: &str| {
+            let records: Vec<&DnsRecord> = records.iter().filter(|r| r.record_type == record_type).collect();
+            let ttl = records.first().map(|r| r.ttl);
+            (!records.is_empty()).then(|| RecordSetSummary::new(ttl, records.iter().map(|r| &r.content)))
+        };
+
+        // Don't allow CNAMEs. Delete them first, since Cloudflare won't add address records alongside a CNAME.
+        let mut changes: Vec<CloudflareChange> = records
+            .iter()
+            .filter(|r| r.record_type == "CNAME")
+            .map(|r| CloudflareChange::Delete(r.id.clone()))
+            .collect();
+        if let Some(old) = summarize("CNAME") {
+            diffs.push(record_set_diff(&hostname, "CNAME", Some(old), None));
+        }
+
+        let families = [
+            ("A", desired_ipv4, hostname_config.get_ipv4_ttl().unwrap_or(default_ttl)),
+            ("AAAA", desired_ipv6, hostname_config.get_ipv6_ttl().unwrap_or(default_ttl)),
+        ];
+
+        for (record_type, desired, ttl) in families {
+            let Some(desired) = desired else {
+                continue;
+            };
+
+            let ttl: i64 = ttl.into();
+            let changes_before = changes.len();
+            let mut unpublished: Vec<&IpAddr> = desired.iter().collect();
+            unpublished.sort();
+            let mut stale = Vec::new();
+
+            for record in records.iter().filter(|r| r.record_type == record_type) {
+                let address = record.content.parse::<IpAddr>().ok();
+                match unpublished.iter().position(|a| Some(**a) == address) {
+                    Some(pos) if record.ttl == ttl && record.proxied == self.proxied => {
+                        unpublished.remove(pos);
+                    }
+                    _ => stale.push(record),
+                }
+            }
+
+            // Reuse stale records for the addresses that still need to be published, then delete any left over.
+            for record in stale {
+                if unpublished.is_empty() {
+                    changes.push(CloudflareChange::Delete(record.id.clone()));
+                    continue;
+                }
+
+                let address = match unpublished.iter().position(|a| Some(**a) == record.content.parse().ok()) {
+                    Some(pos) => unpublished.remove(pos),
+                    None => unpublished.remove(0),
+                };
+                changes.push(CloudflareChange::Replace(
+                    record.id.clone(),
+                    self.record_body(record_type, &hostname, address, ttl),
+                ));
+            }
+
+            for address in unpublished {
+                changes.push(CloudflareChange::Create(self.record_body(record_type, &hostname, address, ttl)));
+            }
+
+            if changes.len() > changes_before {
+                let new = (!desired.is_empty()).then(|| RecordSetSummary::new(Some(ttl), desired));
+                diffs.push(record_set_diff(&hostname, record_type, summarize(record_type), new));
+            }
+        }
+
+        Ok(changes)
+    }
+
+    fn record_body(&self, record_type: &'static str, hostname: &str, address: &IpAddr, ttl: i64) -> DnsRecordBody {
+        DnsRecordBody {
+            record_type,
+            name: hostname.to_string(),
+            content: address.to_string(),
+            ttl,
+            proxied: self.proxied,
+        }
+    }
+}
+
+#[async_trait]
+impl DnsProvider for CloudflareProvider {
+    type Plan = CloudflarePlan;
+
+    /// Each API call is applied before Cloudflare replies, so there is nothing to wait for.
+    type Pending = ();
+
+    async fn list(&self, hostname: &str) -> Result<(HashSet<IpAddr>, HashSet<IpAddr>), BoxError> {
+        let mut ipv4 = HashSet::new();
+        let mut ipv6 = HashSet::new();
+
+        for record in self.get_hostname_records(hostname).await? {
+            match (record.record_type.as_str(), record.content.parse::<IpAddr>()) {
+                ("A", Ok(address)) => ipv4.insert(address),
+                ("AAAA", Ok(address)) => ipv6.insert(address),
+                ("A" | "AAAA", Err(_)) => return Err(Route53IpUpdateError::InvalidIpAddr(record.content).into()),
+                _ => continue,
+            };
+        }
+
+        Ok((ipv4, ipv6))
+    }
+
+    async fn plan(
+        &self,
+        config: &Config,
+        zone_config: &Route53ZoneConfig,
+        desired_ipv4: &HashSet<IpAddr>,
+        desired_ipv6: &HashSet<IpAddr>,
+    ) -> Result<Option<CloudflarePlan>, BoxError> {
+        let default_ttl = zone_config.ttl.or(config.ttl).unwrap_or(DEFAULT_TTL);
+        let mut changes = Vec::new();
+        let mut diffs = Vec::new();
+
+        for target in publish_targets(zone_config, desired_ipv4, desired_ipv6) {
+            debug!(
+                "Getting changes in Cloudflare zone {} for hostname {}",
+                zone_config.zone_id,
+                target.hostname_config.get_hostname()
+            );
+            changes.extend(
+                self.get_changes_for_hostname(
+                    &target.hostname_config,
+                    target.ipv4,
+                    target.ipv6,
+                    default_ttl,
+                    &mut diffs,
+                )
+                .await?,
+            );
+        }
+
+        let timestamp_ttl = i64::from(default_ttl);
+        for hostname in timestamp_record_hostnames(config, zone_config, &diffs) {
+            let name = timestamp_record_name(&hostname);
+            let existing = self.get_hostname_records(&name).await?.into_iter().find(|r| r.record_type == "TXT");
+            let body = DnsRecordBody {
+                record_type: "TXT",
+                name,
+                content: timestamp_record_text(),
+                ttl: timestamp_ttl,
+                proxied: false,
+            };
+
+            changes.push(match existing {
+                Some(record) => CloudflareChange::Replace(record.id, body),
+                None => CloudflareChange::Create(body),
+            });
+        }
+
+        if changes.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(CloudflarePlan {
+                changes,
+                diffs,
+            }))
+        }
+    }
+
+    fn describe<'a>(&self, plan: &'a CloudflarePlan) -> &'a [RecordSetDiff] {
+        &plan.diffs
+    }
+
+    async fn apply(&self, plan: CloudflarePlan) -> Result<(), BoxError> {
+        let records_url = format!("{API_BASE}/zones/{}/dns_records", self.cloudflare_zone_id);
+
+        for change in plan.changes {
+            debug!("Applying Cloudflare change: {change:?}");
+            let _: serde_json::Value = match change {
+                CloudflareChange::Create(body) => {
+                    self.call(Method::POST, &records_url, Some(serde_json::to_vec(&body)?)).await?
+                }
+                CloudflareChange::Replace(id, body) => {
+                    self.call(Method::PUT, &format!("{records_url}/{id}"), Some(serde_json::to_vec(&body)?)).await?
+                }
+                CloudflareChange::Delete(id) => self.call(Method::DELETE, &format!("{records_url}/{id}"), None).await?,
+            };
+        }
+
+        Ok(())
+    }
+
+    async fn wait(&self, _pending: ()) -> Result<(), BoxError> {
+        Ok(())
+    }
+}
+
+fn record_set_diff(
+    hostname: &str,
+    record_type: &str,
+    old: Option<RecordSetSummary>,
+    new: Option<RecordSetSummary>,
+) -> RecordSetDiff {
+    RecordSetDiff {
+        hostname: hostname.to_string(),
+        record_type: record_type.to_string(),
+        old,
+        new,
+    }
+}
+
+/// Returns a domain name in the form used by Cloudflare: lowercase, without a trailing dot.
+fn cloudflare_name(name: &str) -> String {
+    normalize_domain_name(name).trim_end_matches('.').to_string()
+}
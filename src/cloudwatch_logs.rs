@@ -0,0 +1,192 @@
+use {
+    crate::{
+        aws::{is_aws_error_code, load_sdk_config, AwsJsonClient},
+        config::Config,
+        error::ClassifiedError,
+    },
+    gethostname::gethostname,
+    log::{warn, Level},
+    once_cell::sync::OnceCell,
+    serde::Serialize,
+    serde_json::{json, Value},
+    std::{
+        mem::take,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tokio::{
+        sync::{
+            mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+            oneshot,
+        },
+        time::{interval, timeout},
+    },
+    tower::BoxError,
+};
+
+/// How often buffered log events are sent.
+const SEND_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The most events buffered before they're sent without waiting for the interval. PutLogEvents accepts up to 10,000
+/// events and 1 MiB per call; this keeps batches well under both.
+const MAX_BATCH_EVENTS: usize = 1000;
+
+/// How long to wait for buffered events to be sent when exiting.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The channel to the task sending log events, once it has been started.
+static SINK: OnceCell<UnboundedSender<SinkMessage>> = OnceCell::new();
+
+enum SinkMessage {
+    Event(LogEvent),
+
+    /// Send the buffered events now, then signal the sender.
+    Flush(oneshot::Sender<()>),
+}
+
+/// A log event as sent to PutLogEvents.
+#[derive(Debug, Serialize)]
+struct LogEvent {
+    /// When the event happened, in milliseconds since the Unix epoch.
+    timestamp: u64,
+
+    /// The event, as a JSON object.
+    message: String,
+}
+
+/// Creates the log stream (if needed) and starts sending log messages to the configured CloudWatch Logs group. This
+/// does nothing if CloudWatch Logs isn't configured or has already been started.
+pub(crate) async fn start_cloudwatch_logs(config: &Config) -> Result<(), BoxError> {
+    let (Some(cloudwatch_logs), None) = (&config.cloudwatch_logs, SINK.get()) else {
+        return Ok(());
+    };
+
+    let sdk_config = load_sdk_config(config.profile.as_deref(), config.region.as_deref()).await;
+    let client = AwsJsonClient::new(&sdk_config, "logs", "Logs_20140328", "1.1", config.timeout)?;
+    let log_group = cloudwatch_logs.log_group.clone();
+    let log_stream = cloudwatch_logs.log_stream.clone().unwrap_or_else(|| gethostname().to_string_lossy().into_owned());
+
+    let input = json!({"logGroupName": log_group, "logStreamName": log_stream});
+    match client.call("CreateLogStream", &input).await {
+        Ok(_) => (),
+        Err(e) if is_aws_error_code(e.as_ref(), "ResourceAlreadyExistsException") => (),
+        Err(e) => {
+            let context = format!("Unable to create log stream {log_stream} in CloudWatch Logs group {log_group}");
+            return Err(match e.downcast::<ClassifiedError>() {
+                Ok(e) => e.context(context).into(),
+                Err(e) => format!("{context}: {e}").into(),
+            });
+        }
+    }
+
+    let (sender, receiver) = unbounded_channel();
+    if SINK.set(sender).is_ok() {
+        tokio::spawn(send_log_events(client, log_group, log_stream, receiver));
+    }
+
+    Ok(())
+}
+
+/// Queues a message to be sent to CloudWatch Logs, if it has been started. Only this crate's messages are sent, which
+/// also keeps the HTTP client's own logging of the requests from feeding back into them.
+pub(crate) fn ship_log_message(level: Level, target: &str, message: &str) {
+    let Some(sink) = SINK.get() else {
+        return;
+    };
+
+    if !target.starts_with(env!("CARGO_CRATE_NAME")) {
+        return;
+    }
+
+    let _ = sink.send(SinkMessage::Event(LogEvent {
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default(),
+        message: log_event_message(level, target, message).to_string(),
+    }));
+}
+
+/// Waits (briefly) for queued messages to be sent to CloudWatch Logs.
+pub(crate) async fn flush_cloudwatch_logs() {
+    let Some(sink) = SINK.get() else {
+        return;
+    };
+
+    let (done, wait) = oneshot::channel();
+    if sink.send(SinkMessage::Flush(done)).is_ok() {
+        let _ = timeout(FLUSH_TIMEOUT, wait).await;
+    }
+}
+
+/// Returns the structured form of a log message.
+fn log_event_message(level: Level, target: &str, message: &str) -> Value {
+    json!({
+        "level": level.as_str(),
+        "target": target,
+        "message": message,
+    })
+}
+
+/// Sends queued log events in batches until the channel is closed.
+async fn send_log_events(
+    client: AwsJsonClient,
+    log_group: String,
+    log_stream: String,
+    mut receiver: UnboundedReceiver<SinkMessage>,
+) {
+    let mut events = Vec::new();
+    let mut ticks = interval(SEND_INTERVAL);
+
+    loop {
+        tokio::select! {
+            message = receiver.recv() => match message {
+                Some(SinkMessage::Event(event)) => {
+                    events.push(event);
+                    if events.len() >= MAX_BATCH_EVENTS {
+                        put_log_events(&client, &log_group, &log_stream, &mut events).await;
+                    }
+                }
+                Some(SinkMessage::Flush(done)) => {
+                    put_log_events(&client, &log_group, &log_stream, &mut events).await;
+                    let _ = done.send(());
+                }
+                None => {
+                    put_log_events(&client, &log_group, &log_stream, &mut events).await;
+                    return;
+                }
+            },
+            _ = ticks.tick() => put_log_events(&client, &log_group, &log_stream, &mut events).await,
+        }
+    }
+}
+
+/// Sends the buffered events, emptying the buffer. Events that can't be sent are dropped so a long outage doesn't
+/// exhaust memory.
+async fn put_log_events(client: &AwsJsonClient, log_group: &str, log_stream: &str, events: &mut Vec<LogEvent>) {
+    if events.is_empty() {
+        return;
+    }
+
+    // Messages logged from different threads may be queued slightly out of order; CloudWatch Logs requires them sorted.
+    let mut batch = take(events);
+    batch.sort_by_key(|event| event.timestamp);
+
+    let input = json!({"logGroupName": log_group, "logStreamName": log_stream, "logEvents": batch});
+    if let Err(e) = client.call("PutLogEvents", &input).await {
+        warn!("Unable to send {} log message(s) to CloudWatch Logs group {log_group}: {e}", batch.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::log_event_message, log::Level, serde_json::json};
+
+    #[test]
+    fn log_messages_are_structured() {
+        assert_eq!(
+            log_event_message(Level::Info, "route53_ip_update::update", "Hostnames updated successfully for zone Z1"),
+            json!({
+                "level": "INFO",
+                "target": "route53_ip_update::update",
+                "message": "Hostnames updated successfully for zone Z1",
+            })
+        );
+    }
+}
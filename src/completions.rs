@@ -0,0 +1,27 @@
+use {
+    crate::{args::Args, config::Config},
+    clap::{builder::PossibleValuesParser, CommandFactory},
+    clap_complete::{generate, Shell},
+    std::io::Write,
+};
+
+/// Writes a completion script for the shell. The zones in the configuration, by ID and by name, are offered as
+/// completions for `--route53-zone`.
+pub(crate) fn write_completions(shell: Shell, config: &Config, out: &mut dyn Write) {
+    let mut command = Args::command();
+    let name = command.get_name().to_string();
+
+    let mut zones: Vec<String> = Vec::new();
+    for zone in &config.route53_zones {
+        zones.push(zone.zone_id.clone());
+        zones.extend(zone.zone_name.clone());
+    }
+    zones.sort();
+    zones.dedup();
+
+    if !zones.is_empty() {
+        command = command.mut_arg("route53_zones", |arg| arg.value_parser(PossibleValuesParser::new(zones)));
+    }
+
+    generate(shell, &mut command, name, out);
+}
@@ -1,13 +1,38 @@
 use {
-    crate::{args::Args, error::Route53IpUpdateError, query_address_type::QueryAddressType, ttl::Ttl},
+    crate::{
+        address_class::AddressClass,
+        address_ranking::AddressRanking,
+        address_source::{AddressSource, DiscoveredAddress},
+        args::Args,
+        error::Route53IpUpdateError,
+        nat64::Nat64Prefix,
+        query_address_type::QueryAddressType,
+        routing_policy::{FailoverRole, RecordRouting, RoutingPolicy},
+        schedule::parse_cron,
+        ttl::Ttl,
+        zones::{is_hostname_in_zone, is_zone_name, normalize_domain_name},
+    },
+    log::warn,
+    schemars::JsonSchema,
     serde::{Deserialize, Serialize},
-    std::{net::IpAddr, time::Duration},
+    std::{
+        collections::{BTreeMap, HashSet},
+        iter::once,
+        net::SocketAddr,
+        path::PathBuf,
+        time::Duration,
+    },
 };
 
 const DEFAULT_IP_SERVICE: &str = "https://api64.ipify.org";
+const DEFAULT_STUN_SERVERS: [&str; 2] = ["stun.l.google.com:19302", "stun.cloudflare.com:3478"];
+const DEFAULT_MIN_TTL: Ttl = Ttl::from_seconds(30);
+const DEFAULT_MAX_TTL: Ttl = Ttl::from_seconds(604800);
+const DEFAULT_CHANGE_COMMENT: &str = "Route 53 update for {hostnames}";
 
-#[derive(Debug, Default, Deserialize, Clone, Serialize)]
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
 #[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct Config {
     /// Whether to use IPv4, IPv6, or both.
     #[serde(default = "QueryAddressType::default")]
@@ -17,6 +42,19 @@ pub(crate) struct Config {
     #[serde(default = "Config::default_allow_nonroutable")]
     pub(crate) allow_nonroutable: bool,
 
+    /// Whether to publish only the best IPv4 and IPv6 address to each zone, rather than all of them.
+    #[serde(default = "Config::default_single_address")]
+    pub(crate) single_address: bool,
+
+    /// How the address published with `single-address` is chosen from several candidates.
+    #[serde(default = "AddressRanking::default")]
+    pub(crate) address_ranking: AddressRanking,
+
+    /// Whether `single-address` keeps publishing the address published before while it's still a candidate, rather
+    /// than switching to another one that's just as good.
+    #[serde(default = "Config::default_sticky_address")]
+    pub(crate) sticky_address: bool,
+
     /// Whether interfaces should be queried for their addresses.
     #[serde(default = "Config::default_query_interfaces")]
     pub(crate) query_interfaces: bool,
@@ -32,16 +70,309 @@ pub(crate) struct Config {
     #[serde(default = "Config::default_ip_service")]
     pub(crate) ip_service: String,
 
+    /// How many more times to query the IP service if a query fails, before giving up on discovery.
+    #[serde(default = "Config::default_ip_service_retries")]
+    pub(crate) ip_service_retries: u32,
+
+    /// How long to wait before the first retry of the IP service; the wait doubles with each retry after that.
+    #[serde(with = "humantime_serde", default = "Config::default_ip_service_backoff")]
+    #[schemars(with = "String")]
+    pub(crate) ip_service_backoff: Duration,
+
+    /// Whether this host is IPv6-only behind a NAT64, so the IP service is queried for the public IPv4 address through
+    /// the NAT64.
+    #[serde(default = "Config::default_nat64")]
+    pub(crate) nat64: bool,
+
+    /// The NAT64 prefix, e.g. `64:ff9b::/96`. If unset, it's discovered from the resolver (RFC 7050).
+    pub(crate) nat64_prefix: Option<String>,
+
+    /// Whether to classify the NAT in front of this host with STUN, warning when the published IPv4 address may not be
+    /// reachable.
+    #[serde(default = "Config::default_detect_nat")]
+    pub(crate) detect_nat: bool,
+
+    /// The STUN servers (`host:port`) used by `detect-nat`. Two or more are needed to detect symmetric NAT.
+    #[serde(default = "Config::default_stun_servers")]
+    pub(crate) stun_servers: Vec<String>,
+
     /// The timeout to allow for the IP service to respond.
     #[serde(with = "humantime_serde", default = "Config::default_timeout")]
+    #[schemars(with = "String")]
     pub(crate) timeout: Duration,
 
+    /// The most Route 53 API calls to make per second, across all zones; 0 for no limit.
+    #[serde(default = "Config::default_route53_rate_limit")]
+    pub(crate) route53_rate_limit: f64,
+
+    /// Timeouts for the individual address sources and for Route 53, so one slow component can't hold up the rest.
+    #[serde(default)]
+    pub(crate) timeouts: TimeoutsConfig,
+
     /// The Route 53 zones to update.
-    #[serde(default = "Vec::new")]
+    #[serde(default)]
     pub(crate) route53_zones: Vec<Route53ZoneConfig>,
 
+    /// Fully-qualified hostnames to update; the hosted zone for each is discovered automatically.
+    #[serde(default)]
+    pub(crate) hostnames: Vec<HostnameConfig>,
+
+    /// Hostname patterns to update in every hosted zone carrying the specified tags.
+    #[serde(default)]
+    pub(crate) tagged_zones: Vec<TaggedZonesConfig>,
+
+    /// If set, the addresses of the EC2 instances carrying a tag are published under the hostnames in the tag, instead
+    /// of this host's addresses.
+    pub(crate) ec2_fleet: Option<Ec2FleetConfig>,
+
     /// The default TTL to use for all records.
     pub(crate) ttl: Option<Ttl>,
+
+    /// The smallest TTL that may be configured.
+    #[serde(default = "Config::default_min_ttl")]
+    pub(crate) min_ttl: Ttl,
+
+    /// The largest TTL that may be configured.
+    #[serde(default = "Config::default_max_ttl")]
+    pub(crate) max_ttl: Ttl,
+
+    /// Whether TTLs outside of the min/max bounds should be clamped (with a warning) instead of rejected.
+    #[serde(default = "Config::default_clamp_ttl")]
+    pub(crate) clamp_ttl: bool,
+
+    /// The AWS profile to use for credentials.
+    pub(crate) profile: Option<String>,
+
+    /// The AWS region to use.
+    pub(crate) region: Option<String>,
+
+    /// The endpoint URL to use for Route 53 instead of the AWS endpoint, e.g. for LocalStack.
+    pub(crate) aws_endpoint_url: Option<String>,
+
+    /// A command whose output is the MFA token code to use when assuming roles that require MFA.
+    pub(crate) mfa_token_command: Option<String>,
+
+    /// The MFA token code to use when assuming a role that requires MFA. This is only set from the command line.
+    #[serde(skip)]
+    pub(crate) mfa_token: Option<String>,
+
+    /// Whether to ask for confirmation before applying the changes to each zone. This is only set from the command
+    /// line.
+    #[serde(skip)]
+    pub(crate) interactive: bool,
+
+    /// Whether to use the FIPS Route 53 endpoint.
+    #[serde(default = "Config::default_use_fips")]
+    pub(crate) use_fips: bool,
+
+    /// Whether to use the dual-stack (IPv4 and IPv6) Route 53 endpoint.
+    #[serde(default = "Config::default_use_dual_stack")]
+    pub(crate) use_dual_stack: bool,
+
+    /// If set, existing records that are about to change first have their TTL lowered to this value; the old TTL is
+    /// allowed to expire before the new values are published.
+    pub(crate) prechange_ttl: Option<Ttl>,
+
+    /// The template for the comment attached to each Route 53 change batch.
+    #[serde(default = "Config::default_change_comment")]
+    pub(crate) change_comment: String,
+
+    /// If set, keep running and repeat the update at this interval instead of exiting after one update.
+    #[serde(with = "humantime_serde", default)]
+    #[schemars(with = "Option<String>")]
+    pub(crate) interval: Option<Duration>,
+
+    /// If set, keep running and update at the times matched by this cron expression (minute, hour, day of month,
+    /// month, day of week, in local time) instead of at a fixed interval.
+    pub(crate) schedule: Option<String>,
+
+    /// If set, each scheduled update is delayed by a random amount of up to this long, so machines started together
+    /// don't all update at the same moment.
+    #[serde(with = "humantime_serde", default)]
+    #[schemars(with = "Option<String>")]
+    pub(crate) jitter: Option<Duration>,
+
+    /// If set, a hostname isn't changed again until this long after it was last changed.
+    #[serde(with = "humantime_serde", default)]
+    #[schemars(with = "Option<String>")]
+    pub(crate) cooldown: Option<Duration>,
+
+    /// If set, addresses that differ from those discovered on the previous update must be discovered again after this
+    /// long before they are published.
+    #[serde(with = "humantime_serde", default)]
+    #[schemars(with = "Option<String>")]
+    pub(crate) debounce: Option<Duration>,
+
+    /// If set, a run that takes longer than this is abandoned with an error. When running continuously, each update is
+    /// limited to this long instead.
+    #[serde(with = "humantime_serde", default)]
+    #[schemars(with = "Option<String>")]
+    pub(crate) max_runtime: Option<Duration>,
+
+    /// A file to remember when hostnames were last changed and which addresses were last published in, so the cooldown
+    /// and the `previous` address ranking also apply across separate runs.
+    pub(crate) state_file: Option<PathBuf>,
+
+    /// A directory to save the Route 53 record sets about to be changed to before each change, so they can be put back
+    /// with the `restore` command.
+    pub(crate) snapshot_dir: Option<PathBuf>,
+
+    /// If set, Route 53 changes are checked once they've propagated, and put back the way they were if the check fails.
+    pub(crate) verify: Option<VerifyConfig>,
+
+    /// Rules the changes to each zone must follow; changes that break one are refused.
+    #[serde(default)]
+    pub(crate) policies: PoliciesConfig,
+
+    /// If any are set, records are only changed during these windows, in local time; changes are held until one opens.
+    #[serde(default)]
+    pub(crate) change_windows: Vec<TimeWindow>,
+
+    /// Windows, in local time, during which records are never changed; changes are held until the window ends.
+    #[serde(default)]
+    pub(crate) blackout_windows: Vec<TimeWindow>,
+
+    /// Whether to only check that the published records match the discovered addresses, without changing them. If
+    /// they don't match, the drift webhook is notified and the exit status is 2.
+    #[serde(default = "Config::default_monitor_only")]
+    pub(crate) monitor_only: bool,
+
+    /// A URL to POST a JSON report to when monitoring finds records that don't match the discovered addresses.
+    pub(crate) drift_webhook: Option<String>,
+
+    /// The name or ARN of an EventBridge event bus to put an event onto for each hostname whose records are changed.
+    pub(crate) event_bus: Option<String>,
+
+    /// Whether to also maintain a `_r53ipupdate.<hostname>` TXT record for each hostname changed, holding when it was
+    /// updated, the machine that updated it, and the version of this tool.
+    #[serde(default = "Config::default_timestamp_records")]
+    pub(crate) timestamp_records: bool,
+
+    /// Whether to refresh the timestamp records of all hostnames on every update, even when their addresses haven't
+    /// changed, so they show that the updater is still running.
+    #[serde(default = "Config::default_heartbeat")]
+    pub(crate) heartbeat: bool,
+
+    /// If set, this crate's log messages are also sent to a CloudWatch Logs group.
+    pub(crate) cloudwatch_logs: Option<CloudWatchLogsConfig>,
+
+    /// If set, metrics about each update are sent to a statsd server.
+    pub(crate) statsd: Option<StatsdConfig>,
+
+    /// If set, a lock in a DynamoDB table is taken for each zone while it's being updated, so redundant updaters don't
+    /// submit conflicting changes at the same time.
+    pub(crate) lock: Option<LockConfig>,
+
+    /// If set, only the leader of several redundant updaters publishes addresses; the others stand by and take over
+    /// when the leader's lease in a DynamoDB table expires.
+    pub(crate) leader_election: Option<LeaderElectionConfig>,
+
+    /// If set, a directory of per-zone configuration files, each holding one zone's settings as an entry of
+    /// `route53-zones` would, that are added to `route53-zones` when the configuration is loaded.
+    pub(crate) zones_dir: Option<PathBuf>,
+
+    /// Whether to remove the managed records when shutting down after running continuously.
+    #[serde(default = "Config::default_remove_on_exit")]
+    pub(crate) remove_on_exit: bool,
+
+    /// If set, the number of updates in a row that may fail before route53-ip-update exits when running continuously.
+    pub(crate) max_consecutive_failures: Option<u32>,
+
+    /// How many more times to run a single update from the start if it fails in a way worth retrying.
+    #[serde(default = "Config::default_retries")]
+    pub(crate) retries: u32,
+
+    /// How long to wait before the first retry of a single update; the wait doubles with each retry after that.
+    #[serde(with = "humantime_serde", default = "Config::default_retry_backoff")]
+    #[schemars(with = "String")]
+    pub(crate) retry_backoff: Duration,
+
+    /// If set, the address and port to serve the status of updates on as JSON when running continuously.
+    pub(crate) status_listen: Option<SocketAddr>,
+
+    /// If set, a token that requests to the status endpoint and dashboard must include. This is only set from the
+    /// config file.
+    pub(crate) status_token: Option<String>,
+
+    /// Settings for the DynDNS2-compatible server started by the `serve` command.
+    pub(crate) dyndns_server: Option<DyndnsServerConfig>,
+
+    /// If set, discovered addresses are sent to a relay server (running the `serve` command), which holds the AWS
+    /// credentials and updates Route 53, instead of updating Route 53 directly.
+    pub(crate) relay: Option<RelayConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address_type: QueryAddressType::default(),
+            allow_nonroutable: Self::default_allow_nonroutable(),
+            single_address: Self::default_single_address(),
+            address_ranking: AddressRanking::default(),
+            sticky_address: Self::default_sticky_address(),
+            query_interfaces: Self::default_query_interfaces(),
+            query_ip_service: Self::default_query_ip_service(),
+            ignore_interfaces: None,
+            ip_service: Self::default_ip_service(),
+            ip_service_retries: Self::default_ip_service_retries(),
+            ip_service_backoff: Self::default_ip_service_backoff(),
+            nat64: Self::default_nat64(),
+            nat64_prefix: None,
+            detect_nat: Self::default_detect_nat(),
+            stun_servers: Self::default_stun_servers(),
+            timeout: Self::default_timeout(),
+            route53_rate_limit: Self::default_route53_rate_limit(),
+            timeouts: TimeoutsConfig::default(),
+            route53_zones: Vec::new(),
+            hostnames: Vec::new(),
+            tagged_zones: Vec::new(),
+            ec2_fleet: None,
+            ttl: None,
+            min_ttl: Self::default_min_ttl(),
+            max_ttl: Self::default_max_ttl(),
+            clamp_ttl: Self::default_clamp_ttl(),
+            profile: None,
+            region: None,
+            aws_endpoint_url: None,
+            mfa_token_command: None,
+            mfa_token: None,
+            interactive: false,
+            use_fips: Self::default_use_fips(),
+            use_dual_stack: Self::default_use_dual_stack(),
+            prechange_ttl: None,
+            change_comment: Self::default_change_comment(),
+            interval: None,
+            schedule: None,
+            jitter: None,
+            cooldown: None,
+            debounce: None,
+            max_runtime: None,
+            state_file: None,
+            snapshot_dir: None,
+            verify: None,
+            policies: PoliciesConfig::default(),
+            change_windows: Vec::new(),
+            blackout_windows: Vec::new(),
+            monitor_only: Self::default_monitor_only(),
+            drift_webhook: None,
+            event_bus: None,
+            timestamp_records: Self::default_timestamp_records(),
+            heartbeat: Self::default_heartbeat(),
+            cloudwatch_logs: None,
+            statsd: None,
+            lock: None,
+            leader_election: None,
+            zones_dir: None,
+            remove_on_exit: Self::default_remove_on_exit(),
+            max_consecutive_failures: None,
+            retries: Self::default_retries(),
+            retry_backoff: Self::default_retry_backoff(),
+            status_listen: None,
+            status_token: None,
+            dyndns_server: None,
+            relay: None,
+        }
+    }
 }
 
 impl Config {
@@ -49,6 +380,14 @@ impl Config {
         false
     }
 
+    pub(crate) fn default_single_address() -> bool {
+        false
+    }
+
+    pub(crate) fn default_sticky_address() -> bool {
+        false
+    }
+
     pub(crate) fn default_query_ip_service() -> bool {
         true
     }
@@ -61,10 +400,82 @@ impl Config {
         DEFAULT_IP_SERVICE.to_string()
     }
 
+    pub(crate) fn default_ip_service_retries() -> u32 {
+        2
+    }
+
+    pub(crate) fn default_ip_service_backoff() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    pub(crate) fn default_nat64() -> bool {
+        false
+    }
+
+    pub(crate) fn default_detect_nat() -> bool {
+        false
+    }
+
+    pub(crate) fn default_stun_servers() -> Vec<String> {
+        DEFAULT_STUN_SERVERS.iter().map(|server| server.to_string()).collect()
+    }
+
+    pub(crate) fn default_route53_rate_limit() -> f64 {
+        5.0
+    }
+
     pub(crate) fn default_timeout() -> Duration {
         Duration::from_secs(10)
     }
 
+    pub(crate) fn default_min_ttl() -> Ttl {
+        DEFAULT_MIN_TTL
+    }
+
+    pub(crate) fn default_max_ttl() -> Ttl {
+        DEFAULT_MAX_TTL
+    }
+
+    pub(crate) fn default_clamp_ttl() -> bool {
+        false
+    }
+
+    pub(crate) fn default_use_fips() -> bool {
+        false
+    }
+
+    pub(crate) fn default_use_dual_stack() -> bool {
+        false
+    }
+
+    pub(crate) fn default_change_comment() -> String {
+        DEFAULT_CHANGE_COMMENT.to_string()
+    }
+
+    pub(crate) fn default_monitor_only() -> bool {
+        false
+    }
+
+    pub(crate) fn default_retries() -> u32 {
+        0
+    }
+
+    pub(crate) fn default_retry_backoff() -> Duration {
+        Duration::from_secs(30)
+    }
+
+    pub(crate) fn default_remove_on_exit() -> bool {
+        false
+    }
+
+    pub(crate) fn default_timestamp_records() -> bool {
+        false
+    }
+
+    pub(crate) fn default_heartbeat() -> bool {
+        false
+    }
+
     /// Indicates whether the specified interface should be used.
     pub(crate) fn allows_interface(&self, interface: &str) -> bool {
         if let Some(ignore_interfaces) = &self.ignore_interfaces {
@@ -74,30 +485,116 @@ impl Config {
         }
     }
 
-    /// Indicates whether the specified address should be used.
-    pub(crate) fn allows_address(&self, addr: &IpAddr) -> bool {
-        if !addr.is_global() && !self.allow_nonroutable {
-            false
+    /// The address class used for zones that don't override it.
+    pub(crate) fn default_address_class(&self) -> AddressClass {
+        if self.allow_nonroutable {
+            AddressClass::Any
         } else {
-            self.address_type.allows_address(addr)
+            AddressClass::Routable
+        }
+    }
+
+    /// Indicates whether the address source is enabled globally.
+    pub(crate) fn uses_source(&self, source: AddressSource) -> bool {
+        match source {
+            AddressSource::Interfaces => self.query_interfaces,
+            AddressSource::IpService => self.query_ip_service,
         }
     }
 
+    /// Indicates whether the address source needs to be queried, either because it is enabled globally or because a
+    /// zone explicitly uses it.
+    pub(crate) fn queries_source(&self, source: AddressSource) -> bool {
+        self.uses_source(source)
+            || self.route53_zones.iter().any(|r53_zc| r53_zc.sources.as_ref().is_some_and(|s| s.contains(&source)))
+    }
+
+    /// Indicates whether the specified discovered address should be published to the zone.
+    pub(crate) fn zone_allows_address(&self, zone: &Route53ZoneConfig, addr: &DiscoveredAddress) -> bool {
+        let address_class = zone.address_class.unwrap_or_else(|| self.default_address_class());
+        let uses_source = match &zone.sources {
+            Some(sources) => sources.contains(&addr.source),
+            None => self.uses_source(addr.source),
+        };
+
+        let address_type = zone.address_type.unwrap_or(self.address_type);
+        uses_source && address_class.allows_address(&addr.address) && address_type.allows_address(&addr.address)
+    }
+
+    /// The timeout for listing the network interfaces' addresses.
+    pub(crate) fn interfaces_timeout(&self) -> Duration {
+        self.timeouts.interfaces.unwrap_or(self.timeout)
+    }
+
+    /// The timeout for each request to a STUN server.
+    pub(crate) fn stun_timeout(&self) -> Duration {
+        self.timeouts.stun.unwrap_or(self.timeout)
+    }
+
+    /// The timeout for each query of the IP service.
+    pub(crate) fn ip_service_timeout(&self) -> Duration {
+        self.timeouts.ip_service.unwrap_or(self.timeout)
+    }
+
+    /// The types of addresses to discover: the global address type plus any that zones override it with.
+    pub(crate) fn queried_address_type(&self) -> QueryAddressType {
+        self.route53_zones
+            .iter()
+            .filter_map(|r53_zc| r53_zc.address_type)
+            .fold(self.address_type, QueryAddressType::union)
+    }
+
+    /// Returns the discovered addresses that may be published to the zone, split into IPv4 and IPv6 addresses.
+    pub(crate) fn zone_candidates<'a>(
+        &self,
+        zone: &Route53ZoneConfig,
+        discovered: &'a HashSet<DiscoveredAddress>,
+    ) -> (Vec<&'a DiscoveredAddress>, Vec<&'a DiscoveredAddress>) {
+        discovered.iter().filter(|addr| self.zone_allows_address(zone, addr)).partition(|addr| addr.address.is_ipv4())
+    }
+
+    /// Returns the enabled zones followed by the zones their hostnames are mirrored to.
+    pub(crate) fn zones_with_mirrors(&self) -> Vec<Route53ZoneConfig> {
+        Route53ZoneConfig::with_mirrors(self.enabled_zones().cloned().collect())
+    }
+
+    /// Returns the zones that are updated, without their mirrors.
+    pub(crate) fn enabled_zones(&self) -> impl Iterator<Item = &Route53ZoneConfig> {
+        self.route53_zones.iter().filter(|zone| zone.enabled)
+    }
+
+    /// Returns the zones with `enabled: false`, which are skipped along with their mirrors.
+    pub(crate) fn disabled_zones(&self) -> impl Iterator<Item = &Route53ZoneConfig> {
+        self.route53_zones.iter().filter(|zone| !zone.enabled)
+    }
+
     /// Updates the configuration using the specified arguments from the command line.
     pub(crate) fn update_from_args(&mut self, args: Args) {
         if let Some(address_type) = args.address_type {
             self.address_type = address_type;
         }
 
-        if let Some(allow_nonroutable) = args.allow_nonroutable {
+        if let Some(allow_nonroutable) = args.allow_nonroutable.or(args.no_allow_nonroutable) {
             self.allow_nonroutable = allow_nonroutable;
         }
 
-        if let Some(query_interfaces) = args.query_interfaces {
+        if let Some(single_address) = args.single_address.or(args.no_single_address) {
+            self.single_address = single_address;
+        }
+
+        if let Some(address_ranking) = args.address_ranking {
+            self.address_ranking = address_ranking;
+        }
+
+        if let Some(sticky_address) = args.sticky_address.or(args.no_sticky_address) {
+            self.sticky_address = sticky_address;
+        }
+
+        if let Some(query_interfaces) = args.query_interfaces.or(args.no_query_interfaces) {
             self.query_interfaces = query_interfaces;
         }
 
-        if let Some(query_ip_service) = args.query_ip_service {
+        if let Some(query_ip_service) = args.query_ip_service.or(args.no_query_ip_service) {
             self.query_ip_service = query_ip_service;
         }
 
@@ -110,26 +607,252 @@ impl Config {
             self.ip_service = ip_service;
         }
 
+        if let Some(retries) = args.ip_service_retries {
+            self.ip_service_retries = retries;
+        }
+
+        if let Some(backoff) = args.ip_service_backoff {
+            self.ip_service_backoff = *backoff;
+        }
+
+        if let Some(nat64) = args.nat64.or(args.no_nat64) {
+            self.nat64 = nat64;
+        }
+
+        if let Some(nat64_prefix) = args.nat64_prefix {
+            self.nat64_prefix = Some(nat64_prefix);
+        }
+
+        if let Some(detect_nat) = args.detect_nat.or(args.no_detect_nat) {
+            self.detect_nat = detect_nat;
+        }
+
+        if !args.stun_servers.is_empty() {
+            self.stun_servers = args.stun_servers.clone();
+        }
+
         if let Some(timeout) = args.timeout {
             self.timeout = *timeout;
         }
 
+        if let Some(rate_limit) = args.route53_rate_limit {
+            self.route53_rate_limit = rate_limit;
+        }
+
+        if let Some(timeout) = args.interfaces_timeout {
+            self.timeouts.interfaces = Some(*timeout);
+        }
+
+        if let Some(timeout) = args.ip_service_timeout {
+            self.timeouts.ip_service = Some(*timeout);
+        }
+
+        if let Some(timeout) = args.route53_timeout {
+            self.timeouts.route53 = Some(*timeout);
+        }
+
+        if let Some(timeout) = args.stun_timeout {
+            self.timeouts.stun = Some(*timeout);
+        }
+
         if let Some(ttl) = args.ttl {
             self.ttl = Some(ttl);
         }
 
-        if let Some(zone_id) = args.route53_zone {
-            // Get the zone config.
-            let r53_zc = self.get_or_create_zone_config(&zone_id);
+        if let Some(min_ttl) = args.min_ttl {
+            self.min_ttl = min_ttl;
+        }
+
+        if let Some(max_ttl) = args.max_ttl {
+            self.max_ttl = max_ttl;
+        }
+
+        if let Some(clamp_ttl) = args.clamp_ttl.or(args.no_clamp_ttl) {
+            self.clamp_ttl = clamp_ttl;
+        }
+
+        if let Some(profile) = args.profile {
+            self.profile = Some(profile);
+        }
+
+        if let Some(region) = args.region {
+            self.region = Some(region);
+        }
+
+        if let Some(aws_endpoint_url) = args.aws_endpoint_url {
+            self.aws_endpoint_url = Some(aws_endpoint_url);
+        }
+
+        if let Some(mfa_token_command) = args.mfa_token_command {
+            self.mfa_token_command = Some(mfa_token_command);
+        }
+
+        if let Some(mfa_token) = args.mfa_token {
+            self.mfa_token = Some(mfa_token);
+        }
+
+        self.interactive = args.interactive;
+
+        if let Some(use_fips) = args.use_fips.or(args.no_use_fips) {
+            self.use_fips = use_fips;
+        }
+
+        if let Some(use_dual_stack) = args.use_dual_stack.or(args.no_use_dual_stack) {
+            self.use_dual_stack = use_dual_stack;
+        }
+
+        if let Some(prechange_ttl) = args.prechange_ttl {
+            self.prechange_ttl = Some(prechange_ttl);
+        }
+
+        if let Some(change_comment) = args.change_comment {
+            self.change_comment = change_comment;
+        }
+
+        if let Some(interval) = args.interval {
+            self.interval = Some(*interval);
+        }
+
+        if let Some(schedule) = args.schedule {
+            self.schedule = Some(schedule);
+        }
+
+        if let Some(jitter) = args.jitter {
+            self.jitter = Some(*jitter);
+        }
+
+        if let Some(cooldown) = args.cooldown {
+            self.cooldown = Some(*cooldown);
+        }
+
+        if let Some(debounce) = args.debounce {
+            self.debounce = Some(*debounce);
+        }
+
+        if let Some(max_runtime) = args.max_runtime {
+            self.max_runtime = Some(*max_runtime);
+        }
+
+        if let Some(state_file) = args.state_file {
+            self.state_file = Some(state_file);
+        }
+
+        if let Some(snapshot_dir) = args.snapshot_dir {
+            self.snapshot_dir = Some(snapshot_dir);
+        }
+
+        if let Some(monitor_only) = args.monitor_only.or(args.no_monitor_only) {
+            self.monitor_only = monitor_only;
+        }
+
+        if let Some(drift_webhook) = args.drift_webhook {
+            self.drift_webhook = Some(drift_webhook);
+        }
+
+        if let Some(event_bus) = args.event_bus {
+            self.event_bus = Some(event_bus);
+        }
+
+        if let Some(timestamp_records) = args.timestamp_records.or(args.no_timestamp_records) {
+            self.timestamp_records = timestamp_records;
+        }
+
+        if let Some(heartbeat) = args.heartbeat.or(args.no_heartbeat) {
+            self.heartbeat = heartbeat;
+        }
+
+        if let Some(table) = args.lock_table {
+            match &mut self.lock {
+                Some(lock) => lock.table = table,
+                None => {
+                    self.lock = Some(LockConfig {
+                        table,
+                        lease: LockConfig::default_lease(),
+                    })
+                }
+            }
+        }
+
+        if let Some(table) = args.leader_election_table {
+            match &mut self.leader_election {
+                Some(leader_election) => leader_election.table = table,
+                None => {
+                    self.leader_election = Some(LeaderElectionConfig {
+                        table,
+                        lease: LockConfig::default_lease(),
+                        group: LeaderElectionConfig::default_group(),
+                    })
+                }
+            }
+        }
+
+        if let Some(log_group) = args.cloudwatch_log_group {
+            match &mut self.cloudwatch_logs {
+                Some(cloudwatch_logs) => cloudwatch_logs.log_group = log_group,
+                None => {
+                    self.cloudwatch_logs = Some(CloudWatchLogsConfig {
+                        log_group,
+                        log_stream: None,
+                    })
+                }
+            }
+        }
+
+        if let Some(address) = args.statsd {
+            match &mut self.statsd {
+                Some(statsd) => statsd.address = address,
+                None => {
+                    self.statsd = Some(StatsdConfig {
+                        address,
+                        prefix: StatsdConfig::default_prefix(),
+                        dogstatsd: false,
+                        tags: Vec::new(),
+                    })
+                }
+            }
+        }
+
+        if let Some(remove_on_exit) = args.remove_on_exit.or(args.no_remove_on_exit) {
+            self.remove_on_exit = remove_on_exit;
+        }
+
+        if let Some(max_consecutive_failures) = args.max_consecutive_failures {
+            self.max_consecutive_failures = Some(max_consecutive_failures);
+        }
+
+        if let Some(retries) = args.retries {
+            self.retries = retries;
+        }
+
+        if let Some(retry_backoff) = args.retry_backoff {
+            self.retry_backoff = *retry_backoff;
+        }
+
+        if let Some(status_listen) = args.status_listen {
+            self.status_listen = Some(status_listen);
+        }
+
+        for (zone_id, hostnames) in args.zone_hostnames {
+            if let Some(zone_id) = zone_id {
+                // Get the zone config.
+                let r53_zc = self.get_or_create_zone_config(&zone_id);
 
-            // Set any hostnames needed.
-            for hostname in args.hostnames {
-                r53_zc.add_hostname(&hostname);
+                // Set any hostnames needed.
+                for hostname in hostnames {
+                    r53_zc.add_hostname_config(hostname);
+                }
+            } else {
+                // Without a zone, the zone for each hostname is discovered automatically.
+                for hostname in hostnames {
+                    if !self.hostnames.iter().any(|h| h.get_hostname() == hostname.get_hostname()) {
+                        self.hostnames.push(hostname);
+                    }
+                }
             }
         }
     }
 
-    fn get_or_create_zone_config(&mut self, zone_id: &str) -> &mut Route53ZoneConfig {
+    pub(crate) fn get_or_create_zone_config(&mut self, zone_id: &str) -> &mut Route53ZoneConfig {
         let pos = self.route53_zones.iter_mut().position(|r53_zc| r53_zc.zone_id == zone_id);
 
         // If it doesn't exist, add it.
@@ -137,9 +860,8 @@ impl Config {
             None => {
                 let old_len = self.route53_zones.len();
                 self.route53_zones.push(Route53ZoneConfig {
-                    zone_id: zone_id.to_string(),
-                    hostnames: Vec::new(),
                     ttl: self.ttl,
+                    ..Route53ZoneConfig::new(zone_id)
                 });
 
                 &mut self.route53_zones[old_len]
@@ -148,24 +870,333 @@ impl Config {
         }
     }
 
-    pub(crate) fn check(&self) -> Result<(), Route53IpUpdateError> {
-        let mut messages = Vec::new();
-
-        if self.query_ip_service && self.ip_service.is_empty() {
-            messages.push("The IP service cannot be empty if querying the IP service is enabled.".to_string());
+    /// If TTL clamping is enabled, forces all configured TTLs into the min/max bounds, warning about each one changed.
+    pub(crate) fn clamp_ttls(&mut self) {
+        if !self.clamp_ttl || self.min_ttl > self.max_ttl {
+            return;
         }
 
-        if self.route53_zones.is_empty() {
-            messages.push("No Route 53 zones have been configured.".to_string());
-        } else {
-            for r53_zc in &self.route53_zones {
-                if r53_zc.hostnames.is_empty() {
-                    messages.push(format!("No hostnames have been configured for zone {}.", r53_zc.zone_id));
-                }
+        let (min_ttl, max_ttl) = (self.min_ttl, self.max_ttl);
+        let clamp = |ttl: &mut Ttl, location: &str| {
+            let clamped = (*ttl).clamp(min_ttl, max_ttl);
+            if clamped != *ttl {
+                warn!("TTL {ttl} for {location} is outside of the allowed range {min_ttl}-{max_ttl}; using {clamped}");
+                *ttl = clamped;
             }
+        };
+
+        if let Some(ttl) = &mut self.ttl {
+            clamp(ttl, "the default TTL");
         }
 
-        if messages.is_empty() {
+        if let Some(ttl) = &mut self.prechange_ttl {
+            clamp(ttl, "the pre-change TTL");
+        }
+
+        for hostname in &mut self.hostnames {
+            if let HostnameConfig::HostnameAndTtl(hostname_and_ttl) = hostname {
+                let location = format!("hostname {}", hostname_and_ttl.hostname);
+                for ttl in [&mut hostname_and_ttl.ttl, &mut hostname_and_ttl.ipv4_ttl, &mut hostname_and_ttl.ipv6_ttl]
+                    .into_iter()
+                    .flatten()
+                {
+                    clamp(ttl, &location);
+                }
+            }
+        }
+
+        for r53_zc in &mut self.route53_zones {
+            if let Some(ttl) = &mut r53_zc.ttl {
+                clamp(ttl, &format!("zone {}", r53_zc.zone_id));
+            }
+
+            for hostname in &mut r53_zc.hostnames {
+                if let HostnameConfig::HostnameAndTtl(hostname_and_ttl) = hostname {
+                    let location = format!("hostname {}", hostname_and_ttl.hostname);
+                    for ttl in
+                        [&mut hostname_and_ttl.ttl, &mut hostname_and_ttl.ipv4_ttl, &mut hostname_and_ttl.ipv6_ttl]
+                            .into_iter()
+                            .flatten()
+                    {
+                        clamp(ttl, &location);
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn check(&self) -> Result<(), Route53IpUpdateError> {
+        let mut messages = Vec::new();
+
+        if let Some(lock) = &self.lock {
+            // The lease is renewed every third of its duration, so it can't be shorter than a few seconds.
+            if lock.lease < Duration::from_secs(3) {
+                messages.push("The lock lease must be at least 3 seconds.".to_string());
+            }
+        }
+
+        if let Some(leader_election) = &self.leader_election {
+            if leader_election.lease < Duration::from_secs(3) {
+                messages.push("The leader election lease must be at least 3 seconds.".to_string());
+            }
+        }
+
+        if self.policies.max_addresses == Some(0) {
+            messages.push("The max-addresses policy must allow at least 1 address.".to_string());
+        }
+
+        if let (Some(min_ttl), Some(max_ttl)) = (self.policies.min_ttl, self.policies.max_ttl) {
+            if min_ttl > max_ttl {
+                messages.push(format!("The min-ttl policy ({min_ttl}) is larger than the max-ttl policy ({max_ttl})."));
+            }
+        }
+
+        if let Some(verify) = &self.verify {
+            if !verify.test_dns_answer && verify.probe_port.is_none() {
+                messages.push("verify has nothing to check: set test-dns-answer or probe-port.".to_string());
+            }
+        }
+
+        if let Some(statsd) = self.statsd.as_ref().filter(|statsd| !statsd.dogstatsd && !statsd.tags.is_empty()) {
+            messages.push(format!(
+                "statsd.tags ({}) can only be sent to a DogStatsD server; set statsd.dogstatsd.",
+                statsd.tags.join(", ")
+            ));
+        }
+
+        if let Some(Err(e)) = self.nat64_prefix.as_deref().map(str::parse::<Nat64Prefix>) {
+            messages.push(format!("{e}: it must be an IPv6 prefix of length 32, 40, 48, 56, 64, or 96."));
+        }
+
+        if !(self.route53_rate_limit >= 0.0 && self.route53_rate_limit.is_finite()) {
+            messages.push(
+                "The Route 53 rate limit must be a number of requests per second, or 0 for no limit.".to_string(),
+            );
+        }
+
+        if self.detect_nat && self.stun_servers.is_empty() {
+            messages.push("NAT detection requires at least one STUN server.".to_string());
+        }
+
+        if self.min_ttl > self.max_ttl {
+            messages.push(format!(
+                "The minimum TTL ({}) is greater than the maximum TTL ({}).",
+                self.min_ttl, self.max_ttl
+            ));
+        } else {
+            let mut check_ttl = |ttl: Option<Ttl>, location: &str| {
+                if let Some(ttl) = ttl {
+                    if ttl < self.min_ttl || ttl > self.max_ttl {
+                        messages.push(format!(
+                            "TTL {ttl} for {location} is outside of the allowed range {}-{}.",
+                            self.min_ttl, self.max_ttl
+                        ));
+                    }
+                }
+            };
+
+            check_ttl(self.ttl, "the default TTL");
+            check_ttl(self.prechange_ttl, "the pre-change TTL");
+            for hostname in &self.hostnames {
+                let location = format!("hostname {}", hostname.get_hostname());
+                check_ttl(hostname.get_ttl(), &location);
+                check_ttl(hostname.get_ipv4_ttl(), &location);
+                check_ttl(hostname.get_ipv6_ttl(), &location);
+            }
+            for r53_zc in &self.route53_zones {
+                check_ttl(r53_zc.ttl, &format!("zone {}", r53_zc.zone_id));
+                for hostname in &r53_zc.hostnames {
+                    let location = format!("hostname {}", hostname.get_hostname());
+                    check_ttl(hostname.get_ttl(), &location);
+                    check_ttl(hostname.get_ipv4_ttl(), &location);
+                    check_ttl(hostname.get_ipv6_ttl(), &location);
+                }
+            }
+        }
+
+        if self.interval.is_some() && self.schedule.is_some() {
+            messages.push("Only one of interval and schedule can be set.".to_string());
+        }
+
+        if let Some(schedule) = &self.schedule {
+            if let Err(e) = parse_cron(schedule) {
+                messages.push(format!("{}.", e.to_string().trim_end_matches('.')));
+            }
+        }
+
+        if self.max_consecutive_failures == Some(0) {
+            messages.push("max-consecutive-failures must be at least 1.".to_string());
+        }
+
+        if self.max_consecutive_failures.is_some() && self.interval.is_none() && self.schedule.is_none() {
+            messages.push(
+                "max-consecutive-failures only applies when running continuously with an interval or schedule."
+                    .to_string(),
+            );
+        }
+
+        for (key, windows) in [("change-windows", &self.change_windows), ("blackout-windows", &self.blackout_windows)] {
+            for problem in windows.iter().flat_map(TimeWindow::problems) {
+                messages.push(format!("{key}: {problem}."));
+            }
+        }
+
+        if self.retries > 0 && (self.interval.is_some() || self.schedule.is_some()) {
+            messages.push(
+                "retries only applies to a single update; when running continuously, the next update is the retry."
+                    .to_string(),
+            );
+        }
+
+        if self.remove_on_exit && self.interval.is_none() && self.schedule.is_none() {
+            messages.push(
+                "Records can only be removed on exit when running continuously with an interval or schedule."
+                    .to_string(),
+            );
+        }
+
+        if self.status_listen.is_some() && self.interval.is_none() && self.schedule.is_none() {
+            messages.push(
+                "The status endpoint is only served when running continuously with an interval or schedule."
+                    .to_string(),
+            );
+        }
+
+        if self.relay.is_some() && (self.interval.is_some() || self.schedule.is_some()) {
+            messages.push(
+                "A relay agent reports its addresses once and exits, so relay can't be used with interval or schedule; \
+                 run the agent periodically (e.g. from cron) instead."
+                    .to_string(),
+            );
+        }
+
+        if self.remove_on_exit && self.monitor_only {
+            messages.push("Records cannot be removed on exit in monitor-only mode.".to_string());
+        }
+
+        if self.queries_source(AddressSource::IpService) && self.ip_service.is_empty() {
+            messages.push("The IP service cannot be empty if querying the IP service is enabled.".to_string());
+        }
+
+        for tagged_zones in &self.tagged_zones {
+            if tagged_zones.tags.is_empty() {
+                messages.push("Tagged zone selectors must specify at least one tag.".to_string());
+            }
+
+            if tagged_zones.hostnames.is_empty() {
+                messages.push("No hostnames have been configured for a tagged zone selector.".to_string());
+            }
+        }
+
+        for hostname in &self.hostnames {
+            if hostname.get_zones().len() > 1 && !hostname.get_hostname().contains("{zone}") {
+                messages.push(format!(
+                    "Hostname {} is published to several zones, so it must contain {{zone}}.",
+                    hostname.get_hostname()
+                ));
+            }
+        }
+
+        let zone_hostnames = self.route53_zones.iter().flat_map(|r53_zc| &r53_zc.hostnames);
+        let tagged_hostnames = self.tagged_zones.iter().flat_map(|tagged_zones| &tagged_zones.hostnames);
+        for hostname in zone_hostnames.clone().chain(tagged_hostnames.clone()) {
+            if !hostname.get_zones().is_empty() {
+                messages.push(format!(
+                    "Hostname {} lists zones, which is only allowed for top-level hostnames.",
+                    hostname.get_hostname()
+                ));
+            }
+        }
+
+        for hostname in self.hostnames.iter().chain(zone_hostnames).chain(tagged_hostnames) {
+            if let HostnameConfig::HostnameAndTtl(hostname_and_ttl) = hostname {
+                messages.extend(hostname_and_ttl.check_routing());
+            }
+        }
+
+        if self.ec2_fleet.is_some()
+            && (self.relay.is_some()
+                || !self.route53_zones.is_empty()
+                || !self.hostnames.is_empty()
+                || !self.tagged_zones.is_empty())
+        {
+            messages.push(
+                "ec2-fleet publishes the instances' addresses rather than this host's, so it can't be used with relay, \
+                 route53-zones, hostnames, or tagged-zones."
+                    .to_string(),
+            );
+        }
+
+        // Agents don't update zones themselves; the relay server decides which hostnames they update. The fleet's
+        // hostnames come from its instances' tags.
+        if self.relay.is_none() && self.ec2_fleet.is_none() {
+            if self.route53_zones.is_empty() && self.hostnames.is_empty() && self.tagged_zones.is_empty() {
+                messages.push("No Route 53 zones or hostnames have been configured.".to_string());
+            } else {
+                for r53_zc in &self.route53_zones {
+                    if r53_zc.hostnames.is_empty() && r53_zc.manage_matching.is_none() {
+                        messages.push(format!("No hostnames have been configured for zone {}.", r53_zc.zone_id));
+                    }
+
+                    if r53_zc.manage_matching.is_some() && !r53_zc.is_route53() {
+                        messages.push(format!(
+                            "Zone {} uses manage-matching, which is only supported for zones hosted on Route 53.",
+                            r53_zc.zone_id
+                        ));
+                    }
+
+                    // Each mirror is checked like a zone of its own.
+                    for zone in once(r53_zc.clone()).chain(r53_zc.mirrors.iter().map(|m| r53_zc.mirror_zone(m))) {
+                        if zone.rfc2136.is_some() && !is_zone_name(&zone.zone_id) {
+                            messages.push(format!(
+                                "Zone {} uses RFC 2136 updates, so its zone ID must be the zone's domain name.",
+                                zone.zone_id
+                            ));
+                        }
+
+                        if zone.cloudflare.is_some() && !is_zone_name(&zone.zone_id) {
+                            messages.push(format!(
+                                "Zone {} is hosted on Cloudflare, so its zone ID must be the zone's domain name.",
+                                zone.zone_id
+                            ));
+                        }
+
+                        messages.extend(zone.check_hostnames_in_zone());
+
+                        if !zone.is_route53() {
+                            for hostname in zone.hostnames.iter().filter(|hostname| hostname.get_routing().is_some()) {
+                                messages.push(format!(
+                                    "Hostname {} has a routing policy, but zone {} isn't hosted on Route 53.",
+                                    hostname.get_hostname(),
+                                    zone.zone_id
+                                ));
+                            }
+                        }
+
+                        if zone.rfc2136.is_some() && zone.cloudflare.is_some() {
+                            messages.push(format!(
+                                "Zone {} cannot use both RFC 2136 updates and Cloudflare.",
+                                zone.zone_id
+                            ));
+                        }
+
+                        if zone.role_arn.is_none()
+                            && (zone.external_id.is_some()
+                                || zone.role_session_name.is_some()
+                                || zone.mfa_serial.is_some())
+                        {
+                            messages.push(format!(
+                                "An external ID, role session name, or MFA serial was specified for zone {} without a \
+                                 role ARN.",
+                                zone.zone_id
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        if messages.is_empty() {
             Ok(())
         } else {
             Err(Route53IpUpdateError::InvalidConfig(messages))
@@ -173,37 +1204,589 @@ impl Config {
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Serialize)]
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
 #[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct Route53ZoneConfig {
+    /// The hosted zone ID, or the domain name of the hosted zone (e.g. `example.com.`).
     pub(crate) zone_id: String,
 
+    /// The domain name of the hosted zone, once it has been resolved from a domain name.
+    #[serde(skip)]
+    pub(crate) zone_name: Option<String>,
+
+    /// When the zone is specified by domain name, whether to choose the private (`true`) or public (`false`) zone if
+    /// both exist.
+    pub(crate) private_zone: Option<bool>,
+
+    /// Whether this zone and its mirrors are updated. A disabled zone is skipped, but its configuration is kept.
+    #[serde(default = "Route53ZoneConfig::default_enabled")]
+    pub(crate) enabled: bool,
+
+    /// The class of addresses to publish to this zone, overriding `allow-nonroutable`.
+    pub(crate) address_class: Option<AddressClass>,
+
+    /// The types of addresses to publish to this zone, overriding `address-type`. Records of the other type are left
+    /// alone rather than removed.
+    pub(crate) address_type: Option<QueryAddressType>,
+
+    /// The address sources to publish to this zone, overriding `query-interfaces` and `query-ip-service`.
+    pub(crate) sources: Option<Vec<AddressSource>>,
+
+    /// The AWS profile to use for this zone's credentials.
+    pub(crate) profile: Option<String>,
+
+    /// The AWS region to use for this zone's client.
+    pub(crate) region: Option<String>,
+
+    /// The ARN of an IAM role to assume when updating this zone, e.g. for zones in other AWS accounts.
+    pub(crate) role_arn: Option<String>,
+
+    /// The external ID to pass when assuming `role-arn`.
+    pub(crate) external_id: Option<String>,
+
+    /// The session name to use when assuming `role-arn`.
+    pub(crate) role_session_name: Option<String>,
+
+    /// The serial number or ARN of the MFA device required to assume `role-arn`, if any.
+    pub(crate) mfa_serial: Option<String>,
+
     pub(crate) hostnames: Vec<HostnameConfig>,
 
+    /// If set, a pattern such as `*.dyn.example.com`, where `*` matches any characters, selecting existing A and AAAA
+    /// records in the zone to manage along with `hostnames`. The zone is listed on every update, so records created by
+    /// other tooling are picked up automatically. Only for zones hosted on Route 53.
+    pub(crate) manage_matching: Option<String>,
+
     /// The default TTL to use for all records.
     pub(crate) ttl: Option<Ttl>,
+
+    /// If set, this zone is hosted on a nameserver accepting RFC 2136 dynamic updates rather than on Route 53. The
+    /// zone ID must then be the zone's domain name.
+    pub(crate) rfc2136: Option<Rfc2136Config>,
+
+    /// If set, this zone is hosted on Cloudflare rather than on Route 53. The zone ID must then be the zone's domain
+    /// name.
+    pub(crate) cloudflare: Option<CloudflareConfig>,
+
+    /// Secondary zones, possibly at other providers, that this zone's hostnames are also published to. Each mirror is
+    /// updated independently, so a failure to update one doesn't affect the others or this zone.
+    #[serde(default)]
+    pub(crate) mirrors: Vec<MirrorConfig>,
 }
 
 impl Route53ZoneConfig {
-    fn add_hostname(&mut self, hostname: &str) {
+    /// Returns the configuration of a Route 53 zone with no hostnames and default settings.
+    pub(crate) fn new(zone_id: &str) -> Self {
+        Self {
+            zone_id: zone_id.to_string(),
+            zone_name: None,
+            private_zone: None,
+            enabled: true,
+            address_class: None,
+            address_type: None,
+            sources: None,
+            profile: None,
+            region: None,
+            role_arn: None,
+            external_id: None,
+            role_session_name: None,
+            mfa_serial: None,
+            hostnames: Vec::new(),
+            manage_matching: None,
+            ttl: None,
+            rfc2136: None,
+            cloudflare: None,
+            mirrors: Vec::new(),
+        }
+    }
+
+    pub(crate) fn default_enabled() -> bool {
+        true
+    }
+
+    /// Returns whether this zone is hosted on Route 53 rather than another provider.
+    pub(crate) fn is_route53(&self) -> bool {
+        self.rfc2136.is_none() && self.cloudflare.is_none()
+    }
+
+    /// Returns the types of addresses whose records are managed in this zone. Unless the zone overrides the address
+    /// type, records of both types are managed, and those for addresses not being published are removed.
+    pub(crate) fn managed_address_type(&self) -> QueryAddressType {
+        self.address_type.unwrap_or_default()
+    }
+
+    /// Returns the domain name of this zone, if it's known yet. Zones configured by hosted zone ID only learn their
+    /// names once they've been looked up.
+    pub(crate) fn known_zone_name(&self) -> Option<&str> {
+        match &self.zone_name {
+            Some(zone_name) => Some(zone_name),
+            None if is_zone_name(&self.zone_id) => Some(&self.zone_id),
+            None => None,
+        }
+    }
+
+    /// Returns a message for each hostname that isn't in this zone. Nothing is reported if the zone's name isn't known
+    /// yet.
+    pub(crate) fn check_hostnames_in_zone(&self) -> Vec<String> {
+        let Some(zone_name) = self.known_zone_name().map(normalize_domain_name) else {
+            return Vec::new();
+        };
+
+        self.hostnames
+            .iter()
+            .flat_map(|hc| hc.get_hostnames())
+            .filter(|hostname| !is_hostname_in_zone(hostname, &zone_name))
+            .map(|hostname| format!("Zone {zone_name} does not contain hostname {hostname}."))
+            .collect()
+    }
+
+    /// Returns the zones followed by the zones their hostnames are mirrored to.
+    pub(crate) fn with_mirrors(mut zones: Vec<Route53ZoneConfig>) -> Vec<Route53ZoneConfig> {
+        let mirrors: Vec<Route53ZoneConfig> =
+            zones.iter().flat_map(|zone| zone.mirrors.iter().map(|mirror| zone.mirror_zone(mirror))).collect();
+        zones.extend(mirrors);
+        zones
+    }
+
+    /// Returns the configuration for one of this zone's mirrors: the mirror's zone and provider settings, with this
+    /// zone's hostnames, TTL, and address selection.
+    pub(crate) fn mirror_zone(&self, mirror: &MirrorConfig) -> Route53ZoneConfig {
+        Route53ZoneConfig {
+            zone_id: mirror.zone_id.clone(),
+            zone_name: mirror.zone_name.clone(),
+            private_zone: mirror.private_zone,
+            enabled: self.enabled,
+            address_class: self.address_class,
+            address_type: self.address_type,
+            sources: self.sources.clone(),
+            profile: mirror.profile.clone(),
+            region: mirror.region.clone(),
+            role_arn: mirror.role_arn.clone(),
+            external_id: mirror.external_id.clone(),
+            role_session_name: mirror.role_session_name.clone(),
+            mfa_serial: mirror.mfa_serial.clone(),
+            hostnames: self.hostnames.clone(),
+            manage_matching: None,
+            ttl: self.ttl,
+            rfc2136: mirror.rfc2136.clone(),
+            cloudflare: mirror.cloudflare.clone(),
+            mirrors: Vec::new(),
+        }
+    }
+
+    /// Replaces `{zone}` in this zone's hostnames with its domain name, if that's known yet.
+    pub(crate) fn expand_zone_placeholder(&mut self) {
+        if let Some(zone_name) = self.known_zone_name().map(|name| name.trim_end_matches('.').to_string()) {
+            self.hostnames =
+                self.hostnames.iter().map(|hc| hc.map_hostnames(|h| h.replace("{zone}", &zone_name))).collect();
+        }
+    }
+
+    pub(crate) fn add_hostname_config(&mut self, hostname_config: HostnameConfig) {
         // Does this hostname exist?
-        if self.hostnames.iter().any(|h| h.get_hostname() == hostname) {
+        if self.hostnames.iter().any(|h| h.get_hostname() == hostname_config.get_hostname()) {
             return;
         }
 
         // Nope; add it.
-        self.hostnames.push(HostnameConfig::HostnameOnly(hostname.to_string()));
+        self.hostnames.push(hostname_config);
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Serialize)]
+/// A secondary zone that a zone's hostnames are mirrored to.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct MirrorConfig {
+    /// The hosted zone ID, or the domain name of the zone (e.g. `example.com.`).
+    pub(crate) zone_id: String,
+
+    /// The domain name of the hosted zone, once it has been resolved from a domain name.
+    #[serde(skip)]
+    pub(crate) zone_name: Option<String>,
+
+    /// When the zone is specified by domain name, whether to choose the private (`true`) or public (`false`) zone if
+    /// both exist.
+    pub(crate) private_zone: Option<bool>,
+
+    /// The AWS profile to use for this zone's credentials.
+    pub(crate) profile: Option<String>,
+
+    /// The AWS region to use for this zone's client.
+    pub(crate) region: Option<String>,
+
+    /// The ARN of an IAM role to assume when updating this zone, e.g. for zones in other AWS accounts.
+    pub(crate) role_arn: Option<String>,
+
+    /// The external ID to pass when assuming `role-arn`.
+    pub(crate) external_id: Option<String>,
+
+    /// The session name to use when assuming `role-arn`.
+    pub(crate) role_session_name: Option<String>,
+
+    /// The serial number or ARN of the MFA device required to assume `role-arn`, if any.
+    pub(crate) mfa_serial: Option<String>,
+
+    /// If set, this zone is hosted on a nameserver accepting RFC 2136 dynamic updates rather than on Route 53.
+    pub(crate) rfc2136: Option<Rfc2136Config>,
+
+    /// If set, this zone is hosted on Cloudflare rather than on Route 53.
+    pub(crate) cloudflare: Option<CloudflareConfig>,
+}
+
+/// Selects hosted zones by their AWS resource tags.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct TaggedZonesConfig {
+    /// The tags a hosted zone must have (all of them, with these values) to be selected.
+    pub(crate) tags: BTreeMap<String, String>,
+
+    /// The hostnames to update in each selected zone. `{zone}` is replaced by the zone's domain name.
+    pub(crate) hostnames: Vec<HostnameConfig>,
+
+    /// The default TTL to use for all records in the selected zones.
+    pub(crate) ttl: Option<Ttl>,
+}
+
+/// Settings for publishing the addresses of a fleet of EC2 instances.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct Ec2FleetConfig {
+    /// The tag holding the hostname, or comma-separated hostnames, to publish each running instance's addresses
+    /// under. The hosted zone for each hostname is discovered automatically.
+    #[serde(default = "Ec2FleetConfig::default_tag")]
+    pub(crate) tag: String,
+
+    /// Whether to publish each instance's private IPv4 address rather than its public IPv4 and IPv6 addresses.
+    #[serde(default)]
+    pub(crate) private: bool,
+}
+
+impl Ec2FleetConfig {
+    pub(crate) fn default_tag() -> String {
+        "dns-name".to_string()
+    }
+}
+
+/// Settings for the DynDNS2-compatible server.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct DyndnsServerConfig {
+    /// The address and port to listen on.
+    #[serde(default = "DyndnsServerConfig::default_listen")]
+    pub(crate) listen: SocketAddr,
+
+    /// The users allowed to send DynDNS2 updates, and the hostnames each may update.
+    #[serde(default)]
+    pub(crate) users: Vec<DyndnsUserConfig>,
+
+    /// The agents allowed to relay their discovered addresses, and the hostnames each may update.
+    #[serde(default)]
+    pub(crate) agents: Vec<RelayAgentConfig>,
+}
+
+impl DyndnsServerConfig {
+    pub(crate) fn default_listen() -> SocketAddr {
+        SocketAddr::from(([0, 0, 0, 0], 8245))
+    }
+}
+
+/// A user allowed to send DynDNS2 updates.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct DyndnsUserConfig {
+    /// The username sent by the client using HTTP basic authentication.
+    pub(crate) username: String,
+
+    /// The password sent by the client using HTTP basic authentication.
+    pub(crate) password: String,
+
+    /// The hostnames this user may update. Each must also be configured in a zone or in `hostnames`.
+    pub(crate) hostnames: Vec<String>,
+}
+
+/// Settings for sending RFC 2136 dynamic updates to a nameserver.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct Rfc2136Config {
+    /// The nameserver to send updates to, e.g. `192.0.2.53:53`.
+    pub(crate) server: SocketAddr,
+
+    /// The name of the TSIG key to sign updates with.
+    pub(crate) key_name: String,
+
+    /// The TSIG algorithm, e.g. `hmac-sha256`.
+    #[serde(default = "Rfc2136Config::default_key_algorithm")]
+    pub(crate) key_algorithm: String,
+
+    /// The base64-encoded TSIG key secret.
+    pub(crate) key_secret: String,
+}
+
+impl Rfc2136Config {
+    pub(crate) fn default_key_algorithm() -> String {
+        "hmac-sha256".to_string()
+    }
+}
+
+/// Settings for publishing a zone through the Cloudflare API.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct CloudflareConfig {
+    /// The API token to authenticate with. It needs the Zone.DNS edit permission for the zone.
+    pub(crate) api_token: String,
+
+    /// The Cloudflare zone ID. If unspecified, it is looked up from the zone's domain name, which also requires the
+    /// Zone.Zone read permission.
+    pub(crate) zone_id: Option<String>,
+
+    /// Whether records are proxied through Cloudflare. Defaults to false.
+    pub(crate) proxied: Option<bool>,
+}
+
+/// An agent allowed to relay its discovered addresses to the server.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct RelayAgentConfig {
+    /// The name the agent identifies itself with.
+    pub(crate) name: String,
+
+    /// The secret shared with the agent, used to sign its reports.
+    pub(crate) secret: String,
+
+    /// The hostnames updated with the agent's addresses. Each must also be configured in a zone or in `hostnames`.
+    pub(crate) hostnames: Vec<String>,
+}
+
+/// Settings for locking zones while they're updated.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct LockConfig {
+    /// The DynamoDB table holding the locks. Its partition key must be a string named `lock-id`.
+    pub(crate) table: String,
+
+    /// How long a lock is held before it expires if it isn't renewed, e.g. because the updater holding it died. Another
+    /// updater waits up to this long for a lock.
+    #[serde(with = "humantime_serde", default = "LockConfig::default_lease")]
+    #[schemars(with = "String")]
+    pub(crate) lease: Duration,
+}
+
+impl LockConfig {
+    pub(crate) fn default_lease() -> Duration {
+        Duration::from_secs(60)
+    }
+}
+
+/// Timeouts for the individual address sources and for Route 53.
+#[derive(Debug, Default, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct TimeoutsConfig {
+    /// How long to allow for listing the network interfaces' addresses, and for reading their lifetimes with `ip`.
+    /// Defaults to `timeout`.
+    #[serde(with = "humantime_serde", default)]
+    #[schemars(with = "Option<String>")]
+    pub(crate) interfaces: Option<Duration>,
+
+    /// How long to allow for each query of the IP service. Defaults to `timeout`.
+    #[serde(with = "humantime_serde", default)]
+    #[schemars(with = "Option<String>")]
+    pub(crate) ip_service: Option<Duration>,
+
+    /// How long to allow for each Route 53 operation, including its retries. If unset, the AWS SDK's own limits apply.
+    #[serde(with = "humantime_serde", default)]
+    #[schemars(with = "Option<String>")]
+    pub(crate) route53: Option<Duration>,
+
+    /// How long to wait for each STUN server to reply, for `detect-nat`. Defaults to `timeout`.
+    #[serde(with = "humantime_serde", default)]
+    #[schemars(with = "Option<String>")]
+    pub(crate) stun: Option<Duration>,
+}
+
+/// Settings for electing a leader among redundant updaters.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct LeaderElectionConfig {
+    /// The DynamoDB table holding the leader's lease. Its partition key must be a string named `lock-id`; it can be the
+    /// same table used for `lock`.
+    pub(crate) table: String,
+
+    /// How long the leader's lease lasts if it isn't renewed, e.g. because the leader died. A standby takes over at its
+    /// next update after the lease expires.
+    #[serde(with = "humantime_serde", default = "LockConfig::default_lease")]
+    #[schemars(with = "String")]
+    pub(crate) lease: Duration,
+
+    /// The name of the group of updaters electing a leader, so several groups can share a table.
+    #[serde(default = "LeaderElectionConfig::default_group")]
+    pub(crate) group: String,
+}
+
+impl LeaderElectionConfig {
+    pub(crate) fn default_group() -> String {
+        "default".to_string()
+    }
+}
+
+/// Rules the changes to each zone must follow before they're submitted.
+#[derive(Debug, Default, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct PoliciesConfig {
+    /// If set, addresses are only published once addresses of these types have been discovered.
+    pub(crate) require_addresses: Option<QueryAddressType>,
+
+    /// If set, the most addresses a hostname's A or AAAA record set may hold.
+    pub(crate) max_addresses: Option<usize>,
+
+    /// If set, the smallest TTL records may be published with.
+    pub(crate) min_ttl: Option<Ttl>,
+
+    /// If set, the largest TTL records may be published with.
+    pub(crate) max_ttl: Option<Ttl>,
+}
+
+/// Settings for checking Route 53 changes after they've propagated.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct VerifyConfig {
+    /// Whether to check, with TestDNSAnswer, that Route 53's name servers answer with the new addresses.
+    #[serde(default = "VerifyConfig::default_test_dns_answer")]
+    pub(crate) test_dns_answer: bool,
+
+    /// If set, a TCP port that must accept connections on each new address.
+    pub(crate) probe_port: Option<u16>,
+
+    /// How long to wait for each probe's connection to be accepted.
+    #[serde(with = "humantime_serde", default = "VerifyConfig::default_probe_timeout")]
+    #[schemars(with = "String")]
+    pub(crate) probe_timeout: Duration,
+
+    /// Whether to put the records back the way they were when the check fails.
+    #[serde(default = "VerifyConfig::default_rollback")]
+    pub(crate) rollback: bool,
+}
+
+impl VerifyConfig {
+    pub(crate) fn default_test_dns_answer() -> bool {
+        true
+    }
+
+    pub(crate) fn default_probe_timeout() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    pub(crate) fn default_rollback() -> bool {
+        true
+    }
+}
+
+/// A window of time, in local time, that repeats on the given days of the week.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct TimeWindow {
+    /// The days of the week the window starts on, e.g. `mon` or `monday`. Defaults to every day.
+    #[serde(default)]
+    pub(crate) days: Vec<String>,
+
+    /// When the window starts, as HH:MM.
+    pub(crate) start: String,
+
+    /// When the window ends, as HH:MM. If this is before the start, the window ends the next day.
+    pub(crate) end: String,
+}
+
+/// Settings for sending metrics to a statsd server.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct StatsdConfig {
+    /// The statsd server to send metrics to over UDP, as host:port.
+    pub(crate) address: String,
+
+    /// The prefix of each metric's name.
+    #[serde(default = "StatsdConfig::default_prefix")]
+    pub(crate) prefix: String,
+
+    /// Whether the server understands DogStatsD tags, which are then added to metrics about a zone.
+    #[serde(default)]
+    pub(crate) dogstatsd: bool,
+
+    /// DogStatsD tags (e.g. `env:home`) to add to every metric.
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+}
+
+impl StatsdConfig {
+    pub(crate) fn default_prefix() -> String {
+        "route53_ip_update".to_string()
+    }
+}
+
+/// Settings for sending log messages to CloudWatch Logs.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct CloudWatchLogsConfig {
+    /// The log group to send log messages to. It must already exist.
+    pub(crate) log_group: String,
+
+    /// The log stream to send log messages to, which is created if it doesn't exist. Defaults to this machine's
+    /// hostname.
+    pub(crate) log_stream: Option<String>,
+}
+
+/// Settings for sending discovered addresses to a relay server.
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct RelayConfig {
+    /// The URL of the relay server's report endpoint, e.g. `https://updater.example.com:8245/relay/update`.
+    pub(crate) url: String,
+
+    /// The name to identify this agent with.
+    pub(crate) agent: String,
+
+    /// The secret shared with the relay server, used to sign reports.
+    pub(crate) secret: String,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema, Serialize)]
 #[serde(untagged)]
 pub(crate) enum HostnameConfig {
     HostnameOnly(String),
-    HostnameAndTtl(HostnameAndTtlConfig),
+    HostnameAndTtl(Box<HostnameAndTtlConfig>),
 }
 
 impl HostnameConfig {
+    /// Parses a hostname given on the command line, optionally followed by a colon and its TTL, e.g.
+    /// `home.example.com:120` or `home.example.com:5m`.
+    pub fn from_arg(arg: &str) -> Result<Self, Route53IpUpdateError> {
+        let Some((hostname, ttl)) = arg.rsplit_once(':') else {
+            return Ok(HostnameConfig::HostnameOnly(arg.to_string()));
+        };
+
+        Ok(HostnameConfig::HostnameAndTtl(Box::new(HostnameAndTtlConfig {
+            hostname: hostname.to_string(),
+            ttl: Some(ttl.parse()?),
+            ..Default::default()
+        })))
+    }
+
     #[inline]
     pub fn get_hostname(&self) -> &str {
         match self {
@@ -216,14 +1799,217 @@ impl HostnameConfig {
     pub fn get_ttl(&self) -> Option<Ttl> {
         match self {
             HostnameConfig::HostnameOnly(_) => None,
-            HostnameConfig::HostnameAndTtl(hostname_and_ttl) => Some(hostname_and_ttl.ttl),
+            HostnameConfig::HostnameAndTtl(hostname_and_ttl) => hostname_and_ttl.ttl,
+        }
+    }
+
+    /// Returns the hostname to publish only IPv4 addresses to, if any.
+    pub fn get_ipv4_hostname(&self) -> Option<&str> {
+        match self {
+            HostnameConfig::HostnameOnly(_) => None,
+            HostnameConfig::HostnameAndTtl(hostname_and_ttl) => hostname_and_ttl.ipv4_hostname.as_deref(),
+        }
+    }
+
+    /// Returns the hostname to publish only IPv6 addresses to, if any.
+    pub fn get_ipv6_hostname(&self) -> Option<&str> {
+        match self {
+            HostnameConfig::HostnameOnly(_) => None,
+            HostnameConfig::HostnameAndTtl(hostname_and_ttl) => hostname_and_ttl.ipv6_hostname.as_deref(),
+        }
+    }
+
+    /// Returns how the hostname's Route 53 records are routed, if it uses a routing policy other than simple.
+    pub fn get_routing(&self) -> Option<RecordRouting> {
+        let HostnameConfig::HostnameAndTtl(hostname_and_ttl) = self else {
+            return None;
+        };
+
+        match hostname_and_ttl.routing_policy {
+            None | Some(RoutingPolicy::Simple) => None,
+            Some(policy) => Some(RecordRouting {
+                policy,
+                set_identifier: hostname_and_ttl.set_identifier.clone().unwrap_or_default(),
+                weight: hostname_and_ttl.weight,
+                region: hostname_and_ttl.region.clone(),
+                failover: hostname_and_ttl.failover,
+                health_check_id: hostname_and_ttl.health_check_id.clone(),
+            }),
+        }
+    }
+
+    /// Returns the zones a top-level hostname is published to, if they're listed rather than discovered.
+    pub fn get_zones(&self) -> &[String] {
+        match self {
+            HostnameConfig::HostnameOnly(_) => &[],
+            HostnameConfig::HostnameAndTtl(hostname_and_ttl) => &hostname_and_ttl.zones,
+        }
+    }
+
+    /// Returns a copy of this hostname configuration for publishing to the zone with the given domain name: `{zone}` in
+    /// its hostnames is replaced by the zone's name, and it's no longer spread across zones.
+    pub fn for_zone(&self, zone_name: &str) -> Self {
+        let zone_name = zone_name.trim_end_matches('.');
+        match self.map_hostnames(|hostname| hostname.replace("{zone}", zone_name)) {
+            HostnameConfig::HostnameAndTtl(hostname_and_ttl) => {
+                HostnameConfig::HostnameAndTtl(Box::new(HostnameAndTtlConfig {
+                    zones: Vec::new(),
+                    ..*hostname_and_ttl
+                }))
+            }
+            hostname_config => hostname_config,
+        }
+    }
+
+    /// Returns a copy of this hostname configuration with the hostname and its IPv4-only and IPv6-only hostnames, if
+    /// any, transformed.
+    pub fn map_hostnames(&self, f: impl Fn(&str) -> String) -> Self {
+        match self {
+            HostnameConfig::HostnameOnly(hostname) => HostnameConfig::HostnameOnly(f(hostname)),
+            HostnameConfig::HostnameAndTtl(hostname_and_ttl) => {
+                HostnameConfig::HostnameAndTtl(Box::new(HostnameAndTtlConfig {
+                    hostname: f(&hostname_and_ttl.hostname),
+                    ipv4_hostname: hostname_and_ttl.ipv4_hostname.as_deref().map(&f),
+                    ipv6_hostname: hostname_and_ttl.ipv6_hostname.as_deref().map(&f),
+                    ..(**hostname_and_ttl).clone()
+                }))
+            }
+        }
+    }
+
+    /// Returns the hostname followed by its IPv4-only and IPv6-only hostnames, if any.
+    pub fn get_hostnames(&self) -> impl Iterator<Item = &str> {
+        [Some(self.get_hostname()), self.get_ipv4_hostname(), self.get_ipv6_hostname()].into_iter().flatten()
+    }
+
+    /// Returns a copy of this hostname configuration with the hostname replaced.
+    pub fn with_hostname(&self, hostname: String) -> Self {
+        match self {
+            HostnameConfig::HostnameOnly(_) => HostnameConfig::HostnameOnly(hostname),
+            HostnameConfig::HostnameAndTtl(hostname_and_ttl) => {
+                HostnameConfig::HostnameAndTtl(Box::new(HostnameAndTtlConfig {
+                    hostname,
+                    ..(**hostname_and_ttl).clone()
+                }))
+            }
+        }
+    }
+
+    /// Returns the TTL to use for A records for this hostname, if one has been configured.
+    #[inline]
+    pub fn get_ipv4_ttl(&self) -> Option<Ttl> {
+        match self {
+            HostnameConfig::HostnameOnly(_) => None,
+            HostnameConfig::HostnameAndTtl(hostname_and_ttl) => hostname_and_ttl.ipv4_ttl.or(hostname_and_ttl.ttl),
+        }
+    }
+
+    /// Returns the TTL to use for AAAA records for this hostname, if one has been configured.
+    #[inline]
+    pub fn get_ipv6_ttl(&self) -> Option<Ttl> {
+        match self {
+            HostnameConfig::HostnameOnly(_) => None,
+            HostnameConfig::HostnameAndTtl(hostname_and_ttl) => hostname_and_ttl.ipv6_ttl.or(hostname_and_ttl.ttl),
         }
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Serialize)]
+#[derive(Debug, Default, Deserialize, Clone, JsonSchema, Serialize)]
 #[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct HostnameAndTtlConfig {
     pub(crate) hostname: String,
-    pub(crate) ttl: Ttl,
+
+    /// The TTL to use for both A and AAAA records.
+    pub(crate) ttl: Option<Ttl>,
+
+    /// The TTL to use for A records, overriding `ttl`.
+    pub(crate) ipv4_ttl: Option<Ttl>,
+
+    /// The TTL to use for AAAA records, overriding `ttl`.
+    pub(crate) ipv6_ttl: Option<Ttl>,
+
+    /// A hostname in the same zone to publish only the IPv4 addresses to, e.g. for checking connectivity over IPv4.
+    pub(crate) ipv4_hostname: Option<String>,
+
+    /// A hostname in the same zone to publish only the IPv6 addresses to.
+    pub(crate) ipv6_hostname: Option<String>,
+
+    /// For top-level hostnames, the zones (hosted zone IDs or domain names) to publish to instead of the one zone
+    /// containing the hostname. `{zone}` in the hostnames is replaced by each zone's domain name.
+    #[serde(default)]
+    pub(crate) zones: Vec<String>,
+
+    /// The Route 53 routing policy of this hostname's records: simple, weighted, latency, failover, or multivalue.
+    /// Defaults to simple. With any other policy, several updaters can publish the same hostname, each with its own
+    /// `set-identifier`, and the others' records are left alone.
+    pub(crate) routing_policy: Option<RoutingPolicy>,
+
+    /// Distinguishes this updater's records from others with the same hostname. Required by routing policies other
+    /// than simple.
+    pub(crate) set_identifier: Option<String>,
+
+    /// The weight of this updater's records, from 0 to 255, for the weighted routing policy.
+    pub(crate) weight: Option<i64>,
+
+    /// The AWS region this updater's records are served from (e.g. `us-west-2`), for the latency routing policy.
+    pub(crate) region: Option<String>,
+
+    /// Whether this updater's records are the primary or secondary, for the failover routing policy.
+    pub(crate) failover: Option<FailoverRole>,
+
+    /// The ID of a Route 53 health check deciding whether this updater's records are returned.
+    pub(crate) health_check_id: Option<String>,
+}
+
+impl HostnameAndTtlConfig {
+    /// Returns a message for each problem with the routing policy settings.
+    fn check_routing(&self) -> Vec<String> {
+        let hostname = &self.hostname;
+        let policy = self.routing_policy.unwrap_or_default();
+        let mut messages = Vec::new();
+
+        if policy == RoutingPolicy::Simple {
+            if self.set_identifier.is_some() || self.health_check_id.is_some() {
+                messages.push(format!(
+                    "Hostname {hostname} has a set identifier or health check, which require a routing policy other than simple."
+                ));
+            }
+        } else if self.set_identifier.as_deref().is_none_or(str::is_empty) {
+            messages.push(format!("Hostname {hostname} has a routing policy, so it needs a set identifier."));
+        }
+
+        match (policy, self.weight) {
+            (RoutingPolicy::Weighted, None) => {
+                messages.push(format!("Hostname {hostname} uses weighted routing, so it needs a weight."))
+            }
+            (RoutingPolicy::Weighted, Some(weight)) if !(0..=255).contains(&weight) => {
+                messages.push(format!("The weight of hostname {hostname} must be from 0 to 255."))
+            }
+            (RoutingPolicy::Weighted, _) | (_, None) => (),
+            (_, Some(_)) => {
+                messages.push(format!("Hostname {hostname} has a weight but doesn't use weighted routing."))
+            }
+        }
+
+        match (policy, &self.region) {
+            (RoutingPolicy::Latency, None) => {
+                messages.push(format!("Hostname {hostname} uses latency routing, so it needs a region."))
+            }
+            (RoutingPolicy::Latency, _) | (_, None) => (),
+            (_, Some(_)) => messages.push(format!("Hostname {hostname} has a region but doesn't use latency routing.")),
+        }
+
+        match (policy, self.failover) {
+            (RoutingPolicy::Failover, None) => messages.push(format!(
+                "Hostname {hostname} uses failover routing, so it needs failover set to primary or secondary."
+            )),
+            (RoutingPolicy::Failover, _) | (_, None) => (),
+            (_, Some(_)) => {
+                messages.push(format!("Hostname {hostname} has a failover role but doesn't use failover routing."))
+            }
+        }
+
+        messages
+    }
 }
@@ -0,0 +1,251 @@
+use {
+    crate::{
+        config::Config,
+        config_lint::{check_unknown_keys, check_unknown_zone_keys},
+        decrypt::{decrypt_age, decrypt_sops, is_age_encrypted, is_sops_encrypted},
+        env_vars::expand_env_vars,
+        error::{ClassifiedError, ErrorClass, Route53IpUpdateError},
+    },
+    log::debug,
+    serde_yaml::{Mapping, Value},
+    std::{
+        fmt::{Display, Formatter, Result as FmtResult},
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+    tokio::fs::{read, read_dir},
+    tower::BoxError,
+};
+
+/// The key used to match zones when merging configuration files.
+const ZONE_ID_KEY: &str = "zone-id";
+
+/// The key holding the list of Route 53 zones.
+const ROUTE53_ZONES_KEY: &str = "route53-zones";
+
+/// The key naming the directory of per-zone configuration files.
+const ZONES_DIR_KEY: &str = "zones-dir";
+
+/// The extension of a file encrypted with age, added after the extension of the file encrypted.
+const AGE_EXT: &str = "age";
+
+/// Indicates whether the path has an extension we know how to parse.
+pub(crate) fn has_config_ext(path: &Path) -> bool {
+    matches!(config_ext(path), Some("toml" | "json" | "yaml" | "yml"))
+}
+
+/// Returns the extension that determines how a configuration file is parsed. For a file encrypted with age, this is
+/// the extension before `.age`.
+fn config_ext(path: &Path) -> Option<&str> {
+    let ext = path.extension()?.to_str()?;
+    if ext == AGE_EXT {
+        Path::new(path.file_stem()?).extension()?.to_str()
+    } else {
+        Some(ext)
+    }
+}
+
+/// Expands the configuration paths given on the command line into the list of files to read, in order. Directories
+/// are expanded into the configuration files they contain, sorted by name.
+pub(crate) async fn expand_config_paths(paths: &[String]) -> Result<Vec<PathBuf>, BoxError> {
+    let mut result = Vec::new();
+
+    for path in paths {
+        let path = PathBuf::from(path);
+        if path.is_dir() {
+            let mut entries = Vec::new();
+            let mut dir = read_dir(&path).await?;
+            while let Some(entry) = dir.next_entry().await? {
+                let entry_path = entry.path();
+                if entry_path.is_file() && has_config_ext(&entry_path) {
+                    entries.push(entry_path);
+                }
+            }
+
+            entries.sort();
+            result.extend(entries);
+        } else {
+            result.push(path);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Reads a single configuration file into a generic value, decrypting it if it's encrypted with age or SOPS, expanding
+/// environment variable references, and rejecting unknown keys.
+pub(crate) async fn read_config_value(path: &Path, age_identity: Option<&Path>) -> Result<Value, BoxError> {
+    let value = read_value(path, age_identity).await?;
+    check_unknown_keys(&value)?;
+    Ok(value)
+}
+
+/// Reads a per-zone configuration file, which holds one zone's settings as an entry of `route53-zones` would, returning
+/// `None` if it's empty.
+async fn read_zone_value(path: &Path, age_identity: Option<&Path>) -> Result<Option<Value>, BoxError> {
+    let zone = read_value(path, age_identity).await?;
+    if zone.as_mapping().is_some_and(Mapping::is_empty) {
+        return Ok(None);
+    }
+
+    check_unknown_zone_keys(&zone)?;
+    Ok(Some(zone))
+}
+
+/// Reads the per-zone configuration files in a directory, sorted by name, into the `route53-zones` they add to the
+/// configuration.
+async fn read_zones_dir(dir: &Path, age_identity: Option<&Path>) -> Result<Value, BoxError> {
+    if !dir.is_dir() {
+        let message = format!("{ZONES_DIR_KEY} {} is not a directory.", dir.display());
+        return Err(Route53IpUpdateError::InvalidConfig(vec![message]).into());
+    }
+
+    let mut zones = Vec::new();
+    for path in expand_config_paths(&[dir.display().to_string()]).await? {
+        debug!("Reading zone configuration file {}", path.display());
+        let zone = read_zone_value(&path, age_identity)
+            .await
+            .map_err(|e| ClassifiedError::new(ErrorClass::of(e.as_ref()), e).context(path.display()))?;
+        zones.extend(zone);
+    }
+
+    let mut value = Mapping::new();
+    value.insert(ROUTE53_ZONES_KEY.into(), Value::Sequence(zones));
+    Ok(Value::Mapping(value))
+}
+
+/// Reads a configuration file into a generic value, decrypting it if needed and expanding environment variable
+/// references. An empty file is an empty mapping.
+async fn read_value(path: &Path, age_identity: Option<&Path>) -> Result<Value, BoxError> {
+    let Some(ext) = config_ext(path) else {
+        let ext = path.extension().map(|ext| ext.to_string_lossy().to_string());
+        return Err(Route53IpUpdateError::UnknownConfigFileExt(ext).into());
+    };
+
+    if !has_config_ext(path) {
+        return Err(Route53IpUpdateError::UnknownConfigFileExt(Some(ext.to_string())).into());
+    }
+
+    let mut file_contents = read(path).await?;
+    if is_age_encrypted(&file_contents) {
+        file_contents = decrypt_age(path, age_identity).await?;
+    } else if ext != "toml" && is_sops_encrypted(std::str::from_utf8(&file_contents)?) {
+        // SOPS only encrypts the values, so the file is still YAML or JSON; it doesn't handle TOML.
+        let format = if ext == "json" {
+            "json"
+        } else {
+            "yaml"
+        };
+        file_contents = decrypt_sops(path, format, age_identity).await?;
+    }
+
+    let file_contents = expand_env_vars(std::str::from_utf8(&file_contents)?)?;
+
+    let value = match ext {
+        "toml" => serde_yaml::to_value(toml::from_str::<toml::Value>(&file_contents)?)?,
+        _ => serde_yaml::from_str::<Value>(&file_contents)?,
+    };
+
+    // An empty YAML file is null; treat it as an empty configuration.
+    if value.is_null() {
+        return Ok(Value::Mapping(Mapping::new()));
+    }
+
+    Ok(value)
+}
+
+/// Merges `overlay` into `base`. Mappings are merged key by key, sequences are appended (with zones sharing a zone ID
+/// merged together), and anything else in `overlay` replaces the value in `base`.
+pub(crate) fn merge_config_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_config_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(overlay)) => {
+            for value in overlay {
+                let zone_id = value.get(ZONE_ID_KEY).cloned();
+                let existing = zone_id.and_then(|id| base.iter_mut().find(|v| v.get(ZONE_ID_KEY) == Some(&id)));
+
+                match existing {
+                    Some(existing) => merge_config_values(existing, value),
+                    None => {
+                        if !base.contains(&value) {
+                            base.push(value);
+                        }
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Reads and merges the configuration files, in order, followed by the zone files in `zones-dir` if it's set. Encrypted
+/// files are decrypted with the age identity, if given.
+pub(crate) async fn load_config_files(paths: &[String], age_identity: Option<&Path>) -> Result<Config, BoxError> {
+    let mut merged = Value::Mapping(Mapping::new());
+
+    for path in expand_config_paths(paths).await? {
+        debug!("Reading configuration file {}", path.display());
+        let value = read_config_value(&path, age_identity)
+            .await
+            .map_err(|e| ClassifiedError::new(ErrorClass::of(e.as_ref()), e).context(path.display()))?;
+        merge_config_values(&mut merged, value);
+    }
+
+    // Zone files are merged last, so a zone also configured in the main files gets the zone file's settings.
+    if let Some(zones_dir) = merged.get(ZONES_DIR_KEY).and_then(Value::as_str).map(PathBuf::from) {
+        let zones = read_zones_dir(&zones_dir, age_identity).await?;
+        merge_config_values(&mut merged, zones);
+    }
+
+    Ok(serde_yaml::from_value(merged)?)
+}
+
+/// The formats the effective configuration can be printed in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Display for ConfigFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Json => write!(f, "json"),
+            Self::Toml => write!(f, "toml"),
+            Self::Yaml => write!(f, "yaml"),
+        }
+    }
+}
+
+impl FromStr for ConfigFormat {
+    type Err = Route53IpUpdateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            _ => Err(Route53IpUpdateError::InvalidConfigFormat(s.to_string())),
+        }
+    }
+}
+
+/// Renders the configuration in the given format, with every setting present (including defaults).
+pub(crate) fn format_config(config: &Config, format: ConfigFormat) -> Result<String, BoxError> {
+    Ok(match format {
+        ConfigFormat::Json => serde_json::to_string_pretty(config)? + "\n",
+        // Going through a TOML value puts plain values ahead of tables, which the TOML serializer requires.
+        ConfigFormat::Toml => toml::to_string_pretty(&toml::Value::try_from(config)?)?,
+        ConfigFormat::Yaml => serde_yaml::to_string(config)?,
+    })
+}
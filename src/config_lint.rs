@@ -0,0 +1,292 @@
+use {
+    crate::{
+        config::{
+            CloudWatchLogsConfig, CloudflareConfig, Config, DyndnsServerConfig, DyndnsUserConfig, Ec2FleetConfig,
+            HostnameAndTtlConfig, LeaderElectionConfig, LockConfig, MirrorConfig, PoliciesConfig, RelayAgentConfig,
+            RelayConfig, Rfc2136Config, Route53ZoneConfig, StatsdConfig, TaggedZonesConfig, TimeWindow, TimeoutsConfig,
+            VerifyConfig,
+        },
+        error::Route53IpUpdateError,
+    },
+    serde::{
+        de::{self, Visitor},
+        forward_to_deserialize_any, Deserialize, Deserializer,
+    },
+    serde_yaml::Value,
+    std::fmt::{Display, Formatter, Result as FmtResult},
+};
+
+/// The minimum Jaro-Winkler similarity for a known key to be suggested as a replacement for an unknown key.
+const SUGGESTION_THRESHOLD: f64 = 0.8;
+
+/// Checks a configuration value for keys that don't correspond to any configuration setting, so typos aren't silently
+/// ignored. Each unknown key is reported along with the closest valid key, if there is a similar one.
+pub(crate) fn check_unknown_keys(value: &Value) -> Result<(), Route53IpUpdateError> {
+    let mut messages = Vec::new();
+    let config_fields = struct_fields::<Config>();
+    let tagged_zones_fields = struct_fields::<TaggedZonesConfig>();
+    let hostname_fields = struct_fields::<HostnameAndTtlConfig>();
+
+    check_mapping(value, "", config_fields, &mut messages);
+    check_hostnames(value.get("hostnames"), "hostnames", hostname_fields, &mut messages);
+
+    for (i, zone) in sequence_items(value.get("route53-zones")) {
+        check_zone(zone, &format!("route53-zones[{i}]."), &mut messages);
+    }
+
+    for (i, tagged_zones) in sequence_items(value.get("tagged-zones")) {
+        let path = format!("tagged-zones[{i}]");
+        check_mapping(tagged_zones, &path, tagged_zones_fields, &mut messages);
+        check_hostnames(tagged_zones.get("hostnames"), &format!("{path}.hostnames"), hostname_fields, &mut messages);
+    }
+
+    if let Some(dyndns_server) = value.get("dyndns-server") {
+        check_mapping(dyndns_server, "dyndns-server", struct_fields::<DyndnsServerConfig>(), &mut messages);
+        for (i, user) in sequence_items(dyndns_server.get("users")) {
+            let path = format!("dyndns-server.users[{i}]");
+            check_mapping(user, &path, struct_fields::<DyndnsUserConfig>(), &mut messages);
+        }
+
+        for (i, agent) in sequence_items(dyndns_server.get("agents")) {
+            let path = format!("dyndns-server.agents[{i}]");
+            check_mapping(agent, &path, struct_fields::<RelayAgentConfig>(), &mut messages);
+        }
+    }
+
+    if let Some(relay) = value.get("relay") {
+        check_mapping(relay, "relay", struct_fields::<RelayConfig>(), &mut messages);
+    }
+
+    check_section::<TimeoutsConfig>(value, "timeouts", &mut messages);
+    check_section::<PoliciesConfig>(value, "policies", &mut messages);
+    check_section::<StatsdConfig>(value, "statsd", &mut messages);
+    check_section::<CloudWatchLogsConfig>(value, "cloudwatch-logs", &mut messages);
+    check_section::<LockConfig>(value, "lock", &mut messages);
+    check_section::<LeaderElectionConfig>(value, "leader-election", &mut messages);
+    check_section::<VerifyConfig>(value, "verify", &mut messages);
+    for key in ["change-windows", "blackout-windows"] {
+        for (i, window) in sequence_items(value.get(key)) {
+            check_mapping(window, &format!("{key}[{i}]"), struct_fields::<TimeWindow>(), &mut messages);
+        }
+    }
+    check_section::<Ec2FleetConfig>(value, "ec2-fleet", &mut messages);
+
+    if messages.is_empty() {
+        Ok(())
+    } else {
+        Err(Route53IpUpdateError::UnknownConfigKeys(messages))
+    }
+}
+
+/// Checks a per-zone configuration file, which holds a single `route53-zones` entry, for unknown keys.
+pub(crate) fn check_unknown_zone_keys(value: &Value) -> Result<(), Route53IpUpdateError> {
+    let mut messages = Vec::new();
+    check_zone(value, "", &mut messages);
+
+    if messages.is_empty() {
+        Ok(())
+    } else {
+        Err(Route53IpUpdateError::UnknownConfigKeys(messages))
+    }
+}
+
+/// Checks a zone for unknown keys. The prefix, if any, ends with a dot.
+fn check_zone(zone: &Value, prefix: &str, messages: &mut Vec<String>) {
+    check_mapping(zone, prefix.trim_end_matches('.'), struct_fields::<Route53ZoneConfig>(), messages);
+    let hostname_fields = struct_fields::<HostnameAndTtlConfig>();
+    check_hostnames(zone.get("hostnames"), &format!("{prefix}hostnames"), hostname_fields, messages);
+    if let Some(rfc2136) = zone.get("rfc2136") {
+        check_mapping(rfc2136, &format!("{prefix}rfc2136"), struct_fields::<Rfc2136Config>(), messages);
+    }
+    if let Some(cloudflare) = zone.get("cloudflare") {
+        check_mapping(cloudflare, &format!("{prefix}cloudflare"), struct_fields::<CloudflareConfig>(), messages);
+    }
+
+    for (j, mirror) in sequence_items(zone.get("mirrors")) {
+        let path = format!("{prefix}mirrors[{j}]");
+        check_mapping(mirror, &path, struct_fields::<MirrorConfig>(), messages);
+        if let Some(rfc2136) = mirror.get("rfc2136") {
+            check_mapping(rfc2136, &format!("{path}.rfc2136"), struct_fields::<Rfc2136Config>(), messages);
+        }
+        if let Some(cloudflare) = mirror.get("cloudflare") {
+            let fields = struct_fields::<CloudflareConfig>();
+            check_mapping(cloudflare, &format!("{path}.cloudflare"), fields, messages);
+        }
+    }
+}
+
+/// Checks a top-level section holding a single struct for unknown keys.
+fn check_section<'de, T: Deserialize<'de>>(value: &Value, key: &str, messages: &mut Vec<String>) {
+    if let Some(section) = value.get(key) {
+        check_mapping(section, key, struct_fields::<T>(), messages);
+    }
+}
+
+fn sequence_items(value: Option<&Value>) -> impl Iterator<Item = (usize, &Value)> {
+    value.and_then(Value::as_sequence).into_iter().flatten().enumerate()
+}
+
+fn check_hostnames(value: Option<&Value>, path: &str, fields: &[&str], messages: &mut Vec<String>) {
+    for (i, hostname) in sequence_items(value) {
+        // Hostnames may also be plain strings, which have no keys to check.
+        check_mapping(hostname, &format!("{path}[{i}]"), fields, messages);
+    }
+}
+
+fn check_mapping(value: &Value, path: &str, fields: &[&str], messages: &mut Vec<String>) {
+    let Some(mapping) = value.as_mapping() else {
+        return;
+    };
+
+    for key in mapping.keys() {
+        let Some(key) = key.as_str() else {
+            messages.push(format!("Configuration key {key:?} in {path} is not a string."));
+            continue;
+        };
+
+        if fields.contains(&key) {
+            continue;
+        }
+
+        let full_key = if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{path}.{key}")
+        };
+
+        match suggest_key(key, fields) {
+            Some(suggestion) => {
+                messages.push(format!("Unknown configuration key {full_key} (did you mean {suggestion}?)."))
+            }
+            None => messages.push(format!("Unknown configuration key {full_key}.")),
+        }
+    }
+}
+
+/// Returns the known key most similar to the unknown key, if any is similar enough.
+fn suggest_key<'a>(key: &str, fields: &[&'a str]) -> Option<&'a str> {
+    let key = key.replace('_', "-").to_ascii_lowercase();
+    fields
+        .iter()
+        .map(|field| (*field, strsim::jaro_winkler(&key, field)))
+        .filter(|(_, score)| *score >= SUGGESTION_THRESHOLD)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(field, _)| field)
+}
+
+/// Returns the (serialized) field names of a struct deriving `Deserialize`.
+///
+/// This works by handing the type a deserializer that records the field list passed to `deserialize_struct` and then
+/// bails out, so the lists stay in sync with the structs automatically.
+fn struct_fields<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNamesDeserializer {
+        fields: &mut fields,
+    });
+    fields
+}
+
+struct FieldNamesDeserializer<'a> {
+    fields: &'a mut &'static [&'static str],
+}
+
+#[derive(Debug)]
+struct FieldNamesCaptured;
+
+impl Display for FieldNamesCaptured {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("field names captured")
+    }
+}
+
+impl std::error::Error for FieldNamesCaptured {}
+
+impl de::Error for FieldNamesCaptured {
+    fn custom<T: Display>(_msg: T) -> Self {
+        Self
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for FieldNamesDeserializer<'a> {
+    type Error = FieldNamesCaptured;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(FieldNamesCaptured)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.fields = fields;
+        Err(FieldNamesCaptured)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::check_unknown_keys, crate::error::Route53IpUpdateError, serde_yaml::Value};
+
+    #[test]
+    fn reports_unknown_keys_in_sections() {
+        let value: Value = serde_yaml::from_str(
+            "
+timeouts:
+  route53: 5s
+  ipservice: 5s
+policies:
+  max-address: 2
+statsd:
+  address: 127.0.0.1:8125
+  prefx: dns
+cloudwatch-logs:
+  log-groups: route53-ip-update
+lock:
+  table: locks
+  leas: 1m
+leader-election:
+  table: locks
+  groups: edge
+verify:
+  probe-ports: 443
+change-windows:
+  - day: [sat]
+    start: \"01:00\"
+    end: \"05:00\"
+blackout-windows:
+  - start: \"09:00\"
+    ends: \"17:00\"
+ec2-fleet:
+  privat: true
+",
+        )
+        .unwrap();
+
+        let Err(Route53IpUpdateError::UnknownConfigKeys(messages)) = check_unknown_keys(&value) else {
+            panic!("Expected unknown configuration keys");
+        };
+        assert_eq!(
+            messages,
+            [
+                "Unknown configuration key timeouts.ipservice (did you mean ip-service?).",
+                "Unknown configuration key policies.max-address (did you mean max-addresses?).",
+                "Unknown configuration key statsd.prefx (did you mean prefix?).",
+                "Unknown configuration key cloudwatch-logs.log-groups (did you mean log-group?).",
+                "Unknown configuration key lock.leas (did you mean lease?).",
+                "Unknown configuration key leader-election.groups (did you mean group?).",
+                "Unknown configuration key verify.probe-ports (did you mean probe-port?).",
+                "Unknown configuration key change-windows[0].day (did you mean days?).",
+                "Unknown configuration key blackout-windows[0].ends (did you mean end?).",
+                "Unknown configuration key ec2-fleet.privat (did you mean private?).",
+            ]
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use {
+    crate::config_file::has_config_ext,
+    notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher},
+    std::{
+        env::current_dir,
+        future::pending,
+        io::Result as IoResult,
+        path::{Path, PathBuf},
+        time::Duration,
+    },
+    tokio::{
+        sync::mpsc::{unbounded_channel, UnboundedReceiver},
+        time::timeout,
+    },
+    tower::BoxError,
+};
+
+/// How long the configuration files must go without changing before a change is reported. Editors and configuration
+/// management tools often write a file in several steps.
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Watches the configuration files and directories given on the command line for changes.
+pub(crate) struct ConfigWatcher {
+    // Dropping the watcher stops it, so it has to be kept around.
+    _watcher: RecommendedWatcher,
+    changes: UnboundedReceiver<()>,
+}
+
+impl ConfigWatcher {
+    /// Starts watching the configuration paths.
+    ///
+    /// Files are replaced rather than rewritten by many editors, so the directory containing each file is watched
+    /// rather than the file itself.
+    pub(crate) fn new(paths: &[String]) -> Result<Self, BoxError> {
+        let paths = paths.iter().map(absolute_path).collect::<IoResult<Vec<_>>>()?;
+        let watched = paths.clone();
+        let (sender, changes) = unbounded_channel();
+
+        let mut watcher = RecommendedWatcher::new(
+            move |event: notify::Result<Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+
+                if !matches!(event.kind, EventKind::Access(_))
+                    && event.paths.iter().any(|path| is_config_path(&watched, path))
+                {
+                    let _ = sender.send(());
+                }
+            },
+            notify::Config::default(),
+        )?;
+
+        for path in &paths {
+            let dir = if path.is_dir() {
+                path.as_path()
+            } else {
+                path.parent().unwrap_or(path)
+            };
+
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    /// Waits for the configuration files to change, returning once they have settled.
+    pub(crate) async fn changed(&mut self) {
+        if self.changes.recv().await.is_none() {
+            return pending().await;
+        }
+
+        while let Ok(Some(())) = timeout(SETTLE_TIME, self.changes.recv()).await {}
+    }
+}
+
+/// Indicates whether a changed path is one of the configuration files, or a configuration file in one of the
+/// configuration directories.
+fn is_config_path(watched: &[PathBuf], changed: &Path) -> bool {
+    watched.iter().any(|path| path == changed || (changed.parent() == Some(path) && has_config_ext(changed)))
+}
+
+/// Makes a path absolute, dropping any `.` components so it can be compared with the paths in change events.
+fn absolute_path(path: &String) -> IoResult<PathBuf> {
+    Ok(current_dir()?.join(path).components().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::is_config_path, std::path::PathBuf};
+
+    #[test]
+    fn matches_watched_files_and_config_files_in_watched_directories() {
+        let watched = [PathBuf::from("/etc/route53-ip-update.yaml"), PathBuf::from("/etc/route53-ip-update.d")];
+
+        assert!(is_config_path(&watched, &PathBuf::from("/etc/route53-ip-update.yaml")));
+        assert!(is_config_path(&watched, &PathBuf::from("/etc/route53-ip-update.d/zones.toml")));
+        assert!(!is_config_path(&watched, &PathBuf::from("/etc/route53-ip-update.d/README.txt")));
+        assert!(!is_config_path(&watched, &PathBuf::from("/etc/hosts")));
+    }
+}
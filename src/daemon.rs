@@ -0,0 +1,264 @@
+use {
+    crate::{
+        args::Args,
+        aws::Route53Clients,
+        config::Config,
+        config_watch::ConfigWatcher,
+        drift::DRIFT_EXIT_CODE,
+        error::{ErrorClass, Route53IpUpdateError},
+        lock::LeaderElection,
+        rate_limit::ROUTE53_RATE_LIMITER,
+        remove_records,
+        schedule::Schedule,
+        shutdown::{shutdown_requested, shutdown_signal},
+        state::UpdateState,
+        status::serve_status,
+        update_if_leader,
+        windows::window_opened,
+        zones::{assign_hostnames_to_zones, assign_tagged_zones, check_hosted_zones, resolve_zone_ids},
+    },
+    log::{error, info, warn},
+    std::{future::pending, process::ExitCode},
+    tokio::time::timeout,
+    tower::BoxError,
+};
+
+/// Runs updates on the configured schedule until the process is stopped, or until `max-consecutive-failures` updates in
+/// a row have failed, returning the status of the last update. If `remove-on-exit` is set, the managed records are
+/// removed before returning.
+///
+/// On SIGHUP, or when a configuration file changes, the configuration files are read again. If the new configuration is
+/// valid, it's used from the next update on; otherwise the current configuration is kept. The Route 53 clients and the
+/// update state carry over either way.
+/// On SIGUSR1, an update is run right away instead of waiting for the next scheduled update.
+///
+/// With leader election, each update is skipped while another updater is the leader. Changes to the leader election
+/// settings take effect when route53-ip-update is restarted.
+///
+/// With `status-listen` set, the status of the updates is served as JSON, along with a dashboard, in the background.
+/// Changes to `status-listen` and `status-token` also take effect when route53-ip-update is restarted.
+pub(crate) async fn run_daemon(
+    args: Args,
+    mut config: Config,
+    mut route53_clients: Route53Clients,
+    mut state: UpdateState,
+    mut schedule: Schedule,
+    mut election: Option<LeaderElection>,
+) -> ExitCode {
+    let mut signals = match Signals::new() {
+        Ok(signals) => signals,
+        Err(e) => {
+            eprintln!("Error: Unable to handle signals: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut watcher = if args.config_files.is_empty() {
+        None
+    } else {
+        // Zone files are added and removed independently, so zones-dir is watched along with the configuration files.
+        let mut paths = args.config_files.clone();
+        paths.extend(config.zones_dir.as_ref().map(|dir| dir.display().to_string()));
+        match ConfigWatcher::new(&paths) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                warn!("Unable to watch the configuration files for changes: {e}");
+                None
+            }
+        }
+    };
+
+    if let Some(listen) = config.status_listen {
+        let status = state.status.clone();
+        let token = config.status_token.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_status(listen, status, token).await {
+                error!("Unable to serve the update status on {listen}: {e}");
+            }
+        });
+    }
+
+    info!("Updating {schedule}");
+    let mut consecutive_failures = 0;
+    loop {
+        // Failures have already been reported; keep going so a transient failure doesn't stop the updates.
+        let status = update_within_max_runtime(&config, &mut route53_clients, &mut state, &mut election).await;
+        if shutdown_requested() {
+            return shut_down(&config, &mut route53_clients, &state, election, status).await;
+        }
+
+        // Drift found in monitor-only mode is a finding, not a failure.
+        if status == ExitCode::SUCCESS || status == ExitCode::from(DRIFT_EXIT_CODE) {
+            consecutive_failures = 0;
+        } else {
+            consecutive_failures += 1;
+            if config.max_consecutive_failures.is_some_and(|max| consecutive_failures >= max) {
+                error!("{consecutive_failures} updates in a row have failed; exiting");
+                return shut_down(&config, &mut route53_clients, &state, election, status).await;
+            }
+        }
+
+        let event = tokio::select! {
+            _ = schedule.wait(config.jitter) => continue,
+            _ = shutdown_signal() => return shut_down(&config, &mut route53_clients, &state, election, status).await,
+            event = signals.recv() => event,
+            _ = window_opened(&config, !state.queued.is_empty()) => {
+                info!("Changes are no longer held");
+                DaemonEvent::Update
+            }
+            _ = config_changed(&mut watcher) => {
+                info!("Configuration files changed");
+                DaemonEvent::Reload
+            }
+        };
+
+        match event {
+            DaemonEvent::Reload => {
+                info!("Reloading the configuration");
+                match reload_config(&args, &mut route53_clients).await {
+                    Ok(new_config) => {
+                        // A configuration without a schedule keeps the current one.
+                        if let Ok(Some(new_schedule)) = Schedule::from_config(&new_config) {
+                            schedule = new_schedule;
+                        }
+
+                        config = new_config;
+                        info!("Configuration reloaded; updating {schedule}");
+                    }
+                    Err(e) => error!("Keeping the current configuration; the new configuration is invalid: {e}"),
+                }
+            }
+            DaemonEvent::Update => info!("Updating now"),
+        }
+    }
+}
+
+/// Runs one update, abandoning it if it takes longer than `max-runtime` so the next one can still run on time.
+async fn update_within_max_runtime(
+    config: &Config,
+    route53_clients: &mut Route53Clients,
+    state: &mut UpdateState,
+    election: &mut Option<LeaderElection>,
+) -> ExitCode {
+    let update = update_if_leader(config, route53_clients, state, election);
+    let Some(max_runtime) = config.max_runtime else {
+        return update.await;
+    };
+
+    match timeout(max_runtime, update).await {
+        Ok(status) => status,
+        Err(_) => {
+            let e = Route53IpUpdateError::MaxRuntimeExceeded(humantime::format_duration(max_runtime).to_string());
+            error!("{e}");
+            ErrorClass::MaxRuntimeExceeded.exit_code()
+        }
+    }
+}
+
+/// Waits for the configuration files to change. Without a watcher, this never returns.
+async fn config_changed(watcher: &mut Option<ConfigWatcher>) {
+    match watcher {
+        Some(watcher) => watcher.changed().await,
+        None => pending().await,
+    }
+}
+
+/// Removes the managed records if configured to, returning the exit status. With leader election, only the leader
+/// removes them, then resigns so a standby takes over straight away.
+async fn shut_down(
+    config: &Config,
+    route53_clients: &mut Route53Clients,
+    state: &UpdateState,
+    election: Option<LeaderElection>,
+    status: ExitCode,
+) -> ExitCode {
+    let leading = election.as_ref().is_none_or(LeaderElection::holds_lease);
+    let status = if !config.remove_on_exit || !leading {
+        status
+    } else {
+        info!("Removing records before exiting");
+        if remove_records(config, route53_clients, state).await {
+            status
+        } else {
+            ExitCode::FAILURE
+        }
+    };
+
+    if let Some(election) = election {
+        election.resign().await;
+    }
+
+    status
+}
+
+/// Reads and checks the configuration files again, resolving zones with the existing Route 53 clients.
+async fn reload_config(args: &Args, route53_clients: &mut Route53Clients) -> Result<Config, BoxError> {
+    let mut config = args.clone().into_config().await?;
+    config.clamp_ttls();
+    config.check()?;
+    ROUTE53_RATE_LIMITER.set_rate(config.route53_rate_limit);
+
+    if !route53_clients.uses_settings_from(&config) {
+        warn!(
+            "Changes to profile, region, aws-endpoint-url, use-fips, and use-dual-stack take effect when \
+             route53-ip-update is restarted"
+        );
+    }
+
+    let route53 = route53_clients.default_client().await?;
+    resolve_zone_ids(route53_clients, &mut config).await?;
+    assign_hostnames_to_zones(&route53, &mut config).await?;
+    assign_tagged_zones(&route53, &mut config).await?;
+    check_hosted_zones(route53_clients, &mut config).await?;
+    Ok(config)
+}
+
+/// Something other than the schedule that the daemon responds to.
+enum DaemonEvent {
+    /// SIGHUP or a configuration file change: reload the configuration.
+    Reload,
+
+    /// SIGUSR1: update now.
+    Update,
+}
+
+/// The signals the daemon responds to. Signals are only handled on Unix.
+struct Signals {
+    #[cfg(unix)]
+    hangup: tokio::signal::unix::Signal,
+
+    #[cfg(unix)]
+    user_defined1: tokio::signal::unix::Signal,
+}
+
+impl Signals {
+    #[cfg(unix)]
+    fn new() -> Result<Self, std::io::Error> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        Ok(Self {
+            hangup: signal(SignalKind::hangup())?,
+            user_defined1: signal(SignalKind::user_defined1())?,
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn new() -> Result<Self, std::io::Error> {
+        Ok(Self {})
+    }
+
+    /// Waits for the next signal.
+    #[cfg(unix)]
+    async fn recv(&mut self) -> DaemonEvent {
+        tokio::select! {
+            Some(()) = self.hangup.recv() => DaemonEvent::Reload,
+            Some(()) = self.user_defined1.recv() => DaemonEvent::Update,
+            else => pending().await,
+        }
+    }
+
+    #[cfg(not(unix))]
+    async fn recv(&mut self) -> DaemonEvent {
+        pending().await
+    }
+}
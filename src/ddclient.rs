@@ -0,0 +1,306 @@
+use {
+    crate::{ttl::Ttl, zones::normalize_domain_name},
+    humantime::parse_duration,
+    serde_yaml::{Mapping, Value},
+    std::collections::BTreeMap,
+};
+
+/// The settings in effect for a host in a ddclient configuration. Settings persist from line to line until changed.
+#[derive(Clone, Debug, Default)]
+struct DdclientSettings {
+    values: BTreeMap<String, String>,
+}
+
+impl DdclientSettings {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+}
+
+/// The result of converting a ddclient configuration.
+pub(crate) struct DdclientConversion {
+    /// The equivalent configuration, as YAML.
+    pub(crate) config: String,
+
+    /// Settings that could not be converted.
+    pub(crate) warnings: Vec<String>,
+}
+
+/// Converts the contents of a ddclient.conf file into an equivalent configuration.
+///
+/// Hosts with a `zone` setting are placed in that zone; others are listed under `hostnames` so their zones are
+/// discovered automatically. Settings with no equivalent (protocols, servers, credentials) are reported as warnings.
+pub(crate) fn convert_ddclient_config(contents: &str) -> Result<DdclientConversion, serde_yaml::Error> {
+    let mut warnings = Vec::new();
+    let mut settings = DdclientSettings::default();
+    let mut zones: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    let mut hostnames: Vec<Value> = Vec::new();
+    let mut unsupported: BTreeMap<String, String> = BTreeMap::new();
+
+    // The settings of the last host seen, which determine the global address discovery settings.
+    let mut last_settings = None;
+
+    for line in join_continuation_lines(contents) {
+        let mut hosts = Vec::new();
+        for item in split_items(&line) {
+            match item.split_once('=') {
+                Some((key, value)) => {
+                    let key = key.trim().to_ascii_lowercase();
+                    let value = unquote(value.trim()).to_string();
+                    settings.values.insert(key, value);
+                }
+                None => hosts.extend(item.split(',').map(str::trim).filter(|h| !h.is_empty()).map(String::from)),
+            }
+        }
+
+        for host in hosts {
+            for key in ["protocol", "server", "login", "password", "script", "mx", "wildcard"] {
+                if let Some(value) = settings.get(key) {
+                    let value = if key == "password" {
+                        "(hidden)"
+                    } else {
+                        value
+                    };
+                    unsupported.entry(key.to_string()).or_insert_with(|| value.to_string());
+                }
+            }
+
+            let hostname = host_value(&host, &settings);
+            match settings.get("zone") {
+                Some(zone) => {
+                    let zone_name = normalize_domain_name(zone);
+                    let hostname = if host.contains('.') {
+                        hostname
+                    } else {
+                        // ddclient allows hosts to be given relative to their zone.
+                        host_value(&format!("{host}.{zone_name}"), &settings)
+                    };
+
+                    zones.entry(zone_name).or_default().push(hostname);
+                }
+                None => hostnames.push(hostname),
+            }
+
+            last_settings = Some(settings.clone());
+        }
+    }
+
+    let settings = last_settings.unwrap_or(settings);
+    let mut root = Mapping::new();
+
+    match settings.get("use").or_else(|| settings.get("usev4")).or_else(|| settings.get("usev6")) {
+        Some("if" | "ifv4" | "ifv6") => {
+            root.insert("query-interfaces".into(), true.into());
+            root.insert("query-ip-service".into(), false.into());
+            if let Some(interface) = settings.get("if") {
+                warnings.push(format!(
+                    "ddclient reads addresses from interface {interface}; route53-ip-update queries all interfaces, so \
+                     use ignore-interfaces to exclude the others"
+                ));
+            }
+        }
+        Some("web" | "webv4" | "webv6") | None => {
+            root.insert("query-ip-service".into(), true.into());
+            if let Some(web) = settings.get("web").or_else(|| settings.get("webv4")) {
+                if web.starts_with("http://") || web.starts_with("https://") {
+                    root.insert("ip-service".into(), web.into());
+                } else {
+                    warnings.push(format!(
+                        "ddclient web service {web} is a built-in name; using the default IP service instead"
+                    ));
+                }
+            }
+        }
+        Some(other) => warnings
+            .push(format!("ddclient address source use={other} has no equivalent; using the IP service instead")),
+    }
+
+    let ipv4 = settings.get("usev4").is_some() || settings.get("use").is_some();
+    let ipv6 = settings.get("usev6").is_some() || settings.get("ipv6").is_some_and(is_true);
+    if ipv6 && !ipv4 {
+        root.insert("address-type".into(), "ipv6".into());
+    } else if ipv4 && !ipv6 {
+        root.insert("address-type".into(), "ipv4".into());
+    }
+
+    // ddclient takes the interval in seconds unless it has a unit suffix, and runs once if it's zero.
+    if let Some(daemon) = settings.get("daemon").filter(|daemon| !daemon.trim_start_matches('0').is_empty()) {
+        let interval = if daemon.bytes().all(|b| b.is_ascii_digit()) {
+            format!("{daemon}s")
+        } else {
+            daemon.to_string()
+        };
+
+        if parse_duration(&interval).is_ok() {
+            root.insert("interval".into(), interval.into());
+        } else {
+            warnings.push(format!(
+                "ddclient daemon interval {daemon} was not converted; run route53-ip-update periodically instead"
+            ));
+        }
+    }
+
+    for (key, value) in unsupported {
+        warnings.push(format!("ddclient setting {key}={value} has no equivalent and was not converted"));
+    }
+
+    if !hostnames.is_empty() {
+        root.insert("hostnames".into(), Value::Sequence(hostnames));
+    }
+
+    if !zones.is_empty() {
+        let zones = zones
+            .into_iter()
+            .map(|(zone_name, hostnames)| {
+                let mut zone = Mapping::new();
+                zone.insert("zone-id".into(), zone_name.into());
+                zone.insert("hostnames".into(), Value::Sequence(hostnames));
+                Value::Mapping(zone)
+            })
+            .collect();
+        root.insert("route53-zones".into(), Value::Sequence(zones));
+    }
+
+    Ok(DdclientConversion {
+        config: serde_yaml::to_string(&Value::Mapping(root))?,
+        warnings,
+    })
+}
+
+/// Returns the configuration value for a host: just its name, or its name and TTL if a TTL is set.
+fn host_value(host: &str, settings: &DdclientSettings) -> Value {
+    match settings.get("ttl").and_then(|ttl| ttl.parse::<Ttl>().ok()) {
+        Some(ttl) => {
+            let mut mapping = Mapping::new();
+            mapping.insert("hostname".into(), host.into());
+            mapping.insert("ttl".into(), i64::from(ttl).into());
+            Value::Mapping(mapping)
+        }
+        None => host.into(),
+    }
+}
+
+/// Strips comments and joins lines ending in a backslash with the following line.
+fn join_continuation_lines(contents: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for line in contents.lines() {
+        let line = strip_comment(line).trim_end();
+        match line.strip_suffix('\\') {
+            Some(line) => {
+                current.push_str(line);
+                current.push(' ');
+            }
+            None => {
+                current.push_str(line);
+                if !current.trim().is_empty() {
+                    lines.push(std::mem::take(&mut current));
+                }
+                current.clear();
+            }
+        }
+    }
+
+    if !current.trim().is_empty() {
+        lines.push(current);
+    }
+
+    lines
+}
+
+/// Removes a `#` comment from a line, ignoring `#` characters inside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('#', None) => return &line[..i],
+            _ => (),
+        }
+    }
+
+    line
+}
+
+/// Splits a line into its `key=value` settings and host lists. Settings are separated by commas; anything after the
+/// last setting that isn't part of one is the (comma-separated) list of hosts.
+fn split_items(line: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let chars: Vec<char> = line.chars().collect();
+
+    for (i, &c) in chars.iter().enumerate() {
+        // Whitespace around the = of a setting isn't significant.
+        if quote.is_none() && c.is_whitespace() {
+            let next = chars[i..].iter().find(|c| !c.is_whitespace());
+            if current.ends_with('=') || next == Some(&'=') {
+                continue;
+            }
+        }
+
+        match (c, quote) {
+            ('\'' | '"', None) => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (c, Some(q)) if c == q => {
+                quote = None;
+                current.push(c);
+            }
+            (_, None) if c == ',' || c.is_whitespace() => {
+                // A comma or whitespace ends a setting, unless it is separating hosts in a host list.
+                if current.contains('=') || c != ',' {
+                    if !current.trim().is_empty() {
+                        items.push(std::mem::take(&mut current));
+                    }
+                    current.clear();
+                } else {
+                    current.push(c);
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+
+    if !current.trim().is_empty() {
+        items.push(current);
+    }
+
+    items
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['\'', '"'] {
+        if let Some(inner) = value.strip_prefix(quote).and_then(|v| v.strip_suffix(quote)) {
+            return inner;
+        }
+    }
+
+    value
+}
+
+fn is_true(value: &str) -> bool {
+    matches!(value.to_ascii_lowercase().as_str(), "yes" | "true" | "1")
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::convert_ddclient_config, serde_yaml::Value};
+
+    #[test]
+    fn converts_daemon_interval() {
+        let interval = |contents: &str| {
+            let conversion = convert_ddclient_config(contents).unwrap();
+            let config: Value = serde_yaml::from_str(&conversion.config).unwrap();
+            config.get("interval").and_then(Value::as_str).map(str::to_string)
+        };
+
+        assert_eq!(interval("daemon=300\nhome.example.com\n").as_deref(), Some("300s"));
+        assert_eq!(interval("daemon=5m\nhome.example.com\n").as_deref(), Some("5m"));
+        assert_eq!(interval("daemon=0\nhome.example.com\n"), None);
+        assert_eq!(interval("home.example.com\n"), None);
+    }
+}
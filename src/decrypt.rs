@@ -0,0 +1,109 @@
+use {
+    crate::error::Route53IpUpdateError,
+    log::debug,
+    serde_yaml::Value,
+    std::{
+        env,
+        path::{Path, PathBuf},
+    },
+    tokio::process::Command,
+};
+
+/// The first line of a binary age file.
+const AGE_HEADER: &[u8] = b"age-encryption.org/v1\n";
+
+/// The first line of an ASCII-armored age file.
+const AGE_ARMOR_HEADER: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+
+/// The environment variable SOPS reads its age identities from, also used for age-encrypted files when no identity is
+/// given on the command line.
+const SOPS_AGE_KEY_FILE: &str = "SOPS_AGE_KEY_FILE";
+
+/// Indicates whether a file's contents are encrypted with age.
+pub(crate) fn is_age_encrypted(contents: &[u8]) -> bool {
+    contents.starts_with(AGE_HEADER) || contents.trim_ascii_start().starts_with(AGE_ARMOR_HEADER)
+}
+
+/// Indicates whether a YAML or JSON file's contents were encrypted with SOPS, which records its metadata in a top-level
+/// `sops` key.
+pub(crate) fn is_sops_encrypted(contents: &str) -> bool {
+    serde_yaml::from_str::<Value>(contents)
+        .is_ok_and(|value| value.get("sops").is_some_and(|sops| sops.get("mac").is_some()))
+}
+
+/// Decrypts an age-encrypted file with the `age` command, using the identity file given or, if none, the one named by
+/// `SOPS_AGE_KEY_FILE`.
+pub(crate) async fn decrypt_age(path: &Path, identity: Option<&Path>) -> Result<Vec<u8>, Route53IpUpdateError> {
+    let identity = match identity {
+        Some(identity) => identity.to_path_buf(),
+        None => env::var_os(SOPS_AGE_KEY_FILE).map(PathBuf::from).ok_or_else(|| {
+            decryption_failed(
+                path,
+                format!("it's encrypted with age; give an identity with --age-identity or {SOPS_AGE_KEY_FILE}"),
+            )
+        })?,
+    };
+
+    debug!("Decrypting {} with age identity {}", path.display(), identity.display());
+    let mut command = Command::new("age");
+    command.arg("--decrypt").arg("--identity").arg(identity).arg(path);
+    run(path, command).await
+}
+
+/// Decrypts a SOPS-encrypted YAML or JSON file with the `sops` command. SOPS finds its own keys (age, KMS, PGP, and so
+/// on); an age identity file, if given, is passed on to it.
+pub(crate) async fn decrypt_sops(
+    path: &Path,
+    format: &str,
+    identity: Option<&Path>,
+) -> Result<Vec<u8>, Route53IpUpdateError> {
+    debug!("Decrypting {} with SOPS", path.display());
+    let mut command = Command::new("sops");
+    command.args(["--decrypt", "--input-type", format, "--output-type", format]).arg(path);
+    if let Some(identity) = identity {
+        command.env(SOPS_AGE_KEY_FILE, identity);
+    }
+
+    run(path, command).await
+}
+
+/// Runs a decryption command, returning what it printed.
+async fn run(path: &Path, mut command: Command) -> Result<Vec<u8>, Route53IpUpdateError> {
+    let program = command.as_std().get_program().to_string_lossy().to_string();
+    let output =
+        command.output().await.map_err(|e| decryption_failed(path, format!("unable to run {program}: {e}")))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(decryption_failed(path, format!("{program} failed ({}): {}", output.status, stderr.trim())));
+    }
+
+    Ok(output.stdout)
+}
+
+fn decryption_failed(path: &Path, message: String) -> Route53IpUpdateError {
+    Route53IpUpdateError::DecryptionFailed(path.display().to_string(), message)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{is_age_encrypted, is_sops_encrypted},
+        crate::config_file::has_config_ext,
+        std::path::Path,
+    };
+
+    #[test]
+    fn detects_encrypted_files() {
+        assert!(is_age_encrypted(b"age-encryption.org/v1\n-> X25519 abc\n"));
+        assert!(is_age_encrypted(b"-----BEGIN AGE ENCRYPTED FILE-----\nYWdl\n-----END AGE ENCRYPTED FILE-----\n"));
+        assert!(!is_age_encrypted(b"hostnames: [home.example.com]\n"));
+        assert!(has_config_ext(Path::new("secrets.yaml.age")));
+        assert!(!has_config_ext(Path::new("secrets.age")));
+
+        let sops = "hostnames: ENC[AES256_GCM,data:abc,type:str]\nsops:\n  mac: ENC[AES256_GCM,data:def,type:str]\n";
+        assert!(is_sops_encrypted(sops));
+        assert!(is_sops_encrypted(r#"{"event-bus": "ENC[...]", "sops": {"mac": "ENC[...]", "version": "3.8.1"}}"#));
+        assert!(!is_sops_encrypted("hostnames: [home.example.com]\n"));
+        assert!(!is_sops_encrypted("sops: enabled\n"));
+    }
+}
@@ -0,0 +1,229 @@
+use {
+    serde::Serialize,
+    std::{
+        env,
+        fmt::Write as _,
+        io::{stdout, IsTerminal, Write},
+    },
+};
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// The values and TTL of a record set, as shown in a change summary.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct RecordSetSummary {
+    /// The TTL of the records, in seconds, if known.
+    pub(crate) ttl: Option<i64>,
+
+    /// The record values, sorted.
+    pub(crate) values: Vec<String>,
+
+    /// The weight of the record set, if it uses weighted routing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) weight: Option<i64>,
+}
+
+impl RecordSetSummary {
+    pub(crate) fn new(ttl: Option<i64>, values: impl IntoIterator<Item = impl ToString>) -> Self {
+        let mut values: Vec<String> = values.into_iter().map(|v| v.to_string()).collect();
+        values.sort();
+        Self {
+            ttl,
+            values,
+            weight: None,
+        }
+    }
+
+    /// Returns this summary with the record set's weight.
+    pub(crate) fn with_weight(self, weight: Option<i64>) -> Self {
+        Self {
+            weight,
+            ..self
+        }
+    }
+
+    /// Returns the TTL, and the weight if there is one, for display.
+    fn details(&self) -> String {
+        match self.weight {
+            Some(weight) => format!("{}, weight {weight}", format_ttl(self.ttl)),
+            None => format_ttl(self.ttl),
+        }
+    }
+}
+
+/// A planned change to one of a hostname's record sets.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct RecordSetDiff {
+    pub(crate) hostname: String,
+    pub(crate) record_type: String,
+
+    /// The record set being replaced or removed, if any.
+    pub(crate) old: Option<RecordSetSummary>,
+
+    /// The record set being published, if any.
+    pub(crate) new: Option<RecordSetSummary>,
+}
+
+/// Returns the hostnames the changes are to, sorted.
+pub(crate) fn changed_hostnames(diffs: &[RecordSetDiff]) -> Vec<String> {
+    let mut hostnames: Vec<String> = diffs.iter().map(|diff| diff.hostname.clone()).collect();
+    hostnames.sort();
+    hostnames.dedup();
+    hostnames
+}
+
+/// Prints the planned changes for a zone to stdout, colored if stdout is a terminal.
+pub(crate) fn print_changes(zone_id: &str, diffs: &[RecordSetDiff]) {
+    if diffs.is_empty() {
+        return;
+    }
+
+    let color = stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    // Write the whole summary at once so summaries for zones updated concurrently don't interleave.
+    let _ = stdout().lock().write_all(format_changes(zone_id, diffs, color).as_bytes());
+}
+
+/// Formats the planned changes for a zone, grouped by hostname: removed values are marked with `-`, added values with
+/// `+`, and values whose TTL or weight alone is changing with `~`.
+pub(crate) fn format_changes(zone_id: &str, diffs: &[RecordSetDiff], color: bool) -> String {
+    let paint = |code: &str, text: String| {
+        if color {
+            format!("{code}{text}{RESET}")
+        } else {
+            text
+        }
+    };
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", paint(BOLD, format!("Changes for zone {zone_id}:")));
+
+    let mut hostnames: Vec<&str> = Vec::new();
+    for diff in diffs {
+        if !hostnames.contains(&diff.hostname.as_str()) {
+            hostnames.push(&diff.hostname);
+        }
+    }
+
+    for hostname in hostnames {
+        let _ = writeln!(out, "  {hostname}");
+
+        for diff in diffs.iter().filter(|d| d.hostname == hostname) {
+            let line =
+                |marker: char, value: &str, ttl: String| format!("{marker} {:<5} {value} ({ttl})", diff.record_type);
+            let old_ttl = diff.old.as_ref().map(RecordSetSummary::details).unwrap_or_default();
+            let new_ttl = diff.new.as_ref().map(RecordSetSummary::details).unwrap_or_default();
+            let old_values = diff.old.as_ref().map(|old| old.values.as_slice()).unwrap_or_default();
+            let new_values = diff.new.as_ref().map(|new| new.values.as_slice()).unwrap_or_default();
+            let mut lines = Vec::new();
+
+            for value in old_values.iter().filter(|v| !new_values.contains(v)) {
+                lines.push(paint(RED, line('-', value, old_ttl.clone())));
+            }
+
+            for value in new_values.iter().filter(|v| !old_values.contains(v)) {
+                lines.push(paint(GREEN, line('+', value, new_ttl.clone())));
+            }
+
+            // Values being kept only appear if something else about them is changing, such as the TTL or the routing
+            // policy.
+            let kept = new_values.iter().filter(|v| old_values.contains(v));
+            if old_ttl != new_ttl {
+                for value in kept {
+                    lines.push(paint(YELLOW, line('~', value, format!("{old_ttl} -> {new_ttl}"))));
+                }
+            } else if lines.is_empty() {
+                for value in kept {
+                    lines.push(paint(YELLOW, line('~', value, new_ttl.clone())));
+                }
+            }
+
+            for line in lines {
+                let _ = writeln!(out, "    {line}");
+            }
+        }
+    }
+
+    out
+}
+
+fn format_ttl(ttl: Option<i64>) -> String {
+    match ttl {
+        Some(ttl) => format!("TTL {ttl}"),
+        None => "TTL unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_changes, RecordSetDiff, RecordSetSummary};
+
+    fn diff(
+        hostname: &str,
+        record_type: &str,
+        old: Option<(i64, &[&str])>,
+        new: Option<(i64, &[&str])>,
+    ) -> RecordSetDiff {
+        let summary = |(ttl, values): (i64, &[&str])| RecordSetSummary::new(Some(ttl), values.iter());
+        RecordSetDiff {
+            hostname: hostname.to_string(),
+            record_type: record_type.to_string(),
+            old: old.map(summary),
+            new: new.map(summary),
+        }
+    }
+
+    #[test]
+    fn formats_changes_by_hostname() {
+        let diffs = [
+            diff(
+                "home.example.com",
+                "A",
+                Some((300, &["192.0.2.1", "192.0.2.2"])),
+                Some((300, &["192.0.2.2", "192.0.2.3"])),
+            ),
+            diff("office.example.com", "AAAA", None, Some((60, &["2001:db8::1"]))),
+            diff("home.example.com", "CNAME", Some((300, &["elsewhere.example.com"])), None),
+            diff("office.example.com", "A", Some((3600, &["192.0.2.9"])), Some((60, &["192.0.2.9"]))),
+        ];
+
+        assert_eq!(
+            format_changes("Z123", &diffs, false),
+            "Changes for zone Z123:
+  home.example.com
+    - A     192.0.2.1 (TTL 300)
+    + A     192.0.2.3 (TTL 300)
+    - CNAME elsewhere.example.com (TTL 300)
+  office.example.com
+    + AAAA  2001:db8::1 (TTL 60)
+    ~ A     192.0.2.9 (TTL 3600 -> TTL 60)
+"
+        );
+    }
+
+    #[test]
+    fn shows_weight_changes() {
+        let summary = |weight| RecordSetSummary::new(Some(300), ["192.0.2.1"]).with_weight(Some(weight));
+        let diffs = [RecordSetDiff {
+            hostname: "www.example.com".to_string(),
+            record_type: "A".to_string(),
+            old: Some(summary(10)),
+            new: Some(summary(20)),
+        }];
+
+        assert!(format_changes("Z123", &diffs, false)
+            .contains("~ A     192.0.2.1 (TTL 300, weight 10 -> TTL 300, weight 20)"));
+    }
+
+    #[test]
+    fn colors_changes() {
+        let diffs = [diff("home.example.com", "A", Some((300, &["192.0.2.1"])), Some((300, &["192.0.2.2"])))];
+        let formatted = format_changes("Z123", &diffs, true);
+        assert!(formatted.contains("\x1b[31m- A     192.0.2.1 (TTL 300)\x1b[0m"));
+        assert!(formatted.contains("\x1b[32m+ A     192.0.2.2 (TTL 300)\x1b[0m"));
+    }
+}
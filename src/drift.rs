@@ -0,0 +1,96 @@
+use {
+    crate::{diff::RecordSetDiff, error::Route53IpUpdateError, relay::unix_time},
+    reqwest::{header::CONTENT_TYPE, Client},
+    serde::Serialize,
+    std::time::Duration,
+    tower::BoxError,
+};
+
+/// The exit status used when --monitor-only finds records that don't match the discovered addresses.
+pub(crate) const DRIFT_EXIT_CODE: u8 = 2;
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// The report sent to the drift webhook.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct DriftReport {
+    /// When the records were checked, in seconds since the Unix epoch.
+    pub(crate) timestamp: u64,
+
+    /// The zones whose records don't match the discovered addresses.
+    pub(crate) zones: Vec<ZoneDrift>,
+}
+
+/// The record sets in a zone that don't match the discovered addresses. In each change, `old` is what is published
+/// and `new` is what would be published.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct ZoneDrift {
+    pub(crate) zone_id: String,
+    pub(crate) changes: Vec<RecordSetDiff>,
+}
+
+impl DriftReport {
+    pub(crate) fn new(mut zones: Vec<ZoneDrift>) -> Self {
+        zones.sort_by(|a, b| a.zone_id.cmp(&b.zone_id));
+        Self {
+            timestamp: unix_time(),
+            zones,
+        }
+    }
+}
+
+/// POSTs a drift report to the webhook as JSON.
+pub(crate) async fn send_drift_webhook(url: &str, report: &DriftReport, timeout: Duration) -> Result<(), BoxError> {
+    let client = Client::builder().timeout(timeout).user_agent(USER_AGENT).build()?;
+    let response =
+        client.post(url).header(CONTENT_TYPE, "application/json").body(serde_json::to_vec(report)?).send().await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        return Err(Route53IpUpdateError::DriftWebhookFailed(format!("{status}: {}", text.trim())).into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{DriftReport, ZoneDrift},
+        crate::diff::{RecordSetDiff, RecordSetSummary},
+        serde_json::json,
+    };
+
+    #[test]
+    fn report_lists_published_and_desired_records() {
+        let mut report = DriftReport::new(vec![ZoneDrift {
+            zone_id: "Z123".to_string(),
+            changes: vec![RecordSetDiff {
+                hostname: "home.example.com".to_string(),
+                record_type: "A".to_string(),
+                old: Some(RecordSetSummary::new(Some(300), ["192.0.2.1"])),
+                new: Some(RecordSetSummary::new(Some(300), ["192.0.2.2"])),
+            }],
+        }]);
+        report.timestamp = 1700000000;
+
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            json!({
+                "timestamp": 1700000000,
+                "zones": [{
+                    "zone-id": "Z123",
+                    "changes": [{
+                        "hostname": "home.example.com",
+                        "record-type": "A",
+                        "old": {"ttl": 300, "values": ["192.0.2.1"]},
+                        "new": {"ttl": 300, "values": ["192.0.2.2"]},
+                    }],
+                }],
+            })
+        );
+    }
+}
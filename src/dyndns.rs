@@ -0,0 +1,649 @@
+use {
+    crate::{
+        address_ranking::AddressRanker,
+        address_source::DiscoveredAddress,
+        aws::Route53Clients,
+        config::{Config, DyndnsUserConfig, Route53ZoneConfig},
+        provider::ZoneProvider,
+        relay::{
+            unix_time, verify_report, RelayHostnameResult, RelayReply, RelayReport, MAX_CLOCK_SKEW, SIGNATURE_HEADER,
+        },
+        zones::normalize_domain_name,
+    },
+    async_trait::async_trait,
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    hyper::{
+        body::HttpBody,
+        header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, WWW_AUTHENTICATE},
+        server::conn::AddrStream,
+        service::{make_service_fn, service_fn},
+        Body, Method, Request, Response, Server, StatusCode,
+    },
+    log::{error, info, warn},
+    std::{
+        collections::HashSet,
+        convert::Infallible,
+        net::{IpAddr, SocketAddr},
+        sync::Arc,
+    },
+    tokio::sync::Mutex,
+    tower::BoxError,
+};
+
+/// The paths DynDNS2 clients send updates to.
+const UPDATE_PATHS: [&str; 2] = ["/nic/update", "/v3/update"];
+
+/// The path agents send relay reports to.
+const RELAY_PATH: &str = "/relay/update";
+
+/// The largest relay report accepted.
+const MAX_RELAY_REPORT_LEN: usize = 64 * 1024;
+
+/// The realm sent with authentication challenges.
+const AUTH_REALM: &str = "route53-ip-update";
+
+/// Where the server publishes the addresses it receives.
+#[async_trait]
+pub(crate) trait ZoneUpdater: Send + Sync {
+    /// Brings the zone's hostnames up-to-date with the addresses. Returns whether any changes were made.
+    async fn update_zone(
+        &self,
+        config: &Config,
+        zone: &Route53ZoneConfig,
+        ipv4: &HashSet<IpAddr>,
+        ipv6: &HashSet<IpAddr>,
+    ) -> Result<bool, BoxError>;
+
+    /// Returns the addresses currently published for a hostname in the zone.
+    async fn list(
+        &self,
+        config: &Config,
+        zone: &Route53ZoneConfig,
+        hostname: &str,
+    ) -> Result<(HashSet<IpAddr>, HashSet<IpAddr>), BoxError>;
+}
+
+/// Publishes to the provider configured for each zone, creating Route 53 clients as needed.
+#[async_trait]
+impl ZoneUpdater for Mutex<Route53Clients> {
+    async fn update_zone(
+        &self,
+        config: &Config,
+        zone: &Route53ZoneConfig,
+        ipv4: &HashSet<IpAddr>,
+        ipv6: &HashSet<IpAddr>,
+    ) -> Result<bool, BoxError> {
+        let changed = zone_provider(self, config, zone).await?.update_zone(config, zone, ipv4, ipv6).await?;
+        Ok(!changed.is_empty())
+    }
+
+    async fn list(
+        &self,
+        config: &Config,
+        zone: &Route53ZoneConfig,
+        hostname: &str,
+    ) -> Result<(HashSet<IpAddr>, HashSet<IpAddr>), BoxError> {
+        zone_provider(self, config, zone).await?.list(hostname).await
+    }
+}
+
+/// Returns the provider hosting the zone.
+async fn zone_provider(
+    route53_clients: &Mutex<Route53Clients>,
+    config: &Config,
+    zone: &Route53ZoneConfig,
+) -> Result<ZoneProvider, BoxError> {
+    let mut route53_clients = route53_clients.lock().await;
+    ZoneProvider::for_zone(&mut route53_clients, config, zone).await
+}
+
+pub(crate) struct DyndnsServer<Z: ZoneUpdater = Mutex<Route53Clients>> {
+    config: Config,
+    zones: Z,
+}
+
+/// Why a relay report was rejected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum RelayRejection {
+    /// The report was too large or couldn't be parsed.
+    BadRequest,
+
+    /// The report came from an unknown agent, had a bad signature, or had a timestamp too far from the server's clock.
+    BadAuth,
+}
+
+impl RelayRejection {
+    /// The HTTP status returned for the rejection.
+    pub(crate) fn status(self) -> StatusCode {
+        match self {
+            Self::BadRequest => StatusCode::BAD_REQUEST,
+            Self::BadAuth => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    /// The body returned for the rejection.
+    pub(crate) fn message(self) -> &'static str {
+        match self {
+            Self::BadRequest => "Bad Request",
+            Self::BadAuth => "badauth",
+        }
+    }
+}
+
+/// Runs a DynDNS2-compatible server, applying the updates it receives (and the reports relayed by agents) to the
+/// configured zones until the process is stopped.
+pub(crate) async fn run_dyndns_server(
+    config: Config,
+    route53_clients: Route53Clients,
+    listen: SocketAddr,
+) -> Result<(), BoxError> {
+    let server = Arc::new(DyndnsServer::new(config, route53_clients));
+
+    let make_service = make_service_fn(move |conn: &AddrStream| {
+        let server = server.clone();
+        let remote_addr = conn.remote_addr();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let server = server.clone();
+                async move { Ok::<_, Infallible>(server.handle(request, remote_addr).await) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&listen)?.serve(make_service);
+    info!("Listening for DynDNS2 updates on {}", server.local_addr());
+    server.await?;
+    Ok(())
+}
+
+impl DyndnsServer {
+    pub(crate) fn new(config: Config, route53_clients: Route53Clients) -> Self {
+        Self::with_zones(config, Mutex::new(route53_clients))
+    }
+}
+
+impl<Z: ZoneUpdater> DyndnsServer<Z> {
+    /// Creates a server that publishes the addresses it receives through `zones`.
+    pub(crate) fn with_zones(config: Config, zones: Z) -> Self {
+        Self {
+            config,
+            zones,
+        }
+    }
+
+    async fn handle(&self, request: Request<Body>, remote_addr: SocketAddr) -> Response<Body> {
+        match (request.method(), request.uri().path()) {
+            (&Method::GET, path) if UPDATE_PATHS.contains(&path) => self.handle_dyndns(request, remote_addr).await,
+            (&Method::POST, RELAY_PATH) => self.handle_relay(request, remote_addr).await,
+            _ => text_response(StatusCode::NOT_FOUND, "Not Found"),
+        }
+    }
+
+    async fn handle_dyndns(&self, request: Request<Body>, remote_addr: SocketAddr) -> Response<Body> {
+        let Some(user) = self.authenticate(&request) else {
+            warn!("Rejected DynDNS2 update from {remote_addr}: bad credentials");
+            let mut response = text_response(StatusCode::UNAUTHORIZED, "badauth");
+            response
+                .headers_mut()
+                .insert(WWW_AUTHENTICATE, format!("Basic realm=\"{AUTH_REALM}\"").parse().expect("valid header"));
+            return response;
+        };
+
+        let mut hostnames = Vec::new();
+        let mut addresses = Vec::new();
+        for (key, value) in form_urlencoded::parse(request.uri().query().unwrap_or_default().as_bytes()) {
+            match key.as_ref() {
+                "hostname" => hostnames.extend(value.split(',').filter(|h| !h.is_empty()).map(String::from)),
+                "myip" | "myipv6" => {
+                    for address in value.split(',').filter(|a| !a.is_empty()) {
+                        match address.parse::<IpAddr>() {
+                            Ok(address) => addresses.push(address),
+                            // DynDNS2 has no specific code for a malformed address; clients treat 911 as retryable.
+                            Err(_) => return text_response(StatusCode::OK, "911"),
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        if hostnames.is_empty() {
+            return text_response(StatusCode::OK, "notfqdn");
+        }
+
+        if addresses.is_empty() {
+            addresses.push(remote_addr.ip().to_canonical());
+        }
+
+        let mut results = Vec::with_capacity(hostnames.len());
+        for hostname in &hostnames {
+            results.push(self.update_hostname(user, hostname, &addresses).await);
+        }
+
+        text_response(StatusCode::OK, &results.join("\n"))
+    }
+
+    /// Returns the user whose credentials were sent with the request, if any.
+    fn authenticate(&self, request: &Request<Body>) -> Option<&DyndnsUserConfig> {
+        let header = request.headers().get(AUTHORIZATION)?.to_str().ok()?;
+        let encoded = header.strip_prefix("Basic ").or_else(|| header.strip_prefix("basic "))?;
+        let decoded = String::from_utf8(BASE64.decode(encoded.trim()).ok()?).ok()?;
+        let (username, password) = decoded.split_once(':')?;
+
+        self.config.dyndns_server.as_ref()?.users.iter().find(|user| {
+            constant_time_eq(user.username.as_bytes(), username.as_bytes())
+                & constant_time_eq(user.password.as_bytes(), password.as_bytes())
+        })
+    }
+
+    /// Publishes the addresses for one hostname, returning the DynDNS2 result line.
+    async fn update_hostname(&self, user: &DyndnsUserConfig, hostname: &str, addresses: &[IpAddr]) -> String {
+        let normalized = normalize_domain_name(hostname);
+        if !hostname.contains('.') {
+            return "notfqdn".to_string();
+        }
+
+        if !user.hostnames.iter().any(|h| normalize_domain_name(h) == normalized) {
+            warn!("User {} may not update hostname {hostname}", user.username);
+            return "nohost".to_string();
+        }
+
+        let Some(zone) = self.zone_for_hostname(&normalized) else {
+            warn!("Hostname {hostname} is not configured in any zone");
+            return "nohost".to_string();
+        };
+
+        let mut ipv4: HashSet<IpAddr> = addresses.iter().filter(|a| a.is_ipv4()).copied().collect();
+        let mut ipv6: HashSet<IpAddr> = addresses.iter().filter(|a| a.is_ipv6()).copied().collect();
+
+        // Clients typically report only one address family; leave the other family's records as they are.
+        // Route 53 reports names in lowercase, so they're looked up by the configured name rather than the client's.
+        if ipv4.is_empty() || ipv6.is_empty() {
+            match self.zones.list(&self.config, &zone, zone.hostnames[0].get_hostname()).await {
+                Ok((existing_ipv4, existing_ipv6)) => {
+                    if ipv4.is_empty() {
+                        ipv4 = existing_ipv4;
+                    }
+                    if ipv6.is_empty() {
+                        ipv6 = existing_ipv6;
+                    }
+                }
+                Err(e) => {
+                    error!("Unable to read the existing records for {hostname}: {e}");
+                    return "911".to_string();
+                }
+            }
+        }
+
+        let address_list = addresses.iter().map(ToString::to_string).collect::<Vec<_>>().join(",");
+        match self.apply(&zone, &ipv4, &ipv6).await {
+            Ok(true) => {
+                info!("User {} updated {hostname} to {address_list}", user.username);
+                format!("good {address_list}")
+            }
+            Ok(false) => format!("nochg {address_list}"),
+            Err(e) => {
+                error!("Failed to update {hostname}: {e}");
+                "911".to_string()
+            }
+        }
+    }
+
+    async fn handle_relay(&self, request: Request<Body>, remote_addr: SocketAddr) -> Response<Body> {
+        let signature = request.headers().get(SIGNATURE_HEADER).and_then(|s| s.to_str().ok()).map(String::from);
+
+        // Reports are read before they're authenticated, so oversized ones are refused without buffering them.
+        let content_length = request.headers().get(CONTENT_LENGTH).and_then(|l| l.to_str().ok()?.parse::<u64>().ok());
+        if content_length.is_some_and(|len| len > MAX_RELAY_REPORT_LEN as u64) {
+            warn!("Rejected relay report from {remote_addr}: too large");
+            return text_response(StatusCode::BAD_REQUEST, "Bad Request");
+        }
+
+        let Some(body) = read_body(request.into_body(), MAX_RELAY_REPORT_LEN).await else {
+            warn!("Rejected relay report from {remote_addr}: too large or unreadable");
+            return text_response(StatusCode::BAD_REQUEST, "Bad Request");
+        };
+
+        match self.apply_relay_report(&body, signature.as_deref(), &remote_addr.to_string()).await {
+            Ok(reply) => {
+                let mut response = Response::new(Body::from(serde_json::to_vec(&reply).unwrap_or_default()));
+                response.headers_mut().insert(CONTENT_TYPE, "application/json".parse().expect("valid header"));
+                response
+            }
+            Err(rejection) => text_response(rejection.status(), rejection.message()),
+        }
+    }
+
+    /// Verifies a relay report sent by an agent from `remote` and updates the agent's hostnames with the addresses in
+    /// it.
+    pub(crate) async fn apply_relay_report(
+        &self,
+        body: &[u8],
+        signature: Option<&str>,
+        remote: &str,
+    ) -> Result<RelayReply, RelayRejection> {
+        if body.len() > MAX_RELAY_REPORT_LEN {
+            return Err(RelayRejection::BadRequest);
+        }
+
+        let Ok(report) = serde_json::from_slice::<RelayReport>(body) else {
+            return Err(RelayRejection::BadRequest);
+        };
+
+        let agent = self
+            .config
+            .dyndns_server
+            .as_ref()
+            .and_then(|server| server.agents.iter().find(|agent| agent.name == report.agent));
+        let authentic = match (agent, signature) {
+            (Some(agent), Some(signature)) => verify_report(&agent.secret, body, signature),
+            _ => false,
+        };
+        let (Some(agent), true) = (agent, authentic) else {
+            warn!("Rejected relay report from {remote}: unknown agent or bad signature");
+            return Err(RelayRejection::BadAuth);
+        };
+
+        if unix_time().abs_diff(report.timestamp) > MAX_CLOCK_SKEW.as_secs() {
+            warn!("Rejected relay report from agent {} at {remote}: timestamp is too far off", agent.name);
+            return Err(RelayRejection::BadAuth);
+        }
+
+        if let Some(nat) = report.nat.filter(|nat| !nat.is_reachable()) {
+            warn!("Agent {} is behind {nat}: {}", agent.name, nat.explanation());
+        }
+
+        let discovered: HashSet<DiscoveredAddress> = report.addresses.into_iter().collect();
+        let mut reply = RelayReply::default();
+
+        for hostname in &agent.hostnames {
+            let (status, message) = match self.zone_for_hostname(&normalize_domain_name(hostname)) {
+                None => {
+                    warn!("Hostname {hostname} for agent {} is not configured in any zone", agent.name);
+                    ("error", Some("hostname is not configured in any zone".to_string()))
+                }
+                Some(zone) => {
+                    let (ipv4, ipv6) = self.config.zone_candidates(&zone, &discovered);
+                    let ranker = AddressRanker::new(&self.config).await;
+                    let (ipv4, ipv6) = (ranker.select(ipv4, None), ranker.select(ipv6, None));
+                    match self.apply(&zone, &ipv4, &ipv6).await {
+                        Ok(true) => {
+                            info!("Agent {} updated {hostname}", agent.name);
+                            ("good", None)
+                        }
+                        Ok(false) => ("nochg", None),
+                        Err(e) => {
+                            error!("Failed to update {hostname} for agent {}: {e}", agent.name);
+                            ("error", Some(e.to_string()))
+                        }
+                    }
+                }
+            };
+
+            reply.results.push(RelayHostnameResult {
+                hostname: hostname.clone(),
+                status: status.to_string(),
+                message,
+            });
+        }
+
+        Ok(reply)
+    }
+
+    /// Updates the zone with the given addresses, then its mirrors. Returns whether any changes were made to the zone;
+    /// failures to update a mirror are logged but don't affect the result.
+    async fn apply(
+        &self,
+        zone: &Route53ZoneConfig,
+        ipv4: &HashSet<IpAddr>,
+        ipv6: &HashSet<IpAddr>,
+    ) -> Result<bool, BoxError> {
+        let result = self.zones.update_zone(&self.config, zone, ipv4, ipv6).await;
+
+        for mirror in &zone.mirrors {
+            let mirror_zone = zone.mirror_zone(mirror);
+            if let Err(e) = self.zones.update_zone(&self.config, &mirror_zone, ipv4, ipv6).await {
+                error!("Failed to update mirror zone {} of zone {}: {e}", mirror_zone.zone_id, zone.zone_id);
+            }
+        }
+
+        result
+    }
+
+    /// Returns a copy of the enabled zone configuration containing the hostname, restricted to that hostname.
+    fn zone_for_hostname(&self, normalized: &str) -> Option<Route53ZoneConfig> {
+        self.config.route53_zones.iter().filter(|zone| zone.enabled).find_map(|zone| {
+            let hostname =
+                zone.hostnames.iter().find(|h| normalize_domain_name(h.get_hostname()) == normalized)?.clone();
+            let mut zone = zone.clone();
+            zone.hostnames = vec![hostname];
+            Some(zone)
+        })
+    }
+}
+
+/// Reads a request body, returning `None` if it can't be read or is longer than `limit` bytes. Reading stops as soon as
+/// the limit is passed.
+async fn read_body(mut body: Body, limit: usize) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.ok()?;
+        if bytes.len() + chunk.len() > limit {
+            return None;
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Some(bytes)
+}
+
+fn text_response(status: StatusCode, body: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(format!("{body}\n")));
+    *response.status_mut() = status;
+    response.headers_mut().insert(CONTENT_TYPE, "text/plain".parse().expect("valid header"));
+    response
+}
+
+/// Compares two byte strings in time that depends only on their lengths, so passwords can't be guessed by timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{DyndnsServer, RelayRejection, ZoneUpdater, MAX_RELAY_REPORT_LEN, RELAY_PATH},
+        crate::{
+            config::{Config, Route53ZoneConfig},
+            provider::{update_zone_with, DnsProvider},
+            relay::{sign_report, unix_time, RelayReport, MAX_CLOCK_SKEW},
+            route53_api::fake::{rrs, FakeRoute53},
+            update::Route53Provider,
+        },
+        async_trait::async_trait,
+        aws_sdk_route53::model::RrType,
+        base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+        hyper::{
+            body::to_bytes,
+            header::{AUTHORIZATION, CONTENT_LENGTH, WWW_AUTHENTICATE},
+            Body, Request, Response, StatusCode,
+        },
+        std::{
+            collections::HashSet,
+            net::{IpAddr, SocketAddr},
+        },
+        tower::BoxError,
+    };
+
+    #[async_trait]
+    impl ZoneUpdater for FakeRoute53 {
+        async fn update_zone(
+            &self,
+            config: &Config,
+            zone: &Route53ZoneConfig,
+            ipv4: &HashSet<IpAddr>,
+            ipv6: &HashSet<IpAddr>,
+        ) -> Result<bool, BoxError> {
+            let provider = Route53Provider::new(self.clone(), &zone.zone_id);
+            Ok(!update_zone_with(&provider, config, zone, ipv4, ipv6).await?.is_empty())
+        }
+
+        async fn list(
+            &self,
+            _config: &Config,
+            zone: &Route53ZoneConfig,
+            hostname: &str,
+        ) -> Result<(HashSet<IpAddr>, HashSet<IpAddr>), BoxError> {
+            Route53Provider::new(self.clone(), &zone.zone_id).list(hostname).await
+        }
+    }
+
+    fn server(fake: &FakeRoute53) -> DyndnsServer<FakeRoute53> {
+        let config = Config {
+            route53_zones: vec![serde_yaml::from_str("zone-id: Z1\nhostnames: [home.example.com]").unwrap()],
+            dyndns_server: Some(
+                serde_yaml::from_str(
+                    "users: [{username: alice, password: s3cret, hostnames: [home.example.com, lab.example.com]}]\n\
+                     agents: [{name: office, secret: k3y, hostnames: [home.example.com]}]",
+                )
+                .unwrap(),
+            ),
+            ..Config::default()
+        };
+        DyndnsServer::with_zones(config, fake.clone())
+    }
+
+    async fn update(server: &DyndnsServer<FakeRoute53>, query: &str, credentials: Option<&str>) -> Response<Body> {
+        let mut request = Request::get(format!("/nic/update?{query}"));
+        if let Some(credentials) = credentials {
+            request = request.header(AUTHORIZATION, format!("Basic {}", BASE64.encode(credentials)));
+        }
+        let remote_addr: SocketAddr = "198.51.100.7:50000".parse().unwrap();
+        server.handle(request.body(Body::empty()).unwrap(), remote_addr).await
+    }
+
+    fn report(agent: &str, timestamp: u64) -> Vec<u8> {
+        let report = RelayReport {
+            agent: agent.to_string(),
+            timestamp,
+            addresses: Vec::new(),
+            nat: None,
+        };
+        serde_json::to_vec(&report).unwrap()
+    }
+
+    async fn body_text(response: Response<Body>) -> String {
+        String::from_utf8(to_bytes(response.into_body()).await.unwrap().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn single_family_update_keeps_the_other_family() {
+        let fake = FakeRoute53::with_record_sets([
+            rrs("home.example.com", RrType::A, "192.0.2.1"),
+            rrs("home.example.com", RrType::Aaaa, "2001:db8::1"),
+        ]);
+
+        let response = update(&server(&fake), "hostname=Home.example.com&myip=192.0.2.2", Some("alice:s3cret")).await;
+        assert_eq!(body_text(response).await, "good 192.0.2.2\n");
+
+        let record_sets = fake.record_sets();
+        let values = |rr_type: RrType| {
+            let rrs = record_sets.iter().find(|rrs| rrs.r#type() == Some(&rr_type)).unwrap();
+            rrs.resource_records().unwrap().iter().map(|rr| rr.value().unwrap().to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(values(RrType::A), ["192.0.2.2"]);
+        assert_eq!(values(RrType::Aaaa), ["2001:db8::1"]);
+    }
+
+    #[tokio::test]
+    async fn requires_basic_credentials() {
+        let server = server(&FakeRoute53::default());
+        let query = "hostname=home.example.com&myip=192.0.2.2";
+
+        for credentials in [None, Some("alice:wrong"), Some("bob:s3cret"), Some("alice")] {
+            let response = update(&server, query, credentials).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert!(response.headers()[WWW_AUTHENTICATE].to_str().unwrap().starts_with("Basic realm="));
+            assert_eq!(body_text(response).await, "badauth\n");
+        }
+
+        let remote_addr: SocketAddr = "198.51.100.7:50000".parse().unwrap();
+        let request = Request::get(format!("/nic/update?{query}")).header(AUTHORIZATION, "Basic !!!");
+        let response = server.handle(request.body(Body::empty()).unwrap(), remote_addr).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let request = Request::get(format!("/v3/update?{query}"))
+            .header(AUTHORIZATION, format!("basic {}", BASE64.encode("alice:s3cret")));
+        let response = server.handle(request.body(Body::empty()).unwrap(), remote_addr).await;
+        assert_eq!(body_text(response).await, "good 192.0.2.2\n");
+    }
+
+    #[tokio::test]
+    async fn replies_with_dyndns2_result_codes() {
+        let fake = FakeRoute53::default();
+        let server = server(&fake);
+        let reply = |query: &'static str| {
+            let server = &server;
+            async move { body_text(update(server, query, Some("alice:s3cret")).await).await }
+        };
+
+        assert_eq!(reply("myip=192.0.2.2").await, "notfqdn\n");
+        assert_eq!(reply("hostname=home&myip=192.0.2.2").await, "notfqdn\n");
+        assert_eq!(reply("hostname=home.example.com&myip=192.0.2.999").await, "911\n");
+
+        // The user may only update their own hostnames, and only those configured in a zone.
+        assert_eq!(reply("hostname=office.example.com&myip=192.0.2.2").await, "nohost\n");
+        assert_eq!(reply("hostname=lab.example.com&myip=192.0.2.2").await, "nohost\n");
+        assert!(fake.zone.lock().unwrap().batches.is_empty());
+
+        assert_eq!(reply("hostname=home.example.com,lab.example.com&myip=192.0.2.2").await, "good 192.0.2.2\nnohost\n");
+        assert_eq!(reply("hostname=home.example.com&myip=192.0.2.2").await, "nochg 192.0.2.2\n");
+
+        // Without an address, the client's own address is published.
+        assert_eq!(reply("hostname=home.example.com").await, "good 198.51.100.7\n");
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_relay_reports() {
+        let server = server(&FakeRoute53::default());
+        let remote_addr: SocketAddr = "198.51.100.7:50000".parse().unwrap();
+
+        // Refused by the declared length, without reading the body.
+        let request = Request::post(RELAY_PATH).header(CONTENT_LENGTH, "1048576").body(Body::from("{}")).unwrap();
+        assert_eq!(server.handle(request, remote_addr).await.status(), StatusCode::BAD_REQUEST);
+
+        // Refused while reading a body whose length wasn't declared.
+        let request = Request::post(RELAY_PATH).body(Body::from(vec![b' '; MAX_RELAY_REPORT_LEN + 1])).unwrap();
+        assert_eq!(server.handle(request, remote_addr).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn rejects_unauthenticated_relay_reports() {
+        let server = server(&FakeRoute53::default());
+        let apply = |body: Vec<u8>, signature: Option<String>| {
+            let server = &server;
+            async move { server.apply_relay_report(&body, signature.as_deref(), "198.51.100.7:50000").await.err() }
+        };
+
+        let body = report("office", unix_time());
+        assert_eq!(apply(body.clone(), None).await, Some(RelayRejection::BadAuth));
+        assert_eq!(apply(body.clone(), Some(sign_report("wrong", &body))).await, Some(RelayRejection::BadAuth));
+        assert_eq!(apply(body.clone(), Some("not hex".to_string())).await, Some(RelayRejection::BadAuth));
+
+        let body = report("unknown", unix_time());
+        assert_eq!(apply(body.clone(), Some(sign_report("k3y", &body))).await, Some(RelayRejection::BadAuth));
+
+        let body = report("office", unix_time() - MAX_CLOCK_SKEW.as_secs() - 60);
+        assert_eq!(apply(body.clone(), Some(sign_report("k3y", &body))).await, Some(RelayRejection::BadAuth));
+        let body = report("office", unix_time() + MAX_CLOCK_SKEW.as_secs() + 60);
+        assert_eq!(apply(body.clone(), Some(sign_report("k3y", &body))).await, Some(RelayRejection::BadAuth));
+
+        let body = vec![b' '; MAX_RELAY_REPORT_LEN + 1];
+        assert_eq!(apply(body.clone(), Some(sign_report("k3y", &body))).await, Some(RelayRejection::BadRequest));
+        assert_eq!(apply(b"{".to_vec(), Some(sign_report("k3y", b"{"))).await, Some(RelayRejection::BadRequest));
+    }
+}
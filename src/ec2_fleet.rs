@@ -0,0 +1,271 @@
+use {
+    crate::{
+        aws::{load_sdk_config, AwsQueryClient},
+        config::{Config, Ec2FleetConfig, HostnameConfig, Route53ZoneConfig},
+        zones::{
+            find_best_zone_for_hostname, list_all_hosted_zones, normalize_domain_name, strip_hosted_zone_id_prefix,
+        },
+    },
+    aws_sdk_route53::Client as Route53Client,
+    log::{debug, info, warn},
+    roxmltree::{Document, Node},
+    std::{
+        collections::{BTreeMap, BTreeSet, HashSet},
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    },
+    tower::BoxError,
+};
+
+/// The version of the EC2 API used.
+const EC2_API_VERSION: &str = "2016-11-15";
+
+/// A running EC2 instance carrying the fleet tag.
+#[derive(Debug, Default, PartialEq)]
+struct FleetInstance {
+    instance_id: String,
+    hostnames: Vec<String>,
+    public_ipv4: Option<Ipv4Addr>,
+    private_ipv4: Option<Ipv4Addr>,
+    ipv6: Vec<Ipv6Addr>,
+}
+
+impl FleetInstance {
+    /// Returns the instance's addresses to publish.
+    fn addresses(&self, fleet: &Ec2FleetConfig) -> Vec<IpAddr> {
+        if fleet.private {
+            self.private_ipv4.into_iter().map(IpAddr::from).collect()
+        } else {
+            self.public_ipv4.into_iter().map(IpAddr::from).chain(self.ipv6.iter().copied().map(IpAddr::from)).collect()
+        }
+    }
+}
+
+/// Returns a zone for each hostname tagged on the fleet's running instances, holding just that hostname, along with
+/// the IPv4 and IPv6 addresses of the instances tagged with it. Hostnames outside every hosted zone are skipped.
+pub(crate) async fn fleet_zones(
+    config: &Config,
+    fleet: &Ec2FleetConfig,
+    route53: &Route53Client,
+) -> Result<Vec<(Route53ZoneConfig, HashSet<IpAddr>, HashSet<IpAddr>)>, BoxError> {
+    let instances = list_fleet_instances(config, fleet).await?;
+    info!("Found {} running EC2 instances tagged {}", instances.len(), fleet.tag);
+
+    // Several instances tagged with the same hostname all have their addresses published under it.
+    let mut hostname_addresses: BTreeMap<String, (HashSet<IpAddr>, HashSet<IpAddr>)> = BTreeMap::new();
+    for instance in &instances {
+        let addresses = instance.addresses(fleet);
+        if addresses.is_empty() {
+            debug!("EC2 instance {} has no addresses to publish", instance.instance_id);
+        }
+
+        for hostname in &instance.hostnames {
+            let (ipv4, ipv6) = hostname_addresses.entry(hostname.clone()).or_default();
+            for address in &addresses {
+                match address {
+                    IpAddr::V4(_) => ipv4.insert(*address),
+                    IpAddr::V6(_) => ipv6.insert(*address),
+                };
+            }
+        }
+    }
+
+    let hosted_zones = list_all_hosted_zones(route53).await?;
+    let mut zones = Vec::with_capacity(hostname_addresses.len());
+    for (hostname, (ipv4, ipv6)) in hostname_addresses {
+        let Some(hosted_zone) = find_best_zone_for_hostname(&hosted_zones, &hostname) else {
+            warn!("Skipping hostname {hostname} tagged on EC2 instances: no hosted zone contains it");
+            continue;
+        };
+
+        let mut zone = Route53ZoneConfig::new(strip_hosted_zone_id_prefix(hosted_zone.id().unwrap_or_default()));
+        zone.zone_name = hosted_zone.name().map(normalize_domain_name);
+        zone.ttl = config.ttl;
+        zone.hostnames.push(HostnameConfig::HostnameOnly(hostname));
+        zones.push((zone, ipv4, ipv6));
+    }
+
+    Ok(zones)
+}
+
+/// Returns the hostnames in the fleet's zones, keyed by zone ID.
+pub(crate) fn fleet_hostnames(
+    zones: &[(Route53ZoneConfig, HashSet<IpAddr>, HashSet<IpAddr>)],
+) -> BTreeMap<String, BTreeSet<String>> {
+    let mut hostnames: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for (zone, ..) in zones {
+        let zone_hostnames = hostnames.entry(zone.zone_id.clone()).or_default();
+        zone_hostnames.extend(zone.hostnames.iter().map(|hostname| hostname.get_hostname().to_string()));
+    }
+    hostnames
+}
+
+/// Returns a zone without addresses for each hostname published for the fleet earlier that no running instance is
+/// tagged with now, so its records are removed.
+pub(crate) fn departed_zones(
+    config: &Config,
+    published: &BTreeMap<String, BTreeSet<String>>,
+    current: &BTreeMap<String, BTreeSet<String>>,
+) -> Vec<(Route53ZoneConfig, HashSet<IpAddr>, HashSet<IpAddr>)> {
+    let mut zones = Vec::new();
+    for (zone_id, hostnames) in published {
+        for hostname in hostnames.iter().filter(|hostname| !current.get(zone_id).is_some_and(|c| c.contains(*hostname)))
+        {
+            info!("Removing hostname {hostname}: no running EC2 instance is tagged with it");
+            let mut zone = Route53ZoneConfig::new(zone_id);
+            zone.ttl = config.ttl;
+            zone.hostnames.push(HostnameConfig::HostnameOnly(hostname.clone()));
+            zones.push((zone, HashSet::new(), HashSet::new()));
+        }
+    }
+    zones
+}
+
+/// Lists the running EC2 instances carrying the fleet tag.
+async fn list_fleet_instances(config: &Config, fleet: &Ec2FleetConfig) -> Result<Vec<FleetInstance>, BoxError> {
+    let sdk_config = load_sdk_config(config.profile.as_deref(), config.region.as_deref()).await;
+    let ec2 = AwsQueryClient::new(&sdk_config, "ec2", EC2_API_VERSION, config.timeout)?;
+
+    let mut instances = Vec::new();
+    let mut next_token: Option<String> = None;
+    loop {
+        let mut params = vec![
+            ("Filter.1.Name".to_string(), "tag-key".to_string()),
+            ("Filter.1.Value.1".to_string(), fleet.tag.clone()),
+            ("Filter.2.Name".to_string(), "instance-state-name".to_string()),
+            ("Filter.2.Value.1".to_string(), "running".to_string()),
+        ];
+        params.extend(next_token.take().map(|token| ("NextToken".to_string(), token)));
+
+        let reply = ec2.call("DescribeInstances", &params).await?;
+        let (page, token) = parse_instances(&reply, &fleet.tag)?;
+        instances.extend(page);
+        match token {
+            None => return Ok(instances),
+            token => next_token = token,
+        }
+    }
+}
+
+/// Parses a DescribeInstances reply, returning the instances and the token for the next page, if any.
+fn parse_instances(reply: &str, tag: &str) -> Result<(Vec<FleetInstance>, Option<String>), BoxError> {
+    let document = Document::parse(reply)?;
+    let root = document.root_element();
+
+    let mut instances = Vec::new();
+    for instance in root.descendants().filter(|node| node.has_tag_name("instancesSet")).flat_map(items) {
+        let hostnames: Vec<String> = child(instance, "tagSet")
+            .into_iter()
+            .flat_map(items)
+            .filter(|item| child_text(*item, "key") == Some(tag))
+            .flat_map(|item| child_text(item, "value").unwrap_or_default().split(','))
+            .map(|hostname| hostname.trim().trim_end_matches('.').to_ascii_lowercase())
+            .filter(|hostname| !hostname.is_empty())
+            .collect();
+
+        let ipv6 = child(instance, "networkInterfaceSet")
+            .into_iter()
+            .flat_map(items)
+            .filter_map(|interface| child(interface, "ipv6AddressesSet"))
+            .flat_map(items)
+            .filter_map(|address| child_text(address, "ipv6Address")?.parse().ok())
+            .collect();
+
+        instances.push(FleetInstance {
+            instance_id: child_text(instance, "instanceId").unwrap_or_default().to_string(),
+            hostnames,
+            public_ipv4: child_text(instance, "ipAddress").and_then(|address| address.parse().ok()),
+            private_ipv4: child_text(instance, "privateIpAddress").and_then(|address| address.parse().ok()),
+            ipv6,
+        });
+    }
+
+    let next_token = child_text(root, "nextToken").filter(|token| !token.is_empty()).map(str::to_string);
+    Ok((instances, next_token))
+}
+
+/// Returns the child element with the given name.
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+/// Returns the text of the child element with the given name.
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name)?.text()
+}
+
+/// Returns the `item` elements of an EC2 list.
+fn items<'a, 'input>(list: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    list.children().filter(|child| child.has_tag_name("item"))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{departed_zones, parse_instances},
+        crate::config::Config,
+        std::collections::{BTreeMap, BTreeSet},
+    };
+
+    #[test]
+    fn removes_hostnames_no_longer_tagged() {
+        let hostnames = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<BTreeSet<_>>();
+        let published = BTreeMap::from([
+            ("Z1".to_string(), hostnames(&["web1.example.com", "web2.example.com"])),
+            ("Z2".to_string(), hostnames(&["db.example.net"])),
+        ]);
+        let current = BTreeMap::from([("Z1".to_string(), hostnames(&["web1.example.com"]))]);
+
+        let departed = departed_zones(&Config::default(), &published, &current);
+        let departed: Vec<_> = departed
+            .iter()
+            .map(|(zone, ipv4, ipv6)| {
+                assert!(ipv4.is_empty() && ipv6.is_empty());
+                (zone.zone_id.as_str(), zone.hostnames[0].get_hostname())
+            })
+            .collect();
+        assert_eq!(departed, [("Z1", "web2.example.com"), ("Z2", "db.example.net")]);
+    }
+
+    #[test]
+    fn parses_tagged_instances() {
+        let reply = r#"<?xml version="1.0" encoding="UTF-8"?>
+<DescribeInstancesResponse xmlns="http://ec2.amazonaws.com/doc/2016-11-15/">
+  <reservationSet>
+    <item>
+      <instancesSet>
+        <item>
+          <instanceId>i-1</instanceId>
+          <privateIpAddress>10.0.0.1</privateIpAddress>
+          <ipAddress>198.51.100.1</ipAddress>
+          <tagSet>
+            <item><key>Name</key><value>web1</value></item>
+            <item><key>dns-name</key><value>Web1.example.com, www.example.com.</value></item>
+          </tagSet>
+          <networkInterfaceSet>
+            <item>
+              <ipv6AddressesSet><item><ipv6Address>2001:db8::1</ipv6Address></item></ipv6AddressesSet>
+            </item>
+          </networkInterfaceSet>
+        </item>
+        <item>
+          <instanceId>i-2</instanceId>
+          <privateIpAddress>10.0.0.2</privateIpAddress>
+          <tagSet><item><key>dns-name</key><value>web2.example.com</value></item></tagSet>
+        </item>
+      </instancesSet>
+    </item>
+  </reservationSet>
+  <nextToken>page-2</nextToken>
+</DescribeInstancesResponse>"#;
+
+        let (instances, next_token) = parse_instances(reply, "dns-name").unwrap();
+        assert_eq!(next_token.as_deref(), Some("page-2"));
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[0].hostnames, ["web1.example.com", "www.example.com"]);
+        assert_eq!(instances[0].public_ipv4, Some("198.51.100.1".parse().unwrap()));
+        assert_eq!(instances[0].ipv6, ["2001:db8::1".parse::<std::net::Ipv6Addr>().unwrap()]);
+        assert_eq!(instances[1].hostnames, ["web2.example.com"]);
+        assert_eq!(instances[1].public_ipv4, None);
+        assert_eq!(instances[1].private_ipv4, Some("10.0.0.2".parse().unwrap()));
+    }
+}
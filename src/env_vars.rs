@@ -0,0 +1,49 @@
+use {crate::error::Route53IpUpdateError, std::env};
+
+/// Expands `${VAR}` references in the text with the values of environment variables.
+///
+/// `${VAR:-default}` expands to `default` if `VAR` is unset or empty, and `$${` produces a literal `${`. Referencing an
+/// unset variable without a default is an error so typos don't silently produce empty values.
+pub(crate) fn expand_env_vars(text: &str) -> Result<String, Route53IpUpdateError> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        if let Some(after) = rest.strip_prefix("$${") {
+            result.push_str("${");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let Some(end) = after.find('}') else {
+                return Err(Route53IpUpdateError::InvalidEnvVarReference(rest.to_string()));
+            };
+
+            let reference = &after[..end];
+            let (name, default) = match reference.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (reference, None),
+            };
+
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(Route53IpUpdateError::InvalidEnvVarReference(format!("${{{reference}}}")));
+            }
+
+            match (env::var(name), default) {
+                (Ok(value), Some(default)) if value.is_empty() => result.push_str(default),
+                (Ok(value), _) => result.push_str(&value),
+                (Err(_), Some(default)) => result.push_str(default),
+                (Err(_), None) => return Err(Route53IpUpdateError::UndefinedEnvVar(name.to_string())),
+            }
+
+            rest = &after[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
@@ -1,34 +1,498 @@
-use std::{
-    error::Error,
-    fmt::{Display, Formatter, Result as FmtResult},
+use {
+    aws_sdk_route53::types::SdkError,
+    aws_smithy_types::retry::{ErrorKind, ProvideErrorKind},
+    std::{
+        error::Error,
+        fmt::{Display, Formatter, Result as FmtResult},
+        process::ExitCode,
+    },
+    tower::BoxError,
 };
 
+/// AWS error codes returned when the credentials are missing, invalid, expired, or not allowed to make a request.
+const AWS_AUTH_ERROR_CODES: &[&str] = &[
+    "AccessDenied",
+    "AccessDeniedException",
+    "ExpiredToken",
+    "ExpiredTokenException",
+    "IncompleteSignature",
+    "InvalidClientTokenId",
+    "MissingAuthenticationToken",
+    "SignatureDoesNotMatch",
+    "UnrecognizedClientException",
+];
+
+/// AWS error codes returned when requests are being sent too quickly.
+const AWS_THROTTLING_ERROR_CODES: &[&str] = &[
+    "PriorRequestNotComplete",
+    "RequestLimitExceeded",
+    "Throttling",
+    "ThrottlingException",
+    "TooManyRequestsException",
+];
+
+/// The kinds of failure route53-ip-update distinguishes between when deciding whether to retry and which exit status
+/// to use.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorClass {
+    /// Anything not covered by another class.
+    Other,
+
+    /// The configuration is invalid or refers to something that doesn't exist.
+    Config,
+
+    /// The current addresses couldn't be discovered.
+    Discovery,
+
+    /// The AWS credentials are missing, invalid, or not allowed to make a request.
+    AwsAuth,
+
+    /// AWS is throttling requests.
+    AwsThrottling,
+
+    /// An AWS request failed for another reason, e.g. a network error or a server error.
+    AwsService,
+
+    /// The changes were rejected, or would be, as invalid, broke a policy, or failed verification once published.
+    Validation,
+
+    /// The changes were accepted but didn't propagate in time.
+    PropagationTimeout,
+
+    /// The run took longer than `max-runtime`.
+    MaxRuntimeExceeded,
+}
+
+impl ErrorClass {
+    /// Every class, in order of code.
+    pub const ALL: [Self; 9] = [
+        Self::Other,
+        Self::Config,
+        Self::Discovery,
+        Self::AwsAuth,
+        Self::AwsThrottling,
+        Self::AwsService,
+        Self::Validation,
+        Self::PropagationTimeout,
+        Self::MaxRuntimeExceeded,
+    ];
+
+    /// Returns the stable numeric code for this class, which is also the exit status used for it. 2 is used by
+    /// `monitor-only` to report drift, so it's skipped.
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Other => 1,
+            Self::Config => 3,
+            Self::Discovery => 4,
+            Self::AwsAuth => 5,
+            Self::AwsThrottling => 6,
+            Self::AwsService => 7,
+            Self::Validation => 8,
+            Self::PropagationTimeout => 9,
+            Self::MaxRuntimeExceeded => 10,
+        }
+    }
+
+    /// Returns the exit status for this class.
+    pub fn exit_code(self) -> ExitCode {
+        ExitCode::from(self.code())
+    }
+
+    /// Indicates whether errors of this class are usually worth retrying.
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            Self::Discovery
+                | Self::AwsThrottling
+                | Self::AwsService
+                | Self::PropagationTimeout
+                | Self::MaxRuntimeExceeded
+        )
+    }
+
+    /// Returns the class of an error, looking through the errors it wraps for a [`ClassifiedError`] or
+    /// [`Route53IpUpdateError`].
+    pub fn of(error: &(dyn Error + 'static)) -> Self {
+        Classification::of(error).class
+    }
+}
+
+/// The class of an error and whether retrying the operation might succeed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Classification {
+    pub class: ErrorClass,
+    pub retryable: bool,
+}
+
+impl Classification {
+    /// Classifies an error, looking through the errors it wraps for a [`ClassifiedError`] or [`Route53IpUpdateError`].
+    pub fn of(error: &(dyn Error + 'static)) -> Self {
+        let mut current = Some(error);
+        while let Some(error) = current {
+            if let Some(classified) = error.downcast_ref::<ClassifiedError>() {
+                return classified.classification;
+            }
+
+            if let Some(error) = error.downcast_ref::<Route53IpUpdateError>() {
+                return error.class().into();
+            }
+
+            current = error.source();
+        }
+
+        ErrorClass::Other.into()
+    }
+}
+
+impl From<ErrorClass> for Classification {
+    fn from(class: ErrorClass) -> Self {
+        Self {
+            class,
+            retryable: class.is_retryable(),
+        }
+    }
+}
+
+/// An error from another library, such as the AWS SDK, along with its classification.
+#[derive(Debug)]
+pub struct ClassifiedError {
+    classification: Classification,
+
+    /// What was being attempted, prefixed to the error message.
+    context: Option<String>,
+    source: BoxError,
+}
+
+impl ClassifiedError {
+    pub fn new(classification: impl Into<Classification>, source: impl Into<BoxError>) -> Self {
+        Self {
+            classification: classification.into(),
+            context: None,
+            source: source.into(),
+        }
+    }
+
+    /// Prefixes the error message with what was being attempted, keeping the classification and the original error as
+    /// the source.
+    pub fn context(self, context: impl Display) -> Self {
+        let context = match self.context {
+            Some(inner) => format!("{context}: {inner}"),
+            None => context.to_string(),
+        };
+
+        Self {
+            context: Some(context),
+            ..self
+        }
+    }
+
+    /// Classifies an error returned by an AWS SDK operation.
+    pub fn from_sdk<E, R>(error: SdkError<E, R>) -> Self
+    where
+        E: Error + ProvideErrorKind + Send + Sync + 'static,
+        R: std::fmt::Debug + Send + Sync + 'static,
+    {
+        let classification = match &error {
+            SdkError::ServiceError {
+                err,
+                ..
+            } => match (err.code(), err.retryable_error_kind()) {
+                (Some(code), _) if AWS_AUTH_ERROR_CODES.contains(&code) => ErrorClass::AwsAuth.into(),
+                (Some(code), _) if AWS_THROTTLING_ERROR_CODES.contains(&code) => ErrorClass::AwsThrottling.into(),
+                (_, Some(ErrorKind::ThrottlingError)) => ErrorClass::AwsThrottling.into(),
+                (Some("InvalidInput" | "InvalidChangeBatch"), _) => ErrorClass::Validation.into(),
+                (_, Some(_)) => ErrorClass::AwsService.into(),
+                (_, None) => Classification {
+                    class: ErrorClass::AwsService,
+                    retryable: false,
+                },
+            },
+            // The request couldn't be built, usually because there are no credentials to sign it with.
+            SdkError::ConstructionFailure(_) => ErrorClass::AwsAuth.into(),
+            SdkError::TimeoutError(_)
+            | SdkError::DispatchFailure(_)
+            | SdkError::ResponseError {
+                ..
+            } => ErrorClass::AwsService.into(),
+        };
+
+        Self::new(classification, error)
+    }
+
+    /// Classifies an error returned by an AWS JSON API called without an SDK.
+    pub fn from_aws_json(operation: &str, status: u16, code: &str, message: &str) -> Self {
+        let classification = if AWS_AUTH_ERROR_CODES.contains(&code) {
+            ErrorClass::AwsAuth.into()
+        } else if AWS_THROTTLING_ERROR_CODES.contains(&code) || status == 429 {
+            ErrorClass::AwsThrottling.into()
+        } else {
+            Classification {
+                class: ErrorClass::AwsService,
+                retryable: status >= 500,
+            }
+        };
+
+        let error = Route53IpUpdateError::AwsApiFailed(operation.to_string(), code.to_string(), message.to_string());
+        Self::new(classification, error)
+    }
+}
+
+impl Display for ClassifiedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        if let Some(context) = &self.context {
+            write!(f, "{context}: ")?;
+        }
+
+        Display::fmt(&self.source, f)
+    }
+}
+
+impl Error for ClassifiedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
 #[derive(Debug)]
 pub enum Route53IpUpdateError {
+    AmbiguousZoneName(String, Vec<String>),
+    AssumeRoleFailed(String, String),
+    AwsApiFailed(String, String, String),
+    ChangeBatchRejected(Vec<String>),
+    DecryptionFailed(String, String),
+    DnsUpdateFailed(String, String),
+    DriftWebhookFailed(String),
+    EventBridgeFailed(String),
+    InvalidAddressClass(String),
+    InvalidAddressRanking(String),
+    InvalidAddressSource(String),
+    InvalidChangeBatch(String, String),
     InvalidConfig(Vec<String>),
+    InvalidConfigFormat(String),
+    InvalidEndpointUrl(String),
+    InvalidEnvVarReference(String),
     InvalidIpAddr(String),
+    InvalidNat64Prefix(String),
     InvalidQueryAddressType(String),
+    InvalidSchedule(String, String),
     InvalidTtl(String),
+    MaxRuntimeExceeded(String),
+    MfaTokenUnavailable(String),
+    MissingCredentials,
     MissingExpectedAwsReplyField(String),
+    Nat64NotFound,
+    NoChangeHistory(String),
+    NoHostedZones,
+    NoSuchHostedZone(String, Vec<String>),
+    NoZoneForHostname(String),
+    NotATerminal(String),
+    OutputFileExists(String),
+    PolicyViolated(String, Vec<String>),
+    PropagationTimedOut(String),
+    RelayFailed(String),
+    SourceTimedOut(String),
+    StunFailed(String),
+    UndefinedEnvVar(String),
+    Unhealthy(String),
     UnexpectedRoute53Status(String),
     UnknownConfigFileExt(Option<String>),
+    UnknownConfigKeys(Vec<String>),
+    UnsupportedEndpointVariant(String),
+    UnsupportedTsigAlgorithm(String),
+    VerificationFailed(String, Vec<String>, bool),
+    ZoneLocked(String),
+    ZoneNotFound(String),
 }
 
 impl Display for Route53IpUpdateError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         match self {
+            Self::AmbiguousZoneName(name, ids) => write!(
+                f,
+                "Multiple hosted zones are named {name} ({}); use the zone ID or set private-zone",
+                ids.join(", ")
+            ),
+            Self::AssumeRoleFailed(role_arn, message) => write!(f, "Failed to assume role {role_arn}: {message}"),
+            Self::AwsApiFailed(operation, code, message) => write!(f, "{operation} failed: {code}: {message}"),
+            Self::ChangeBatchRejected(messages) => write!(f, "Route 53 rejected the changes: {}", messages.join("; ")),
+            Self::DecryptionFailed(path, message) => write!(f, "Unable to decrypt {path}: {message}"),
+            Self::DnsUpdateFailed(target, code) => write!(f, "DNS update of {target} failed: {code}"),
+            Self::DriftWebhookFailed(message) => write!(f, "Drift webhook failed: {message}"),
+            Self::EventBridgeFailed(message) => write!(f, "EventBridge PutEvents failed: {message}"),
+            Self::InvalidAddressClass(class) => write!(f, "Invalid address class: {class}"),
+            Self::InvalidAddressRanking(ranking) => write!(f, "Invalid address ranking: {ranking}"),
+            Self::InvalidAddressSource(source) => write!(f, "Invalid address source: {source}"),
+            Self::InvalidChangeBatch(hostname, message) => {
+                write!(f, "Changes to {hostname} would be rejected by Route 53: {message}")
+            }
             Self::InvalidConfig(messages) => write!(f, "Invalid configuration: {}", messages.join(" ")),
+            Self::InvalidConfigFormat(format) => write!(f, "Invalid configuration format: {format}"),
+            Self::InvalidEndpointUrl(url) => write!(f, "Invalid AWS endpoint URL: {url}"),
+            Self::InvalidEnvVarReference(reference) => write!(f, "Invalid environment variable reference: {reference}"),
             Self::InvalidIpAddr(ip) => write!(f, "Invalid IP address: {ip}"),
+            Self::InvalidNat64Prefix(prefix) => write!(f, "Invalid NAT64 prefix: {prefix}"),
             Self::InvalidQueryAddressType(qat) => write!(f, "Invalid query address type: {qat}"),
+            Self::InvalidSchedule(expression, message) => write!(f, "Invalid schedule {expression}: {message}"),
             Self::InvalidTtl(ttl) => write!(f, "Invalid TTL: {ttl}"),
+            Self::MaxRuntimeExceeded(max_runtime) => {
+                write!(f, "Gave up after {max_runtime}: the run took longer than max-runtime")
+            }
+            Self::MfaTokenUnavailable(message) => write!(f, "Unable to obtain an MFA token: {message}"),
+            Self::MissingCredentials => write!(f, "No AWS credentials are available"),
             Self::MissingExpectedAwsReplyField(field) => write!(f, "AWS reply is missing expected field: {field}"),
+            Self::Nat64NotFound => {
+                write!(
+                    f,
+                    "nat64 is set, but no NAT64 prefix was found; the resolver doesn't do DNS64 (set nat64-prefix)"
+                )
+            }
+            Self::NoChangeHistory(hostname) => write!(f, "No change to {hostname} was found in the snapshots"),
+            Self::NoHostedZones => write!(f, "No hosted zones are visible to these credentials"),
+            Self::NoSuchHostedZone(zone_id, suggestions) => {
+                write!(f, "Hosted zone {zone_id} does not exist or is not accessible to these credentials")?;
+                match suggestions.as_slice() {
+                    [] => Ok(()),
+                    [suggestion] => write!(f, "; did you mean {suggestion}?"),
+                    suggestions => write!(f, "; did you mean one of {}?", suggestions.join(", ")),
+                }
+            }
+            Self::NoZoneForHostname(hostname) => write!(f, "No accessible hosted zone contains hostname {hostname}"),
+            Self::NotATerminal(command) => write!(f, "The {command} command must be run interactively from a terminal"),
+            Self::OutputFileExists(path) => write!(f, "{path} already exists; use --force to overwrite it"),
+            Self::PolicyViolated(zone_id, violations) => {
+                write!(
+                    f,
+                    "Refusing to change zone {zone_id}; the changes break the policies: {}",
+                    violations.join("; ")
+                )
+            }
+            Self::PropagationTimedOut(change_id) => {
+                write!(f, "Route 53 change {change_id} did not propagate in time; it may still complete")
+            }
+            Self::RelayFailed(message) => write!(f, "Relay server did not apply the update: {message}"),
+            Self::SourceTimedOut(source) => write!(f, "Timed out querying {source} for addresses"),
+            Self::StunFailed(message) => write!(f, "Unable to detect the NAT type: {message}"),
+            Self::UndefinedEnvVar(name) => write!(f, "Environment variable {name} is not set"),
+            Self::Unhealthy(message) => write!(f, "Unhealthy: {message}"),
             Self::UnexpectedRoute53Status(status) => write!(f, "Unepxected Route 53 change status reported: {status}"),
             Self::UnknownConfigFileExt(ext) => match ext {
                 Some(ext) => write!(f, "Unknown extension for configuration file: {ext}"),
                 None => write!(f, "Configuration file has no extension"),
             },
+            Self::UnknownConfigKeys(messages) => write!(f, "{}", messages.join(" ")),
+            Self::UnsupportedEndpointVariant(region) => {
+                write!(f, "FIPS and dual-stack Route 53 endpoints are not available for region {region}")
+            }
+            Self::UnsupportedTsigAlgorithm(algorithm) => write!(f, "Unsupported TSIG algorithm: {algorithm}"),
+            Self::VerificationFailed(zone_id, problems, rolled_back) => {
+                write!(f, "The changes to zone {zone_id} failed verification: {}", problems.join("; "))?;
+                if *rolled_back {
+                    write!(f, "; the previous records were restored")
+                } else {
+                    Ok(())
+                }
+            }
+            Self::ZoneLocked(zone_id) => write!(f, "Zone {zone_id} is still locked by another updater"),
+            Self::ZoneNotFound(name) => write!(f, "No accessible hosted zone named {name} was found"),
+        }
+    }
+}
+
+impl Route53IpUpdateError {
+    /// Returns the class of this error.
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            Self::AmbiguousZoneName(..)
+            | Self::DecryptionFailed(..)
+            | Self::InvalidAddressClass(_)
+            | Self::InvalidAddressRanking(_)
+            | Self::InvalidAddressSource(_)
+            | Self::InvalidConfig(_)
+            | Self::InvalidConfigFormat(_)
+            | Self::InvalidEndpointUrl(_)
+            | Self::InvalidEnvVarReference(_)
+            | Self::InvalidNat64Prefix(_)
+            | Self::InvalidQueryAddressType(_)
+            | Self::InvalidSchedule(..)
+            | Self::InvalidTtl(_)
+            | Self::NoHostedZones
+            | Self::NoSuchHostedZone(..)
+            | Self::NoZoneForHostname(_)
+            | Self::UndefinedEnvVar(_)
+            | Self::UnknownConfigFileExt(_)
+            | Self::UnknownConfigKeys(_)
+            | Self::UnsupportedEndpointVariant(_)
+            | Self::UnsupportedTsigAlgorithm(_)
+            | Self::ZoneNotFound(_) => ErrorClass::Config,
+            Self::AssumeRoleFailed(..) | Self::MfaTokenUnavailable(_) | Self::MissingCredentials => ErrorClass::AwsAuth,
+            Self::AwsApiFailed(..)
+            | Self::EventBridgeFailed(_)
+            | Self::MissingExpectedAwsReplyField(_)
+            | Self::UnexpectedRoute53Status(_) => ErrorClass::AwsService,
+            Self::ChangeBatchRejected(_)
+            | Self::InvalidChangeBatch(..)
+            | Self::PolicyViolated(..)
+            | Self::VerificationFailed(..) => ErrorClass::Validation,
+            Self::PropagationTimedOut(_) => ErrorClass::PropagationTimeout,
+            Self::MaxRuntimeExceeded(_) => ErrorClass::MaxRuntimeExceeded,
+            Self::Nat64NotFound | Self::SourceTimedOut(_) | Self::StunFailed(_) => ErrorClass::Discovery,
+            Self::DnsUpdateFailed(..)
+            | Self::DriftWebhookFailed(_)
+            | Self::InvalidIpAddr(_)
+            | Self::NoChangeHistory(_)
+            | Self::NotATerminal(_)
+            | Self::OutputFileExists(_)
+            | Self::RelayFailed(_)
+            | Self::Unhealthy(_)
+            | Self::ZoneLocked(_) => ErrorClass::Other,
         }
     }
 }
 
 impl Error for Route53IpUpdateError {}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{Classification, ClassifiedError, ErrorClass, Route53IpUpdateError},
+        std::error::Error,
+        tower::BoxError,
+    };
+
+    #[test]
+    fn classifies_wrapped_errors() {
+        let error: BoxError = Route53IpUpdateError::ChangeBatchRejected(vec!["bad".to_string()]).into();
+        assert_eq!(ErrorClass::of(error.as_ref()), ErrorClass::Validation);
+        assert!(!Classification::of(error.as_ref()).retryable);
+
+        let error: BoxError = Route53IpUpdateError::MaxRuntimeExceeded("5m".to_string()).into();
+        assert_eq!(ErrorClass::of(error.as_ref()).code(), 10);
+        assert!(Classification::of(error.as_ref()).retryable);
+
+        let error: BoxError = ClassifiedError::new(ErrorClass::AwsThrottling, "Rate exceeded").into();
+        let classification = Classification::of(error.as_ref());
+        assert_eq!(classification.class, ErrorClass::AwsThrottling);
+        assert!(classification.retryable);
+        assert_eq!(error.to_string(), "Rate exceeded");
+
+        let error: BoxError = "something else".into();
+        assert_eq!(ErrorClass::of(error.as_ref()), ErrorClass::Other);
+        assert_eq!(ErrorClass::Other.code(), 1);
+
+        let retryable: Vec<u8> =
+            ErrorClass::ALL.into_iter().filter(|class| class.is_retryable()).map(ErrorClass::code).collect();
+        assert_eq!(retryable, [4, 6, 7, 9, 10]);
+    }
+
+    #[test]
+    fn context_keeps_the_original_error() {
+        let error = ClassifiedError::new(ErrorClass::AwsAuth, Route53IpUpdateError::MissingCredentials)
+            .context("listing zones")
+            .context("zone Z1");
+        assert_eq!(error.to_string(), "zone Z1: listing zones: No AWS credentials are available");
+
+        let source = error.source().unwrap();
+        assert!(matches!(source.downcast_ref(), Some(Route53IpUpdateError::MissingCredentials)));
+        assert_eq!(Classification::of(&error).class, ErrorClass::AwsAuth);
+    }
+}
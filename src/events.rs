@@ -0,0 +1,199 @@
+use {
+    crate::{
+        aws::{load_sdk_config, AwsJsonClient},
+        config::Config,
+        diff::RecordSetDiff,
+        error::Route53IpUpdateError,
+    },
+    log::{info, warn},
+    serde::Serialize,
+    serde_json::{json, Value},
+    tower::BoxError,
+};
+
+/// The source of the events put onto the event bus.
+const EVENT_SOURCE: &str = "route53-ip-update";
+
+/// The detail type of the events put onto the event bus.
+const EVENT_DETAIL_TYPE: &str = "DNS Records Changed";
+
+/// The detail type of the event put onto the event bus when changes fail verification.
+const VERIFICATION_FAILED_DETAIL_TYPE: &str = "DNS Update Verification Failed";
+
+/// The most entries PutEvents accepts in one call.
+const MAX_ENTRIES_PER_CALL: usize = 10;
+
+/// The detail of the event put onto the event bus when a hostname's records are changed.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct RecordsChangedDetail<'a> {
+    pub(crate) zone_id: &'a str,
+    pub(crate) hostname: &'a str,
+
+    /// The Route 53 change ID, if the zone is hosted on Route 53.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) change_id: Option<&'a str>,
+
+    /// The record sets that were changed. In each, `old` is what was published before and `new` is what is published
+    /// now.
+    pub(crate) changes: Vec<&'a RecordSetDiff>,
+}
+
+/// The detail of the event put onto the event bus when changes to a zone fail verification.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct VerificationFailedDetail<'a> {
+    pub(crate) zone_id: &'a str,
+
+    /// What verification found wrong.
+    pub(crate) problems: &'a [String],
+
+    /// Whether the records were put back the way they were before the changes.
+    pub(crate) rolled_back: bool,
+
+    /// The record sets that were changed.
+    pub(crate) changes: &'a [RecordSetDiff],
+}
+
+/// Returns the PutEvents entries for changes made to a zone: one per hostname changed.
+pub(crate) fn change_event_entries(
+    event_bus: &str,
+    zone_id: &str,
+    diffs: &[RecordSetDiff],
+    change_id: Option<&str>,
+) -> Vec<Value> {
+    let mut hostnames: Vec<&str> = diffs.iter().map(|diff| diff.hostname.as_str()).collect();
+    hostnames.sort();
+    hostnames.dedup();
+
+    hostnames
+        .into_iter()
+        .map(|hostname| {
+            let detail = RecordsChangedDetail {
+                zone_id,
+                hostname,
+                change_id,
+                changes: diffs.iter().filter(|diff| diff.hostname == hostname).collect(),
+            };
+
+            json!({
+                "Source": EVENT_SOURCE,
+                "DetailType": EVENT_DETAIL_TYPE,
+                "Detail": serde_json::to_string(&detail).unwrap_or_default(),
+                "EventBusName": event_bus,
+            })
+        })
+        .collect()
+}
+
+/// Puts an event onto the configured event bus for each hostname changed in a zone. Failures are logged rather than
+/// returned, since the records have already been changed by the time the events are sent.
+pub(crate) async fn send_change_events(
+    config: &Config,
+    zone_id: &str,
+    diffs: &[RecordSetDiff],
+    change_id: Option<&str>,
+) {
+    let Some(event_bus) = &config.event_bus else {
+        return;
+    };
+
+    if diffs.is_empty() {
+        return;
+    }
+
+    let entries = change_event_entries(event_bus, zone_id, diffs, change_id);
+    match put_events(config, &entries).await {
+        Ok(()) => info!("Sent {} change event(s) for zone {zone_id} to event bus {event_bus}", entries.len()),
+        Err(e) => warn!("Unable to send change events for zone {zone_id} to event bus {event_bus}: {e}"),
+    }
+}
+
+/// Puts an event onto the configured event bus reporting that changes to a zone failed verification, so the failure can
+/// be alerted on.
+pub(crate) async fn send_verification_failed_event(
+    config: &Config,
+    zone_id: &str,
+    diffs: &[RecordSetDiff],
+    problems: &[String],
+    rolled_back: bool,
+) {
+    let Some(event_bus) = &config.event_bus else {
+        return;
+    };
+
+    let detail = VerificationFailedDetail {
+        zone_id,
+        problems,
+        rolled_back,
+        changes: diffs,
+    };
+    let entry = json!({
+        "Source": EVENT_SOURCE,
+        "DetailType": VERIFICATION_FAILED_DETAIL_TYPE,
+        "Detail": serde_json::to_string(&detail).unwrap_or_default(),
+        "EventBusName": event_bus,
+    });
+
+    match put_events(config, &[entry]).await {
+        Ok(()) => info!("Sent a verification failure event for zone {zone_id} to event bus {event_bus}"),
+        Err(e) => warn!("Unable to send a verification failure event for zone {zone_id} to event bus {event_bus}: {e}"),
+    }
+}
+
+/// Calls EventBridge's PutEvents with the entries, using the configured credentials.
+async fn put_events(config: &Config, entries: &[Value]) -> Result<(), BoxError> {
+    let sdk_config = load_sdk_config(config.profile.as_deref(), config.region.as_deref()).await;
+    let events = AwsJsonClient::new(&sdk_config, "events", "AWSEvents", "1.1", config.timeout)?;
+
+    for chunk in entries.chunks(MAX_ENTRIES_PER_CALL) {
+        let reply = events.call("PutEvents", &json!({ "Entries": chunk })).await?;
+        if let Some(failed) = reply["FailedEntryCount"].as_u64().filter(|&count| count > 0) {
+            let reasons: Vec<&str> =
+                reply["Entries"].as_array().into_iter().flatten().filter_map(|e| e["ErrorMessage"].as_str()).collect();
+            return Err(Route53IpUpdateError::EventBridgeFailed(format!(
+                "{failed} event(s) were not accepted: {}",
+                reasons.join("; ")
+            ))
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::change_event_entries,
+        crate::diff::{RecordSetDiff, RecordSetSummary},
+        serde_json::{json, Value},
+    };
+
+    #[test]
+    fn one_event_per_changed_hostname() {
+        let diff = |hostname: &str, record_type: &str, old: &str, new: &str| RecordSetDiff {
+            hostname: hostname.to_string(),
+            record_type: record_type.to_string(),
+            old: Some(RecordSetSummary::new(Some(300), [old])),
+            new: Some(RecordSetSummary::new(Some(300), [new])),
+        };
+        let diffs = vec![
+            diff("home.example.com", "A", "192.0.2.1", "192.0.2.2"),
+            diff("home.example.com", "AAAA", "2001:db8::1", "2001:db8::2"),
+            diff("gw.example.com", "A", "192.0.2.1", "192.0.2.2"),
+        ];
+
+        let entries = change_event_entries("default", "Z123", &diffs, Some("/change/C123"));
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["EventBusName"], "default");
+        assert_eq!(entries[0]["DetailType"], "DNS Records Changed");
+
+        let detail: Value = serde_json::from_str(entries[1]["Detail"].as_str().unwrap()).unwrap();
+        assert_eq!(detail["zone-id"], "Z123");
+        assert_eq!(detail["hostname"], "home.example.com");
+        assert_eq!(detail["change-id"], "/change/C123");
+        assert_eq!(detail["changes"][1]["old"], json!({"ttl": 300, "values": ["2001:db8::1"]}));
+        assert_eq!(detail["changes"][1]["new"]["values"], json!(["2001:db8::2"]));
+    }
+}
@@ -0,0 +1,208 @@
+use {
+    crate::{
+        aws::Route53Clients,
+        config::{Config, Route53ZoneConfig},
+        diff::{print_changes, RecordSetDiff, RecordSetSummary},
+        prompt::prompt_bool,
+        provider::{parse_timestamp_record_text, timestamp_record_hostname},
+        route53_api::{list_zone_record_sets, Route53Api},
+        update::update_route53_zone,
+    },
+    aws_sdk_route53::model::{Change, ChangeAction, ResourceRecordSet, RrType},
+    log::{info, warn},
+    std::{
+        collections::HashSet,
+        time::{Duration, SystemTime},
+    },
+    tower::BoxError,
+};
+
+/// The comment attached to the Route 53 changes removing stale records.
+const GC_CHANGE_COMMENT: &str = concat!("Stale records removed by ", env!("CARGO_PKG_NAME"), " gc");
+
+/// The changes removing the records of hostnames whose updaters haven't checked in recently enough.
+#[derive(Debug, Default)]
+struct StaleRecords {
+    /// The stale hostnames, in listing order.
+    hostnames: Vec<String>,
+
+    /// The deletions of their A, AAAA, and timestamp records.
+    changes: Vec<Change>,
+
+    /// The record sets removed, for display.
+    diffs: Vec<RecordSetDiff>,
+}
+
+/// Removes the A and AAAA records of hostnames in the configured zones whose timestamp records say they haven't been
+/// updated within `max_age`, along with the timestamp records. Only zones hosted on Route 53 are examined. With
+/// `dry_run`, the records are only listed. If any zone fails, the others are still cleaned up and the first error is
+/// returned.
+pub(crate) async fn collect_garbage(
+    config: &Config,
+    route53_clients: &mut Route53Clients,
+    max_age: Duration,
+    dry_run: bool,
+) -> Result<(), BoxError> {
+    let mut first_error = None;
+    for zone in config.zones_with_mirrors() {
+        if zone.cloudflare.is_some() || zone.rfc2136.is_some() {
+            warn!("Skipping zone {}: gc only supports zones hosted on Route 53", zone.zone_id);
+            continue;
+        }
+
+        let result = match route53_clients.for_zone(&zone).await {
+            Ok(route53) => collect_zone_garbage(route53, config, &zone, max_age, dry_run).await,
+            Err(e) => Err(e.into()),
+        };
+
+        if let Err(e) = result {
+            // Keep going so one inaccessible zone doesn't stop the others from being cleaned up.
+            eprintln!("Error: Failed to remove stale records from zone {}: {e}", zone.zone_id);
+            first_error.get_or_insert(e);
+        }
+    }
+
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Removes the stale records from one Route 53 zone.
+async fn collect_zone_garbage<R: Route53Api>(
+    route53: R,
+    config: &Config,
+    zone_config: &Route53ZoneConfig,
+    max_age: Duration,
+    dry_run: bool,
+) -> Result<(), BoxError> {
+    let record_sets = list_zone_record_sets(&route53, &zone_config.zone_id).await?;
+    let stale = stale_records(&record_sets, max_age, SystemTime::now());
+    if stale.hostnames.is_empty() {
+        info!("No stale records in zone {}", zone_config.zone_id);
+        return Ok(());
+    }
+
+    print_changes(&zone_config.zone_id, &stale.diffs);
+    if dry_run {
+        info!("Not removing stale records from zone {}: dry run", zone_config.zone_id);
+        return Ok(());
+    }
+
+    if config.interactive
+        && !prompt_bool(&format!("Remove the stale records from zone {}?", zone_config.zone_id), false)?
+    {
+        info!("Skipping changes to zone {}", zone_config.zone_id);
+        return Ok(());
+    }
+
+    update_route53_zone(route53, &zone_config.zone_id, stale.changes, GC_CHANGE_COMMENT).await?;
+    info!("Removed stale records for {} from zone {}", stale.hostnames.join(", "), zone_config.zone_id);
+    Ok(())
+}
+
+/// Returns the deletions needed to remove the records of hostnames whose timestamp records are older than `max_age`.
+///
+/// Hostnames without a timestamp record, or whose timestamp record can't be parsed, are left alone: there's no telling
+/// whether an updater still owns them. Records with a routing policy (a set identifier) are left alone too, since they
+/// weren't necessarily created by an updater.
+fn stale_records(record_sets: &[ResourceRecordSet], max_age: Duration, now: SystemTime) -> StaleRecords {
+    let mut stale_hostnames = HashSet::new();
+    let mut stale = StaleRecords::default();
+
+    for rrs in record_sets {
+        let (Some(&RrType::Txt), Some(hostname)) = (rrs.r#type(), rrs.name().and_then(timestamp_record_hostname))
+        else {
+            continue;
+        };
+
+        let Some(updated) =
+            rrs.resource_records().unwrap_or_default().iter().find_map(|rr| parse_timestamp_record_text(rr.value()?))
+        else {
+            warn!("Unable to tell when {hostname} was last updated from its timestamp record; leaving it alone");
+            continue;
+        };
+
+        if now.duration_since(updated).unwrap_or_default() > max_age {
+            stale_hostnames.insert(format!("{hostname}."));
+            stale.hostnames.push(hostname.to_string());
+        }
+    }
+
+    for rrs in record_sets {
+        let Some(name) = rrs.name() else {
+            continue;
+        };
+
+        let owner = match rrs.r#type() {
+            Some(&RrType::A | &RrType::Aaaa) if rrs.set_identifier().is_none() => name.to_string(),
+            Some(&RrType::Txt) => match timestamp_record_hostname(name) {
+                Some(hostname) => format!("{hostname}."),
+                None => continue,
+            },
+            _ => continue,
+        };
+
+        if !stale_hostnames.contains(&owner) {
+            continue;
+        }
+
+        let values = rrs.resource_records().unwrap_or_default().iter().filter_map(|rr| rr.value());
+        stale.diffs.push(RecordSetDiff {
+            hostname: name.trim_end_matches('.').to_string(),
+            record_type: rrs.r#type().map(|t| t.as_str()).unwrap_or_default().to_string(),
+            old: Some(RecordSetSummary::new(rrs.ttl(), values)),
+            new: None,
+        });
+        stale.changes.push(Change::builder().action(ChangeAction::Delete).resource_record_set(rrs.clone()).build());
+    }
+
+    stale
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::collect_zone_garbage,
+        crate::{
+            config::{Config, Route53ZoneConfig},
+            route53_api::fake::{rrs, FakeRoute53},
+        },
+        aws_sdk_route53::model::RrType,
+        std::time::{Duration, SystemTime},
+    };
+
+    fn timestamp(age: Duration) -> String {
+        let updated = humantime::format_rfc3339_seconds(SystemTime::now() - age);
+        format!("\"updated={updated} source=laptop version=route53-ip-update/0.1.0\"")
+    }
+
+    #[tokio::test]
+    async fn removes_records_of_hostnames_not_updated_recently() {
+        let day = Duration::from_secs(86400);
+        let fake = FakeRoute53::with_record_sets([
+            rrs("old.example.com", RrType::A, "192.0.2.1"),
+            rrs("old.example.com", RrType::Aaaa, "2001:db8::1"),
+            rrs("old.example.com", RrType::Mx, "10 mail.example.com"),
+            rrs("_r53ipupdate.old.example.com", RrType::Txt, &timestamp(40 * day)),
+            rrs("new.example.com", RrType::A, "192.0.2.2"),
+            rrs("_r53ipupdate.new.example.com", RrType::Txt, &timestamp(day)),
+            rrs("unowned.example.com", RrType::A, "192.0.2.3"),
+        ]);
+        let zone: Route53ZoneConfig = serde_yaml::from_str("zone-id: Z1\nhostnames: []").unwrap();
+
+        collect_zone_garbage(fake.clone(), &Config::default(), &zone, 30 * day, false).await.unwrap();
+
+        let remaining: Vec<_> = fake
+            .record_sets()
+            .iter()
+            .map(|rrs| format!("{} {}", rrs.name().unwrap(), rrs.r#type().unwrap().as_str()))
+            .collect();
+        assert_eq!(
+            remaining,
+            [
+                "_r53ipupdate.new.example.com. TXT",
+                "new.example.com. A",
+                "old.example.com. MX",
+                "unowned.example.com. A",
+            ]
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use {
+    crate::{config::Config, error::Route53IpUpdateError, schedule::Schedule, state::UpdateState},
+    chrono::Local,
+    log::debug,
+    serde::Deserialize,
+    std::{
+        net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tower::BoxError,
+};
+
+/// How recent the last successful sync must be when neither --max-age nor an interval or schedule is set.
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(3600);
+
+/// The part of the status endpoint's response the healthcheck looks at.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct StatusResponse {
+    last_sync: Option<String>,
+}
+
+/// Checks that the daemon brought every zone up-to-date within `max_age`, asking its status endpoint (the URL given,
+/// or status-listen) or, failing that, reading the state file. An error means the daemon isn't healthy.
+pub(crate) async fn run_healthcheck(
+    config: &Config,
+    url: Option<&str>,
+    max_age: Option<Duration>,
+) -> Result<(), BoxError> {
+    // An update can be missed once, e.g. when one takes a while, before the daemon counts as unhealthy. With a schedule,
+    // that's measured against the longest wait between its updates, e.g. overnight.
+    let gap = Schedule::from_config(config)?.and_then(|schedule| schedule.longest_gap_from(Local::now()));
+    let max_age = max_age.or(gap.map(|gap| gap * 3)).unwrap_or(DEFAULT_MAX_AGE);
+    let url = url.map(str::to_string).or(config.status_listen.map(status_url));
+
+    let last_sync = match (url, &config.state_file) {
+        (Some(url), _) => {
+            debug!("Asking {url} for the update status");
+            let client = reqwest::Client::builder().timeout(config.timeout).build()?;
+            let mut request = client.get(&url);
+            if let Some(token) = &config.status_token {
+                request = request.bearer_auth(token);
+            }
+
+            let response = request.send().await?.error_for_status()?;
+            let status: StatusResponse = serde_json::from_slice(&response.bytes().await?)?;
+            status.last_sync.map(|last_sync| humantime::parse_rfc3339(&last_sync)).transpose()?
+        }
+        (None, Some(state_file)) => {
+            debug!("Reading the time of the last sync from {}", state_file.display());
+            UpdateState::load(Some(state_file)).await.last_sync.map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+        }
+        (None, None) => {
+            return Err(Route53IpUpdateError::InvalidConfig(vec![
+                "The healthcheck command needs --url, status-listen, or state-file to find the update status."
+                    .to_string(),
+            ])
+            .into())
+        }
+    };
+
+    let age = check_freshness(last_sync, max_age, SystemTime::now())?;
+    println!("Healthy: the last successful sync was {} ago.", humantime::format_duration(age));
+    Ok(())
+}
+
+/// Returns the URL of the status endpoint listening on an address. A daemon listening on all addresses is reached
+/// through the loopback address.
+fn status_url(listen: SocketAddr) -> String {
+    let mut addr = listen;
+    if addr.ip().is_unspecified() {
+        addr.set_ip(if addr.is_ipv4() {
+            Ipv4Addr::LOCALHOST.into()
+        } else {
+            Ipv6Addr::LOCALHOST.into()
+        });
+    }
+
+    format!("http://{addr}/status")
+}
+
+/// Returns how long ago the last successful sync was, failing if there hasn't been one within `max_age`.
+fn check_freshness(
+    last_sync: Option<SystemTime>,
+    max_age: Duration,
+    now: SystemTime,
+) -> Result<Duration, Route53IpUpdateError> {
+    let Some(last_sync) = last_sync else {
+        return Err(Route53IpUpdateError::Unhealthy("no update has succeeded yet".to_string()));
+    };
+
+    // Truncated to whole seconds, since that's all the status endpoint and state file record.
+    let age = Duration::from_secs(now.duration_since(last_sync).unwrap_or_default().as_secs());
+    if age > max_age {
+        return Err(Route53IpUpdateError::Unhealthy(format!(
+            "the last successful sync was {} ago, more than {}",
+            humantime::format_duration(age),
+            humantime::format_duration(max_age)
+        )));
+    }
+
+    Ok(age)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{check_freshness, status_url},
+        std::time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn requires_a_recent_sync() {
+        let now = SystemTime::now();
+        let max_age = Duration::from_secs(900);
+        assert_eq!(check_freshness(Some(now - Duration::from_secs(60)), max_age, now).unwrap().as_secs(), 60);
+        assert_eq!(
+            check_freshness(Some(now - Duration::from_secs(3600)), max_age, now).unwrap_err().to_string(),
+            "Unhealthy: the last successful sync was 1h ago, more than 15m"
+        );
+        assert_eq!(
+            check_freshness(None, max_age, now).unwrap_err().to_string(),
+            "Unhealthy: no update has succeeded yet"
+        );
+
+        assert_eq!(status_url("0.0.0.0:8246".parse().unwrap()), "http://127.0.0.1:8246/status");
+        assert_eq!(status_url("[::]:8246".parse().unwrap()), "http://[::1]:8246/status");
+        assert_eq!(status_url("192.0.2.1:8246".parse().unwrap()), "http://192.0.2.1:8246/status");
+    }
+}
@@ -0,0 +1,158 @@
+use {
+    crate::{
+        config::{Config, HostnameConfig},
+        error::Route53IpUpdateError,
+    },
+    network_interface::{Addr, NetworkInterface, NetworkInterfaceConfig},
+    std::net::IpAddr,
+    tower::BoxError,
+};
+
+/// The values substituted for the placeholders in hostnames that are known when the configuration is loaded.
+struct Placeholders {
+    /// This machine's hostname, without any domain: `{machine}`.
+    machine: String,
+
+    /// The name of the network interface addresses are published from: `{interface}`.
+    interface: Option<String>,
+}
+
+impl Placeholders {
+    fn expand(&self, hostname: &str) -> String {
+        let mut hostname = hostname.replace("{machine}", &self.machine);
+        if let Some(interface) = &self.interface {
+            hostname = hostname.replace("{interface}", interface);
+        }
+
+        hostname
+    }
+}
+
+/// Expands the placeholders in the configured hostnames, so one configuration can be shared by many machines:
+///
+/// * `{machine}`: this machine's hostname, without any domain, in lowercase.
+/// * `{interface}`: the name of the first network interface, other than loopback interfaces and those in
+///   `ignore-interfaces`, that has an address.
+/// * `{zone}`: the domain name of the zone the hostname is published to. This is expanded here for zones configured by
+///   domain name; the others (zones configured by hosted zone ID, top-level hostnames, and tagged zone selectors) have
+///   it expanded once their zones have been looked up.
+///
+/// Short hostnames (a single label, such as `nas`) in zones, tagged zone selectors, and top-level hostnames listing
+/// their zones are qualified with the zone's domain name, becoming e.g. `nas.{zone}`.
+pub(crate) fn expand_hostname_placeholders(config: &mut Config) -> Result<(), BoxError> {
+    let interface_used = config
+        .hostnames
+        .iter()
+        .chain(config.route53_zones.iter().flat_map(|r53_zc| &r53_zc.hostnames))
+        .chain(config.tagged_zones.iter().flat_map(|tagged_zones| &tagged_zones.hostnames))
+        .flat_map(HostnameConfig::get_hostnames)
+        .any(|hostname| hostname.contains("{interface}"));
+    let interface = if interface_used {
+        let interface = first_interface(config)?.ok_or_else(|| {
+            Route53IpUpdateError::InvalidConfig(vec![
+                "A hostname uses {interface}, but no network interface with an address was found.".to_string(),
+            ])
+        })?;
+        Some(interface)
+    } else {
+        None
+    };
+
+    let placeholders = Placeholders {
+        machine: machine_name(),
+        interface,
+    };
+    let expand = |hostnames: &mut Vec<HostnameConfig>, in_zone: bool| {
+        *hostnames = hostnames
+            .iter()
+            .map(|hc| {
+                let in_zone = in_zone || !hc.get_zones().is_empty();
+                hc.map_hostnames(|hostname| {
+                    let hostname = placeholders.expand(hostname);
+                    if in_zone {
+                        qualify_short_hostname(&hostname)
+                    } else {
+                        hostname
+                    }
+                })
+            })
+            .collect();
+    };
+
+    expand(&mut config.hostnames, false);
+    for r53_zc in &mut config.route53_zones {
+        expand(&mut r53_zc.hostnames, true);
+        r53_zc.expand_zone_placeholder();
+    }
+
+    for tagged_zones in &mut config.tagged_zones {
+        expand(&mut tagged_zones.hostnames, true);
+    }
+
+    Ok(())
+}
+
+/// Appends `.{zone}` to a hostname that's a single label, so it's qualified with the domain name of its zone.
+fn qualify_short_hostname(hostname: &str) -> String {
+    if hostname.contains('.') || hostname.contains("{zone}") {
+        hostname.to_string()
+    } else {
+        format!("{hostname}.{{zone}}")
+    }
+}
+
+/// Returns this machine's hostname without any domain, in lowercase, as used in DNS names.
+fn machine_name() -> String {
+    let hostname = gethostname::gethostname().to_string_lossy().to_ascii_lowercase();
+    hostname.split('.').next().unwrap_or_default().to_string()
+}
+
+/// Returns the name of the first network interface, other than loopback interfaces and those being ignored, that has
+/// an address.
+fn first_interface(config: &Config) -> Result<Option<String>, BoxError> {
+    let interfaces = NetworkInterface::show()?;
+    Ok(interfaces
+        .into_iter()
+        .find(|interface| {
+            let addr = interface.addr.map(|addr| match addr {
+                Addr::V4(addr) => IpAddr::V4(addr.ip),
+                Addr::V6(addr) => IpAddr::V6(addr.ip),
+            });
+
+            config.allows_interface(&interface.name) && addr.is_some_and(|addr| !addr.is_loopback())
+        })
+        .map(|interface| interface.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{qualify_short_hostname, Placeholders},
+        crate::config::HostnameConfig,
+    };
+
+    #[test]
+    fn expands_machine_and_interface() {
+        let placeholders = Placeholders {
+            machine: "laptop-7".to_string(),
+            interface: Some("wlan0".to_string()),
+        };
+        let hostname: HostnameConfig = serde_yaml::from_str(
+            "{hostname: '{machine}.fleet.{zone}', ipv6-hostname: '{interface}.{machine}.example.com'}",
+        )
+        .unwrap();
+
+        let expanded = hostname.map_hostnames(|hostname| placeholders.expand(hostname));
+        assert_eq!(
+            expanded.get_hostnames().collect::<Vec<_>>(),
+            ["laptop-7.fleet.{zone}", "wlan0.laptop-7.example.com"]
+        );
+    }
+
+    #[test]
+    fn qualifies_short_hostnames() {
+        assert_eq!(qualify_short_hostname("nas"), "nas.{zone}");
+        assert_eq!(qualify_short_hostname("nas.example.com"), "nas.example.com");
+        assert_eq!(qualify_short_hostname("{zone}"), "{zone}");
+    }
+}
@@ -0,0 +1,230 @@
+use {
+    crate::{aws::partition, config::Config, provider::timestamp_record_name, zones::is_zone_name},
+    serde_json::{json, Value},
+    std::collections::BTreeSet,
+};
+
+/// The record types route53-ip-update changes: the addresses it publishes, and CNAMEs it removes in their way.
+const CHANGED_RECORD_TYPES: &[&str] = &["A", "AAAA", "CNAME"];
+
+/// The actions needed to find the hosted zones for a configuration before it can be updated.
+///
+/// This must be called before the zone IDs are resolved, since resolving them removes the zones configured by name and
+/// the top-level hostnames.
+pub(crate) fn zone_lookup_actions(config: &Config) -> Vec<&'static str> {
+    let mut actions = Vec::new();
+    if !config.hostnames.is_empty() || !config.tagged_zones.is_empty() || config.ec2_fleet.is_some() {
+        actions.push("route53:ListHostedZones");
+    }
+
+    let zones = config.zones_with_mirrors();
+    if zones.iter().any(|zone| zone.is_route53() && is_zone_name(&zone.zone_id)) {
+        actions.push("route53:ListHostedZonesByName");
+    }
+
+    if !config.tagged_zones.is_empty() {
+        actions.push("route53:ListTagsForResources");
+    }
+
+    actions
+}
+
+/// Returns a least-privilege IAM policy for updating the configured Route 53 zones, which must have been resolved to
+/// hosted zone IDs. Record changes are limited to the configured hostnames and the record types this tool changes.
+pub(crate) fn iam_policy(config: &Config, lookup_actions: &[&str]) -> Value {
+    let partition = partition(config.region.as_deref().unwrap_or_default());
+
+    let mut zone_arns = Vec::new();
+    let mut record_names = BTreeSet::new();
+    for zone in config.zones_with_mirrors().iter().filter(|zone| zone.is_route53()) {
+        let zone_arn = format!("arn:{partition}:route53:::hostedzone/{}", zone.zone_id);
+        if !zone_arns.contains(&zone_arn) {
+            zone_arns.push(zone_arn);
+        }
+
+        // Route 53 compares these with names that are lowercase and have no trailing dot.
+        for hostname in zone.hostnames.iter().flat_map(|hc| hc.get_hostnames()) {
+            let hostname = hostname.trim_end_matches('.').to_ascii_lowercase();
+            if config.timestamp_records || config.heartbeat {
+                record_names.insert(timestamp_record_name(&hostname));
+            }
+
+            record_names.insert(hostname);
+        }
+    }
+
+    let mut record_types = CHANGED_RECORD_TYPES.to_vec();
+    if config.timestamp_records || config.heartbeat {
+        record_types.push("TXT");
+    }
+
+    // The fleet's hostnames and zones come from its instances' tags, so they aren't known in advance.
+    let mut conditions = json!({ "route53:ChangeResourceRecordSetsRecordTypes": record_types });
+    if config.ec2_fleet.is_some() {
+        zone_arns = vec![format!("arn:{partition}:route53:::hostedzone/*")];
+    } else {
+        conditions["route53:ChangeResourceRecordSetsNormalizedRecordNames"] = json!(record_names);
+    }
+
+    let mut statements = vec![
+        json!({
+            "Sid": "ReadHostedZones",
+            "Effect": "Allow",
+            "Action": ["route53:GetHostedZone", "route53:ListResourceRecordSets"],
+            "Resource": zone_arns,
+        }),
+        json!({
+            "Sid": "ChangeRecords",
+            "Effect": "Allow",
+            "Action": "route53:ChangeResourceRecordSets",
+            "Resource": zone_arns,
+            "Condition": {
+                "ForAllValues:StringEquals": conditions,
+            },
+        }),
+        json!({
+            "Sid": "WaitForChanges",
+            "Effect": "Allow",
+            "Action": "route53:GetChange",
+            "Resource": format!("arn:{partition}:route53:::change/*"),
+        }),
+    ];
+
+    if config.verify.as_ref().is_some_and(|verify| verify.test_dns_answer) {
+        // TestDNSAnswer doesn't support resource-level permissions.
+        statements.push(json!({
+            "Sid": "VerifyChanges",
+            "Effect": "Allow",
+            "Action": "route53:TestDNSAnswer",
+            "Resource": "*",
+        }));
+    }
+
+    if config.ec2_fleet.is_some() {
+        // DescribeInstances doesn't support resource-level permissions.
+        statements.push(json!({
+            "Sid": "ListFleetInstances",
+            "Effect": "Allow",
+            "Action": "ec2:DescribeInstances",
+            "Resource": "*",
+        }));
+    }
+
+    if let Some(event_bus) = &config.event_bus {
+        let event_bus_arn = if event_bus.starts_with("arn:") {
+            event_bus.clone()
+        } else {
+            // The account isn't known without asking STS, so any account's bus of that name is allowed.
+            format!("arn:{partition}:events:{}:*:event-bus/{event_bus}", config.region.as_deref().unwrap_or("*"))
+        };
+
+        statements.push(json!({
+            "Sid": "SendChangeEvents",
+            "Effect": "Allow",
+            "Action": "events:PutEvents",
+            "Resource": event_bus_arn,
+        }));
+    }
+
+    let lock_tables: BTreeSet<&str> = config
+        .lock
+        .iter()
+        .map(|lock| lock.table.as_str())
+        .chain(config.leader_election.iter().map(|election| election.table.as_str()))
+        .collect();
+    if !lock_tables.is_empty() {
+        let region = config.region.as_deref().unwrap_or("*");
+        let table_arns: Vec<String> =
+            lock_tables.iter().map(|table| format!("arn:{partition}:dynamodb:{region}:*:table/{table}")).collect();
+        statements.push(json!({
+            "Sid": "TakeLeases",
+            "Effect": "Allow",
+            "Action": ["dynamodb:PutItem", "dynamodb:DeleteItem"],
+            "Resource": table_arns,
+        }));
+    }
+
+    if let Some(cloudwatch_logs) = &config.cloudwatch_logs {
+        statements.push(json!({
+            "Sid": "SendLogs",
+            "Effect": "Allow",
+            "Action": ["logs:CreateLogStream", "logs:PutLogEvents"],
+            "Resource": format!(
+                "arn:{partition}:logs:{}:*:log-group:{}:*",
+                config.region.as_deref().unwrap_or("*"),
+                cloudwatch_logs.log_group
+            ),
+        }));
+    }
+
+    if !lookup_actions.is_empty() {
+        // None of these can be limited to particular hosted zones.
+        statements.push(json!({
+            "Sid": "FindHostedZones",
+            "Effect": "Allow",
+            "Action": lookup_actions,
+            "Resource": "*",
+        }));
+    }
+
+    json!({
+        "Version": "2012-10-17",
+        "Statement": statements,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{iam_policy, zone_lookup_actions},
+        crate::config::Config,
+        serde_json::json,
+    };
+
+    #[test]
+    fn policy_is_scoped_to_zones_and_hostnames() {
+        let mut config: Config = serde_yaml::from_str(
+            "hostnames: [nas.example.org]
+route53-zones:
+  - zone-id: Z1
+    hostnames:
+      - Home.Example.com.
+      - hostname: dual.example.com
+        ipv6-hostname: v6.example.com
+    mirrors:
+      - zone-id: Z2
+  - zone-id: example.net.
+    rfc2136: {server: 192.0.2.53:53, key-name: update-key, key-secret: c2VjcmV0}
+    hostnames: [gw.example.net]",
+        )
+        .unwrap();
+
+        let lookup_actions = zone_lookup_actions(&config);
+        assert_eq!(lookup_actions, ["route53:ListHostedZones"]);
+
+        // Stand in for the top-level hostname having been assigned to its zone.
+        let hostname = config.hostnames.remove(0);
+        config.get_or_create_zone_config("Z3").add_hostname_config(hostname);
+
+        let policy = iam_policy(&config, &lookup_actions);
+        let statements = policy["Statement"].as_array().unwrap();
+        assert_eq!(
+            statements[0]["Resource"],
+            json!([
+                "arn:aws:route53:::hostedzone/Z1",
+                "arn:aws:route53:::hostedzone/Z3",
+                "arn:aws:route53:::hostedzone/Z2"
+            ])
+        );
+        assert_eq!(
+            statements[1]["Condition"]["ForAllValues:StringEquals"]
+                ["route53:ChangeResourceRecordSetsNormalizedRecordNames"],
+            json!(["dual.example.com", "home.example.com", "nas.example.org", "v6.example.com"])
+        );
+        assert_eq!(statements[3]["Action"], json!(["route53:ListHostedZones"]));
+
+        config.event_bus = Some("ip-changes".to_string());
+        let policy = iam_policy(&config, &[]);
+        assert_eq!(policy["Statement"][3]["Resource"], "arn:aws:events:*:*:event-bus/ip-changes");
+    }
+}
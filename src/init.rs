@@ -0,0 +1,158 @@
+use {
+    crate::{
+        aws::{load_sdk_config, Route53Clients},
+        config::Config,
+        error::Route53IpUpdateError,
+        prompt::{prompt, prompt_bool},
+        query_address_type::QueryAddressType,
+        ttl::Ttl,
+        zones::{is_private_zone, list_all_hosted_zones, normalize_domain_name, strip_hosted_zone_id_prefix},
+    },
+    serde_yaml::{Mapping, Value},
+    std::{
+        fs::OpenOptions,
+        io::{stdin, IsTerminal, Write},
+        path::Path,
+    },
+    tower::BoxError,
+};
+
+/// The TTL suggested for new records.
+const DEFAULT_INIT_TTL: &str = "300";
+
+/// Walks the user through choosing zones, hostnames, TTLs, and address sources, then writes a config file.
+pub(crate) async fn run_init(config: &Config, output: &Path, force: bool) -> Result<(), BoxError> {
+    if !stdin().is_terminal() {
+        return Err(Route53IpUpdateError::NotATerminal("init".to_string()).into());
+    }
+
+    if output.exists() && !force {
+        return Err(Route53IpUpdateError::OutputFileExists(output.display().to_string()).into());
+    }
+
+    let sdk_config = load_sdk_config(config.profile.as_deref(), config.region.as_deref()).await;
+    let mut route53_clients = Route53Clients::new(sdk_config, config)?;
+    let route53 = route53_clients.default_client().await?;
+
+    eprintln!("Listing hosted zones...");
+    let mut hosted_zones = list_all_hosted_zones(&route53).await?;
+    if hosted_zones.is_empty() {
+        return Err(Route53IpUpdateError::NoHostedZones.into());
+    }
+    hosted_zones.sort_by(|a, b| a.name().cmp(&b.name()));
+
+    for (i, hosted_zone) in hosted_zones.iter().enumerate() {
+        eprintln!(
+            "  {:>3}) {} {} ({})",
+            i + 1,
+            hosted_zone.name().unwrap_or_default(),
+            strip_hosted_zone_id_prefix(hosted_zone.id().unwrap_or_default()),
+            if is_private_zone(hosted_zone) {
+                "private"
+            } else {
+                "public"
+            },
+        );
+    }
+
+    let selected = loop {
+        let answer = prompt("Zones to update (numbers, separated by commas)", None)?;
+        match parse_selection(&answer, hosted_zones.len()) {
+            Some(selected) if !selected.is_empty() => break selected,
+            _ => eprintln!("Please enter one or more numbers between 1 and {}.", hosted_zones.len()),
+        }
+    };
+
+    let mut zones = Vec::with_capacity(selected.len());
+    for index in selected {
+        let hosted_zone = &hosted_zones[index];
+        let zone_name = normalize_domain_name(hosted_zone.name().unwrap_or_default());
+        let hostnames = loop {
+            let answer = prompt(
+                &format!("Hostnames to update in {zone_name} (names without a trailing dot are relative to the zone)"),
+                None,
+            )?;
+            let hostnames = parse_hostnames(&answer, &zone_name);
+            if !hostnames.is_empty() {
+                break hostnames;
+            }
+            eprintln!("Please enter at least one hostname.");
+        };
+
+        let mut zone = Mapping::new();
+        zone.insert("zone-id".into(), strip_hosted_zone_id_prefix(hosted_zone.id().unwrap_or_default()).into());
+        zone.insert("hostnames".into(), Value::Sequence(hostnames.into_iter().map(Value::from).collect()));
+        zones.push(Value::Mapping(zone));
+    }
+
+    let ttl = loop {
+        let answer = prompt("TTL for the records (seconds or a duration such as 5m)", Some(DEFAULT_INIT_TTL))?;
+        match answer.parse::<Ttl>() {
+            Ok(ttl) => break ttl,
+            Err(e) => eprintln!("{e}"),
+        }
+    };
+
+    let address_type = loop {
+        let answer = prompt("Address types to publish (ipv4, ipv6, or both)", Some("both"))?;
+        match answer.parse::<QueryAddressType>() {
+            Ok(address_type) => break address_type,
+            Err(e) => eprintln!("{e}"),
+        }
+    };
+
+    let query_ip_service = prompt_bool("Query an IP service for your public address?", true)?;
+    let query_interfaces = prompt_bool("Query network interfaces for addresses?", false)?;
+
+    let mut root = Mapping::new();
+    root.insert("address-type".into(), address_type.to_string().into());
+    root.insert("query-ip-service".into(), query_ip_service.into());
+    root.insert("query-interfaces".into(), query_interfaces.into());
+    if let Some(profile) = &config.profile {
+        root.insert("profile".into(), profile.as_str().into());
+    }
+    if let Some(region) = &config.region {
+        root.insert("region".into(), region.as_str().into());
+    }
+    root.insert("ttl".into(), i64::from(ttl).into());
+    root.insert("route53-zones".into(), Value::Sequence(zones));
+
+    let yaml = serde_yaml::to_string(&Value::Mapping(root))?;
+    let mut file = OpenOptions::new().write(true).create(true).truncate(true).open(output)?;
+    file.write_all(yaml.as_bytes())?;
+
+    eprintln!("Wrote {}. Run `route53-ip-update -c {}` to update your records.", output.display(), output.display());
+    Ok(())
+}
+
+/// Parses a comma-separated list of 1-based indexes into 0-based indexes, without duplicates.
+fn parse_selection(answer: &str, count: usize) -> Option<Vec<usize>> {
+    let mut selected = Vec::new();
+    for part in answer.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let index = part.parse::<usize>().ok().filter(|i| (1..=count).contains(i))? - 1;
+        if !selected.contains(&index) {
+            selected.push(index);
+        }
+    }
+
+    Some(selected)
+}
+
+/// Parses a comma-separated list of hostnames. Names without a trailing dot are relative to the zone; `@` is the zone
+/// apex.
+fn parse_hostnames(answer: &str, zone_name: &str) -> Vec<String> {
+    answer
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            if part == "@" {
+                zone_name.to_string()
+            } else if part.ends_with('.') {
+                part.to_ascii_lowercase()
+            } else {
+                format!("{}.{zone_name}", part.to_ascii_lowercase())
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,114 @@
+use {
+    crate::{aws::Route53Clients, config::Config, dyndns::DyndnsServer, relay::SIGNATURE_HEADER},
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    lambda_runtime::{service_fn, LambdaEvent},
+    serde_json::{json, Value},
+    std::{env, sync::Arc},
+    tower::BoxError,
+};
+
+/// The environment variable Lambda sets for the runtime API; its presence means we're running inside Lambda.
+const LAMBDA_RUNTIME_API_VAR: &str = "AWS_LAMBDA_RUNTIME_API";
+
+/// The environment variable naming the configuration file(s) to load in Lambda, where there are no arguments.
+const LAMBDA_CONFIG_VAR: &str = "ROUTE53_IP_UPDATE_CONFIG";
+
+/// Returns whether the process is running as a Lambda function.
+pub(crate) fn in_lambda() -> bool {
+    env::var_os(LAMBDA_RUNTIME_API_VAR).is_some()
+}
+
+/// Returns the configuration files named by `ROUTE53_IP_UPDATE_CONFIG`, separated by commas.
+pub(crate) fn lambda_config_files() -> Vec<String> {
+    env::var(LAMBDA_CONFIG_VAR)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Handles Lambda invocations until the runtime shuts the function down. Each invocation is an API Gateway (REST or
+/// HTTP API) proxy event carrying a signed relay report, which is applied the same way `serve` applies reports sent to
+/// `/relay/update`.
+pub(crate) async fn run_lambda(config: Config, route53_clients: Route53Clients) -> Result<(), BoxError> {
+    let server = Arc::new(DyndnsServer::new(config, route53_clients));
+    lambda_runtime::run(service_fn(move |event: LambdaEvent<Value>| {
+        let server = server.clone();
+        async move { Ok::<_, BoxError>(handle_event(&server, &event.payload).await) }
+    }))
+    .await
+}
+
+/// Applies the relay report in an API Gateway proxy event, returning the proxy response.
+async fn handle_event(server: &DyndnsServer, event: &Value) -> Value {
+    let Some(body) = event_body(event) else {
+        return proxy_response(400, "text/plain", "Bad Request".to_string());
+    };
+
+    let signature = header(event, SIGNATURE_HEADER);
+    let source_ip = event["requestContext"]["identity"]["sourceIp"]
+        .as_str()
+        .or_else(|| event["requestContext"]["http"]["sourceIp"].as_str())
+        .unwrap_or("API Gateway");
+
+    match server.apply_relay_report(&body, signature, source_ip).await {
+        Ok(reply) => proxy_response(200, "application/json", serde_json::to_string(&reply).unwrap_or_default()),
+        Err(rejection) => proxy_response(rejection.status().as_u16(), "text/plain", rejection.message().to_string()),
+    }
+}
+
+/// Returns the body of an API Gateway proxy event, decoding it if API Gateway base64-encoded it.
+fn event_body(event: &Value) -> Option<Vec<u8>> {
+    let body = event["body"].as_str()?;
+    if event["isBase64Encoded"].as_bool().unwrap_or(false) {
+        BASE64.decode(body).ok()
+    } else {
+        Some(body.as_bytes().to_vec())
+    }
+}
+
+/// Returns a header from an API Gateway proxy event. REST APIs preserve the case of header names, so they're compared
+/// case-insensitively.
+fn header<'a>(event: &'a Value, name: &str) -> Option<&'a str> {
+    event["headers"].as_object()?.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).and_then(|(_, v)| v.as_str())
+}
+
+fn proxy_response(status: u16, content_type: &str, body: String) -> Value {
+    json!({
+        "statusCode": status,
+        "headers": {"content-type": content_type},
+        "body": body,
+        "isBase64Encoded": false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{event_body, header},
+        serde_json::json,
+    };
+
+    #[test]
+    fn reads_api_gateway_events() {
+        let rest = json!({
+            "headers": {"X-Route53-Ip-Update-Signature": "abc123"},
+            "body": "{\"agent\": \"home\"}",
+            "isBase64Encoded": false,
+        });
+        assert_eq!(header(&rest, "x-route53-ip-update-signature"), Some("abc123"));
+        assert_eq!(event_body(&rest).unwrap(), b"{\"agent\": \"home\"}");
+
+        let http = json!({
+            "headers": {"x-route53-ip-update-signature": "abc123"},
+            "body": "eyJhZ2VudCI6ICJob21lIn0=",
+            "isBase64Encoded": true,
+        });
+        assert_eq!(header(&http, "x-route53-ip-update-signature"), Some("abc123"));
+        assert_eq!(event_body(&http).unwrap(), b"{\"agent\": \"home\"}");
+
+        assert_eq!(event_body(&json!({"agent": "home"})), None);
+    }
+}
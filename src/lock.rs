@@ -0,0 +1,323 @@
+use {
+    crate::{
+        aws::{is_aws_error_code, load_sdk_config, AwsJsonClient},
+        config::{Config, LeaderElectionConfig, LockConfig},
+        error::Route53IpUpdateError,
+        relay::unix_time,
+    },
+    gethostname::gethostname,
+    log::{debug, info, warn},
+    serde_json::{json, Value},
+    std::{
+        process,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    },
+    tokio::{task::JoinHandle, time::sleep},
+    tower::BoxError,
+};
+
+/// How often to try again to take a lock that's held by another updater.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A lease on a zone's lock in the DynamoDB table, renewed in the background until it's released.
+pub(crate) struct ZoneLock {
+    client: AwsJsonClient,
+    table: String,
+    lock_id: String,
+    owner: String,
+    renewal: JoinHandle<()>,
+}
+
+/// Takes the zone's lock, if locking is configured, waiting for up to the lease duration if another updater holds it.
+///
+/// The lock is held in an item keyed by `lock-id` holding its `owner` and when its lease `expires` (in seconds since
+/// the Unix epoch). A lock whose lease has expired is treated as free, so an updater that dies while holding it only
+/// blocks the others until then.
+pub(crate) async fn lock_zone(config: &Config, zone_id: &str) -> Result<Option<ZoneLock>, BoxError> {
+    let Some(lock) = &config.lock else {
+        return Ok(None);
+    };
+
+    let client = dynamodb_client(config).await?;
+    let lock_id = format!("zone/{zone_id}");
+    let owner = lock_owner();
+    let give_up_at = unix_time() + lock.lease.as_secs();
+
+    loop {
+        let input = acquire_input(&lock.table, lock.lease, &lock_id, &owner, unix_time());
+        match client.call("PutItem", &input).await {
+            Ok(_) => break,
+            Err(e) if is_aws_error_code(e.as_ref(), "ConditionalCheckFailedException") => {
+                if unix_time() >= give_up_at {
+                    return Err(Route53IpUpdateError::ZoneLocked(zone_id.to_string()).into());
+                }
+
+                debug!("Zone {zone_id} is locked by another updater; waiting for it");
+                sleep(RETRY_INTERVAL).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    info!("Took the lock for zone {zone_id}");
+    let renewal = tokio::spawn(renew_zone_lock(client.clone(), lock.clone(), lock_id.clone(), owner.clone()));
+    Ok(Some(ZoneLock {
+        client,
+        table: lock.table.clone(),
+        lock_id,
+        owner,
+        renewal,
+    }))
+}
+
+impl ZoneLock {
+    /// Releases the lock so other updaters don't have to wait for the lease to expire. Failures are logged; the lease
+    /// will expire anyway.
+    pub(crate) async fn release(self) {
+        self.renewal.abort();
+        release_lease(&self.client, &self.table, &self.lock_id, &self.owner).await;
+    }
+}
+
+impl Drop for ZoneLock {
+    /// Stops renewing the lease if the lock is dropped without being released (e.g. when an update fails with `?`), so
+    /// it expires instead of being held for as long as the process runs.
+    fn drop(&mut self) {
+        self.renewal.abort();
+    }
+}
+
+/// Active/standby leader election between redundant updaters, using a lease in the DynamoDB table.
+///
+/// The leader renews its lease in the background for as long as it runs. The others stand by, trying to take the lease
+/// over each time they would update, which succeeds once the leader has stopped renewing it and it has expired.
+pub(crate) struct LeaderElection {
+    client: AwsJsonClient,
+    election: LeaderElectionConfig,
+    lock_id: String,
+    owner: String,
+
+    /// When our lease expires, in seconds since the Unix epoch, or 0 if we don't hold it.
+    lease_expires: Arc<AtomicU64>,
+    renewal: Option<JoinHandle<()>>,
+}
+
+impl LeaderElection {
+    /// Returns the leader election, if it's configured.
+    pub(crate) async fn new(config: &Config) -> Result<Option<Self>, BoxError> {
+        let Some(election) = &config.leader_election else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            client: dynamodb_client(config).await?,
+            election: election.clone(),
+            lock_id: format!("leader/{}", election.group),
+            owner: lock_owner(),
+            lease_expires: Arc::new(AtomicU64::new(0)),
+            renewal: None,
+        }))
+    }
+
+    /// Returns whether this updater is the leader, trying to take the lease if it doesn't hold it.
+    ///
+    /// Leadership is only claimed while our lease is known to be unexpired, so if renewing it fails (e.g. because
+    /// DynamoDB can't be reached), we stop publishing before a standby can take over.
+    pub(crate) async fn is_leader(&mut self) -> Result<bool, BoxError> {
+        if self.holds_lease() {
+            return Ok(true);
+        }
+
+        let now = unix_time();
+        let input = acquire_input(&self.election.table, self.election.lease, &self.lock_id, &self.owner, now);
+        match self.client.call("PutItem", &input).await {
+            Ok(_) => (),
+            Err(e) if is_aws_error_code(e.as_ref(), "ConditionalCheckFailedException") => {
+                info!("Standing by; another updater is the leader of group {}", self.election.group);
+                return Ok(false);
+            }
+            Err(e) => return Err(e),
+        }
+
+        info!("Became the leader of group {}", self.election.group);
+        self.lease_expires.store(now + self.election.lease.as_secs(), Ordering::SeqCst);
+        if let Some(renewal) = self.renewal.take() {
+            renewal.abort();
+        }
+
+        self.renewal = Some(tokio::spawn(renew_leadership(
+            self.client.clone(),
+            self.election.clone(),
+            self.lock_id.clone(),
+            self.owner.clone(),
+            self.lease_expires.clone(),
+        )));
+        Ok(true)
+    }
+
+    /// Returns whether we hold the lease, without trying to take it.
+    pub(crate) fn holds_lease(&self) -> bool {
+        unix_time() < self.lease_expires.load(Ordering::SeqCst)
+    }
+
+    /// Gives up the lease, if we hold it, so a standby can take over without waiting for it to expire.
+    pub(crate) async fn resign(mut self) {
+        if let Some(renewal) = self.renewal.take() {
+            renewal.abort();
+        }
+
+        if self.holds_lease() {
+            self.lease_expires.store(0, Ordering::SeqCst);
+            info!("Resigning as the leader of group {}", self.election.group);
+            release_lease(&self.client, &self.election.table, &self.lock_id, &self.owner).await;
+        }
+    }
+}
+
+async fn dynamodb_client(config: &Config) -> Result<AwsJsonClient, BoxError> {
+    let sdk_config = load_sdk_config(config.profile.as_deref(), config.region.as_deref()).await;
+    AwsJsonClient::new(&sdk_config, "dynamodb", "DynamoDB_20120810", "1.0", config.timeout)
+}
+
+/// Identifies this process as the owner of a lease.
+fn lock_owner() -> String {
+    format!("{}:{}", gethostname().to_string_lossy(), process::id())
+}
+
+/// Deletes a lease item if we still own it. Failures are logged; the lease will expire anyway.
+async fn release_lease(client: &AwsJsonClient, table: &str, lock_id: &str, owner: &str) {
+    let input = json!({
+        "TableName": table,
+        "Key": {"lock-id": {"S": lock_id}},
+        "ConditionExpression": "#owner = :owner",
+        "ExpressionAttributeNames": {"#owner": "owner"},
+        "ExpressionAttributeValues": {":owner": {"S": owner}},
+    });
+
+    if let Err(e) = client.call("DeleteItem", &input).await {
+        warn!("Unable to release lock {lock_id}: {e}");
+    }
+}
+
+/// Returns the PutItem input that takes a lease, or extends it if we already hold it.
+fn acquire_input(table: &str, lease: Duration, lock_id: &str, owner: &str, now: u64) -> Value {
+    json!({
+        "TableName": table,
+        "Item": {
+            "lock-id": {"S": lock_id},
+            "owner": {"S": owner},
+            "expires": {"N": (now + lease.as_secs()).to_string()},
+        },
+        "ConditionExpression": "attribute_not_exists(#id) OR #expires < :now OR #owner = :owner",
+        "ExpressionAttributeNames": {"#id": "lock-id", "#expires": "expires", "#owner": "owner"},
+        "ExpressionAttributeValues": {":now": {"N": now.to_string()}, ":owner": {"S": owner}},
+    })
+}
+
+/// Extends the lease every third of its duration so long updates (e.g. waiting for lowered TTLs to expire) keep the
+/// lock.
+async fn renew_zone_lock(client: AwsJsonClient, lock: LockConfig, lock_id: String, owner: String) {
+    loop {
+        sleep(lock.lease / 3).await;
+        let input = acquire_input(&lock.table, lock.lease, &lock_id, &owner, unix_time());
+        if let Err(e) = client.call("PutItem", &input).await {
+            warn!("Unable to renew lock {lock_id}: {e}");
+        }
+    }
+}
+
+/// Extends the leader's lease every third of its duration until another updater takes it over.
+async fn renew_leadership(
+    client: AwsJsonClient,
+    election: LeaderElectionConfig,
+    lock_id: String,
+    owner: String,
+    lease_expires: Arc<AtomicU64>,
+) {
+    loop {
+        sleep(election.lease / 3).await;
+        let now = unix_time();
+        let input = acquire_input(&election.table, election.lease, &lock_id, &owner, now);
+        match client.call("PutItem", &input).await {
+            Ok(_) => lease_expires.store(now + election.lease.as_secs(), Ordering::SeqCst),
+            Err(e) if is_aws_error_code(e.as_ref(), "ConditionalCheckFailedException") => {
+                warn!("Lost the leadership of group {} to another updater", election.group);
+                lease_expires.store(0, Ordering::SeqCst);
+                return;
+            }
+            Err(e) => warn!("Unable to renew the leadership of group {}: {e}", election.group),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{acquire_input, ZoneLock},
+        crate::aws::AwsJsonClient,
+        aws_config::SdkConfig,
+        aws_sdk_route53::Region,
+        aws_types::{credentials::SharedCredentialsProvider, Credentials},
+        serde_json::json,
+        std::{
+            sync::{
+                atomic::{AtomicU64, Ordering},
+                Arc,
+            },
+            time::Duration,
+        },
+        tokio::time::sleep,
+    };
+
+    #[test]
+    fn lock_can_be_taken_when_free_expired_or_ours() {
+        let input = acquire_input("locks", Duration::from_secs(60), "zone/Z1", "router:42", 1700000000);
+        assert_eq!(input["Item"]["expires"], json!({"N": "1700000060"}));
+        assert_eq!(input["ConditionExpression"], "attribute_not_exists(#id) OR #expires < :now OR #owner = :owner");
+        assert_eq!(
+            input["ExpressionAttributeValues"],
+            json!({":now": {"N": "1700000000"}, ":owner": {"S": "router:42"}})
+        );
+    }
+
+    #[tokio::test]
+    async fn dropping_a_lock_stops_renewing_it() {
+        let sdk_config = SdkConfig::builder()
+            .region(Region::new("us-east-1"))
+            .credentials_provider(SharedCredentialsProvider::new(Credentials::new(
+                "AKID", "SECRET", None, None, "test",
+            )))
+            .build();
+        let client =
+            AwsJsonClient::new(&sdk_config, "dynamodb", "DynamoDB_20120810", "1.0", Duration::from_secs(1)).unwrap();
+        let renewals = Arc::new(AtomicU64::new(0));
+        let renewal = tokio::spawn({
+            let renewals = renewals.clone();
+            async move {
+                loop {
+                    renewals.fetch_add(1, Ordering::SeqCst);
+                    sleep(Duration::from_millis(10)).await;
+                }
+            }
+        });
+
+        let lock = ZoneLock {
+            client,
+            table: "locks".to_string(),
+            lock_id: "zone/Z1".to_string(),
+            owner: "router:42".to_string(),
+            renewal,
+        };
+        sleep(Duration::from_millis(30)).await;
+        drop(lock);
+        sleep(Duration::from_millis(10)).await;
+
+        let after_drop = renewals.load(Ordering::SeqCst);
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(renewals.load(Ordering::SeqCst), after_drop);
+    }
+}
@@ -0,0 +1,59 @@
+use {
+    crate::cloudwatch_logs::ship_log_message,
+    env_logger::{Builder, Logger},
+    log::{LevelFilter, Log, Metadata, Record},
+};
+
+/// Writes log messages to stderr, and also queues them for CloudWatch Logs once that has been started.
+struct TeeLogger {
+    stderr: Logger,
+}
+
+impl Log for TeeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.stderr.matches(record) {
+            self.stderr.log(record);
+            ship_log_message(record.level(), record.target(), &record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+    }
+}
+
+/// Initializes the logger from the --verbose and --quiet counts.
+///
+/// By default, this crate's informational messages (including the summary of changes made) and every crate's warnings
+/// are shown. If neither flag is given, RUST_LOG may be used to choose the levels instead.
+pub(crate) fn init_logging(verbose: u8, quiet: u8) {
+    let stderr = if verbose == 0 && quiet == 0 && std::env::var_os("RUST_LOG").is_some() {
+        Builder::from_default_env().build()
+    } else {
+        let (own_level, other_level) = log_levels(verbose, quiet);
+        Builder::new().filter_level(other_level).filter_module(env!("CARGO_CRATE_NAME"), own_level).build()
+    };
+
+    log::set_max_level(stderr.filter());
+    log::set_boxed_logger(Box::new(TeeLogger {
+        stderr,
+    }))
+    .expect("logger should only be initialized once");
+}
+
+/// Returns the levels to log this crate's messages and other crates' messages at.
+fn log_levels(verbose: u8, quiet: u8) -> (LevelFilter, LevelFilter) {
+    match i16::from(verbose) - i16::from(quiet) {
+        i16::MIN..=-3 => (LevelFilter::Off, LevelFilter::Off),
+        -2 => (LevelFilter::Error, LevelFilter::Error),
+        -1 => (LevelFilter::Warn, LevelFilter::Warn),
+        0 => (LevelFilter::Info, LevelFilter::Warn),
+        1 => (LevelFilter::Debug, LevelFilter::Warn),
+        2 => (LevelFilter::Trace, LevelFilter::Debug),
+        _ => (LevelFilter::Trace, LevelFilter::Trace),
+    }
+}
@@ -1,94 +1,1088 @@
-#![feature(ip)]
 #![warn(clippy::all)]
 
+mod address_class;
+mod address_ranking;
+mod address_source;
 mod args;
+mod aws;
+mod cloudflare;
+mod cloudwatch_logs;
+mod completions;
 mod config;
+mod config_file;
+mod config_lint;
+mod config_watch;
+mod daemon;
+mod ddclient;
+mod decrypt;
+mod diff;
+mod drift;
+mod dyndns;
+mod ec2_fleet;
+mod env_vars;
 mod error;
+mod events;
+mod gc;
+mod healthcheck;
+mod hostname_template;
+mod iam_policy;
+mod init;
+#[cfg(feature = "lambda")]
+mod lambda;
+mod lock;
+mod logging;
+mod man;
+mod nat64;
+mod policy;
+mod prompt;
+mod provider;
 mod query_address_type;
 mod query_interfaces;
 mod query_ip_service;
+mod rate_limit;
+mod relay;
+mod rfc2136;
+mod route53_api;
+mod routing_policy;
+mod schedule;
+mod schema;
+mod shutdown;
+mod snapshot;
+mod state;
+mod statsd;
+mod status;
+mod stun;
+mod summary;
 mod ttl;
 mod update;
+mod validate;
+mod verify;
+mod windows;
+mod zones;
 
 use {
-    args::Args,
-    aws_config::load_from_env as load_aws_config_from_env,
-    aws_sdk_route53::Client as Route53Client,
-    clap::Parser,
+    address_ranking::AddressRanker,
+    address_source::{AddressSource, DiscoveredAddress},
+    args::{Args, Command},
+    aws::{load_sdk_config, Route53Clients},
+    chrono::Local,
+    cloudwatch_logs::{flush_cloudwatch_logs, ship_log_message, start_cloudwatch_logs},
+    completions::write_completions,
+    config::{Config, Ec2FleetConfig, HostnameConfig, Route53ZoneConfig},
+    config_file::format_config,
+    daemon::run_daemon,
+    ddclient::convert_ddclient_config,
+    diff::changed_hostnames,
+    drift::{send_drift_webhook, DriftReport, ZoneDrift, DRIFT_EXIT_CODE},
+    dyndns::run_dyndns_server,
+    ec2_fleet::{departed_zones, fleet_hostnames, fleet_zones},
+    error::{Classification, ErrorClass, Route53IpUpdateError},
     futures::stream::{futures_unordered::FuturesUnordered, StreamExt},
-    log::info,
-    query_address_type::QueryAddressType,
+    gc::collect_garbage,
+    healthcheck::run_healthcheck,
+    humantime::{format_duration, format_rfc3339_seconds},
+    iam_policy::{iam_policy, zone_lookup_actions},
+    init::run_init,
+    lock::LeaderElection,
+    log::{info, log_enabled, warn, Level},
+    logging::init_logging,
+    man::write_man_page,
+    nat64::Nat64Prefix,
+    provider::{publish_targets, ZoneProvider},
     query_interfaces::get_addresses_from_network_interfaces,
-    query_ip_service::get_address_from_ip_service,
-    std::{collections::HashSet, future::Future, net::IpAddr, pin::Pin, process::ExitCode},
+    query_ip_service::{discover_nat64_prefix, get_address_from_ip_service_with_retries, IpServiceClients},
+    rate_limit::ROUTE53_RATE_LIMITER,
+    relay::{send_relay_report, unix_time},
+    schedule::Schedule,
+    schema::config_schema,
+    shutdown::{exit_after, listen_for_shutdown, shutdown_requested, shutdown_signal},
+    snapshot::{restore_snapshot, undo_hostnames},
+    state::UpdateState,
+    statsd::start_statsd,
+    std::{
+        collections::{BTreeSet, HashSet},
+        future::Future,
+        io::{stdin, stdout, IsTerminal},
+        net::IpAddr,
+        path::Path,
+        pin::Pin,
+        process::ExitCode,
+        time::{Duration, Instant, UNIX_EPOCH},
+    },
+    stun::{observe_nat, NatType},
+    summary::RunSummary,
+    tokio::{
+        fs::{read_to_string, write},
+        time::sleep,
+    },
     tower::BoxError,
     trust_dns_resolver::config::LookupIpStrategy,
-    update::update_zone,
+    validate::validate_config,
+    windows::{changes_held, next_opening},
+    zones::{
+        adopt_matching_records, assign_hostnames_to_zones, assign_tagged_zones, check_hosted_zones, resolve_zone_ids,
+    },
 };
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> ExitCode {
-    env_logger::init();
-    let args = Args::parse();
-    let config = match args.into_config().await {
-        Ok(config) => config,
-        Err(err) => {
-            eprintln!("Error: {err}");
-            return ExitCode::FAILURE;
+    let status = run().await;
+    flush_cloudwatch_logs().await;
+    status
+}
+
+async fn run() -> ExitCode {
+    let mut args = Args::parse_grouped();
+    init_logging(args.verbose, args.quiet);
+    if let Err(e) = args.read_hostname_lists() {
+        return report_error(e);
+    }
+
+    let mut serve_listen = None;
+    let mut print_iam_policy = false;
+    let mut gc = None;
+    let mut restore = None;
+    let mut undo = None;
+    let mut flush = false;
+    match args.command.take() {
+        Some(Command::Schema) => {
+            println!("{}", config_schema());
+            return ExitCode::SUCCESS;
         }
+        Some(Command::Init {
+            output,
+            force,
+        }) => {
+            let config = match args.into_config().await {
+                Ok(config) => config,
+                Err(err) => return report_error(err),
+            };
+
+            return match run_init(&config, &output, force).await {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => report_error(e),
+            };
+        }
+        Some(Command::Validate) => {
+            let mut config = match args.into_config().await {
+                Ok(config) => config,
+                Err(err) => return report_error(err),
+            };
+
+            config.clamp_ttls();
+            return match validate_config(&config) {
+                Ok(()) => {
+                    println!("Configuration is valid.");
+                    ExitCode::SUCCESS
+                }
+                Err(e) => report_error(e),
+            };
+        }
+        Some(Command::ImportDdclient {
+            input,
+            output,
+        }) => {
+            return match import_ddclient(&input, output.as_deref()).await {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => report_error(e),
+            };
+        }
+        Some(Command::Serve {
+            listen,
+        }) => serve_listen = Some(listen),
+        Some(Command::IamPolicy) => print_iam_policy = true,
+        Some(Command::Gc {
+            max_age,
+            dry_run,
+        }) => gc = Some((max_age, dry_run)),
+        Some(Command::Restore {
+            snapshot,
+            dry_run,
+        }) => restore = Some((snapshot, dry_run)),
+        Some(Command::Undo {
+            hostnames,
+            dry_run,
+        }) => undo = Some((hostnames, dry_run)),
+        Some(Command::Flush) => flush = true,
+        Some(Command::Healthcheck {
+            max_age,
+            url,
+        }) => {
+            let config = match args.into_config().await {
+                Ok(config) => config,
+                Err(err) => return report_error(err),
+            };
+
+            return match run_healthcheck(&config, url.as_deref(), max_age.map(Into::into)).await {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => report_error(e),
+            };
+        }
+        Some(Command::Completions {
+            shell,
+        }) => {
+            let config = match args.into_config().await {
+                Ok(config) => config,
+                Err(err) => return report_error(err),
+            };
+
+            write_completions(shell, &config, &mut stdout());
+            return ExitCode::SUCCESS;
+        }
+        Some(Command::Man) => {
+            return match write_man_page(&mut stdout()) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(err) => report_error(err),
+            };
+        }
+        None => (),
+    }
+
+    // Lambda runs the bootstrap binary without arguments, so the configuration is named in the environment instead.
+    #[cfg(feature = "lambda")]
+    let lambda = lambda::in_lambda();
+    #[cfg(feature = "lambda")]
+    if lambda && args.config_files.is_empty() {
+        args.config_files = lambda::lambda_config_files();
+    }
+
+    let print_config = args.print_config;
+    let reload_args = args.clone();
+    let mut config = match args.into_config().await {
+        Ok(config) => config,
+        Err(err) => return report_error(err),
     };
 
+    config.clamp_ttls();
+
+    if let Some(format) = print_config {
+        return match format_config(&config, format) {
+            Ok(text) => {
+                print!("{text}");
+                ExitCode::SUCCESS
+            }
+            Err(e) => report_error(e),
+        };
+    }
+
     if let Err(e) = config.check() {
-        eprintln!("Error: {e}");
-        return ExitCode::FAILURE;
+        return report_error(e);
+    }
+
+    ROUTE53_RATE_LIMITER.set_rate(config.route53_rate_limit);
+
+    // When running continuously, max-runtime limits each update instead of the whole run.
+    let continuous = serve_listen.is_some() || config.schedule.is_some() || config.interval.is_some();
+    #[cfg(feature = "lambda")]
+    let continuous = continuous || lambda;
+    if let Some(max_runtime) = config.max_runtime.filter(|_| !continuous) {
+        exit_after(max_runtime);
+    }
+
+    if config.interactive && !stdin().is_terminal() {
+        eprintln!("Error: --interactive requires a terminal to ask for confirmation on");
+        return ErrorClass::Config.exit_code();
+    }
+
+    if let Err(e) = start_cloudwatch_logs(&config).await {
+        return report_error(e);
+    }
+
+    if let Err(e) = start_statsd(&config).await {
+        return report_error(e);
+    }
+
+    // An agent only discovers its addresses; the relay server it reports to holds the AWS credentials.
+    if let Some(relay) = &config.relay {
+        let Some(discovered) = discover_addresses(&config, &IpServiceClients::default()).await else {
+            return ErrorClass::Discovery.exit_code();
+        };
+
+        let nat = detect_nat(&config, None).await;
+        return match send_relay_report(relay, &discovered, nat, config.timeout).await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => report_error(e),
+        };
+    }
+
+    // Which lookups are needed has to be worked out before the zones are resolved.
+    let lookup_actions = zone_lookup_actions(&config);
+
+    let sdk_config = load_sdk_config(config.profile.as_deref(), config.region.as_deref()).await;
+    let mut route53_clients = match Route53Clients::new(sdk_config, &config) {
+        Ok(route53_clients) => route53_clients,
+        Err(e) => return report_error(e),
+    };
+    let route53 = match route53_clients.default_client().await {
+        Ok(route53) => route53,
+        Err(e) => return report_error(e),
+    };
+
+    if let Err(e) = resolve_zone_ids(&mut route53_clients, &mut config).await {
+        return report_error(e);
+    }
+
+    if let Err(e) = assign_hostnames_to_zones(&route53, &mut config).await {
+        return report_error(e);
+    }
+
+    if let Err(e) = assign_tagged_zones(&route53, &mut config).await {
+        return report_error(e);
+    }
+
+    if print_iam_policy {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&iam_policy(&config, &lookup_actions)).expect("JSON should serialize")
+        );
+        return ExitCode::SUCCESS;
+    }
+
+    if let Err(e) = check_hosted_zones(&mut route53_clients, &mut config).await {
+        return report_error(e);
+    }
+
+    for zone in config.disabled_zones() {
+        info!("Skipping zone {} and its mirrors; it's disabled", zone.zone_id);
+    }
+
+    if let Some((max_age, dry_run)) = gc {
+        return match collect_garbage(&config, &mut route53_clients, max_age.into(), dry_run).await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => ErrorClass::of(e.as_ref()).exit_code(),
+        };
+    }
+
+    if let Some((snapshot, dry_run)) = restore {
+        return match restore_snapshot(&config, &mut route53_clients, &snapshot, dry_run).await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => report_error(e),
+        };
+    }
+
+    if let Some((hostnames, dry_run)) = undo {
+        return match undo_hostnames(&config, &mut route53_clients, &hostnames, dry_run).await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => ErrorClass::of(e.as_ref()).exit_code(),
+        };
+    }
+
+    if flush {
+        let Some(state_file) = &config.state_file else {
+            return report_error(Route53IpUpdateError::InvalidConfig(vec![
+                "The flush command needs state-file to find the queued updates.".to_string(),
+            ]));
+        };
+
+        let mut state = UpdateState::load(Some(state_file)).await;
+        if state.queued.is_empty() {
+            println!("No updates are queued.");
+            return ExitCode::SUCCESS;
+        }
+
+        let desired = queued_zones(&config, &mut state);
+        return publish_addresses(&config, &mut route53_clients, &mut state, desired, None, Vec::new()).await;
+    }
+
+    #[cfg(feature = "lambda")]
+    if lambda {
+        if config.dyndns_server.as_ref().is_none_or(|server| server.agents.is_empty()) {
+            eprintln!("Error: Running in Lambda requires dyndns-server agents to be configured");
+            return ErrorClass::Config.exit_code();
+        }
+
+        return match lambda::run_lambda(config, route53_clients).await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => report_error(e),
+        };
+    }
+
+    if let Some(listen) = serve_listen {
+        let Some(dyndns_server) = &config.dyndns_server else {
+            eprintln!("Error: The serve command requires dyndns-server users or agents to be configured");
+            return ErrorClass::Config.exit_code();
+        };
+
+        let listen = listen.unwrap_or(dyndns_server.listen);
+        return match run_dyndns_server(config, route53_clients, listen).await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => report_error(e),
+        };
+    }
+
+    listen_for_shutdown();
+    let mut state = UpdateState::load(config.state_file.as_deref()).await;
+    let schedule = match Schedule::from_config(&config) {
+        Ok(schedule) => schedule,
+        Err(e) => return report_error(e),
+    };
+
+    let mut election = match LeaderElection::new(&config).await {
+        Ok(election) => election,
+        Err(e) => return report_error(e),
+    };
+
+    let Some(schedule) = schedule else {
+        // A leader keeps its lease after a single update, so standbys keep standing by until it expires.
+        return update_with_retries(&config, &mut route53_clients, &mut state, &mut election).await;
+    };
+
+    run_daemon(reload_args, config, route53_clients, state, schedule, election).await
+}
+
+/// Runs a single update, running it again from the start up to `retries` times if it fails in a way worth retrying.
+async fn update_with_retries(
+    config: &Config,
+    route53_clients: &mut Route53Clients,
+    state: &mut UpdateState,
+    election: &mut Option<LeaderElection>,
+) -> ExitCode {
+    let retryable: Vec<ExitCode> =
+        ErrorClass::ALL.into_iter().filter(|class| class.is_retryable()).map(ErrorClass::exit_code).collect();
+    let mut delay = config.retry_backoff;
+    let mut retries_left = config.retries;
+
+    loop {
+        state.failure = None;
+        let status = update_if_leader(config, route53_clients, state, election).await;
+
+        // When a zone failed to update, its error says whether retrying might help, e.g. not if AWS rejected the request.
+        let retry = state.failure.map_or(retryable.contains(&status), |failure| failure.retryable);
+        if retries_left == 0 || shutdown_requested() || !retry {
+            return status;
+        }
+
+        warn!("Update failed; trying again in {} ({retries_left} retries left)", format_duration(delay));
+        tokio::select! {
+            _ = sleep(delay) => (),
+            _ = shutdown_signal() => return status,
+        }
+
+        delay = delay.saturating_mul(2);
+        retries_left -= 1;
+    }
+}
+
+/// Runs an update, unless leader election is configured and another updater is the leader.
+async fn update_if_leader(
+    config: &Config,
+    route53_clients: &mut Route53Clients,
+    state: &mut UpdateState,
+    election: &mut Option<LeaderElection>,
+) -> ExitCode {
+    if let Some(election) = election {
+        match election.is_leader().await {
+            Ok(true) => (),
+            Ok(false) => return ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("Error: Unable to take part in the leader election: {e}");
+                return ErrorClass::of(e.as_ref()).exit_code();
+            }
+        }
+    }
+
+    let start = Instant::now();
+    let status = run_update(config, route53_clients, state).await;
+    statsd::timing("run.duration", start.elapsed(), &[]);
+    statsd::count(
+        if status == ExitCode::SUCCESS {
+            "runs.succeeded"
+        } else {
+            "runs.failed"
+        },
+        1,
+        &[],
+    );
+    status
+}
+
+/// Reports an error, returning the exit status for its class.
+fn report_error(e: impl Into<BoxError>) -> ExitCode {
+    let e = e.into();
+    eprintln!("Error: {e}");
+    ship_log_message(Level::Error, env!("CARGO_CRATE_NAME"), &e.to_string());
+    ErrorClass::of(e.as_ref()).exit_code()
+}
+
+/// Discovers the current addresses and brings each zone up-to-date with them (or, in monitor-only mode, checks whether
+/// they are), returning the exit status for the run.
+async fn run_update(config: &Config, route53_clients: &mut Route53Clients, state: &mut UpdateState) -> ExitCode {
+    if let Some(fleet) = &config.ec2_fleet {
+        return run_fleet_update(config, fleet, route53_clients, state).await;
+    }
+
+    let start = Instant::now();
+    let discovered = discover_addresses(config, &state.ip_service_clients).await;
+    statsd::timing("discovery.duration", start.elapsed(), &[]);
+    let Some(discovered) = discovered else {
+        statsd::count("discovery.failures", 1, &[]);
+        let errors = vec!["Unable to discover the current addresses".to_string()];
+        if state.queued.is_empty() || config.monitor_only || changes_held(config) {
+            state.status.lock().unwrap().finish_update(&errors);
+            return ErrorClass::Discovery.exit_code();
+        }
+
+        // AWS may be reachable again even if the IP service isn't, so the addresses queued earlier can be published.
+        let desired = queued_zones(config, state);
+        let failure = Some(ErrorClass::Discovery.into());
+        return publish_addresses(config, route53_clients, state, desired, failure, errors).await;
+    };
+    state.status.lock().unwrap().record_discovered(discovered.iter().map(|discovered| discovered.address).collect());
+
+    // Make sure changed addresses have settled before publishing them.
+    let previous = state.last_discovered.replace(discovered.clone());
+    if let (Some(debounce), Some(previous)) = (config.debounce, previous) {
+        if previous != discovered {
+            info!("Discovered addresses have changed; checking them again in {}", format_duration(debounce));
+            tokio::select! {
+                _ = sleep(debounce) => (),
+                _ = shutdown_signal() => return ExitCode::SUCCESS,
+            }
+
+            let Some(rediscovered) = discover_addresses(config, &state.ip_service_clients).await else {
+                return ErrorClass::Discovery.exit_code();
+            };
+
+            if rediscovered != discovered {
+                info!("Discovered addresses are still changing; not publishing them yet");
+                state.last_discovered = Some(rediscovered);
+                return ExitCode::SUCCESS;
+            }
+        }
+    }
+
+    if let Some(nat) = detect_nat(config, state.nat_type).await {
+        state.nat_type = Some(nat);
+    }
+
+    // Work out which addresses go to each zone, including the existing records it manages with manage-matching. Mirrors
+    // are updated as zones of their own. Hostnames changed recently are left alone until their cooldown expires.
+    let cooldown = if config.monitor_only {
+        None
+    } else {
+        config.cooldown
+    };
+    let mut failure = None;
+    let mut errors = Vec::new();
+    let mut enabled: Vec<Route53ZoneConfig> = config.enabled_zones().cloned().collect();
+    for zone in &mut enabled {
+        if let Err(e) = adopt_matching_records(route53_clients, zone).await {
+            // The configured hostnames are still updated.
+            eprintln!("Error: Unable to find the records to manage in zone {}: {e}", zone.zone_id);
+            failure.get_or_insert(Classification::of(e.as_ref()));
+            errors.push(format!("zone {}: {e}", zone.zone_id));
+        }
+    }
+
+    let zones: Vec<Route53ZoneConfig> = Route53ZoneConfig::with_mirrors(enabled)
+        .iter()
+        .map(|zone| state.without_cooling_hostnames(zone, cooldown))
+        .collect();
+    let ranker = AddressRanker::new(config).await;
+    let mut desired = Vec::with_capacity(zones.len());
+    for zone in zones {
+        let (ipv4_candidates, ipv6_candidates) = config.zone_candidates(&zone, &discovered);
+        let previous = state.last_published.get(&zone.zone_id);
+        let ipv4_addresses = ranker.select(ipv4_candidates, previous);
+        let ipv6_addresses = ranker.select(ipv6_candidates, previous);
+
+        let mut ipv4_addresses_sorted: Vec<&IpAddr> = ipv4_addresses.iter().collect();
+        let mut ipv6_addresses_sorted: Vec<&IpAddr> = ipv6_addresses.iter().collect();
+        ipv4_addresses_sorted.sort();
+        ipv6_addresses_sorted.sort();
+
+        let ipv4_address_strings: Vec<String> = ipv4_addresses_sorted.iter().map(|a| a.to_string()).collect();
+        let ipv6_address_strings: Vec<String> = ipv6_addresses_sorted.iter().map(|a| a.to_string()).collect();
+
+        info!("IPv4 addresses for zone {}: {}", zone.zone_id, ipv4_address_strings.join(", "));
+        info!("IPv6 addresses for zone {}: {}", zone.zone_id, ipv6_address_strings.join(", "));
+        desired.push((zone, ipv4_addresses, ipv6_addresses));
+    }
+
+    if !config.monitor_only && changes_held(config) {
+        hold_changes(config, state, &desired).await;
+        return ExitCode::SUCCESS;
+    }
+
+    publish_addresses(config, route53_clients, state, desired, failure, errors).await
+}
+
+/// Publishes the addresses of the EC2 instances in the fleet under the hostnames they're tagged with, returning the
+/// exit status for the run.
+async fn run_fleet_update(
+    config: &Config,
+    fleet: &Ec2FleetConfig,
+    route53_clients: &mut Route53Clients,
+    state: &mut UpdateState,
+) -> ExitCode {
+    let desired = match route53_clients.default_client().await {
+        Ok(route53) => fleet_zones(config, fleet, &route53).await,
+        Err(e) => Err(e.into()),
+    };
+
+    let mut desired = match desired {
+        Ok(desired) => desired,
+        Err(e) => {
+            state.status.lock().unwrap().finish_update(&[format!("Unable to list the EC2 fleet: {e}")]);
+            return report_error(e);
+        }
+    };
+
+    // Hostnames that were published earlier but aren't tagged on any running instance now have their records removed.
+    // They're remembered until that succeeds, so a failed removal is tried again by the next update.
+    let current = fleet_hostnames(&desired);
+    desired.extend(departed_zones(config, &state.fleet_hostnames, &current));
+    if !config.monitor_only {
+        for (zone_id, hostnames) in &current {
+            state.fleet_hostnames.entry(zone_id.clone()).or_default().extend(hostnames.iter().cloned());
+        }
     }
 
-    type IpQueryResult = Result<Vec<IpAddr>, BoxError>;
+    if !config.monitor_only && changes_held(config) {
+        hold_changes(config, state, &desired).await;
+        return ExitCode::SUCCESS;
+    }
+
+    let status = publish_addresses(config, route53_clients, state, desired, None, Vec::new()).await;
+    if status == ExitCode::SUCCESS && !config.monitor_only && state.fleet_hostnames != current {
+        state.fleet_hostnames = current;
+        if let Some(state_file) = &config.state_file {
+            if let Err(e) = state.save(state_file).await {
+                warn!("Unable to write state file {}: {e}", state_file.display());
+            }
+        }
+    }
+
+    status
+}
+
+/// Brings each zone up-to-date with the addresses desired for it (or, in monitor-only mode, checks whether it is),
+/// returning the exit status for the run. `failure` and `errors` carry any failures from earlier in the run.
+///
+/// Zones that can't be updated because AWS is unreachable or throttling have their desired addresses queued, to be
+/// published by a later update if discovery fails then, or by the flush command.
+async fn publish_addresses(
+    config: &Config,
+    route53_clients: &mut Route53Clients,
+    state: &mut UpdateState,
+    desired: Vec<(Route53ZoneConfig, HashSet<IpAddr>, HashSet<IpAddr>)>,
+    // The classification of the first failure, which determines the exit status and whether the update is retried.
+    mut failure: Option<Classification>,
+    mut errors: Vec<String>,
+) -> ExitCode {
+    let mut summary = RunSummary::default();
+    for zone in config.disabled_zones() {
+        summary.add_disabled_zone(zone);
+    }
+
+    let mut queue_changed = false;
+    let mut zone_addresses = Vec::with_capacity(desired.len());
+    for (zone, ipv4_addresses, ipv6_addresses) in &desired {
+        let provider = match ZoneProvider::for_zone(route53_clients, config, zone).await {
+            Ok(provider) => provider,
+            Err(e) => {
+                // Keep going so a broken mirror doesn't hold up the other zones.
+                eprintln!("Error: Failed to update zone {}: {e}", zone.zone_id);
+                failure.get_or_insert(Classification::of(e.as_ref()));
+                errors.push(format!("zone {}: {e}", zone.zone_id));
+                summary.add_zone(zone, None, Duration::ZERO);
+                if is_queueable(e.as_ref()) && !config.monitor_only {
+                    queue_changed |= state.queue_update(&zone.zone_id, ipv4_addresses, ipv6_addresses, unix_time());
+                }
+                continue;
+            }
+        };
+
+        zone_addresses.push((zone, provider, ipv4_addresses.clone(), ipv6_addresses.clone()));
+    }
+
+    if config.monitor_only {
+        return check_for_drift(config, &zone_addresses, failure.map(|failure| failure.class)).await;
+    }
+
+    let mut f = FuturesUnordered::new();
+    for (zone, provider, ipv4_addresses, ipv6_addresses) in &zone_addresses {
+        f.push(async move {
+            let start = Instant::now();
+            let result = provider.update_zone(config, zone, ipv4_addresses, ipv6_addresses).await;
+            (zone, result, start.elapsed())
+        });
+    }
+
+    let mut changes_made = false;
+    while let Some((zone, result, duration)) = f.next().await {
+        summary.add_zone(zone, result.as_deref().ok(), duration);
+        let tags = [("zone", zone.zone_id.as_str())];
+        statsd::timing("zone.duration", duration, &tags);
+        let Some((_, _, ipv4_addresses, ipv6_addresses)) =
+            zone_addresses.iter().find(|(published, ..)| published.zone_id == zone.zone_id)
+        else {
+            continue;
+        };
+
+        match result {
+            Ok(diffs) => {
+                statsd::count("changes.applied", diffs.len() as u64, &tags);
+                let changed = changed_hostnames(&diffs);
+                changes_made |= !changed.is_empty();
+                queue_changed |= state.queued.remove(&zone.zone_id).is_some();
+                state.record_changes(&zone.zone_id, &changed, unix_time());
+                state.status.lock().unwrap().record_changes(&changed);
+                changes_made |=
+                    state.record_published(&zone.zone_id, ipv4_addresses.iter().chain(ipv6_addresses).copied());
+                let mut status = state.status.lock().unwrap();
+                for target in publish_targets(zone, ipv4_addresses, ipv6_addresses) {
+                    let addresses = target.ipv4.into_iter().chain(target.ipv6).flatten().copied().collect();
+                    let hostname = target.hostname_config.get_hostname().trim_end_matches('.').to_string();
+                    status.published.insert(hostname, addresses);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: Failed to update zone {}: {e}", zone.zone_id);
+                statsd::count("zone.failures", 1, &tags);
+                failure.get_or_insert(Classification::of(e.as_ref()));
+                errors.push(format!("zone {}: {e}", zone.zone_id));
+                if is_queueable(e.as_ref()) {
+                    queue_changed |= state.queue_update(&zone.zone_id, ipv4_addresses, ipv6_addresses, unix_time());
+                }
+            }
+        }
+    }
+
+    if !state.queued.is_empty() && queue_changed {
+        warn!(
+            "Queued the updates to zone(s) {} until AWS can be reached",
+            state.queued.keys().cloned().collect::<Vec<_>>().join(", ")
+        );
+    }
+
+    // The state file is also written after each successful update, so the healthcheck command can see how recent it is.
+    if errors.is_empty() {
+        state.last_sync = Some(unix_time());
+    }
+
+    if let (true, Some(state_file)) = (changes_made || queue_changed || errors.is_empty(), &config.state_file) {
+        if let Err(e) = state.save(state_file).await {
+            warn!("Unable to write state file {}: {e}", state_file.display());
+        }
+    }
+
+    state.status.lock().unwrap().finish_update(&errors);
+
+    // When running continuously, updates that find nothing to do aren't worth a table each.
+    let continuous = config.interval.is_some() || config.schedule.is_some();
+    if log_enabled!(Level::Info) && (!continuous || summary.is_eventful()) {
+        summary.print();
+    }
+
+    state.failure = failure;
+    failure.map_or(ExitCode::SUCCESS, |failure| failure.class.exit_code())
+}
+
+/// Queues the addresses for each zone that differ from those last published, instead of publishing them, while a change
+/// or blackout window holds changes.
+async fn hold_changes(
+    config: &Config,
+    state: &mut UpdateState,
+    desired: &[(Route53ZoneConfig, HashSet<IpAddr>, HashSet<IpAddr>)],
+) {
+    for (zone, ipv4_addresses, ipv6_addresses) in desired {
+        let addresses: BTreeSet<IpAddr> = ipv4_addresses.iter().chain(ipv6_addresses).copied().collect();
+        if state.last_published.get(&zone.zone_id) == Some(&addresses) {
+            state.queued.remove(&zone.zone_id);
+        } else {
+            state.queue_update(&zone.zone_id, ipv4_addresses, ipv6_addresses, unix_time());
+        }
+    }
+
+    if !state.queued.is_empty() {
+        let until = next_opening(config, Local::now().naive_local())
+            .map_or("a change window opens".to_string(), |opening| opening.format("%Y-%m-%d %H:%M").to_string());
+        info!(
+            "Holding the changes to zone(s) {} until {until}",
+            state.queued.keys().cloned().collect::<Vec<_>>().join(", ")
+        );
+    }
+
+    // Holding changes isn't a failure, so it doesn't make the healthcheck fail during a long blackout. The state file
+    // is written each time, like after a successful update, so the healthcheck command sees the hold as a sync.
+    state.last_sync = Some(unix_time());
+    if let Some(state_file) = &config.state_file {
+        if let Err(e) = state.save(state_file).await {
+            warn!("Unable to write state file {}: {e}", state_file.display());
+        }
+    }
+
+    state.status.lock().unwrap().finish_update(&[]);
+}
+
+/// Indicates whether a zone failed to update because AWS couldn't be reached, or is throttling requests, so the update
+/// is worth queueing. Errors that AWS says retrying won't fix, such as a rejected request, aren't queued.
+fn is_queueable(e: &(dyn std::error::Error + 'static)) -> bool {
+    let classification = Classification::of(e);
+    classification.retryable && matches!(classification.class, ErrorClass::AwsService | ErrorClass::AwsThrottling)
+}
+
+/// Returns the zones with queued updates and the addresses queued for them. Queued updates for zones that are no longer
+/// configured, or are disabled, are dropped.
+fn queued_zones(
+    config: &Config,
+    state: &mut UpdateState,
+) -> Vec<(Route53ZoneConfig, HashSet<IpAddr>, HashSet<IpAddr>)> {
+    let zones = config.zones_with_mirrors();
+    state.queued.retain(|zone_id, _| {
+        let configured = zones.iter().any(|zone| &zone.zone_id == zone_id);
+        if !configured {
+            warn!("Dropping the update queued for zone {zone_id}; it's no longer configured or is disabled");
+        }
+        configured
+    });
+
+    zones
+        .iter()
+        .filter_map(|zone| {
+            let queued = state.queued.get(&zone.zone_id)?;
+            info!(
+                "Publishing the addresses queued for zone {} at {}",
+                zone.zone_id,
+                format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(queued.queued_at))
+            );
+            Some((
+                state.without_cooling_hostnames(zone, config.cooldown),
+                queued.ipv4.iter().copied().collect(),
+                queued.ipv6.iter().copied().collect(),
+            ))
+        })
+        .collect()
+}
+
+/// Removes the A and AAAA records of every managed hostname, including those published for the EC2 fleet, returning
+/// whether every zone was updated successfully.
+async fn remove_records(config: &Config, route53_clients: &mut Route53Clients, state: &UpdateState) -> bool {
+    // Records are removed straight away: there's no point in lowering their TTLs first, and nobody is there to confirm.
+    let config = Config {
+        prechange_ttl: None,
+        interactive: false,
+        ..config.clone()
+    };
+    let no_addresses = HashSet::new();
+
+    let mut zones = config.zones_with_mirrors();
+    for (zone_id, hostnames) in &state.fleet_hostnames {
+        let mut zone = Route53ZoneConfig::new(zone_id);
+        zone.hostnames.extend(hostnames.iter().cloned().map(HostnameConfig::HostnameOnly));
+        zones.push(zone);
+    }
+    let mut providers = Vec::with_capacity(zones.len());
+    let mut errors_found = false;
+    for zone in &zones {
+        match ZoneProvider::for_zone(route53_clients, &config, zone).await {
+            Ok(provider) => providers.push((zone, provider)),
+            Err(e) => {
+                eprintln!("Error: Failed to remove records from zone {}: {e}", zone.zone_id);
+                errors_found = true;
+            }
+        }
+    }
+
+    let mut f = FuturesUnordered::new();
+    for (zone, provider) in &providers {
+        let (config, no_addresses) = (&config, &no_addresses);
+        f.push(async move { (zone, provider.update_zone(config, zone, no_addresses, no_addresses).await) });
+    }
+
+    while let Some((zone, result)) = f.next().await {
+        if let Err(e) = result {
+            eprintln!("Error: Failed to remove records from zone {}: {e}", zone.zone_id);
+            errors_found = true;
+        }
+    }
+
+    !errors_found
+}
+
+/// Compares the published records for each zone with its addresses without changing them, notifying the drift webhook
+/// if any don't match.
+async fn check_for_drift(
+    config: &Config,
+    zone_addresses: &[(&Route53ZoneConfig, ZoneProvider, HashSet<IpAddr>, HashSet<IpAddr>)],
+    mut failure: Option<ErrorClass>,
+) -> ExitCode {
+    let mut f = FuturesUnordered::new();
+    for (zone, provider, ipv4_addresses, ipv6_addresses) in zone_addresses {
+        f.push(async move { (zone, provider.check_zone(config, zone, ipv4_addresses, ipv6_addresses).await) });
+    }
+
+    let mut drifted = Vec::new();
+    while let Some((zone, result)) = f.next().await {
+        match result {
+            Ok(changes) if changes.is_empty() => (),
+            Ok(changes) => drifted.push(ZoneDrift {
+                zone_id: zone.zone_id.clone(),
+                changes,
+            }),
+            Err(e) => {
+                eprintln!("Error: Failed to check zone {}: {e}", zone.zone_id);
+                failure.get_or_insert(ErrorClass::of(e.as_ref()));
+            }
+        }
+    }
+
+    let drift_found = !drifted.is_empty();
+    if let (true, Some(url)) = (drift_found, &config.drift_webhook) {
+        if let Err(e) = send_drift_webhook(url, &DriftReport::new(drifted), config.timeout).await {
+            eprintln!("Error: {e}");
+            failure.get_or_insert(ErrorClass::of(e.as_ref()));
+        }
+    }
+
+    if let Some(failure) = failure {
+        failure.exit_code()
+    } else if drift_found {
+        ExitCode::from(DRIFT_EXIT_CODE)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Converts a ddclient configuration file, writing the result to the output file or standard output.
+async fn import_ddclient(input: &Path, output: Option<&Path>) -> Result<(), BoxError> {
+    let contents = read_to_string(input).await?;
+    let conversion = convert_ddclient_config(&contents)?;
+
+    for warning in &conversion.warnings {
+        eprintln!("Warning: {warning}");
+    }
+
+    match output {
+        Some(output) => write(output, conversion.config).await?,
+        None => print!("{}", conversion.config),
+    }
+
+    Ok(())
+}
+
+/// Classifies the NAT in front of this host with STUN if `detect-nat` is set, explaining what it means for the published
+/// IPv4 address unless it's the same as `previous`. Failures are logged rather than returned, since they don't stop
+/// the addresses from being published.
+async fn detect_nat(config: &Config, previous: Option<NatType>) -> Option<NatType> {
+    if !config.detect_nat || !config.queried_address_type().includes_ipv4() {
+        return None;
+    }
+
+    let observation = match observe_nat(&config.stun_servers, config.stun_timeout()).await {
+        Ok(observation) => observation,
+        Err(e) => {
+            warn!("{e}");
+            return None;
+        }
+    };
+
+    let nat = observation.nat_type();
+    if Some(nat) != previous {
+        match nat {
+            NatType::Open => info!("Not behind NAT: {}", nat.explanation()),
+            _ => {
+                let message = format!(
+                    "Behind {nat} ({} is seen as {}): {}",
+                    observation.local,
+                    observation.mapped.ip(),
+                    nat.explanation()
+                );
+                if nat.is_reachable() {
+                    info!("{message}");
+                } else {
+                    warn!("{message}");
+                }
+            }
+        }
+    }
+
+    Some(nat)
+}
+
+/// Returns the NAT64 prefix the IP service is queried for the IPv4 address through, if `nat64` is set: the configured
+/// prefix, or the one discovered from the resolver.
+async fn nat64_prefix(config: &Config) -> Result<Option<Nat64Prefix>, BoxError> {
+    if !config.nat64 {
+        return Ok(None);
+    }
+
+    match &config.nat64_prefix {
+        Some(prefix) => Ok(Some(prefix.parse()?)),
+        None => Ok(Some(discover_nat64_prefix().await?)),
+    }
+}
+
+/// Queries the configured address sources, printing any errors. Returns `None` if any source failed.
+async fn discover_addresses(
+    config: &Config,
+    ip_service_clients: &IpServiceClients,
+) -> Option<HashSet<DiscoveredAddress>> {
+    type IpQueryResult = (AddressSource, Result<Vec<IpAddr>, BoxError>);
     let mut f: FuturesUnordered<Pin<Box<dyn Future<Output = IpQueryResult>>>> = FuturesUnordered::new();
 
     // If we're querying interfaces, add that to the futures.
-    if config.query_interfaces {
-        f.push(Box::pin(get_addresses_from_network_interfaces(&config)));
+    if config.queries_source(AddressSource::Interfaces) {
+        f.push(Box::pin(async { (AddressSource::Interfaces, get_addresses_from_network_interfaces(config).await) }));
     }
 
     // If we're querying an IP service, add the IPv4 and/or IPv6 queries to the futures.
-    if config.query_ip_service {
-        if config.address_type == QueryAddressType::Both || config.address_type == QueryAddressType::Ipv4 {
-            f.push(Box::pin(get_address_from_ip_service(
-                &config.ip_service,
-                config.timeout,
-                LookupIpStrategy::Ipv4Only,
-            )));
+    if config.queries_source(AddressSource::IpService) {
+        let address_type = config.queried_address_type();
+        if address_type.includes_ipv4() {
+            f.push(Box::pin(async {
+                let nat64 = match nat64_prefix(config).await {
+                    Ok(nat64) => nat64,
+                    Err(e) => return (AddressSource::IpService, Err(e)),
+                };
+                let result = get_address_from_ip_service_with_retries(
+                    ip_service_clients,
+                    &config.ip_service,
+                    config.ip_service_timeout(),
+                    LookupIpStrategy::Ipv4Only,
+                    nat64,
+                    config.ip_service_retries,
+                    config.ip_service_backoff,
+                )
+                .await;
+                (AddressSource::IpService, result)
+            }));
         }
 
-        if config.address_type == QueryAddressType::Both || config.address_type == QueryAddressType::Ipv6 {
-            f.push(Box::pin(get_address_from_ip_service(
-                &config.ip_service,
-                config.timeout,
-                LookupIpStrategy::Ipv6Only,
-            )));
+        if address_type.includes_ipv6() {
+            f.push(Box::pin(async {
+                let result = get_address_from_ip_service_with_retries(
+                    ip_service_clients,
+                    &config.ip_service,
+                    config.ip_service_timeout(),
+                    LookupIpStrategy::Ipv6Only,
+                    None,
+                    config.ip_service_retries,
+                    config.ip_service_backoff,
+                )
+                .await;
+                (AddressSource::IpService, result)
+            }));
         }
     }
 
     if f.is_empty() {
         eprintln!("Error: Not querying any interfaces or IP services.");
-        return ExitCode::FAILURE;
+        return None;
     }
 
-    let mut ipv4_addresses = HashSet::<IpAddr>::new();
-    let mut ipv6_addresses = HashSet::<IpAddr>::new();
+    let mut discovered = HashSet::<DiscoveredAddress>::new();
     let mut errors_found = false;
 
-    while let Some(result) = f.next().await {
+    while let Some((source, result)) = f.next().await {
         match result {
             Ok(addresses) => {
                 for address in addresses {
-                    if config.allows_address(&address) {
-                        match address {
-                            IpAddr::V4(_) => ipv4_addresses.insert(address),
-                            IpAddr::V6(_) => ipv6_addresses.insert(address),
-                        };
-                    }
+                    discovered.insert(DiscoveredAddress {
+                        address,
+                        source,
+                    });
                 }
             }
             Err(err) => {
@@ -100,46 +1094,62 @@ async fn main() -> ExitCode {
 
     // Don't continue if we found any errors.
     if errors_found {
-        return ExitCode::FAILURE;
+        return None;
     }
 
-    let mut ipv4_addresses_sorted: Vec<&IpAddr> = ipv4_addresses.iter().collect();
-    let mut ipv6_addresses_sorted: Vec<&IpAddr> = ipv6_addresses.iter().collect();
-    ipv4_addresses_sorted.sort();
-    ipv6_addresses_sorted.sort();
-
-    let mut ipv4_address_strings = Vec::with_capacity(ipv4_addresses_sorted.len());
-    let mut ipv6_address_strings = Vec::with_capacity(ipv6_addresses_sorted.len());
+    let mut discovered_sorted: Vec<&DiscoveredAddress> = discovered.iter().collect();
+    discovered_sorted.sort();
+    let discovered_strings: Vec<String> = discovered_sorted.iter().map(|addr| addr.to_string()).collect();
+    info!("Discovered addresses: {}", discovered_strings.join(", "));
 
-    for address in &ipv4_addresses_sorted {
-        ipv4_address_strings.push(address.to_string());
-    }
+    Some(discovered)
+}
 
-    for address in &ipv6_addresses_sorted {
-        ipv6_address_strings.push(address.to_string());
-    }
+#[cfg(test)]
+mod tests {
+    use {
+        super::{hold_changes, is_queueable},
+        crate::{
+            config::{Config, Route53ZoneConfig},
+            error::ClassifiedError,
+            state::UpdateState,
+        },
+        std::{collections::HashSet, env::temp_dir},
+    };
 
-    info!("IPv4 addresses: {}", ipv4_address_strings.join(", "));
-    info!("IPv6 addresses: {}", ipv6_address_strings.join(", "));
+    #[tokio::test]
+    async fn holding_changes_counts_as_a_sync() {
+        let state_file = temp_dir().join(format!("route53-ip-update-hold-{}.json", std::process::id()));
+        let config = Config {
+            state_file: Some(state_file.clone()),
+            ..Config::default()
+        };
+        let ipv4 = HashSet::from(["192.0.2.1".parse().unwrap()]);
+        let desired = [(Route53ZoneConfig::new("Z1"), ipv4, HashSet::new())];
 
-    let sdk_config = load_aws_config_from_env().await;
-    let route53 = Route53Client::new(&sdk_config);
+        let mut state = UpdateState::default();
+        hold_changes(&config, &mut state, &desired).await;
+        assert!(state.last_sync.is_some());
+        assert!(state.queued.contains_key("Z1"));
 
-    let mut f = FuturesUnordered::new();
-    for zone in &config.route53_zones {
-        f.push(update_zone(route53.clone(), zone, config.ttl, &ipv4_addresses, &ipv6_addresses))
+        // Holding the same changes again doesn't change the queue, but still records the sync.
+        state.last_sync = None;
+        hold_changes(&config, &mut state, &desired).await;
+        let saved = UpdateState::load(Some(&state_file)).await;
+        std::fs::remove_file(&state_file).unwrap();
+        assert_eq!(saved.last_sync, state.last_sync);
+        assert!(saved.last_sync.is_some());
     }
 
-    while let Some(result) = f.next().await {
-        if let Err(e) = result {
-            eprintln!("Error: {e}");
-            errors_found = true;
-        }
-    }
+    #[test]
+    fn only_retryable_aws_failures_are_queued() {
+        let unavailable = ClassifiedError::from_aws_json("ChangeResourceRecordSets", 503, "ServiceUnavailable", "");
+        assert!(is_queueable(&unavailable));
 
-    if errors_found {
-        ExitCode::FAILURE
-    } else {
-        ExitCode::SUCCESS
+        let throttled = ClassifiedError::from_aws_json("ChangeResourceRecordSets", 400, "Throttling", "Rate exceeded");
+        assert!(is_queueable(&throttled));
+
+        let rejected = ClassifiedError::from_aws_json("ChangeResourceRecordSets", 400, "NoSuchHostedZone", "Z1");
+        assert!(!is_queueable(&rejected));
     }
 }
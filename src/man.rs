@@ -0,0 +1,127 @@
+use {
+    crate::{args::Args, config::Config},
+    clap::CommandFactory,
+    clap_mangen::{
+        roff::{bold, roman, Roff},
+        Man,
+    },
+    schemars::{
+        schema::{RootSchema, Schema, SchemaObject, SingleOrVec},
+        schema_for,
+    },
+    std::{collections::HashSet, io::Write},
+};
+
+/// Writes a roff man page covering the command-line options, the subcommands, and the configuration file keys.
+pub(crate) fn write_man_page(out: &mut dyn Write) -> Result<(), std::io::Error> {
+    let man = Man::new(Args::command());
+    man.render_title(out)?;
+    man.render_name_section(out)?;
+    man.render_synopsis_section(out)?;
+    man.render_description_section(out)?;
+    man.render_options_section(out)?;
+    man.render_subcommands_section(out)?;
+
+    let mut roff = Roff::default();
+    render_config_section(&mut roff);
+    roff.to_writer(out)?;
+
+    man.render_version_section(out)?;
+    man.render_authors_section(out)
+}
+
+/// Renders the CONFIGURATION FILE section from the configuration's JSON Schema, so it stays in step with the
+/// structures the file is parsed into.
+fn render_config_section(roff: &mut Roff) {
+    let root = schema_for!(Config);
+
+    roff.control("SH", ["CONFIGURATION FILE"]);
+    roff.text([roman(
+        "The configuration file is YAML, TOML, or JSON, chosen by its extension. Settings on the command line \
+         override those in the file. The keys are:",
+    )]);
+
+    let mut visited = HashSet::new();
+    let mut nested = Vec::new();
+    render_keys(roff, &root, &root.schema, "", &mut visited, &mut nested);
+
+    // Render nested tables breadth-first, so they follow the keys that introduce them.
+    while !nested.is_empty() {
+        for (path, definition) in std::mem::take(&mut nested) {
+            let Some(Schema::Object(schema)) = root.definitions.get(&definition) else {
+                continue;
+            };
+
+            roff.control("SS", [path.as_str()]);
+            render_keys(roff, &root, schema, &path, &mut visited, &mut nested);
+        }
+    }
+}
+
+/// Renders the properties of an object schema, queueing any properties that are themselves tables.
+fn render_keys(
+    roff: &mut Roff,
+    root: &RootSchema,
+    schema: &SchemaObject,
+    path: &str,
+    visited: &mut HashSet<String>,
+    nested: &mut Vec<(String, String)>,
+) {
+    let Some(object) = &schema.object else {
+        return;
+    };
+
+    for (key, property) in &object.properties {
+        let Schema::Object(property) = property else {
+            continue;
+        };
+
+        let description = property.metadata.as_ref().and_then(|m| m.description.as_deref()).unwrap_or_default();
+        roff.control("TP", []);
+        roff.text([bold(key.as_str())]);
+        roff.text([roman(description)]);
+
+        if let Some((definition, is_list)) = table_definition(root, property) {
+            if visited.insert(definition.clone()) {
+                let suffix = if is_list {
+                    "[]"
+                } else {
+                    ""
+                };
+                let path = if path.is_empty() {
+                    format!("{key}{suffix}")
+                } else {
+                    format!("{path}.{key}{suffix}")
+                };
+                nested.push((path, definition));
+            }
+        }
+    }
+}
+
+/// Returns the name of the definition describing a property's table (or its list items), and whether the property is
+/// a list of them.
+fn table_definition(root: &RootSchema, schema: &SchemaObject) -> Option<(String, bool)> {
+    if let Some(reference) = &schema.reference {
+        let name = reference.strip_prefix("#/definitions/")?;
+        return match root.definitions.get(name) {
+            Some(Schema::Object(definition)) if definition.object.is_some() => Some((name.to_string(), false)),
+            Some(Schema::Object(definition)) => table_definition(root, definition),
+            _ => None,
+        };
+    }
+
+    if let Some(SingleOrVec::Single(items)) = schema.array.as_ref().and_then(|array| array.items.as_ref()) {
+        if let Schema::Object(items) = items.as_ref() {
+            return table_definition(root, items).map(|(name, _)| (name, true));
+        }
+    }
+
+    // Optional tables and untagged enums are expressed as alternatives; use the first that is a table.
+    let subschemas = schema.subschemas.as_ref()?;
+    let alternatives = subschemas.all_of.iter().chain(subschemas.any_of.iter()).chain(subschemas.one_of.iter());
+    alternatives.flatten().find_map(|alternative| match alternative {
+        Schema::Object(alternative) => table_definition(root, alternative),
+        _ => None,
+    })
+}
@@ -0,0 +1,149 @@
+use {
+    crate::error::Route53IpUpdateError,
+    std::{
+        fmt::{Display, Formatter, Result as FmtResult},
+        net::{Ipv4Addr, Ipv6Addr},
+        str::FromStr,
+    },
+};
+
+/// The name whose AAAA records reveal the NAT64 prefix when the resolver does DNS64 (RFC 7050).
+pub(crate) const IPV4ONLY_ARPA: &str = "ipv4only.arpa.";
+
+/// The IPv4 addresses `ipv4only.arpa` resolves to, which DNS64 embeds in the prefix.
+const IPV4ONLY_ARPA_ADDRESSES: [Ipv4Addr; 2] = [Ipv4Addr::new(192, 0, 0, 170), Ipv4Addr::new(192, 0, 0, 171)];
+
+/// The prefix lengths IPv4 addresses can be embedded under (RFC 6052).
+const PREFIX_LENGTHS: [u8; 6] = [32, 40, 48, 56, 64, 96];
+
+/// A NAT64 prefix, under which IPv4 addresses are reachable from IPv6-only networks.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct Nat64Prefix {
+    prefix: Ipv6Addr,
+    len: u8,
+}
+
+impl Nat64Prefix {
+    /// Returns the IPv6 address the NAT64 translates to the IPv4 address.
+    pub(crate) fn synthesize(&self, addr: Ipv4Addr) -> Ipv6Addr {
+        let mut octets = self.prefix.octets();
+        for (position, octet) in self.embedded_positions().zip(addr.octets()) {
+            octets[position] = octet;
+        }
+
+        Ipv6Addr::from(octets)
+    }
+
+    /// Returns the IPv4 address embedded in an IPv6 address under this prefix, if it's under it.
+    pub(crate) fn extract(&self, addr: Ipv6Addr) -> Option<Ipv4Addr> {
+        if mask(addr, self.len) != self.prefix {
+            return None;
+        }
+
+        let octets = addr.octets();
+        let mut embedded = [0; 4];
+        for (octet, position) in embedded.iter_mut().zip(self.embedded_positions()) {
+            *octet = octets[position];
+        }
+
+        Some(Ipv4Addr::from(embedded))
+    }
+
+    /// Returns the NAT64 prefix a DNS64 resolver synthesized one of the AAAA records of `ipv4only.arpa` under, if any.
+    pub(crate) fn from_ipv4only_arpa(addrs: impl IntoIterator<Item = Ipv6Addr>) -> Option<Self> {
+        addrs.into_iter().find_map(|addr| {
+            PREFIX_LENGTHS.iter().rev().find_map(|&len| {
+                let prefix = Self {
+                    prefix: mask(addr, len),
+                    len,
+                };
+                let embedded = prefix.extract(addr)?;
+                (IPV4ONLY_ARPA_ADDRESSES.contains(&embedded) && prefix.synthesize(embedded) == addr).then_some(prefix)
+            })
+        })
+    }
+
+    /// Returns the positions of the octets of an IPv6 address the IPv4 address is embedded in. Bits 64 to 71 are
+    /// skipped.
+    fn embedded_positions(&self) -> impl Iterator<Item = usize> {
+        (usize::from(self.len / 8)..16).filter(|&position| position != 8).take(4)
+    }
+}
+
+impl Display for Nat64Prefix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}/{}", self.prefix, self.len)
+    }
+}
+
+impl FromStr for Nat64Prefix {
+    type Err = Route53IpUpdateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Route53IpUpdateError::InvalidNat64Prefix(s.to_string());
+        let (prefix, len) = s.split_once('/').ok_or_else(invalid)?;
+        let prefix: Ipv6Addr = prefix.parse().map_err(|_| invalid())?;
+        let len: u8 = len.parse().map_err(|_| invalid())?;
+        if !PREFIX_LENGTHS.contains(&len) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            prefix: mask(prefix, len),
+            len,
+        })
+    }
+}
+
+/// Returns the first `len` bits of an address, with the rest cleared.
+fn mask(addr: Ipv6Addr, len: u8) -> Ipv6Addr {
+    let bits = u128::from(addr);
+    Ipv6Addr::from(bits & !(u128::MAX >> len))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::Nat64Prefix,
+        std::net::{Ipv4Addr, Ipv6Addr},
+    };
+
+    /// The well-known NAT64 prefix (RFC 6052).
+    const WELL_KNOWN_PREFIX: Nat64Prefix = Nat64Prefix {
+        prefix: Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0),
+        len: 96,
+    };
+
+    #[test]
+    fn embeds_ipv4_addresses_per_rfc_6052() {
+        // The examples from RFC 6052, section 2.4.
+        let addr = Ipv4Addr::new(192, 0, 2, 33);
+        for (prefix, expected) in [
+            ("2001:db8::/32", "2001:db8:c000:221::"),
+            ("2001:db8:100::/40", "2001:db8:1c0:2:21::"),
+            ("2001:db8:122::/48", "2001:db8:122:c000:2:2100::"),
+            ("2001:db8:122:300::/56", "2001:db8:122:3c0:0:221::"),
+            ("2001:db8:122:344::/64", "2001:db8:122:344:c0:2:2100:0"),
+            ("2001:db8:122:344::/96", "2001:db8:122:344::192.0.2.33"),
+        ] {
+            let prefix: Nat64Prefix = prefix.parse().unwrap();
+            let expected: Ipv6Addr = expected.parse().unwrap();
+            assert_eq!(prefix.synthesize(addr), expected, "{prefix}");
+            assert_eq!(prefix.extract(expected), Some(addr), "{prefix}");
+        }
+
+        assert_eq!(WELL_KNOWN_PREFIX.extract("2001:db8::1".parse().unwrap()), None);
+        assert!("64:ff9b::/80".parse::<Nat64Prefix>().is_err());
+    }
+
+    #[test]
+    fn finds_the_prefix_from_ipv4only_arpa() {
+        let found = Nat64Prefix::from_ipv4only_arpa(["64:ff9b::c000:aa".parse().unwrap()]);
+        assert_eq!(found, Some(WELL_KNOWN_PREFIX));
+
+        let found = Nat64Prefix::from_ipv4only_arpa(["2001:db8:122:3c0:0:aa::".parse().unwrap()]);
+        assert_eq!(found, Some("2001:db8:122:300::/56".parse().unwrap()));
+
+        assert_eq!(Nat64Prefix::from_ipv4only_arpa(["2001:db8::1".parse().unwrap()]), None);
+    }
+}
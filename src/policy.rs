@@ -0,0 +1,128 @@
+use {
+    crate::{config::PoliciesConfig, diff::RecordSetDiff},
+    std::{collections::HashSet, net::IpAddr},
+};
+
+/// Returns a description of each way the planned changes to a zone break the policies. Changes that only remove
+/// records are always allowed, so records can be taken down whatever the policies say.
+pub(crate) fn policy_violations(
+    policies: &PoliciesConfig,
+    diffs: &[RecordSetDiff],
+    desired_ipv4: &HashSet<IpAddr>,
+    desired_ipv6: &HashSet<IpAddr>,
+) -> Vec<String> {
+    let mut violations = Vec::new();
+    let published: Vec<&RecordSetDiff> =
+        diffs.iter().filter(|diff| diff.new.as_ref().is_some_and(|new| !new.values.is_empty())).collect();
+    if published.is_empty() {
+        return violations;
+    }
+
+    if let Some(required) = policies.require_addresses {
+        if required.includes_ipv4() && desired_ipv4.is_empty() {
+            violations.push("require-addresses: no IPv4 address was discovered".to_string());
+        }
+
+        if required.includes_ipv6() && desired_ipv6.is_empty() {
+            violations.push("require-addresses: no IPv6 address was discovered".to_string());
+        }
+    }
+
+    for diff in published {
+        let Some(new) = &diff.new else {
+            continue;
+        };
+
+        if let Some(max_addresses) = policies.max_addresses {
+            if matches!(diff.record_type.as_str(), "A" | "AAAA") && new.values.len() > max_addresses {
+                violations.push(format!(
+                    "max-addresses: {} {} would have {} addresses ({}), more than {max_addresses}",
+                    diff.hostname,
+                    diff.record_type,
+                    new.values.len(),
+                    new.values.join(", ")
+                ));
+            }
+        }
+
+        let Some(ttl) = new.ttl else {
+            continue;
+        };
+
+        if let Some(min_ttl) = policies.min_ttl.filter(|&min_ttl| ttl < i64::from(min_ttl)) {
+            violations.push(format!(
+                "min-ttl: {} {} would have a TTL of {ttl}, less than {}",
+                diff.hostname,
+                diff.record_type,
+                i64::from(min_ttl)
+            ));
+        }
+
+        if let Some(max_ttl) = policies.max_ttl.filter(|&max_ttl| ttl > i64::from(max_ttl)) {
+            violations.push(format!(
+                "max-ttl: {} {} would have a TTL of {ttl}, more than {}",
+                diff.hostname,
+                diff.record_type,
+                i64::from(max_ttl)
+            ));
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::policy_violations,
+        crate::{
+            config::PoliciesConfig,
+            diff::{RecordSetDiff, RecordSetSummary},
+        },
+        std::{collections::HashSet, net::IpAddr},
+    };
+
+    fn diff(record_type: &str, ttl: i64, values: &[&str]) -> RecordSetDiff {
+        RecordSetDiff {
+            hostname: "home.example.com".to_string(),
+            record_type: record_type.to_string(),
+            old: None,
+            new: Some(RecordSetSummary::new(Some(ttl), values)),
+        }
+    }
+
+    fn addrs(addresses: &[&str]) -> HashSet<IpAddr> {
+        addresses.iter().map(|a| a.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn reports_each_broken_policy() {
+        let policies: PoliciesConfig =
+            serde_yaml::from_str("{require-addresses: both, max-addresses: 2, min-ttl: 60, max-ttl: 1h}").unwrap();
+        let ipv4 = addrs(&["192.0.2.1", "192.0.2.2", "192.0.2.3"]);
+        let diffs = [diff("A", 30, &["192.0.2.1", "192.0.2.2", "192.0.2.3"])];
+
+        let violations = policy_violations(&policies, &diffs, &ipv4, &HashSet::new());
+        assert_eq!(
+            violations,
+            [
+                "require-addresses: no IPv6 address was discovered",
+                "max-addresses: home.example.com A would have 3 addresses (192.0.2.1, 192.0.2.2, 192.0.2.3), more \
+                 than 2",
+                "min-ttl: home.example.com A would have a TTL of 30, less than 60",
+            ]
+        );
+
+        let ipv6 = addrs(&["2001:db8::1"]);
+        let diffs = [diff("A", 300, &["192.0.2.1"]), diff("AAAA", 300, &["2001:db8::1"])];
+        assert!(policy_violations(&policies, &diffs, &addrs(&["192.0.2.1"]), &ipv6).is_empty());
+
+        // Removing records is always allowed.
+        let removal = RecordSetDiff {
+            new: None,
+            old: Some(RecordSetSummary::new(Some(300), ["192.0.2.1"])),
+            ..diff("A", 300, &[])
+        };
+        assert!(policy_violations(&policies, &[removal], &HashSet::new(), &HashSet::new()).is_empty());
+    }
+}
@@ -0,0 +1,44 @@
+use {
+    std::io::{stderr, stdin, BufRead, ErrorKind, Write},
+    tower::BoxError,
+};
+
+/// Prints a question on stderr and reads the answer from stdin. An empty answer selects the default, if any.
+pub(crate) fn prompt(question: &str, default: Option<&str>) -> Result<String, BoxError> {
+    let mut stderr = stderr();
+    match default {
+        Some(default) => write!(stderr, "{question} [{default}]: ")?,
+        None => write!(stderr, "{question}: ")?,
+    }
+    stderr.flush()?;
+
+    let mut answer = String::new();
+    if stdin().lock().read_line(&mut answer)? == 0 {
+        return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+    }
+
+    let answer = answer.trim();
+    Ok(match default {
+        Some(default) if answer.is_empty() => default.to_string(),
+        _ => answer.to_string(),
+    })
+}
+
+/// Asks a yes/no question, repeating it until the answer is understood.
+pub(crate) fn prompt_bool(question: &str, default: bool) -> Result<bool, BoxError> {
+    loop {
+        let answer = prompt(
+            question,
+            Some(if default {
+                "yes"
+            } else {
+                "no"
+            }),
+        )?;
+        match answer.to_ascii_lowercase().as_str() {
+            "y" | "yes" | "true" => return Ok(true),
+            "n" | "no" | "false" => return Ok(false),
+            _ => eprintln!("Please answer yes or no."),
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use {
+    crate::{
+        aws::Route53Clients,
+        cloudflare::CloudflareProvider,
+        config::{Config, HostnameConfig, Route53ZoneConfig},
+        diff::{print_changes, RecordSetDiff},
+        error::Route53IpUpdateError,
+        events::{send_change_events, send_verification_failed_event},
+        lock::lock_zone,
+        policy::policy_violations,
+        prompt::prompt_bool,
+        rfc2136::Rfc2136Provider,
+        update::Route53Provider,
+        zones::suggest_hosted_zones,
+    },
+    async_trait::async_trait,
+    log::{info, log_enabled, warn, Level},
+    once_cell::sync::Lazy,
+    std::{collections::HashSet, net::IpAddr, time::SystemTime},
+    tower::BoxError,
+};
+
+/// A DNS service that hostnames can be published to.
+///
+/// An update is split into phases: `plan` works out the changes needed by comparing the published records with the
+/// desired addresses, `apply` submits them, and `wait` blocks until the service reports that they have been published.
+#[async_trait]
+pub(crate) trait DnsProvider {
+    /// The changes needed to bring a zone up-to-date.
+    type Plan: Send;
+
+    /// A handle to changes that have been submitted but may not have been published yet.
+    type Pending: Send;
+
+    /// Returns the IPv4 and IPv6 addresses currently published for a hostname.
+    async fn list(&self, hostname: &str) -> Result<(HashSet<IpAddr>, HashSet<IpAddr>), BoxError>;
+
+    /// Returns the changes needed to publish the desired addresses for the zone's hostnames, or `None` if they are
+    /// already up-to-date.
+    async fn plan(
+        &self,
+        config: &Config,
+        zone_config: &Route53ZoneConfig,
+        desired_ipv4: &HashSet<IpAddr>,
+        desired_ipv6: &HashSet<IpAddr>,
+    ) -> Result<Option<Self::Plan>, BoxError>;
+
+    /// Summarizes planned changes for display.
+    fn describe<'a>(&self, plan: &'a Self::Plan) -> &'a [RecordSetDiff];
+
+    /// Submits planned changes.
+    async fn apply(&self, plan: Self::Plan) -> Result<Self::Pending, BoxError>;
+
+    /// Waits for submitted changes to be published.
+    async fn wait(&self, pending: Self::Pending) -> Result<(), BoxError>;
+
+    /// Returns the service's ID for submitted changes, if it has one.
+    fn change_id(&self, _pending: &Self::Pending) -> Option<String> {
+        None
+    }
+}
+
+static NO_ADDRESSES: Lazy<HashSet<IpAddr>> = Lazy::new(HashSet::new);
+
+/// The label prepended to a hostname to name the TXT record holding when it was last updated.
+const TIMESTAMP_RECORD_LABEL: &str = "_r53ipupdate";
+
+/// Returns the name of the TXT record holding when a hostname was last updated.
+pub(crate) fn timestamp_record_name(hostname: &str) -> String {
+    format!("{TIMESTAMP_RECORD_LABEL}.{}", hostname.trim_end_matches('.'))
+}
+
+/// Returns the text of a timestamp record: when the hostname was updated, the machine that updated it, and the version
+/// of this tool that did.
+pub(crate) fn timestamp_record_text() -> String {
+    format!(
+        "updated={} source={} version={}",
+        humantime::format_rfc3339_seconds(SystemTime::now()),
+        gethostname::gethostname().to_string_lossy(),
+        concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"))
+    )
+}
+
+/// Returns the hostname a timestamp record is named after, if the name is that of a timestamp record.
+pub(crate) fn timestamp_record_hostname(name: &str) -> Option<&str> {
+    name.trim_end_matches('.').strip_prefix(TIMESTAMP_RECORD_LABEL)?.strip_prefix('.')
+}
+
+/// Returns when a timestamp record says its hostname was updated, if the text can be parsed.
+pub(crate) fn parse_timestamp_record_text(text: &str) -> Option<SystemTime> {
+    let updated = text.trim_matches('"').split_whitespace().find_map(|field| field.strip_prefix("updated="))?;
+    humantime::parse_rfc3339(updated).ok()
+}
+
+/// Returns the hostnames whose timestamp records should be updated along with the planned changes: those changed, if
+/// `timestamp-records` is set, or all of the zone's hostnames, if `heartbeat` is set.
+pub(crate) fn timestamp_record_hostnames(
+    config: &Config,
+    zone_config: &Route53ZoneConfig,
+    diffs: &[RecordSetDiff],
+) -> Vec<String> {
+    let hostnames: Vec<&str> = if config.heartbeat {
+        zone_config.hostnames.iter().flat_map(|hc| hc.get_hostnames()).collect()
+    } else if config.timestamp_records {
+        diffs.iter().map(|diff| diff.hostname.as_str()).collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut hostnames: Vec<String> = hostnames.into_iter().map(|h| h.trim_end_matches('.').to_string()).collect();
+    hostnames.sort();
+    hostnames.dedup();
+    hostnames
+}
+
+/// A hostname to publish addresses to. If the addresses of a type are `None`, that type isn't managed for the hostname
+/// and its records are left alone.
+pub(crate) struct PublishTarget<'a> {
+    pub(crate) hostname_config: HostnameConfig,
+    pub(crate) ipv4: Option<&'a HashSet<IpAddr>>,
+    pub(crate) ipv6: Option<&'a HashSet<IpAddr>>,
+}
+
+/// Returns the hostnames in the zone to publish addresses to. Each configured hostname gets the addresses of the types
+/// managed in the zone; its `ipv4-hostname` and `ipv6-hostname`, if any, only get addresses of one type, and any
+/// records of the other type are removed from them.
+pub(crate) fn publish_targets<'a>(
+    zone_config: &Route53ZoneConfig,
+    desired_ipv4: &'a HashSet<IpAddr>,
+    desired_ipv6: &'a HashSet<IpAddr>,
+) -> Vec<PublishTarget<'a>> {
+    let address_type = zone_config.managed_address_type();
+    let ipv4 = address_type.includes_ipv4().then_some(desired_ipv4);
+    let ipv6 = address_type.includes_ipv6().then_some(desired_ipv6);
+    let no_ipv4 = address_type.includes_ipv4().then_some(&*NO_ADDRESSES);
+    let no_ipv6 = address_type.includes_ipv6().then_some(&*NO_ADDRESSES);
+
+    let mut targets = Vec::new();
+    for hostname_config in &zone_config.hostnames {
+        targets.push(PublishTarget {
+            hostname_config: hostname_config.clone(),
+            ipv4,
+            ipv6,
+        });
+
+        if let Some(hostname) = hostname_config.get_ipv4_hostname() {
+            targets.push(PublishTarget {
+                hostname_config: hostname_config.with_hostname(hostname.to_string()),
+                ipv4,
+                ipv6: no_ipv6,
+            });
+        }
+
+        if let Some(hostname) = hostname_config.get_ipv6_hostname() {
+            targets.push(PublishTarget {
+                hostname_config: hostname_config.with_hostname(hostname.to_string()),
+                ipv4: no_ipv4,
+                ipv6,
+            });
+        }
+    }
+
+    targets
+}
+
+/// Brings the hostnames in the zone up-to-date with the desired addresses using the given provider. Returns the changes
+/// made.
+///
+/// If locking is configured, the zone's lock is held from before the changes are planned until they've been
+/// published, so another updater's changes are seen rather than overwritten.
+pub(crate) async fn update_zone_with<P: DnsProvider + Sync>(
+    provider: &P,
+    config: &Config,
+    zone_config: &Route53ZoneConfig,
+    desired_ipv4: &HashSet<IpAddr>,
+    desired_ipv6: &HashSet<IpAddr>,
+) -> Result<Vec<RecordSetDiff>, BoxError> {
+    let lock = lock_zone(config, &zone_config.zone_id).await?;
+    let result = plan_and_apply(provider, config, zone_config, desired_ipv4, desired_ipv6).await;
+    if let Some(lock) = lock {
+        lock.release().await;
+    }
+
+    result
+}
+
+async fn plan_and_apply<P: DnsProvider + Sync>(
+    provider: &P,
+    config: &Config,
+    zone_config: &Route53ZoneConfig,
+    desired_ipv4: &HashSet<IpAddr>,
+    desired_ipv6: &HashSet<IpAddr>,
+) -> Result<Vec<RecordSetDiff>, BoxError> {
+    let Some(plan) = provider.plan(config, zone_config, desired_ipv4, desired_ipv6).await? else {
+        info!("All IP addresses are for zone {} up-to-date; no changes to make.", zone_config.zone_id);
+        return Ok(Vec::new());
+    };
+
+    // The summary is informational, so it's omitted with --quiet unless the user is being asked to confirm it.
+    if config.interactive || log_enabled!(Level::Info) {
+        print_changes(&zone_config.zone_id, provider.describe(&plan));
+    }
+
+    let violations = policy_violations(&config.policies, provider.describe(&plan), desired_ipv4, desired_ipv6);
+    if !violations.is_empty() {
+        return Err(Route53IpUpdateError::PolicyViolated(zone_config.zone_id.clone(), violations).into());
+    }
+
+    // A plan that only refreshes heartbeat timestamp records doesn't change anything worth confirming.
+    let heartbeat_only = provider.describe(&plan).is_empty();
+    if heartbeat_only {
+        info!("Refreshing heartbeat records for zone {}", zone_config.zone_id);
+    } else if config.interactive
+        && !prompt_bool(&format!("Apply these changes to zone {}?", zone_config.zone_id), false)?
+    {
+        info!("Skipping changes to zone {}", zone_config.zone_id);
+        return Ok(Vec::new());
+    }
+
+    let diffs = provider.describe(&plan).to_vec();
+    let pending = provider.apply(plan).await?;
+    let change_id = provider.change_id(&pending);
+    if let Err(e) = provider.wait(pending).await {
+        if let Some(Route53IpUpdateError::VerificationFailed(_, problems, rolled_back)) = e.downcast_ref() {
+            send_verification_failed_event(config, &zone_config.zone_id, &diffs, problems, *rolled_back).await;
+        }
+        return Err(e);
+    }
+    info!("Hostnames updated successfully for zone {}", zone_config.zone_id);
+    send_change_events(config, &zone_config.zone_id, &diffs, change_id.as_deref()).await;
+    Ok(diffs)
+}
+
+/// Compares the records published in the zone with the desired addresses using the given provider, without changing
+/// them. Returns the changes that would be needed to bring the zone up-to-date.
+pub(crate) async fn check_zone_with<P: DnsProvider + Sync>(
+    provider: &P,
+    config: &Config,
+    zone_config: &Route53ZoneConfig,
+    desired_ipv4: &HashSet<IpAddr>,
+    desired_ipv6: &HashSet<IpAddr>,
+) -> Result<Vec<RecordSetDiff>, BoxError> {
+    // Heartbeat timestamp records are refreshed even when nothing else needs changing; that isn't drift.
+    let plan = provider.plan(config, zone_config, desired_ipv4, desired_ipv6).await?;
+    let Some(plan) = plan.filter(|plan| !provider.describe(plan).is_empty()) else {
+        info!("All IP addresses for zone {} are up-to-date.", zone_config.zone_id);
+        return Ok(Vec::new());
+    };
+
+    warn!("Records in zone {} don't match the discovered addresses", zone_config.zone_id);
+    let diffs = provider.describe(&plan);
+    if log_enabled!(Level::Info) {
+        print_changes(&zone_config.zone_id, diffs);
+    }
+
+    Ok(diffs.to_vec())
+}
+
+/// The provider hosting a configured zone.
+pub(crate) enum ZoneProvider {
+    Cloudflare(CloudflareProvider),
+    Rfc2136(Rfc2136Provider),
+    Route53(Route53Provider),
+}
+
+impl ZoneProvider {
+    /// Returns the provider for a zone, creating a Route 53 client for it if the zone is hosted on Route 53.
+    pub(crate) async fn for_zone(
+        clients: &mut Route53Clients,
+        config: &Config,
+        zone_config: &Route53ZoneConfig,
+    ) -> Result<Self, BoxError> {
+        if let Some(cloudflare) = &zone_config.cloudflare {
+            let provider = CloudflareProvider::new(cloudflare, &zone_config.zone_id, config.timeout).await?;
+            return Ok(Self::Cloudflare(provider));
+        }
+
+        if let Some(rfc2136) = &zone_config.rfc2136 {
+            return Ok(Self::Rfc2136(Rfc2136Provider::new(rfc2136.clone(), config.timeout)));
+        }
+
+        let route53 = clients.for_zone(zone_config).await?;
+        let provider = Route53Provider::new(route53, &zone_config.zone_id)
+            .with_snapshot_dir(config.snapshot_dir.clone())
+            .with_verify(config.verify.clone());
+        Ok(Self::Route53(provider))
+    }
+
+    /// Brings the hostnames in the zone up-to-date with the desired addresses. Returns the changes made.
+    pub(crate) async fn update_zone(
+        &self,
+        config: &Config,
+        zone_config: &Route53ZoneConfig,
+        desired_ipv4: &HashSet<IpAddr>,
+        desired_ipv6: &HashSet<IpAddr>,
+    ) -> Result<Vec<RecordSetDiff>, BoxError> {
+        match self {
+            Self::Cloudflare(provider) => {
+                update_zone_with(provider, config, zone_config, desired_ipv4, desired_ipv6).await
+            }
+            Self::Rfc2136(provider) => {
+                update_zone_with(provider, config, zone_config, desired_ipv4, desired_ipv6).await
+            }
+            Self::Route53(provider) => {
+                let result = update_zone_with(provider, config, zone_config, desired_ipv4, desired_ipv6).await;
+                match result {
+                    Err(e) => Err(suggest_hosted_zones(provider.client(), zone_config, e).await),
+                    result => result,
+                }
+            }
+        }
+    }
+
+    /// Compares the records published in the zone with the desired addresses, without changing them. Returns the
+    /// changes that would be needed to bring the zone up-to-date.
+    pub(crate) async fn check_zone(
+        &self,
+        config: &Config,
+        zone_config: &Route53ZoneConfig,
+        desired_ipv4: &HashSet<IpAddr>,
+        desired_ipv6: &HashSet<IpAddr>,
+    ) -> Result<Vec<RecordSetDiff>, BoxError> {
+        match self {
+            Self::Cloudflare(provider) => {
+                check_zone_with(provider, config, zone_config, desired_ipv4, desired_ipv6).await
+            }
+            Self::Rfc2136(provider) => check_zone_with(provider, config, zone_config, desired_ipv4, desired_ipv6).await,
+            Self::Route53(provider) => {
+                let result = check_zone_with(provider, config, zone_config, desired_ipv4, desired_ipv6).await;
+                match result {
+                    Err(e) => Err(suggest_hosted_zones(provider.client(), zone_config, e).await),
+                    result => result,
+                }
+            }
+        }
+    }
+
+    /// Returns the IPv4 and IPv6 addresses currently published for a hostname.
+    pub(crate) async fn list(&self, hostname: &str) -> Result<(HashSet<IpAddr>, HashSet<IpAddr>), BoxError> {
+        match self {
+            Self::Cloudflare(provider) => provider.list(hostname).await,
+            Self::Rfc2136(provider) => provider.list(hostname).await,
+            Self::Route53(provider) => provider.list(hostname).await,
+        }
+    }
+}
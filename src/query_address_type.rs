@@ -1,5 +1,6 @@
 use {
     crate::error::Route53IpUpdateError,
+    schemars::JsonSchema,
     serde::{Deserialize, Serialize},
     std::{
         fmt::{Display, Formatter, Result as FmtResult},
@@ -8,9 +9,10 @@ use {
     },
 };
 
-#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub(crate) enum QueryAddressType {
+    #[default]
     Both,
     Ipv4,
     Ipv6,
@@ -19,15 +21,26 @@ pub(crate) enum QueryAddressType {
 impl QueryAddressType {
     pub fn allows_address(&self, addr: &IpAddr) -> bool {
         match addr {
-            IpAddr::V4(_) => self == &Self::Both || self == &Self::Ipv4,
-            IpAddr::V6(_) => self == &Self::Both || self == &Self::Ipv6,
+            IpAddr::V4(_) => self.includes_ipv4(),
+            IpAddr::V6(_) => self.includes_ipv6(),
         }
     }
-}
 
-impl Default for QueryAddressType {
-    fn default() -> Self {
-        Self::Both
+    pub fn includes_ipv4(&self) -> bool {
+        self != &Self::Ipv6
+    }
+
+    pub fn includes_ipv6(&self) -> bool {
+        self != &Self::Ipv4
+    }
+
+    /// Returns the address type including the addresses of both types.
+    pub fn union(self, other: Self) -> Self {
+        if self == other {
+            self
+        } else {
+            Self::Both
+        }
     }
 }
 
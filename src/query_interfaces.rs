@@ -1,14 +1,23 @@
 use {
-    crate::config::Config,
+    crate::{config::Config, error::Route53IpUpdateError},
+    log::debug,
     network_interface::{Addr, NetworkInterface, NetworkInterfaceConfig},
-    std::net::IpAddr,
+    std::{collections::HashMap, net::IpAddr, time::Duration},
+    tokio::{process::Command, task::spawn_blocking},
     tower::BoxError,
 };
 
 pub(crate) async fn get_addresses_from_network_interfaces(config: &Config) -> Result<Vec<IpAddr>, BoxError> {
     let mut result = Vec::with_capacity(16);
+    let address_type = config.queried_address_type();
+
+    // Listing the interfaces blocks, so do it on a blocking thread that can be abandoned if it takes too long.
+    let interfaces =
+        match tokio::time::timeout(config.interfaces_timeout(), spawn_blocking(NetworkInterface::show)).await {
+            Ok(interfaces) => interfaces??,
+            Err(_) => return Err(Route53IpUpdateError::SourceTimedOut("the network interfaces".to_string()).into()),
+        };
 
-    let interfaces = NetworkInterface::show()?;
     for interface in interfaces {
         if config.allows_interface(&interface.name) {
             if let Some(addr) = interface.addr {
@@ -17,7 +26,7 @@ pub(crate) async fn get_addresses_from_network_interfaces(config: &Config) -> Re
                     Addr::V6(addr) => IpAddr::V6(addr.ip),
                 };
 
-                if config.allows_address(&addr) {
+                if address_type.allows_address(&addr) {
                     result.push(addr);
                 }
             }
@@ -26,3 +35,71 @@ pub(crate) async fn get_addresses_from_network_interfaces(config: &Config) -> Re
 
     Ok(result)
 }
+
+/// Returns the preferred lifetimes of the interface addresses, as reported by `ip -o addr show` on Linux. Addresses
+/// without a limited lifetime (e.g. those configured statically) have a lifetime of `Duration::MAX`. If the lifetimes
+/// can't be read within the timeout, the map is empty.
+pub(crate) async fn get_address_lifetimes(timeout: Duration) -> HashMap<IpAddr, Duration> {
+    let output = Command::new("ip").args(["-o", "addr", "show"]).kill_on_drop(true).output();
+    match tokio::time::timeout(timeout, output).await {
+        Ok(Ok(output)) if output.status.success() => parse_address_lifetimes(&String::from_utf8_lossy(&output.stdout)),
+        Ok(Ok(output)) => {
+            debug!("Unable to read address lifetimes: ip exited with {}", output.status);
+            HashMap::new()
+        }
+        Ok(Err(e)) => {
+            debug!("Unable to read address lifetimes: {e}");
+            HashMap::new()
+        }
+        Err(_) => {
+            debug!("Unable to read address lifetimes: ip took longer than {timeout:?}");
+            HashMap::new()
+        }
+    }
+}
+
+/// Parses the output of `ip -o addr show`, one address per line, e.g.
+/// `2: eth0    inet6 2001:db8::5/64 scope global dynamic \       valid_lft 86393sec preferred_lft 14393sec`.
+fn parse_address_lifetimes(output: &str) -> HashMap<IpAddr, Duration> {
+    let mut lifetimes = HashMap::new();
+    for line in output.lines() {
+        let mut words = line.split_whitespace();
+        let Some(address) = words.by_ref().skip_while(|word| !matches!(*word, "inet" | "inet6")).nth(1) else {
+            continue;
+        };
+
+        let Ok(address) = address.split('/').next().unwrap_or_default().parse::<IpAddr>() else {
+            continue;
+        };
+
+        let lifetime = match words.skip_while(|word| *word != "preferred_lft").nth(1) {
+            Some("forever") | None => Duration::MAX,
+            Some(lifetime) => match lifetime.trim_end_matches("sec").parse() {
+                Ok(secs) => Duration::from_secs(secs),
+                Err(_) => continue,
+            },
+        };
+
+        lifetimes.insert(address, lifetime);
+    }
+
+    lifetimes
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::parse_address_lifetimes, std::time::Duration};
+
+    #[test]
+    fn parses_address_lifetimes() {
+        let output = "\
+1: lo    inet 127.0.0.1/8 scope host lo\\       valid_lft forever preferred_lft forever
+2: eth0    inet6 2001:db8::5/64 scope global dynamic mngtmpaddr \\       valid_lft 86393sec preferred_lft 14393sec
+2: eth0    inet6 fe80::1/64 scope link \\       valid_lft forever preferred_lft 0sec
+";
+        let lifetimes = parse_address_lifetimes(output);
+        assert_eq!(lifetimes[&"127.0.0.1".parse().unwrap()], Duration::MAX);
+        assert_eq!(lifetimes[&"2001:db8::5".parse().unwrap()], Duration::from_secs(14393));
+        assert_eq!(lifetimes[&"fe80::1".parse().unwrap()], Duration::ZERO);
+    }
+}
@@ -1,6 +1,11 @@
 use {
+    crate::{
+        error::Route53IpUpdateError,
+        nat64::{Nat64Prefix, IPV4ONLY_ARPA},
+    },
+    humantime::format_duration,
     hyper::client::connect::dns::Name,
-    log::debug,
+    log::{debug, warn},
     once_cell::sync::Lazy,
     reqwest::{
         dns::{Addrs, Resolve, Resolving},
@@ -9,11 +14,13 @@ use {
     std::{
         error::Error,
         fmt::{Display, Formatter, Result as FmtResult},
+        future::Future,
         io::Error as IoError,
         net::{IpAddr, SocketAddr},
         sync::{Arc, Mutex},
         time::Duration,
     },
+    tokio::time::sleep,
     tower::BoxError,
     trust_dns_proto::xfer::dns_handle::DnsHandle,
     trust_dns_resolver::{
@@ -27,26 +34,94 @@ use {
 
 const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
 
-#[derive(Clone, Copy, Debug)]
-pub(crate) struct NoLocalAddressFound;
+/// How the IP service is reached: the lookup strategy, the timeout, and the NAT64 prefix to reach it through, if any.
+type ClientKey = (LookupIpStrategy, Duration, Option<Nat64Prefix>);
 
-impl Display for NoLocalAddressFound {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(f, "No suitable local address found")
+/// The HTTP clients used to query the IP service, one per lookup strategy, timeout, and NAT64 prefix. They're kept from
+/// one update to the next so their resolvers, connections, and TLS sessions are reused rather than set up again every
+/// cycle.
+#[derive(Debug, Default)]
+pub(crate) struct IpServiceClients {
+    clients: Mutex<Vec<(ClientKey, Client)>>,
+}
+
+impl IpServiceClients {
+    /// Returns the client for the lookup strategy, timeout, and NAT64 prefix, creating it if necessary.
+    fn get(&self, key: ClientKey) -> Result<Client, BoxError> {
+        let mut clients = self.clients.lock().expect("IP service clients lock poisoned");
+        if let Some((_, client)) = clients.iter().find(|(client_key, _)| *client_key == key) {
+            return Ok(client.clone());
+        }
+
+        let (lookup_ip_strategy, timeout, nat64) = key;
+        let resolver = Arc::new(QueryResolver::new(lookup_ip_strategy, nat64)?);
+        let client = Client::builder().dns_resolver(resolver).timeout(timeout).user_agent(USER_AGENT).build()?;
+        clients.push((key, client.clone()));
+        Ok(client)
     }
 }
 
-impl Error for NoLocalAddressFound {}
+/// Discovers the NAT64 prefix from the AAAA records a DNS64 resolver synthesizes for `ipv4only.arpa` (RFC 7050).
+pub(crate) async fn discover_nat64_prefix() -> Result<Nat64Prefix, BoxError> {
+    let (config, opts) = get_global_resolve_config()?;
+    let resolver = AsyncResolver::tokio(config, opts)?;
+    let addrs = match resolver.ipv6_lookup(IPV4ONLY_ARPA).await {
+        Ok(addrs) => addrs.iter().copied().collect(),
+        Err(e) => {
+            debug!("Unable to look up {IPV4ONLY_ARPA}: {e}");
+            Vec::new()
+        }
+    };
+
+    let prefix = Nat64Prefix::from_ipv4only_arpa(addrs).ok_or(Route53IpUpdateError::Nat64NotFound)?;
+    debug!("Discovered NAT64 prefix {prefix}");
+    Ok(prefix)
+}
 
-pub(crate) async fn get_address_from_ip_service(
+/// Queries the IP service, trying again up to `retries` more times if a query fails so a single dropped packet doesn't
+/// fail the whole update. The first retry waits for `backoff`, and each one after that waits twice as long as the last.
+pub(crate) async fn get_address_from_ip_service_with_retries(
+    clients: &IpServiceClients,
     ip_service: &str,
     timeout: Duration,
     lookup_ip_strategy: LookupIpStrategy,
+    nat64: Option<Nat64Prefix>,
+    retries: u32,
+    backoff: Duration,
 ) -> Result<Vec<IpAddr>, BoxError> {
-    let mut result = Vec::with_capacity(1);
-    let resolver = Arc::new(QueryResolver::new(lookup_ip_strategy)?);
-    let client = Client::builder().dns_resolver(resolver).timeout(timeout).user_agent(USER_AGENT).build()?;
+    let client = clients.get((lookup_ip_strategy, timeout, nat64))?;
+    with_retries(retries, backoff, || get_address_from_ip_service(&client, ip_service, lookup_ip_strategy)).await
+}
+
+/// Calls `f` until it succeeds or has been retried `retries` times, doubling the wait between calls each time.
+async fn with_retries<T, F, Fut>(retries: u32, backoff: Duration, mut f: F) -> Result<T, BoxError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, BoxError>>,
+{
+    let mut delay = backoff;
+    let mut retries_left = retries;
+
+    loop {
+        match f().await {
+            Ok(result) => return Ok(result),
+            Err(e) if retries_left > 0 => {
+                warn!("{e}; trying again in {}", format_duration(delay));
+                sleep(delay).await;
+                delay = delay.saturating_mul(2);
+                retries_left -= 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
 
+async fn get_address_from_ip_service(
+    client: &Client,
+    ip_service: &str,
+    lookup_ip_strategy: LookupIpStrategy,
+) -> Result<Vec<IpAddr>, BoxError> {
+    let mut result = Vec::with_capacity(1);
     debug!("Querying IP service at {ip_service} using strategy {lookup_ip_strategy:?}");
 
     let response = client.get(ip_service.to_string()).send().await?.error_for_status()?;
@@ -60,16 +135,20 @@ pub(crate) async fn get_address_from_ip_service(
 
 struct QueryResolver {
     wrapped: AsyncResolver<GenericConnection, GenericConnectionProvider<TokioRuntime>>,
+
+    /// The NAT64 prefix the IPv4 addresses are reached through, if any.
+    nat64: Option<Nat64Prefix>,
 }
 
 impl QueryResolver {
-    fn new(lookup_ip_strategy: LookupIpStrategy) -> Result<Self, BoxError> {
+    fn new(lookup_ip_strategy: LookupIpStrategy, nat64: Option<Nat64Prefix>) -> Result<Self, BoxError> {
         let (config, mut opts) = get_global_resolve_config()?;
         opts.ip_strategy = lookup_ip_strategy;
         let resolver = AsyncResolver::tokio(config, opts)?;
 
         Ok(Self {
             wrapped: resolver,
+            nat64,
         })
     }
 }
@@ -77,11 +156,17 @@ impl QueryResolver {
 impl Resolve for QueryResolver {
     fn resolve(&self, name: Name) -> Resolving {
         let wrapped = self.wrapped.clone();
-        Box::pin(resolve_name(wrapped, name))
+        Box::pin(resolve_name(wrapped, name, self.nat64))
     }
 }
 
-async fn resolve_name<C, P>(resolver: AsyncResolver<C, P>, name: Name) -> Result<Addrs, BoxError>
+/// Resolves a name, translating IPv4 addresses to their NAT64 addresses if a NAT64 prefix is given, so the IP service
+/// sees the NAT64's public IPv4 address.
+async fn resolve_name<C, P>(
+    resolver: AsyncResolver<C, P>,
+    name: Name,
+    nat64: Option<Nat64Prefix>,
+) -> Result<Addrs, BoxError>
 where
     C: DnsHandle<Error = ResolveError>,
     P: ConnectionProvider<Conn = C>,
@@ -89,6 +174,10 @@ where
     let addrs = resolver.lookup_ip(name.as_str()).await?;
     let mut result = Vec::new();
     for addr in addrs.iter() {
+        let addr = match (addr, nat64) {
+            (IpAddr::V4(addr), Some(nat64)) => IpAddr::V6(nat64.synthesize(addr)),
+            (addr, _) => addr,
+        };
         result.push(SocketAddr::new(addr, 0));
     }
 
@@ -123,3 +212,34 @@ fn get_global_resolve_config() -> Result<(ResolverConfig, ResolverOpts), Resolve
 
 static RESOLVE_CONFIG: Lazy<Mutex<Result<(ResolverConfig, ResolverOpts), IoError>>> =
     Lazy::new(|| Mutex::new(read_system_conf()));
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::with_retries,
+        std::{cell::Cell, time::Duration},
+        tower::BoxError,
+    };
+
+    #[tokio::test]
+    async fn retries_until_success_or_out_of_retries() {
+        let calls = Cell::new(0);
+        let flaky = || {
+            calls.set(calls.get() + 1);
+            let call = calls.get();
+            async move {
+                if call < 3 {
+                    Err::<u32, BoxError>("dropped".into())
+                } else {
+                    Ok(call)
+                }
+            }
+        };
+
+        assert_eq!(with_retries(2, Duration::from_millis(1), flaky).await.unwrap(), 3);
+
+        calls.set(0);
+        assert!(with_retries(1, Duration::from_millis(1), flaky).await.is_err());
+        assert_eq!(calls.get(), 2);
+    }
+}
@@ -0,0 +1,102 @@
+use {
+    once_cell::sync::Lazy,
+    std::{
+        sync::Mutex,
+        time::{Duration, Instant},
+    },
+    tokio::time::sleep,
+};
+
+/// Limits the Route 53 API calls made by every zone together, since Route 53 throttles an account making more than
+/// five requests per second.
+pub(crate) static ROUTE53_RATE_LIMITER: Lazy<RateLimiter> = Lazy::new(RateLimiter::default);
+
+/// A token bucket limiting how often requests are made. Tokens are added at `rate` per second, up to a burst of `rate`
+/// tokens (but at least one), and each request takes one, waiting for it if there are none left.
+#[derive(Debug, Default)]
+pub(crate) struct RateLimiter {
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug, Default)]
+struct Bucket {
+    /// The requests allowed per second; 0 means unlimited.
+    rate: f64,
+
+    /// The tokens available. This goes negative when requests are waiting, so each one waits its turn.
+    tokens: f64,
+
+    /// When the tokens were last topped up.
+    updated: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Sets the requests allowed per second; 0 allows any number.
+    pub(crate) fn set_rate(&self, rate: f64) {
+        let mut bucket = self.bucket.lock().expect("rate limiter lock poisoned");
+        bucket.rate = rate.max(0.0);
+        bucket.tokens = bucket.tokens.min(bucket.burst());
+    }
+
+    /// Waits until a request may be made.
+    pub(crate) async fn acquire(&self) {
+        let wait = self.bucket.lock().expect("rate limiter lock poisoned").take(Instant::now());
+        if let Some(wait) = wait {
+            sleep(wait).await;
+        }
+    }
+}
+
+impl Bucket {
+    fn burst(&self) -> f64 {
+        self.rate.max(1.0)
+    }
+
+    /// Takes a token, returning how long to wait for it if none is available yet.
+    fn take(&mut self, now: Instant) -> Option<Duration> {
+        if self.rate == 0.0 {
+            return None;
+        }
+
+        let elapsed = self.updated.map_or(Duration::MAX, |updated| now.saturating_duration_since(updated));
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(self.burst());
+        self.updated = Some(now);
+        self.tokens -= 1.0;
+
+        (self.tokens < 0.0).then(|| Duration::from_secs_f64(-self.tokens / self.rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::Bucket,
+        std::time::{Duration, Instant},
+    };
+
+    #[test]
+    fn requests_beyond_the_burst_wait_their_turn() {
+        let mut bucket = Bucket {
+            rate: 5.0,
+            ..Bucket::default()
+        };
+        let start = Instant::now();
+
+        for _ in 0..5 {
+            assert_eq!(bucket.take(start), None);
+        }
+
+        assert_eq!(bucket.take(start), Some(Duration::from_millis(200)));
+        assert_eq!(bucket.take(start), Some(Duration::from_millis(400)));
+
+        // After two seconds, the waiting requests have gone and the bucket has refilled.
+        assert_eq!(bucket.take(start + Duration::from_secs(2)), None);
+    }
+
+    #[test]
+    fn zero_rate_is_unlimited() {
+        let mut bucket = Bucket::default();
+        let now = Instant::now();
+        assert!((0..100).all(|_| bucket.take(now).is_none()));
+    }
+}
@@ -0,0 +1,153 @@
+use {
+    crate::{address_source::DiscoveredAddress, config::RelayConfig, error::Route53IpUpdateError, stun::NatType},
+    log::{info, warn},
+    reqwest::{header::CONTENT_TYPE, Client},
+    ring::hmac,
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::HashSet,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tower::BoxError,
+};
+
+/// The header carrying the hex-encoded HMAC-SHA256 signature of a relay report's body.
+pub(crate) const SIGNATURE_HEADER: &str = "x-route53-ip-update-signature";
+
+/// How far a report's timestamp may be from the server's clock before it is rejected as a possible replay.
+pub(crate) const MAX_CLOCK_SKEW: Duration = Duration::from_secs(300);
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// The addresses discovered by an agent, as sent to the relay server.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct RelayReport {
+    /// The name of the agent sending the report.
+    pub(crate) agent: String,
+
+    /// When the report was made, in seconds since the Unix epoch.
+    pub(crate) timestamp: u64,
+
+    /// The addresses discovered by the agent.
+    pub(crate) addresses: Vec<DiscoveredAddress>,
+
+    /// The NAT the agent is behind, if it detected it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) nat: Option<NatType>,
+}
+
+/// The relay server's reply to a report.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct RelayReply {
+    pub(crate) results: Vec<RelayHostnameResult>,
+}
+
+/// The outcome of updating one of the agent's hostnames.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct RelayHostnameResult {
+    pub(crate) hostname: String,
+
+    /// `good` if the records were changed, `nochg` if they were already up-to-date, or `error`.
+    pub(crate) status: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) message: Option<String>,
+}
+
+/// Signs a report body with the shared secret, returning the hex-encoded signature.
+pub(crate) fn sign_report(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hex::encode(hmac::sign(&key, body).as_ref())
+}
+
+/// Verifies the signature of a report body in constant time.
+pub(crate) fn verify_report(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature.trim()) else {
+        return false;
+    };
+
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hmac::verify(&key, body, &signature).is_ok()
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+pub(crate) fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+/// Sends the discovered addresses to the relay server, which updates the agent's hostnames.
+pub(crate) async fn send_relay_report(
+    relay: &RelayConfig,
+    discovered: &HashSet<DiscoveredAddress>,
+    nat: Option<NatType>,
+    timeout: Duration,
+) -> Result<(), BoxError> {
+    let mut addresses: Vec<DiscoveredAddress> = discovered.iter().copied().collect();
+    addresses.sort();
+
+    let report = RelayReport {
+        agent: relay.agent.clone(),
+        timestamp: unix_time(),
+        addresses,
+        nat,
+    };
+    let body = serde_json::to_vec(&report)?;
+    let signature = sign_report(&relay.secret, &body);
+
+    let client = Client::builder().timeout(timeout).user_agent(USER_AGENT).build()?;
+    let response = client
+        .post(&relay.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, signature)
+        .body(body)
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        return Err(Route53IpUpdateError::RelayFailed(format!("{status}: {}", text.trim())).into());
+    }
+
+    let reply: RelayReply = serde_json::from_slice(&response.bytes().await?)?;
+    let mut failed = Vec::new();
+    for result in reply.results {
+        match result.status.as_str() {
+            "good" => info!("Relay server updated {}", result.hostname),
+            "nochg" => info!("Relay server reports {} is up-to-date", result.hostname),
+            _ => {
+                let message = result.message.unwrap_or(result.status);
+                warn!("Relay server failed to update {}: {message}", result.hostname);
+                failed.push(format!("{}: {message}", result.hostname));
+            }
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(Route53IpUpdateError::RelayFailed(failed.join(", ")).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign_report, verify_report};
+
+    #[test]
+    fn verifies_signed_reports() {
+        let body = br#"{"agent":"office","timestamp":1700000000,"addresses":[]}"#;
+        let signature = sign_report("k3y", body);
+        assert!(verify_report("k3y", body, &signature));
+        assert!(verify_report("k3y", body, &format!(" {signature}\n")));
+        assert!(verify_report("k3y", body, &signature.to_uppercase()));
+
+        assert!(!verify_report("other", body, &signature));
+        assert!(!verify_report("k3y", br#"{"agent":"office","timestamp":1700000001,"addresses":[]}"#, &signature));
+        assert!(!verify_report("k3y", body, &signature[2..]));
+        assert!(!verify_report("k3y", body, "not hex"));
+    }
+}
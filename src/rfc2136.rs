@@ -0,0 +1,434 @@
+use {
+    crate::{
+        config::{Config, HostnameConfig, Rfc2136Config, Route53ZoneConfig},
+        diff::{RecordSetDiff, RecordSetSummary},
+        error::Route53IpUpdateError,
+        provider::{
+            publish_targets, timestamp_record_hostnames, timestamp_record_name, timestamp_record_text, DnsProvider,
+        },
+        ttl::Ttl,
+    },
+    async_trait::async_trait,
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    log::debug,
+    std::{
+        collections::HashSet,
+        net::IpAddr,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tokio::{net::UdpSocket, time::timeout},
+    tower::BoxError,
+    trust_dns_proto::{
+        op::{Message, MessageType, OpCode, Query, ResponseCode},
+        rr::{
+            dnssec::rdata::tsig::{make_tsig_record, message_tbs, TsigAlgorithm, TSIG},
+            rdata::TXT,
+            DNSClass, Name, RData, Record, RecordType,
+        },
+    },
+};
+
+const DEFAULT_TTL: Ttl = Ttl::from_seconds(300);
+
+/// How far the nameserver's clock may be from ours when checking the TSIG signature, in seconds.
+const TSIG_FUDGE: u16 = 300;
+
+/// The largest UDP reply we accept.
+const MAX_REPLY_LEN: usize = 4096;
+
+/// An UPDATE message bringing a zone's hostnames up-to-date.
+pub(crate) struct Rfc2136Plan {
+    message: Message,
+    zone_id: String,
+    hostnames: Vec<String>,
+    diffs: Vec<RecordSetDiff>,
+}
+
+/// Publishes hostnames by sending RFC 2136 UPDATE messages, signed with TSIG, to the zone's nameserver.
+pub(crate) struct Rfc2136Provider {
+    rfc2136: Rfc2136Config,
+
+    /// How long to wait for the nameserver to reply.
+    timeout: Duration,
+}
+
+impl Rfc2136Provider {
+    pub(crate) fn new(rfc2136: Rfc2136Config, timeout: Duration) -> Self {
+        Self {
+            rfc2136,
+            timeout,
+        }
+    }
+
+    /// Returns the update records needed to bring a hostname up-to-date: for each address family that differs, the
+    /// existing record set is deleted and the desired records are added, so the whole change is applied atomically. A
+    /// summary of each change is added to `diffs`. Address families whose desired addresses are `None` aren't managed
+    /// and are left as they are.
+    async fn get_changes_for_hostname(
+        &self,
+        hostname: &Name,
+        hostname_config: &HostnameConfig,
+        desired_ipv4: Option<&HashSet<IpAddr>>,
+        desired_ipv6: Option<&HashSet<IpAddr>>,
+        default_ttl: Ttl,
+        diffs: &mut Vec<RecordSetDiff>,
+    ) -> Result<Vec<Record>, BoxError> {
+        let mut changes = Vec::new();
+        let families = [
+            (RecordType::A, desired_ipv4, hostname_config.get_ipv4_ttl().unwrap_or(default_ttl)),
+            (RecordType::AAAA, desired_ipv6, hostname_config.get_ipv6_ttl().unwrap_or(default_ttl)),
+        ];
+
+        for (record_type, desired, ttl) in families {
+            let Some(desired) = desired else {
+                continue;
+            };
+
+            let ttl = u32::try_from(i64::from(ttl)).unwrap_or(u32::MAX);
+            let (existing, existing_ttl) = self.query_addresses(hostname, record_type).await?;
+            debug!("Hostname {hostname} has {record_type} records {existing:?} with TTL {existing_ttl:?}");
+
+            if &existing == desired && (desired.is_empty() || existing_ttl == Some(ttl)) {
+                continue;
+            }
+
+            diffs.push(RecordSetDiff {
+                hostname: hostname.to_string(),
+                record_type: record_type.to_string(),
+                old: (!existing.is_empty()).then(|| RecordSetSummary::new(existing_ttl.map(i64::from), &existing)),
+                new: (!desired.is_empty()).then(|| RecordSetSummary::new(Some(ttl.into()), desired)),
+            });
+
+            // Delete the RRset: class ANY, TTL 0, no data (RFC 2136 section 2.5.2).
+            let mut delete = Record::with(hostname.clone(), record_type, 0);
+            delete.set_dns_class(DNSClass::ANY);
+            changes.push(delete);
+
+            for address in desired {
+                let rdata = match address {
+                    IpAddr::V4(address) => RData::A(*address),
+                    IpAddr::V6(address) => RData::AAAA(*address),
+                };
+                changes.push(Record::from_rdata(hostname.clone(), ttl, rdata));
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Asks the nameserver for a hostname's records of one address type, returning the addresses and their TTL.
+    async fn query_addresses(
+        &self,
+        hostname: &Name,
+        record_type: RecordType,
+    ) -> Result<(HashSet<IpAddr>, Option<u32>), BoxError> {
+        let mut message = Message::new();
+        message
+            .set_id(rand::random())
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Query)
+            .set_recursion_desired(false)
+            .add_query(Query::query(hostname.clone(), record_type));
+
+        let reply = self.exchange(&message).await?;
+        if !matches!(reply.response_code(), ResponseCode::NoError | ResponseCode::NXDomain) {
+            return Err(
+                Route53IpUpdateError::DnsUpdateFailed(hostname.to_string(), reply.response_code().to_string()).into()
+            );
+        }
+
+        let mut addresses = HashSet::new();
+        let mut ttl = None;
+        for record in reply.answers() {
+            if record.name() != hostname || record.record_type() != record_type {
+                continue;
+            }
+
+            match record.data() {
+                Some(RData::A(address)) => addresses.insert(IpAddr::V4(*address)),
+                Some(RData::AAAA(address)) => addresses.insert(IpAddr::V6(*address)),
+                _ => continue,
+            };
+            ttl = Some(record.ttl());
+        }
+
+        Ok((addresses, ttl))
+    }
+
+    /// Sends a message to the nameserver over UDP and waits for the matching reply.
+    async fn exchange(&self, message: &Message) -> Result<Message, BoxError> {
+        let bind_addr = if self.rfc2136.server.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(bind_addr).await?;
+        socket.connect(self.rfc2136.server).await?;
+        socket.send(&message.to_vec()?).await?;
+
+        let mut buf = vec![0; MAX_REPLY_LEN];
+        timeout(self.timeout, async {
+            loop {
+                let len = socket.recv(&mut buf).await?;
+                let reply = Message::from_vec(&buf[..len])?;
+                if reply.id() == message.id() {
+                    return Ok(reply);
+                }
+            }
+        })
+        .await
+        .map_err(|_| Route53IpUpdateError::DnsUpdateFailed(self.rfc2136.server.to_string(), "timed out".to_string()))?
+    }
+}
+
+#[async_trait]
+impl DnsProvider for Rfc2136Provider {
+    type Plan = Rfc2136Plan;
+
+    /// The nameserver applies an update before replying, so there is nothing to wait for.
+    type Pending = ();
+
+    async fn list(&self, hostname: &str) -> Result<(HashSet<IpAddr>, HashSet<IpAddr>), BoxError> {
+        let hostname = Name::from_ascii(hostname)?;
+        let (ipv4, _) = self.query_addresses(&hostname, RecordType::A).await?;
+        let (ipv6, _) = self.query_addresses(&hostname, RecordType::AAAA).await?;
+        Ok((ipv4, ipv6))
+    }
+
+    async fn plan(
+        &self,
+        config: &Config,
+        zone_config: &Route53ZoneConfig,
+        desired_ipv4: &HashSet<IpAddr>,
+        desired_ipv6: &HashSet<IpAddr>,
+    ) -> Result<Option<Rfc2136Plan>, BoxError> {
+        let zone_name = Name::from_ascii(&zone_config.zone_id)?;
+        let default_ttl = zone_config.ttl.or(config.ttl).unwrap_or(DEFAULT_TTL);
+        let mut message = update_message(&zone_name);
+        let mut hostnames = Vec::new();
+        let mut diffs = Vec::new();
+
+        for target in publish_targets(zone_config, desired_ipv4, desired_ipv6) {
+            let hostname_config = &target.hostname_config;
+            let hostname = Name::from_ascii(hostname_config.get_hostname())?;
+            let changes = self
+                .get_changes_for_hostname(&hostname, hostname_config, target.ipv4, target.ipv6, default_ttl, &mut diffs)
+                .await?;
+
+            if !changes.is_empty() {
+                message.add_name_servers(changes);
+                hostnames.push(hostname_config.get_hostname().to_string());
+            }
+        }
+
+        let timestamp_hostnames = timestamp_record_hostnames(config, zone_config, &diffs);
+        if hostnames.is_empty() && timestamp_hostnames.is_empty() {
+            return Ok(None);
+        }
+
+        let timestamp_ttl = u32::try_from(i64::from(default_ttl)).unwrap_or(u32::MAX);
+        for hostname in timestamp_hostnames {
+            let name = Name::from_ascii(timestamp_record_name(&hostname))?;
+            let mut delete = Record::with(name.clone(), RecordType::TXT, 0);
+            delete.set_dns_class(DNSClass::ANY);
+            message.add_name_server(delete);
+            message.add_name_server(Record::from_rdata(
+                name,
+                timestamp_ttl,
+                RData::TXT(TXT::new(vec![timestamp_record_text()])),
+            ));
+        }
+
+        Ok(Some(Rfc2136Plan {
+            message,
+            zone_id: zone_config.zone_id.clone(),
+            hostnames,
+            diffs,
+        }))
+    }
+
+    fn describe<'a>(&self, plan: &'a Rfc2136Plan) -> &'a [RecordSetDiff] {
+        &plan.diffs
+    }
+
+    async fn apply(&self, plan: Rfc2136Plan) -> Result<(), BoxError> {
+        let Rfc2136Plan {
+            mut message,
+            zone_id,
+            hostnames,
+            ..
+        } = plan;
+
+        debug!("Sending update for hostnames {} in zone {zone_id}", hostnames.join(", "));
+        sign_message(&mut message, &self.rfc2136)?;
+        let reply = self.exchange(&message).await?;
+        match reply.response_code() {
+            ResponseCode::NoError => Ok(()),
+            code => Err(Route53IpUpdateError::DnsUpdateFailed(zone_id, code.to_string()).into()),
+        }
+    }
+
+    async fn wait(&self, _pending: ()) -> Result<(), BoxError> {
+        Ok(())
+    }
+}
+
+/// Creates an empty UPDATE message for the zone.
+fn update_message(zone_name: &Name) -> Message {
+    let mut zone = Query::query(zone_name.clone(), RecordType::SOA);
+    zone.set_query_class(DNSClass::IN);
+
+    let mut message = Message::new();
+    message.set_id(rand::random()).set_message_type(MessageType::Query).set_op_code(OpCode::Update).add_query(zone);
+    message
+}
+
+/// Adds a TSIG record (RFC 8945) signing the message with the zone's key.
+fn sign_message(message: &mut Message, rfc2136: &Rfc2136Config) -> Result<(), BoxError> {
+    let key_name = Name::from_ascii(&rfc2136.key_name)?;
+    let algorithm = TsigAlgorithm::from_name(Name::from_ascii(&rfc2136.key_algorithm)?);
+    if !algorithm.supported() {
+        return Err(Route53IpUpdateError::UnsupportedTsigAlgorithm(rfc2136.key_algorithm.clone()).into());
+    }
+
+    let key = BASE64.decode(rfc2136.key_secret.trim())?;
+    let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let pre_tsig = TSIG::new(algorithm, time, TSIG_FUDGE, Vec::new(), message.id(), 0, Vec::new());
+    let tbs = message_tbs(None, message, &pre_tsig, &key_name)?;
+    let mac = pre_tsig.algorithm().mac_data(&key, &tbs)?;
+    message.add_tsig(make_tsig_record(key_name, pre_tsig.set_mac(mac)));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{sign_message, update_message, Rfc2136Provider},
+        crate::{
+            config::{Config, Route53ZoneConfig},
+            provider::DnsProvider,
+        },
+        std::{
+            collections::HashSet,
+            net::{IpAddr, SocketAddr},
+            sync::{Arc, Mutex},
+            time::Duration,
+        },
+        tokio::net::UdpSocket,
+        trust_dns_proto::{
+            op::{Message, MessageType, OpCode, ResponseCode},
+            rr::{dnssec::rdata::DNSSECRData, DNSClass, Name, RData, Record, RecordType},
+        },
+    };
+
+    /// Starts a nameserver on localhost that answers queries with `records` and accepts every update, recording them.
+    async fn nameserver(records: Vec<Record>) -> (SocketAddr, Arc<Mutex<Vec<Message>>>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let received = updates.clone();
+
+        tokio::spawn(async move {
+            let mut buf = vec![0; 4096];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                let request = Message::from_vec(&buf[..len]).unwrap();
+                let mut reply = Message::new();
+                reply
+                    .set_id(request.id())
+                    .set_message_type(MessageType::Response)
+                    .set_op_code(request.op_code())
+                    .set_response_code(ResponseCode::NoError);
+
+                if request.op_code() == OpCode::Update {
+                    received.lock().unwrap().push(request);
+                } else {
+                    let query = &request.queries()[0];
+                    let answers = records.iter().filter(|r| r.record_type() == query.query_type()).map(|r| {
+                        let mut answer = r.clone();
+                        answer.set_name(query.name().clone());
+                        answer
+                    });
+                    reply.add_query(query.clone()).add_answers(answers.collect::<Vec<_>>());
+                }
+
+                socket.send_to(&reply.to_vec().unwrap(), peer).await.unwrap();
+            }
+        });
+
+        (addr, updates)
+    }
+
+    fn zone(server: SocketAddr, key_algorithm: &str) -> Route53ZoneConfig {
+        serde_yaml::from_str(&format!(
+            "zone-id: example.com.\nhostnames: [home.example.com]\nrfc2136: {{server: '{server}', key-name: update-key, \
+             key-algorithm: {key_algorithm}, key-secret: c2VjcmV0LWtleS1ieXRlcw==}}"
+        ))
+        .unwrap()
+    }
+
+    fn addrs(addresses: &[&str]) -> HashSet<IpAddr> {
+        addresses.iter().map(|a| a.parse().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn sends_signed_updates_replacing_changed_record_sets() {
+        let existing = Record::from_rdata(Name::root(), 300, RData::A("192.0.2.1".parse().unwrap()));
+        let (server, updates) = nameserver(vec![existing]).await;
+        let zone = zone(server, "hmac-sha256");
+        let provider = Rfc2136Provider::new(zone.rfc2136.clone().unwrap(), Duration::from_secs(5));
+
+        let (ipv4, ipv6) = provider.list("home.example.com").await.unwrap();
+        assert_eq!((ipv4, ipv6), (addrs(&["192.0.2.1"]), HashSet::new()));
+
+        let plan =
+            provider.plan(&Config::default(), &zone, &addrs(&["192.0.2.2"]), &HashSet::new()).await.unwrap().unwrap();
+        assert_eq!(provider.describe(&plan).len(), 1);
+        assert_eq!(provider.describe(&plan)[0].record_type, "A");
+        provider.apply(plan).await.unwrap();
+
+        let updates = updates.lock().unwrap();
+        assert_eq!(updates.len(), 1);
+        let update = &updates[0];
+        assert_eq!(update.queries()[0].name(), &Name::from_ascii("example.com.").unwrap());
+        assert_eq!(update.queries()[0].query_type(), RecordType::SOA);
+
+        // The A record set is deleted (class ANY, no data), then the new address is added.
+        let records = update.name_servers();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].record_type(), RecordType::A);
+        assert_eq!(records[0].dns_class(), DNSClass::ANY);
+        assert_eq!(records[0].ttl(), 0);
+        assert!(records[0].data().is_none());
+        assert_eq!(records[1].dns_class(), DNSClass::IN);
+        assert_eq!(records[1].ttl(), 300);
+        assert_eq!(records[1].data(), Some(&RData::A("192.0.2.2".parse().unwrap())));
+
+        let signature = update.signature();
+        assert_eq!(signature.len(), 1);
+        assert_eq!(signature[0].name(), &Name::from_ascii("update-key").unwrap());
+        let Some(RData::DNSSEC(DNSSECRData::TSIG(tsig))) = signature[0].data() else {
+            panic!("expected a TSIG record, got {:?}", signature[0]);
+        };
+        assert!(!tsig.mac().is_empty());
+    }
+
+    #[tokio::test]
+    async fn plans_nothing_when_records_are_current() {
+        let existing = Record::from_rdata(Name::root(), 300, RData::A("192.0.2.1".parse().unwrap()));
+        let (server, _) = nameserver(vec![existing]).await;
+        let zone = zone(server, "hmac-sha256");
+        let provider = Rfc2136Provider::new(zone.rfc2136.clone().unwrap(), Duration::from_secs(5));
+
+        let plan = provider.plan(&Config::default(), &zone, &addrs(&["192.0.2.1"]), &HashSet::new()).await.unwrap();
+        assert!(plan.is_none());
+    }
+
+    #[test]
+    fn rejects_unsupported_tsig_algorithms() {
+        let zone = zone("127.0.0.1:53".parse().unwrap(), "hmac-md5");
+        let mut message = update_message(&Name::from_ascii("example.com.").unwrap());
+        assert!(sign_message(&mut message, zone.rfc2136.as_ref().unwrap()).is_err());
+        assert!(message.signature().is_empty());
+    }
+}
@@ -0,0 +1,375 @@
+use {
+    crate::{
+        error::{ClassifiedError, Route53IpUpdateError},
+        rate_limit::ROUTE53_RATE_LIMITER,
+    },
+    async_trait::async_trait,
+    aws_sdk_route53::{
+        error::{ChangeResourceRecordSetsError, ChangeResourceRecordSetsErrorKind},
+        model::{ChangeBatch, ChangeInfo, ResourceRecordSet, RrType},
+        types::SdkError,
+        Client as Route53Client,
+    },
+    tower::BoxError,
+};
+
+/// A page of record sets returned by ListResourceRecordSets.
+#[derive(Debug, Default)]
+pub(crate) struct RecordSetPage {
+    /// The record sets on this page, in Route 53's order.
+    pub(crate) record_sets: Vec<ResourceRecordSet>,
+
+    /// The name and type of the first record set on the next page, if the listing was truncated.
+    pub(crate) next: Option<(String, RrType)>,
+}
+
+/// The Route 53 operations used to update records, so the planning and propagation logic can run against something
+/// other than the AWS SDK client.
+#[async_trait]
+pub(crate) trait Route53Api: Clone + Send + Sync {
+    /// Lists a zone's record sets, starting at the given name and type, or at the start of the zone if `None`.
+    async fn list_resource_record_sets(
+        &self,
+        zone_id: &str,
+        start: Option<(&str, RrType)>,
+    ) -> Result<RecordSetPage, BoxError>;
+
+    /// Submits a batch of changes to a zone, returning the status of the change. If Route 53 rejects the batch as
+    /// invalid, the error is a [`Route53IpUpdateError::ChangeBatchRejected`] with Route 53's messages.
+    async fn change_resource_record_sets(
+        &self,
+        zone_id: &str,
+        change_batch: ChangeBatch,
+    ) -> Result<ChangeInfo, BoxError>;
+
+    /// Returns the current status of a change.
+    async fn get_change(&self, change_id: &str) -> Result<ChangeInfo, BoxError>;
+
+    /// Returns the values Route 53's name servers answer with for a record, as reported by TestDNSAnswer.
+    async fn test_dns_answer(&self, zone_id: &str, name: &str, rr_type: RrType) -> Result<Vec<String>, BoxError>;
+}
+
+/// Lists every record set in a zone.
+pub(crate) async fn list_zone_record_sets<R: Route53Api>(
+    route53: &R,
+    zone_id: &str,
+) -> Result<Vec<ResourceRecordSet>, BoxError> {
+    let mut record_sets = Vec::new();
+    let mut start: Option<(String, RrType)> = None;
+
+    loop {
+        let start_ref = start.as_ref().map(|(name, rr_type)| (name.as_str(), rr_type.clone()));
+        let page = route53.list_resource_record_sets(zone_id, start_ref).await?;
+        record_sets.extend(page.record_sets);
+        match page.next {
+            None => return Ok(record_sets),
+            next => start = next,
+        }
+    }
+}
+
+#[async_trait]
+impl Route53Api for Route53Client {
+    async fn list_resource_record_sets(
+        &self,
+        zone_id: &str,
+        start: Option<(&str, RrType)>,
+    ) -> Result<RecordSetPage, BoxError> {
+        let (start_record_name, start_record_type) = start.unzip();
+        ROUTE53_RATE_LIMITER.acquire().await;
+        let result = self
+            .list_resource_record_sets()
+            .hosted_zone_id(zone_id)
+            .set_start_record_name(start_record_name.map(String::from))
+            .set_start_record_type(start_record_type)
+            .send()
+            .await
+            .map_err(|e| match e {
+                SdkError::ServiceError {
+                    err,
+                    ..
+                } if err.is_no_such_hosted_zone() => no_such_hosted_zone(zone_id),
+                e => ClassifiedError::from_sdk(e).into(),
+            })?;
+
+        let next = match (result.is_truncated(), result.next_record_name(), result.next_record_type()) {
+            (false, _, _) => None,
+            (true, Some(name), Some(rr_type)) => Some((name.to_string(), rr_type.clone())),
+            (true, None, _) => Err(Route53IpUpdateError::MissingExpectedAwsReplyField("NextRecordName".to_string()))?,
+            (true, _, None) => Err(Route53IpUpdateError::MissingExpectedAwsReplyField("NextRecordType".to_string()))?,
+        };
+
+        Ok(RecordSetPage {
+            record_sets: result.resource_record_sets.unwrap_or_default(),
+            next,
+        })
+    }
+
+    async fn change_resource_record_sets(
+        &self,
+        zone_id: &str,
+        change_batch: ChangeBatch,
+    ) -> Result<ChangeInfo, BoxError> {
+        ROUTE53_RATE_LIMITER.acquire().await;
+        let result = self
+            .change_resource_record_sets()
+            .hosted_zone_id(zone_id)
+            .change_batch(change_batch)
+            .send()
+            .await
+            .map_err(|e| change_batch_error(zone_id, e))?;
+        Ok(result
+            .change_info
+            .ok_or_else(|| Route53IpUpdateError::MissingExpectedAwsReplyField("ChangeInfo".to_string()))?)
+    }
+
+    async fn get_change(&self, change_id: &str) -> Result<ChangeInfo, BoxError> {
+        ROUTE53_RATE_LIMITER.acquire().await;
+        let result = self.get_change().id(change_id).send().await.map_err(ClassifiedError::from_sdk)?;
+        Ok(result
+            .change_info
+            .ok_or_else(|| Route53IpUpdateError::MissingExpectedAwsReplyField("ChangeInfo".to_string()))?)
+    }
+
+    async fn test_dns_answer(&self, zone_id: &str, name: &str, rr_type: RrType) -> Result<Vec<String>, BoxError> {
+        ROUTE53_RATE_LIMITER.acquire().await;
+        let result = self
+            .test_dns_answer()
+            .hosted_zone_id(zone_id)
+            .record_name(name)
+            .record_type(rr_type)
+            .send()
+            .await
+            .map_err(ClassifiedError::from_sdk)?;
+        Ok(result.record_data.unwrap_or_default())
+    }
+}
+
+/// Extracts the per-change messages from an InvalidChangeBatch error.
+fn change_batch_error(zone_id: &str, error: SdkError<ChangeResourceRecordSetsError>) -> BoxError {
+    match error {
+        SdkError::ServiceError {
+            err,
+            ..
+        } if err.is_no_such_hosted_zone() => no_such_hosted_zone(zone_id),
+        SdkError::ServiceError {
+            err:
+                ChangeResourceRecordSetsError {
+                    kind: ChangeResourceRecordSetsErrorKind::InvalidChangeBatch(invalid),
+                    ..
+                },
+            ..
+        } => {
+            let messages = match (invalid.messages(), invalid.message()) {
+                (Some(messages), _) if !messages.is_empty() => messages.to_vec(),
+                (_, Some(message)) => vec![message.to_string()],
+                _ => vec!["InvalidChangeBatch".to_string()],
+            };
+            Route53IpUpdateError::ChangeBatchRejected(messages).into()
+        }
+        error => ClassifiedError::from_sdk(error).into(),
+    }
+}
+
+/// Returns the error for a hosted zone that doesn't exist. Suggestions are added by
+/// [`crate::zones::suggest_hosted_zones`], which has the zone's hostnames to go on.
+fn no_such_hosted_zone(zone_id: &str) -> BoxError {
+    Route53IpUpdateError::NoSuchHostedZone(zone_id.to_string(), Vec::new()).into()
+}
+
+/// An in-memory stand-in for a Route 53 hosted zone.
+#[cfg(test)]
+pub(crate) mod fake {
+    use {
+        super::{RecordSetPage, Route53Api},
+        crate::error::Route53IpUpdateError,
+        async_trait::async_trait,
+        aws_sdk_route53::model::{
+            ChangeAction, ChangeBatch, ChangeInfo, ChangeStatus, ResourceRecord, ResourceRecordSet, RrType,
+        },
+        std::{
+            collections::BTreeMap,
+            sync::{Arc, Mutex},
+        },
+        tower::BoxError,
+    };
+
+    /// Record sets are keyed by name, type, and set identifier, so they're listed in a stable order.
+    type RecordSetKey = (String, String, String);
+
+    #[derive(Debug, Default)]
+    pub(crate) struct FakeZone {
+        /// The record sets in the zone.
+        pub(crate) record_sets: BTreeMap<RecordSetKey, ResourceRecordSet>,
+
+        /// The most record sets returned by one listing call, or 0 for no limit.
+        pub(crate) page_size: usize,
+
+        /// How many times GetChange reports a change as pending before reporting it in sync.
+        pub(crate) pending_polls: usize,
+
+        /// The change batches submitted, in order.
+        pub(crate) batches: Vec<ChangeBatch>,
+
+        /// How many times GetChange has been called.
+        pub(crate) get_change_calls: usize,
+
+        /// If set, what TestDNSAnswer answers for every record, in place of the values in the zone.
+        pub(crate) test_dns_answers: Option<Vec<String>>,
+    }
+
+    #[derive(Clone, Debug, Default)]
+    pub(crate) struct FakeRoute53 {
+        pub(crate) zone: Arc<Mutex<FakeZone>>,
+    }
+
+    impl FakeRoute53 {
+        pub(crate) fn with_record_sets(record_sets: impl IntoIterator<Item = ResourceRecordSet>) -> Self {
+            let fake = Self::default();
+            {
+                let mut zone = fake.zone.lock().unwrap();
+                for rrs in record_sets {
+                    let rrs = normalize(rrs);
+                    zone.record_sets.insert(key(&rrs), rrs);
+                }
+            }
+            fake
+        }
+
+        /// Returns the record sets in the zone, in listing order.
+        pub(crate) fn record_sets(&self) -> Vec<ResourceRecordSet> {
+            self.zone.lock().unwrap().record_sets.values().cloned().collect()
+        }
+    }
+
+    /// Returns a record set with one value and a TTL of 300 seconds.
+    pub(crate) fn rrs(name: &str, rr_type: RrType, value: &str) -> ResourceRecordSet {
+        ResourceRecordSet::builder()
+            .name(name)
+            .r#type(rr_type)
+            .ttl(300)
+            .resource_records(ResourceRecord::builder().value(value).build())
+            .build()
+    }
+
+    #[async_trait]
+    impl Route53Api for FakeRoute53 {
+        async fn list_resource_record_sets(
+            &self,
+            _zone_id: &str,
+            start: Option<(&str, RrType)>,
+        ) -> Result<RecordSetPage, BoxError> {
+            let zone = self.zone.lock().unwrap();
+            let start = start.map_or_else(Default::default, |(name, rr_type)| {
+                (absolute(name), rr_type.as_str().to_string(), String::new())
+            });
+            let mut remaining = zone.record_sets.range(start..).map(|(_, rrs)| rrs.clone());
+            let page_size = if zone.page_size == 0 {
+                usize::MAX
+            } else {
+                zone.page_size
+            };
+
+            let record_sets: Vec<_> = remaining.by_ref().take(page_size).collect();
+            let next = remaining.next().map(|rrs| (rrs.name().unwrap().to_string(), rrs.r#type().unwrap().clone()));
+
+            Ok(RecordSetPage {
+                record_sets,
+                next,
+            })
+        }
+
+        async fn change_resource_record_sets(
+            &self,
+            _zone_id: &str,
+            change_batch: ChangeBatch,
+        ) -> Result<ChangeInfo, BoxError> {
+            let mut zone = self.zone.lock().unwrap();
+
+            // Like Route 53, apply all of the changes or none of them.
+            let mut record_sets = zone.record_sets.clone();
+            for change in change_batch.changes().unwrap_or_default() {
+                let rrs = normalize(change.resource_record_set().unwrap().clone());
+                let key = key(&rrs);
+                match change.action().unwrap() {
+                    ChangeAction::Create if record_sets.contains_key(&key) => {
+                        let message = format!(
+                            "Tried to create resource record set [name='{}', type='{}'] but it already exists",
+                            key.0, key.1
+                        );
+                        return Err(Route53IpUpdateError::ChangeBatchRejected(vec![message]).into());
+                    }
+                    ChangeAction::Delete if record_sets.get(&key) != Some(&rrs) => {
+                        let message = format!(
+                            "Tried to delete resource record set [name='{}', type='{}'] but the values provided do \
+                             not match the current values",
+                            key.0, key.1
+                        );
+                        return Err(Route53IpUpdateError::ChangeBatchRejected(vec![message]).into());
+                    }
+                    ChangeAction::Delete => {
+                        record_sets.remove(&key);
+                    }
+                    _ => {
+                        record_sets.insert(key, rrs);
+                    }
+                }
+            }
+
+            zone.record_sets = record_sets;
+            zone.batches.push(change_batch);
+            let status = if zone.pending_polls > 0 {
+                ChangeStatus::Pending
+            } else {
+                ChangeStatus::Insync
+            };
+
+            Ok(ChangeInfo::builder().id(format!("C{}", zone.batches.len())).status(status).build())
+        }
+
+        async fn get_change(&self, change_id: &str) -> Result<ChangeInfo, BoxError> {
+            let mut zone = self.zone.lock().unwrap();
+            zone.get_change_calls += 1;
+            let status = if zone.get_change_calls < zone.pending_polls {
+                ChangeStatus::Pending
+            } else {
+                ChangeStatus::Insync
+            };
+
+            Ok(ChangeInfo::builder().id(change_id).status(status).build())
+        }
+
+        async fn test_dns_answer(&self, _zone_id: &str, name: &str, rr_type: RrType) -> Result<Vec<String>, BoxError> {
+            let zone = self.zone.lock().unwrap();
+            if let Some(answers) = &zone.test_dns_answers {
+                return Ok(answers.clone());
+            }
+
+            let key = (absolute(name), rr_type.as_str().to_string(), String::new());
+            let records = zone.record_sets.get(&key).and_then(|rrs| rrs.resource_records());
+            Ok(records.unwrap_or_default().iter().filter_map(|rr| rr.value()).map(String::from).collect())
+        }
+    }
+
+    fn absolute(name: &str) -> String {
+        if name.ends_with('.') {
+            name.to_string()
+        } else {
+            format!("{name}.")
+        }
+    }
+
+    /// Returns the record set with its name made absolute, as Route 53 reports it.
+    fn normalize(mut rrs: ResourceRecordSet) -> ResourceRecordSet {
+        rrs.name = rrs.name.as_deref().map(absolute);
+        rrs
+    }
+
+    fn key(rrs: &ResourceRecordSet) -> RecordSetKey {
+        (
+            rrs.name().unwrap().to_string(),
+            rrs.r#type().unwrap().as_str().to_string(),
+            rrs.set_identifier().unwrap_or_default().to_string(),
+        )
+    }
+}
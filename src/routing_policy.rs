@@ -0,0 +1,113 @@
+use {
+    aws_sdk_route53::model::{
+        resource_record_set, ResourceRecordSet, ResourceRecordSetFailover, ResourceRecordSetRegion,
+    },
+    schemars::JsonSchema,
+    serde::{Deserialize, Serialize},
+};
+
+/// How Route 53 chooses between record sets with the same name and type.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RoutingPolicy {
+    /// A single record set for the hostname.
+    #[default]
+    Simple,
+
+    /// Record sets are chosen in proportion to their `weight`.
+    Weighted,
+
+    /// The record set in the AWS `region` with the lowest latency to the client is chosen.
+    Latency,
+
+    /// The `primary` record set is chosen while it's healthy; the `secondary` otherwise.
+    Failover,
+
+    /// Up to eight healthy record sets are returned at random.
+    Multivalue,
+}
+
+/// The role of a record set under the failover routing policy.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FailoverRole {
+    Primary,
+    Secondary,
+}
+
+impl FailoverRole {
+    fn as_route53(&self) -> ResourceRecordSetFailover {
+        match self {
+            Self::Primary => ResourceRecordSetFailover::Primary,
+            Self::Secondary => ResourceRecordSetFailover::Secondary,
+        }
+    }
+}
+
+/// How a hostname's record sets are routed when it uses a routing policy other than simple. Several updaters can then
+/// publish the same hostname, each owning the record sets with its set identifier.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct RecordRouting {
+    pub(crate) policy: RoutingPolicy,
+    pub(crate) set_identifier: String,
+    pub(crate) weight: Option<i64>,
+    pub(crate) region: Option<String>,
+    pub(crate) failover: Option<FailoverRole>,
+    pub(crate) health_check_id: Option<String>,
+}
+
+impl RecordRouting {
+    /// Returns the record set builder with this routing applied.
+    pub(crate) fn apply(&self, builder: resource_record_set::Builder) -> resource_record_set::Builder {
+        let builder = builder.set_identifier(&self.set_identifier).set_health_check_id(self.health_check_id.clone());
+        match self.policy {
+            RoutingPolicy::Simple => builder,
+            RoutingPolicy::Weighted => builder.set_weight(self.weight),
+            RoutingPolicy::Latency => builder.set_region(self.region.as_deref().map(ResourceRecordSetRegion::from)),
+            RoutingPolicy::Failover => builder.set_failover(self.failover.as_ref().map(FailoverRole::as_route53)),
+            RoutingPolicy::Multivalue => builder.multi_value_answer(true),
+        }
+    }
+
+    /// Returns whether a record set is routed this way.
+    pub(crate) fn matches(&self, rrs: &ResourceRecordSet) -> bool {
+        let expected = self.apply(ResourceRecordSet::builder()).build();
+        rrs.set_identifier() == expected.set_identifier()
+            && rrs.weight() == expected.weight()
+            && rrs.region() == expected.region()
+            && rrs.failover() == expected.failover()
+            && rrs.multi_value_answer().unwrap_or(false) == expected.multi_value_answer().unwrap_or(false)
+            && rrs.health_check_id() == expected.health_check_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{RecordRouting, RoutingPolicy},
+        aws_sdk_route53::model::{ResourceRecordSet, ResourceRecordSetRegion},
+    };
+
+    #[test]
+    fn routing_is_applied_and_compared() {
+        let routing = RecordRouting {
+            policy: RoutingPolicy::Latency,
+            set_identifier: "home".to_string(),
+            weight: None,
+            region: Some("us-west-2".to_string()),
+            failover: None,
+            health_check_id: None,
+        };
+
+        let rrs = routing.apply(ResourceRecordSet::builder().name("home.example.com")).build();
+        assert_eq!(rrs.set_identifier(), Some("home"));
+        assert_eq!(rrs.region(), Some(&ResourceRecordSetRegion::UsWest2));
+        assert!(routing.matches(&rrs));
+
+        let elsewhere = RecordRouting {
+            region: Some("eu-west-1".to_string()),
+            ..routing.clone()
+        };
+        assert!(!elsewhere.matches(&rrs));
+    }
+}
@@ -0,0 +1,147 @@
+use {
+    crate::{config::Config, error::Route53IpUpdateError},
+    chrono::{DateTime, Local, TimeZone},
+    croner::Cron,
+    humantime::format_duration,
+    std::{
+        fmt::{Display, Formatter, Result as FmtResult},
+        future::pending,
+        time::Duration,
+    },
+    tokio::time::sleep,
+};
+
+/// The most updates of a cron schedule looked at to find the longest gap between them: one a minute for a week.
+const MAX_GAP_OCCURRENCES: usize = 7 * 24 * 60;
+
+/// When updates run while running continuously.
+#[derive(Clone, Debug)]
+pub(crate) enum Schedule {
+    /// At a fixed interval after the previous update.
+    Interval(Duration),
+
+    /// At the times matched by a cron expression, in the local time zone.
+    Cron(Box<Cron>),
+}
+
+impl Schedule {
+    /// Returns the configured schedule, or `None` if route53-ip-update should update once and exit.
+    pub(crate) fn from_config(config: &Config) -> Result<Option<Self>, Route53IpUpdateError> {
+        if let Some(expression) = &config.schedule {
+            return Ok(Some(Self::Cron(Box::new(parse_cron(expression)?))));
+        }
+
+        Ok(config.interval.map(Self::Interval))
+    }
+
+    /// Waits until the next update is due, plus a random delay of up to `jitter`. If a cron expression never matches
+    /// again, this never returns.
+    pub(crate) async fn wait(&self, jitter: Option<Duration>) {
+        let jitter = jitter.map(random_jitter).unwrap_or_default();
+        match self.wait_from(Local::now()) {
+            Some(duration) => sleep(duration + jitter).await,
+            None => pending().await,
+        }
+    }
+
+    /// Returns how long to wait from `now` until the next update is due.
+    fn wait_from<Tz: TimeZone>(&self, now: DateTime<Tz>) -> Option<Duration> {
+        match self {
+            Self::Interval(interval) => Some(*interval),
+            Self::Cron(cron) => {
+                let next = cron.find_next_occurrence(&now, false).ok()?;
+                Some((next - now).to_std().unwrap_or_default())
+            }
+        }
+    }
+
+    /// Returns the longest time between two updates due after `now`, looking at those due over the following week, so
+    /// the healthcheck can tell when an update was missed. If a cron expression matches fewer than twice, there's none.
+    pub(crate) fn longest_gap_from<Tz: TimeZone>(&self, now: DateTime<Tz>) -> Option<Duration> {
+        let cron = match self {
+            Self::Interval(interval) => return Some(*interval),
+            Self::Cron(cron) => cron,
+        };
+
+        let mut previous = cron.find_next_occurrence(&now, false).ok()?;
+        let until = previous.clone() + chrono::Duration::days(7);
+        let mut longest = None;
+        for _ in 0..MAX_GAP_OCCURRENCES {
+            let Ok(next) = cron.find_next_occurrence(&previous, false) else {
+                break;
+            };
+
+            longest = longest.max(Some((next.clone() - previous).to_std().unwrap_or_default()));
+            previous = next;
+            if previous > until {
+                break;
+            }
+        }
+
+        longest
+    }
+}
+
+impl Display for Schedule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Interval(interval) => write!(f, "every {}", format_duration(*interval)),
+            Self::Cron(cron) => write!(f, "on the schedule {}", cron.as_str()),
+        }
+    }
+}
+
+/// Returns a random delay of up to `max`.
+fn random_jitter(max: Duration) -> Duration {
+    max.mul_f64(rand::random())
+}
+
+/// Parses a standard five-field cron expression (minute, hour, day of month, month, day of week).
+pub(crate) fn parse_cron(expression: &str) -> Result<Cron, Route53IpUpdateError> {
+    Cron::new(expression)
+        .parse()
+        .map_err(|e| Route53IpUpdateError::InvalidSchedule(expression.to_string(), e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{parse_cron, random_jitter, Schedule},
+        chrono::{TimeZone, Utc},
+        std::time::Duration,
+    };
+
+    #[test]
+    fn cron_schedule_waits_for_next_match() {
+        let schedule = Schedule::Cron(Box::new(parse_cron("*/5 6-23 * * *").unwrap()));
+
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 10, 2, 30).unwrap();
+        assert_eq!(schedule.wait_from(now), Some(Duration::from_secs(150)));
+
+        // Outside of the allowed hours, the next update is at the start of the next allowed hour.
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 23, 58, 0).unwrap();
+        assert_eq!(schedule.wait_from(now), Some(Duration::from_secs(6 * 3600 + 120)));
+
+        assert!(parse_cron("*/5 25 * * *").is_err());
+    }
+
+    #[test]
+    fn longest_gap_spans_quiet_hours() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 10, 2, 30).unwrap();
+        let schedule = Schedule::Cron(Box::new(parse_cron("*/5 6-23 * * *").unwrap()));
+        assert_eq!(schedule.longest_gap_from(now), Some(Duration::from_secs(6 * 3600 + 300)));
+
+        let schedule = Schedule::Cron(Box::new(parse_cron("0 3 * * 1").unwrap()));
+        assert_eq!(schedule.longest_gap_from(now), Some(Duration::from_secs(7 * 86400)));
+
+        let schedule = Schedule::Interval(Duration::from_secs(300));
+        assert_eq!(schedule.longest_gap_from(now), Some(Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn jitter_is_bounded() {
+        let max = Duration::from_secs(30);
+        assert!((0..100).map(|_| random_jitter(max)).all(|jitter| jitter <= max));
+        assert_eq!(random_jitter(Duration::ZERO), Duration::ZERO);
+    }
+}
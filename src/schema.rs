@@ -0,0 +1,9 @@
+use {crate::config::Config, schemars::schema_for};
+
+/// Returns the JSON Schema for the configuration file format as pretty-printed JSON.
+///
+/// The schema describes the YAML and TOML formats equally well, since both are parsed into the same structure.
+pub(crate) fn config_schema() -> String {
+    let schema = schema_for!(Config);
+    serde_json::to_string_pretty(&schema).expect("JSON Schema should always serialize")
+}
@@ -0,0 +1,108 @@
+use {
+    crate::error::{ErrorClass, Route53IpUpdateError},
+    log::{info, warn},
+    std::{process::exit, sync::OnceLock, time::Duration},
+    tokio::{sync::watch, time::sleep},
+};
+
+/// The exit status used when a second interrupt forces an immediate exit.
+const FORCED_EXIT_CODE: i32 = 130;
+
+fn shutdown_sender() -> &'static watch::Sender<bool> {
+    static SHUTDOWN: OnceLock<watch::Sender<bool>> = OnceLock::new();
+    SHUTDOWN.get_or_init(|| watch::channel(false).0)
+}
+
+/// Starts listening for SIGTERM and SIGINT (Ctrl-C).
+///
+/// The first signal asks for a graceful shutdown: the update in progress finishes without waiting for Route 53 to
+/// propagate its changes, the state file is written, and route53-ip-update exits with the update's status. A second
+/// signal exits immediately.
+pub(crate) fn listen_for_shutdown() {
+    tokio::spawn(async {
+        let mut terminate = match Terminate::new() {
+            Ok(terminate) => terminate,
+            Err(e) => {
+                warn!("Unable to handle SIGTERM: {e}");
+                Terminate::default()
+            }
+        };
+
+        wait_for_signal(&mut terminate).await;
+        info!("Shutting down; interrupt again to exit immediately");
+        shutdown_sender().send_replace(true);
+
+        wait_for_signal(&mut terminate).await;
+        warn!("Exiting immediately");
+        exit(FORCED_EXIT_CODE);
+    });
+}
+
+/// Exits with the `max-runtime` exit status if the run is still going after `max_runtime`, so a hung propagation wait
+/// or stuck connection can't hold up the next run.
+pub(crate) fn exit_after(max_runtime: Duration) {
+    tokio::spawn(async move {
+        sleep(max_runtime).await;
+        let e = Route53IpUpdateError::MaxRuntimeExceeded(humantime::format_duration(max_runtime).to_string());
+        eprintln!("Error: {e}");
+        exit(ErrorClass::MaxRuntimeExceeded.code().into());
+    });
+}
+
+/// Indicates whether a graceful shutdown has been requested.
+pub(crate) fn shutdown_requested() -> bool {
+    *shutdown_sender().borrow()
+}
+
+/// Waits until a graceful shutdown is requested.
+pub(crate) async fn shutdown_signal() {
+    let mut receiver = shutdown_sender().subscribe();
+    // The sender is static, so it's never dropped and this can't fail.
+    let _ = receiver.wait_for(|requested| *requested).await;
+}
+
+async fn wait_for_signal(terminate: &mut Terminate) {
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => {
+            if result.is_err() {
+                // Ctrl-C can't be handled; only SIGTERM is left to wait for.
+                terminate.recv().await
+            }
+        }
+        _ = terminate.recv() => (),
+    }
+}
+
+/// SIGTERM, which is only handled on Unix.
+#[derive(Default)]
+struct Terminate {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl Terminate {
+    #[cfg(unix)]
+    fn new() -> Result<Self, std::io::Error> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        Ok(Self {
+            signal: Some(signal(SignalKind::terminate())?),
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn new() -> Result<Self, std::io::Error> {
+        Ok(Self {})
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = &mut self.signal {
+            if signal.recv().await.is_some() {
+                return;
+            }
+        }
+
+        std::future::pending::<()>().await
+    }
+}
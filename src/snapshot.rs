@@ -0,0 +1,502 @@
+use {
+    crate::{
+        aws::Route53Clients,
+        config::Config,
+        diff::{print_changes, RecordSetDiff, RecordSetSummary},
+        error::Route53IpUpdateError,
+        prompt::prompt_bool,
+        provider::timestamp_record_name,
+        route53_api::Route53Api,
+        update::{get_hostname_record_sets, update_route53_zone},
+    },
+    aws_sdk_route53::{
+        model::{
+            AliasTarget, Change, ChangeAction, ResourceRecord, ResourceRecordSet, ResourceRecordSetFailover,
+            ResourceRecordSetRegion, RrType,
+        },
+        Client as Route53Client,
+    },
+    chrono::{DateTime, Utc},
+    log::{info, warn},
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        path::{Path, PathBuf},
+    },
+    tokio::fs::{create_dir_all, read_dir, read_to_string, write},
+    tower::BoxError,
+};
+
+/// The comment attached to the Route 53 changes restoring a snapshot.
+const RESTORE_CHANGE_COMMENT: &str = concat!("Snapshot restored by ", env!("CARGO_PKG_NAME"));
+
+/// The comment attached to the Route 53 changes rolling back a change that failed verification.
+const ROLLBACK_CHANGE_COMMENT: &str = concat!("Failed change rolled back by ", env!("CARGO_PKG_NAME"));
+
+/// The record sets about to be changed in a zone, as they were before the change.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Snapshot {
+    pub(crate) zone_id: String,
+
+    /// When the snapshot was taken, in RFC 3339 format.
+    pub(crate) timestamp: String,
+
+    /// Every record set the change touched, whether or not it existed beforehand.
+    pub(crate) affected: Vec<RecordKey>,
+
+    /// The affected record sets that existed beforehand.
+    pub(crate) record_sets: Vec<SnapshotRecordSet>,
+}
+
+/// Identifies a record set within a zone.
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct RecordKey {
+    pub(crate) name: String,
+
+    #[serde(rename = "type")]
+    pub(crate) record_type: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) set_identifier: Option<String>,
+}
+
+impl Snapshot {
+    /// Returns the part of the snapshot covering the hostnames (and their timestamp records), or `None` if it doesn't
+    /// cover any of them. Hostnames are compared in lowercase without a trailing dot.
+    fn for_hostnames(&self, hostnames: &BTreeSet<String>) -> Option<Self> {
+        let covered = |key: &RecordKey| {
+            hostnames.iter().any(|hostname| key.name == *hostname || key.name == timestamp_record_name(hostname))
+        };
+        let affected: Vec<RecordKey> = self.affected.iter().filter(|key| covered(key)).cloned().collect();
+        if affected.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            zone_id: self.zone_id.clone(),
+            timestamp: self.timestamp.clone(),
+            affected,
+            record_sets: self.record_sets.iter().filter(|rrs| covered(&rrs.key)).cloned().collect(),
+        })
+    }
+}
+
+impl RecordKey {
+    fn of(rrs: &ResourceRecordSet) -> Self {
+        Self {
+            name: rrs.name().unwrap_or_default().trim_end_matches('.').to_ascii_lowercase(),
+            record_type: rrs.r#type().map(|t| t.as_str()).unwrap_or_default().to_string(),
+            set_identifier: rrs.set_identifier().map(String::from),
+        }
+    }
+}
+
+/// A record set as saved in a snapshot.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct SnapshotRecordSet {
+    #[serde(flatten)]
+    pub(crate) key: RecordKey,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) ttl: Option<i64>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) values: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) weight: Option<i64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) region: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) failover: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) multi_value_answer: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) health_check_id: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) alias_target: Option<SnapshotAliasTarget>,
+}
+
+/// The target of an alias record set, as saved in a snapshot.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct SnapshotAliasTarget {
+    pub(crate) hosted_zone_id: String,
+    pub(crate) dns_name: String,
+    pub(crate) evaluate_target_health: bool,
+}
+
+impl From<&ResourceRecordSet> for SnapshotRecordSet {
+    fn from(rrs: &ResourceRecordSet) -> Self {
+        Self {
+            key: RecordKey::of(rrs),
+            ttl: rrs.ttl(),
+            values: rrs
+                .resource_records()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|rr| rr.value())
+                .map(String::from)
+                .collect(),
+            weight: rrs.weight(),
+            region: rrs.region().map(|region| region.as_str().to_string()),
+            failover: rrs.failover().map(|failover| failover.as_str().to_string()),
+            multi_value_answer: rrs.multi_value_answer(),
+            health_check_id: rrs.health_check_id().map(String::from),
+            alias_target: rrs.alias_target().map(|alias| SnapshotAliasTarget {
+                hosted_zone_id: alias.hosted_zone_id().unwrap_or_default().to_string(),
+                dns_name: alias.dns_name().unwrap_or_default().to_string(),
+                evaluate_target_health: alias.evaluate_target_health(),
+            }),
+        }
+    }
+}
+
+impl SnapshotRecordSet {
+    /// Returns the record set to submit to Route 53.
+    fn to_route53(&self) -> ResourceRecordSet {
+        let records = self.values.iter().map(|value| ResourceRecord::builder().value(value).build()).collect();
+        ResourceRecordSet::builder()
+            .name(&self.key.name)
+            .r#type(RrType::from(self.key.record_type.as_str()))
+            .set_set_identifier(self.key.set_identifier.clone())
+            .set_ttl(self.ttl)
+            .set_resource_records((!self.values.is_empty()).then_some(records))
+            .set_weight(self.weight)
+            .set_region(self.region.as_deref().map(ResourceRecordSetRegion::from))
+            .set_failover(self.failover.as_deref().map(ResourceRecordSetFailover::from))
+            .set_multi_value_answer(self.multi_value_answer)
+            .set_health_check_id(self.health_check_id.clone())
+            .set_alias_target(self.alias_target.as_ref().map(|alias| {
+                AliasTarget::builder()
+                    .hosted_zone_id(&alias.hosted_zone_id)
+                    .dns_name(&alias.dns_name)
+                    .evaluate_target_health(alias.evaluate_target_health)
+                    .build()
+            }))
+            .build()
+    }
+
+    fn summary(&self) -> RecordSetSummary {
+        RecordSetSummary::new(self.ttl, &self.values).with_weight(self.weight)
+    }
+}
+
+/// Returns the record sets the changes are about to touch, as they are now.
+pub(crate) async fn take_snapshot<R: Route53Api>(
+    route53: &R,
+    zone_id: &str,
+    changes: &[&Change],
+) -> Result<Snapshot, BoxError> {
+    let affected: BTreeSet<RecordKey> =
+        changes.iter().filter_map(|change| change.resource_record_set()).map(RecordKey::of).collect();
+    Ok(Snapshot {
+        zone_id: zone_id.to_string(),
+        timestamp: Utc::now().to_rfc3339(),
+        record_sets: current_record_sets(route53, zone_id, &affected).await?.iter().map(Into::into).collect(),
+        affected: affected.into_iter().collect(),
+    })
+}
+
+/// Saves a snapshot to a file in `dir` named after its zone and timestamp. Returns the path of the file.
+pub(crate) async fn save_snapshot(dir: &Path, snapshot: &Snapshot) -> Result<PathBuf, BoxError> {
+    let timestamp = DateTime::parse_from_rfc3339(&snapshot.timestamp)?.with_timezone(&Utc);
+    create_dir_all(dir).await?;
+    let zone = snapshot.zone_id.trim_start_matches("/hostedzone/").replace('/', "_");
+    let path = dir.join(format!("{zone}-{}.json", timestamp.format("%Y%m%dT%H%M%S%.3fZ")));
+    write(&path, serde_json::to_vec_pretty(snapshot)?).await?;
+    Ok(path)
+}
+
+/// Lists the affected record sets that currently exist.
+async fn current_record_sets<R: Route53Api>(
+    route53: &R,
+    zone_id: &str,
+    affected: &BTreeSet<RecordKey>,
+) -> Result<Vec<ResourceRecordSet>, BoxError> {
+    let names: BTreeSet<&str> = affected.iter().map(|key| key.name.as_str()).collect();
+    let mut record_sets = Vec::new();
+    for name in names {
+        let found = get_hostname_record_sets(route53.clone(), zone_id, name).await?;
+        record_sets.extend(found.into_iter().filter(|rrs| affected.contains(&RecordKey::of(rrs))));
+    }
+
+    Ok(record_sets)
+}
+
+/// Puts the record sets in a snapshot back the way they were: those that existed are restored, and those created since
+/// are removed. With `dry_run`, the changes are only printed.
+pub(crate) async fn restore_snapshot(
+    config: &Config,
+    route53_clients: &mut Route53Clients,
+    path: &Path,
+    dry_run: bool,
+) -> Result<(), BoxError> {
+    let snapshot: Snapshot = serde_json::from_str(&read_to_string(path).await?)?;
+    let route53 = client_for_zone(config, route53_clients, &snapshot.zone_id).await?;
+    restore_zone_snapshot(route53, config, &snapshot, dry_run).await
+}
+
+/// Reverts the most recent change to each hostname, using the snapshots in `snapshot-dir` as the change history: the
+/// hostname's record sets are put back the way they were in the newest snapshot that covers them. If a hostname has no
+/// history, the others are still reverted and an error is returned.
+pub(crate) async fn undo_hostnames(
+    config: &Config,
+    route53_clients: &mut Route53Clients,
+    hostnames: &[String],
+    dry_run: bool,
+) -> Result<(), BoxError> {
+    let Some(snapshot_dir) = &config.snapshot_dir else {
+        return Err(Route53IpUpdateError::InvalidConfig(vec![
+            "undo needs snapshot-dir to be set; the snapshots are the change history.".to_string(),
+        ])
+        .into());
+    };
+
+    let hostnames: Vec<String> =
+        hostnames.iter().map(|hostname| hostname.trim_end_matches('.').to_ascii_lowercase()).collect();
+    let snapshots = read_snapshots(snapshot_dir).await?;
+    let (undos, missing) = latest_changes(&snapshots, &hostnames);
+
+    let mut first_error: Option<BoxError> = None;
+    for hostname in missing {
+        let e = Route53IpUpdateError::NoChangeHistory(hostname);
+        eprintln!("Error: {e}");
+        first_error.get_or_insert(e.into());
+    }
+
+    for undo in undos {
+        let result = match client_for_zone(config, route53_clients, &undo.zone_id).await {
+            Ok(route53) => restore_zone_snapshot(route53, config, &undo, dry_run).await,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            eprintln!("Error: Failed to undo the changes to zone {} from {}: {e}", undo.zone_id, undo.timestamp);
+            first_error.get_or_insert(e);
+        }
+    }
+
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Reads every snapshot in the directory, newest first. Files that aren't snapshots are skipped.
+async fn read_snapshots(dir: &Path) -> Result<Vec<Snapshot>, BoxError> {
+    let mut snapshots = Vec::new();
+    let mut entries = read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+
+        match serde_json::from_str::<Snapshot>(&read_to_string(&path).await?) {
+            Ok(snapshot) => snapshots.push(snapshot),
+            Err(e) => warn!("Skipping {}: not a snapshot: {e}", path.display()),
+        }
+    }
+
+    // Timestamps are all in UTC, so they sort chronologically.
+    snapshots.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    Ok(snapshots)
+}
+
+/// Returns, for the snapshots (newest first), the parts covering the most recent change to each hostname, along with
+/// the hostnames no snapshot covers.
+fn latest_changes(snapshots: &[Snapshot], hostnames: &[String]) -> (Vec<Snapshot>, Vec<String>) {
+    let mut by_snapshot: BTreeMap<usize, BTreeSet<String>> = BTreeMap::new();
+    let mut missing = Vec::new();
+    for hostname in hostnames {
+        let wanted = BTreeSet::from([hostname.clone()]);
+        match snapshots.iter().position(|snapshot| snapshot.for_hostnames(&wanted).is_some()) {
+            Some(index) => {
+                by_snapshot.entry(index).or_default().insert(hostname.clone());
+            }
+            None => missing.push(hostname.clone()),
+        }
+    }
+
+    let undos = by_snapshot.into_iter().filter_map(|(index, hostnames)| snapshots[index].for_hostnames(&hostnames));
+    (undos.collect(), missing)
+}
+
+/// Returns the Route 53 client for a zone, using the zone's own credentials if it's configured.
+async fn client_for_zone(
+    config: &Config,
+    route53_clients: &mut Route53Clients,
+    zone_id: &str,
+) -> Result<Route53Client, BoxError> {
+    let zone = config.zones_with_mirrors().into_iter().find(|zone| zone.zone_id == zone_id);
+    Ok(match &zone {
+        Some(zone) => route53_clients.for_zone(zone).await?,
+        None => route53_clients.default_client().await?,
+    })
+}
+
+async fn restore_zone_snapshot<R: Route53Api>(
+    route53: R,
+    config: &Config,
+    snapshot: &Snapshot,
+    dry_run: bool,
+) -> Result<(), BoxError> {
+    let zone_id = &snapshot.zone_id;
+    let (changes, diffs) = restore_changes(&route53, snapshot).await?;
+    if diffs.is_empty() {
+        info!("The records in zone {zone_id} already match the snapshot from {}", snapshot.timestamp);
+        return Ok(());
+    }
+
+    print_changes(zone_id, &diffs);
+    if dry_run {
+        info!("Not restoring the snapshot of zone {zone_id}: dry run");
+        return Ok(());
+    }
+
+    if config.interactive && !prompt_bool(&format!("Restore the records in zone {zone_id} to the snapshot?"), false)? {
+        info!("Skipping changes to zone {zone_id}");
+        return Ok(());
+    }
+
+    update_route53_zone(route53, zone_id, changes, RESTORE_CHANGE_COMMENT).await?;
+    info!("Restored the records in zone {zone_id} to the snapshot from {}", snapshot.timestamp);
+    Ok(())
+}
+
+/// Puts the record sets in a snapshot back the way they were without asking, after a change failed verification.
+/// Returns the changes made.
+pub(crate) async fn roll_back<R: Route53Api>(route53: R, snapshot: &Snapshot) -> Result<Vec<RecordSetDiff>, BoxError> {
+    let (changes, diffs) = restore_changes(&route53, snapshot).await?;
+    if !changes.is_empty() {
+        update_route53_zone(route53, &snapshot.zone_id, changes, ROLLBACK_CHANGE_COMMENT).await?;
+    }
+
+    Ok(diffs)
+}
+
+/// Returns the changes that put the record sets in a snapshot back the way they were, along with a summary of them.
+async fn restore_changes<R: Route53Api>(
+    route53: &R,
+    snapshot: &Snapshot,
+) -> Result<(Vec<Change>, Vec<RecordSetDiff>), BoxError> {
+    let zone_id = &snapshot.zone_id;
+    let affected: BTreeSet<RecordKey> = snapshot.affected.iter().cloned().collect();
+    let current: Vec<(SnapshotRecordSet, ResourceRecordSet)> =
+        current_record_sets(route53, zone_id, &affected).await?.into_iter().map(|rrs| ((&rrs).into(), rrs)).collect();
+
+    // Deletions go first, so a CNAME can replace the A and AAAA records created in its place.
+    let mut deletions = Vec::new();
+    let mut upserts = Vec::new();
+    let mut diffs = Vec::new();
+    for key in &affected {
+        let now = current.iter().find(|(rrs, _)| rrs.key == *key);
+        let then = snapshot.record_sets.iter().find(|rrs| rrs.key == *key);
+        let (action, rrs, changes) = match (now, then) {
+            (Some((now, _)), Some(then)) if now == then => continue,
+            (_, Some(then)) => (ChangeAction::Upsert, then.to_route53(), &mut upserts),
+            // Deletions must match the record set exactly, so the one listed is used as is.
+            (Some((_, now)), None) => (ChangeAction::Delete, now.clone(), &mut deletions),
+            (None, None) => continue,
+        };
+
+        diffs.push(RecordSetDiff {
+            hostname: key.name.clone(),
+            record_type: key.record_type.clone(),
+            old: now.map(|(now, _)| now.summary()),
+            new: then.map(SnapshotRecordSet::summary),
+        });
+        changes.push(Change::builder().action(action).resource_record_set(rrs).build());
+    }
+
+    deletions.extend(upserts);
+    Ok((deletions, diffs))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            latest_changes, restore_zone_snapshot, save_snapshot, take_snapshot, RecordKey, Snapshot, SnapshotRecordSet,
+        },
+        crate::{
+            config::Config,
+            route53_api::fake::{rrs, FakeRoute53},
+            update::update_route53_zone,
+        },
+        aws_sdk_route53::model::{Change, ChangeAction, ResourceRecordSet, RrType},
+        std::env::temp_dir,
+    };
+
+    fn upsert(rrs: ResourceRecordSet) -> Change {
+        Change::builder().action(ChangeAction::Upsert).resource_record_set(rrs).build()
+    }
+
+    #[tokio::test]
+    async fn restores_changed_records_and_removes_new_ones() {
+        let fake = FakeRoute53::with_record_sets([
+            rrs("home.example.com", RrType::A, "192.0.2.1"),
+            rrs("other.example.com", RrType::A, "192.0.2.9"),
+        ]);
+        let changes = [
+            upsert(rrs("home.example.com", RrType::A, "192.0.2.2")),
+            upsert(rrs("home.example.com", RrType::Aaaa, "2001:db8::2")),
+        ];
+
+        let dir = temp_dir().join(format!("route53-ip-update-snapshot-{}", std::process::id()));
+        let snapshot = take_snapshot(&fake, "Z1", &changes.iter().collect::<Vec<_>>()).await.unwrap();
+        let path = save_snapshot(&dir, &snapshot).await.unwrap();
+        let snapshot: Snapshot = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(snapshot.affected.len(), 2);
+        assert_eq!(snapshot.record_sets.len(), 1);
+
+        update_route53_zone(fake.clone(), "Z1", changes.to_vec(), "update").await.unwrap();
+        assert_eq!(fake.record_sets().len(), 3);
+        restore_zone_snapshot(fake.clone(), &Config::default(), &snapshot, false).await.unwrap();
+
+        let remaining: Vec<_> = fake
+            .record_sets()
+            .iter()
+            .map(|rrs| {
+                let value = rrs.resource_records().unwrap()[0].value().unwrap().to_string();
+                format!("{} {} {value}", rrs.name().unwrap(), rrs.r#type().unwrap().as_str())
+            })
+            .collect();
+        assert_eq!(remaining, ["home.example.com. A 192.0.2.1", "other.example.com. A 192.0.2.9"]);
+    }
+
+    #[test]
+    fn undo_uses_the_latest_change_to_each_hostname() {
+        let key = |name: &str| RecordKey {
+            name: name.to_string(),
+            record_type: "A".to_string(),
+            set_identifier: None,
+        };
+        let snapshot = |timestamp: &str, names: &[&str]| Snapshot {
+            zone_id: "Z1".to_string(),
+            timestamp: timestamp.to_string(),
+            affected: names.iter().map(|name| key(name)).collect(),
+            record_sets: names.iter().map(|name| SnapshotRecordSet::from(&rrs(name, RrType::A, "192.0.2.1"))).collect(),
+        };
+        let snapshots = [
+            snapshot("2026-01-02T00:00:00+00:00", &["home.example.com", "_r53ipupdate.home.example.com"]),
+            snapshot("2026-01-01T00:00:00+00:00", &["home.example.com", "nas.example.com"]),
+        ];
+        let hostnames = ["home.example.com", "nas.example.com", "gone.example.com"].map(String::from);
+
+        let (undos, missing) = latest_changes(&snapshots, &hostnames);
+        assert_eq!(missing, ["gone.example.com"]);
+        assert_eq!(undos.len(), 2);
+        assert_eq!(undos[0].timestamp, "2026-01-02T00:00:00+00:00");
+        assert_eq!(undos[0].affected, [key("home.example.com"), key("_r53ipupdate.home.example.com")]);
+        assert_eq!(undos[1].affected, [key("nas.example.com")]);
+        assert_eq!(undos[1].record_sets.len(), 1);
+    }
+}
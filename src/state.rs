@@ -0,0 +1,240 @@
+use {
+    crate::{
+        address_source::DiscoveredAddress, config::Route53ZoneConfig, error::Classification,
+        query_ip_service::IpServiceClients, relay::unix_time, status::SharedStatus, stun::NatType,
+        zones::normalize_domain_name,
+    },
+    log::{debug, warn},
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::{BTreeMap, BTreeSet, HashSet},
+        io::ErrorKind,
+        net::IpAddr,
+        path::Path,
+        time::Duration,
+    },
+    tokio::fs::{read_to_string, rename, write},
+    tower::BoxError,
+};
+
+/// What route53-ip-update remembers between update cycles, and between runs if a state file is configured.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct UpdateState {
+    /// When each hostname was last changed, in seconds since the Unix epoch, keyed by zone ID and then hostname.
+    #[serde(default)]
+    pub(crate) last_changed: BTreeMap<String, BTreeMap<String, u64>>,
+
+    /// The addresses last published to each zone, keyed by zone ID, for preferring them when choosing a single address.
+    #[serde(default)]
+    pub(crate) last_published: BTreeMap<String, BTreeSet<IpAddr>>,
+
+    /// The hostnames published for the EC2 fleet, keyed by zone ID, so their records can be removed once no running
+    /// instance is tagged with them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) fleet_hostnames: BTreeMap<String, BTreeSet<String>>,
+
+    /// When an update last brought every zone up-to-date, in seconds since the Unix epoch, for the healthcheck command.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) last_sync: Option<u64>,
+
+    /// Updates that couldn't be made because AWS was unreachable, keyed by zone ID, to be made by a later update whose
+    /// discovery fails or by the flush command.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) queued: BTreeMap<String, QueuedUpdate>,
+
+    /// The addresses found by the previous discovery. This is only kept in memory.
+    #[serde(skip)]
+    pub(crate) last_discovered: Option<HashSet<DiscoveredAddress>>,
+
+    /// The clients used to query the IP service, reused across updates. This is only kept in memory.
+    #[serde(skip)]
+    pub(crate) ip_service_clients: IpServiceClients,
+
+    /// How the last update failed, if a zone couldn't be updated, so only failures worth retrying are retried. This is
+    /// only kept in memory.
+    #[serde(skip)]
+    pub(crate) failure: Option<Classification>,
+
+    /// The NAT type last detected, so what it means is only logged when it changes. This is only kept in memory.
+    #[serde(skip)]
+    pub(crate) nat_type: Option<NatType>,
+
+    /// The status of the updates, for the status endpoint. This is only kept in memory.
+    #[serde(skip)]
+    pub(crate) status: SharedStatus,
+}
+
+/// The addresses to publish to a zone once AWS can be reached.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct QueuedUpdate {
+    /// When the update was queued, in seconds since the Unix epoch.
+    pub(crate) queued_at: u64,
+
+    /// The IPv4 addresses to publish.
+    pub(crate) ipv4: BTreeSet<IpAddr>,
+
+    /// The IPv6 addresses to publish.
+    pub(crate) ipv6: BTreeSet<IpAddr>,
+}
+
+impl UpdateState {
+    /// Reads the state file. A missing or unreadable state file is treated as empty, so a damaged file can't stop
+    /// records from being updated.
+    pub(crate) async fn load(path: Option<&Path>) -> Self {
+        let Some(path) = path else {
+            return Self::default();
+        };
+
+        let contents = match read_to_string(path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                warn!("Unable to read state file {}: {e}", path.display());
+                return Self::default();
+            }
+        };
+
+        match serde_json::from_str(&contents) {
+            Ok(state) => state,
+            Err(e) => {
+                warn!("Ignoring invalid state file {}: {e}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    /// Writes the state file, replacing it atomically.
+    pub(crate) async fn save(&self, path: &Path) -> Result<(), BoxError> {
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        write(&temp_path, serde_json::to_vec_pretty(self)?).await?;
+        rename(&temp_path, path).await?;
+        Ok(())
+    }
+
+    /// Records that hostnames in a zone were changed at the given time.
+    pub(crate) fn record_changes(&mut self, zone_id: &str, hostnames: &[String], now: u64) {
+        let zone = self.last_changed.entry(zone_id.to_string()).or_default();
+        for hostname in hostnames {
+            zone.insert(normalize_domain_name(hostname), now);
+        }
+    }
+
+    /// Records the addresses published to a zone, returning whether they differ from those published before.
+    pub(crate) fn record_published(&mut self, zone_id: &str, addresses: impl IntoIterator<Item = IpAddr>) -> bool {
+        let addresses: BTreeSet<IpAddr> = addresses.into_iter().collect();
+        self.last_published.insert(zone_id.to_string(), addresses.clone()) != Some(addresses)
+    }
+
+    /// Queues the addresses to publish to a zone once AWS can be reached, replacing any queued before. Returns whether
+    /// the queued addresses changed; if they didn't, the update keeps the time it was first queued.
+    pub(crate) fn queue_update(
+        &mut self,
+        zone_id: &str,
+        ipv4: &HashSet<IpAddr>,
+        ipv6: &HashSet<IpAddr>,
+        now: u64,
+    ) -> bool {
+        let ipv4: BTreeSet<IpAddr> = ipv4.iter().copied().collect();
+        let ipv6: BTreeSet<IpAddr> = ipv6.iter().copied().collect();
+        if self.queued.get(zone_id).is_some_and(|queued| queued.ipv4 == ipv4 && queued.ipv6 == ipv6) {
+            return false;
+        }
+
+        self.queued.insert(
+            zone_id.to_string(),
+            QueuedUpdate {
+                queued_at: now,
+                ipv4,
+                ipv6,
+            },
+        );
+        true
+    }
+
+    /// Returns how much longer a hostname must wait before it may be changed again, if it was changed within the
+    /// cooldown.
+    pub(crate) fn cooldown_remaining(
+        &self,
+        zone_id: &str,
+        hostname: &str,
+        cooldown: Duration,
+        now: u64,
+    ) -> Option<u64> {
+        let changed = *self.last_changed.get(zone_id)?.get(&normalize_domain_name(hostname))?;
+        let elapsed = now.saturating_sub(changed);
+        cooldown.as_secs().checked_sub(elapsed).filter(|remaining| *remaining > 0)
+    }
+
+    /// Returns a copy of the zone without the hostnames that are still cooling down from a recent change.
+    pub(crate) fn without_cooling_hostnames(
+        &self,
+        zone: &Route53ZoneConfig,
+        cooldown: Option<Duration>,
+    ) -> Route53ZoneConfig {
+        let mut zone = zone.clone();
+        let Some(cooldown) = cooldown else {
+            return zone;
+        };
+
+        let now = unix_time();
+        zone.hostnames.retain(|hostname_config| {
+            let hostname = hostname_config.get_hostname();
+            // A hostname's IPv4-only and IPv6-only hostnames are changed along with it, so they share its cooldown.
+            let remaining = hostname_config
+                .get_hostnames()
+                .filter_map(|hostname| self.cooldown_remaining(&zone.zone_id, hostname, cooldown, now))
+                .max();
+            match remaining {
+                Some(remaining) => {
+                    debug!(
+                        "Not changing {hostname} in zone {} for another {remaining} seconds (cooldown)",
+                        zone.zone_id
+                    );
+                    false
+                }
+                None => true,
+            }
+        });
+        zone
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::UpdateState,
+        std::{collections::HashSet, net::IpAddr, time::Duration},
+    };
+
+    #[test]
+    fn cooldown_expires() {
+        let mut state = UpdateState::default();
+        state.record_changes("Z1", &["Home.Example.com".to_string()], 1000);
+
+        let cooldown = Duration::from_secs(600);
+        assert_eq!(state.cooldown_remaining("Z1", "home.example.com.", cooldown, 1100), Some(500));
+        assert_eq!(state.cooldown_remaining("Z1", "home.example.com", cooldown, 1600), None);
+        assert_eq!(state.cooldown_remaining("Z2", "home.example.com", cooldown, 1100), None);
+        assert_eq!(state.cooldown_remaining("Z1", "other.example.com", cooldown, 1100), None);
+    }
+
+    #[test]
+    fn queued_updates_keep_their_time_until_the_addresses_change() {
+        let mut state = UpdateState::default();
+        let ipv4: HashSet<IpAddr> = ["192.0.2.1".parse().unwrap()].into();
+        assert!(state.queue_update("Z1", &ipv4, &HashSet::new(), 1000));
+        assert!(!state.queue_update("Z1", &ipv4, &HashSet::new(), 1300));
+        assert_eq!(state.queued["Z1"].queued_at, 1000);
+
+        let ipv4: HashSet<IpAddr> = ["192.0.2.2".parse().unwrap()].into();
+        assert!(state.queue_update("Z1", &ipv4, &HashSet::new(), 1600));
+        assert_eq!(state.queued["Z1"].queued_at, 1600);
+
+        let saved = serde_json::to_string(&state).unwrap();
+        let loaded: UpdateState = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded.queued, state.queued);
+    }
+}
@@ -0,0 +1,106 @@
+use {
+    crate::config::{Config, StatsdConfig},
+    log::debug,
+    once_cell::sync::OnceCell,
+    std::{net::UdpSocket, time::Duration},
+    tokio::net::lookup_host,
+    tower::BoxError,
+};
+
+/// The client sending metrics, once it has been started.
+static CLIENT: OnceCell<StatsdClient> = OnceCell::new();
+
+/// Sends metrics to a statsd server over UDP.
+#[derive(Debug)]
+struct StatsdClient {
+    socket: UdpSocket,
+    settings: StatsdConfig,
+}
+
+/// Starts sending metrics to the configured statsd server. This does nothing if statsd isn't configured or has already
+/// been started.
+pub(crate) async fn start_statsd(config: &Config) -> Result<(), BoxError> {
+    let (Some(statsd), None) = (&config.statsd, CLIENT.get()) else {
+        return Ok(());
+    };
+
+    let addr = lookup_host(&statsd.address)
+        .await?
+        .next()
+        .ok_or_else(|| format!("Unable to resolve statsd server {}", statsd.address))?;
+    let socket = UdpSocket::bind(if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    })?;
+    socket.connect(addr)?;
+    socket.set_nonblocking(true)?;
+
+    debug!("Sending metrics to statsd server {addr}");
+    let _ = CLIENT.set(StatsdClient {
+        socket,
+        settings: statsd.clone(),
+    });
+    Ok(())
+}
+
+/// Adds to a counter, if statsd has been started.
+pub(crate) fn count(name: &str, value: u64, tags: &[(&str, &str)]) {
+    send(name, &value.to_string(), "c", tags);
+}
+
+/// Records a timing in milliseconds, if statsd has been started.
+pub(crate) fn timing(name: &str, duration: Duration, tags: &[(&str, &str)]) {
+    send(name, &duration.as_millis().to_string(), "ms", tags);
+}
+
+/// Sends a metric. Metrics that can't be sent are dropped, as they would be by the network anyway.
+fn send(name: &str, value: &str, metric_type: &str, tags: &[(&str, &str)]) {
+    let Some(client) = CLIENT.get() else {
+        return;
+    };
+
+    let line = format_metric(&client.settings, name, value, metric_type, tags);
+    if let Err(e) = client.socket.send(line.as_bytes()) {
+        debug!("Unable to send metric {line} to statsd: {e}");
+    }
+}
+
+/// Formats a metric as a statsd line, adding tags in the DogStatsD format if enabled.
+fn format_metric(settings: &StatsdConfig, name: &str, value: &str, metric_type: &str, tags: &[(&str, &str)]) -> String {
+    let mut line = format!("{}.{name}:{value}|{metric_type}", settings.prefix);
+    if settings.dogstatsd {
+        let tags: Vec<String> =
+            settings.tags.iter().cloned().chain(tags.iter().map(|(key, value)| format!("{key}:{value}"))).collect();
+        if !tags.is_empty() {
+            line.push_str("|#");
+            line.push_str(&tags.join(","));
+        }
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::format_metric, crate::config::StatsdConfig};
+
+    #[test]
+    fn formats_metrics_with_tags_only_for_dogstatsd() {
+        let mut settings: StatsdConfig = serde_yaml::from_str("{address: localhost:8125, tags: [env:home]}").unwrap();
+        assert_eq!(
+            format_metric(&settings, "changes.applied", "2", "c", &[("zone", "Z1")]),
+            "route53_ip_update.changes.applied:2|c"
+        );
+
+        settings.dogstatsd = true;
+        assert_eq!(
+            format_metric(&settings, "changes.applied", "2", "c", &[("zone", "Z1")]),
+            "route53_ip_update.changes.applied:2|c|#env:home,zone:Z1"
+        );
+        assert_eq!(
+            format_metric(&settings, "run.duration", "1500", "ms", &[]),
+            "route53_ip_update.run.duration:1500|ms|#env:home"
+        );
+    }
+}
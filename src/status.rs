@@ -0,0 +1,263 @@
+use {
+    hyper::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        service::{make_service_fn, service_fn},
+        Body, Method, Request, Response, Server, StatusCode,
+    },
+    log::info,
+    ring::constant_time::verify_slices_are_equal,
+    serde::Serialize,
+    std::{
+        collections::{BTreeMap, BTreeSet, VecDeque},
+        convert::Infallible,
+        net::{IpAddr, SocketAddr},
+        sync::{Arc, Mutex},
+        time::SystemTime,
+    },
+    tower::BoxError,
+};
+
+/// How many address changes and errors the status keeps.
+const HISTORY_LEN: usize = 20;
+
+/// The single-page dashboard, which renders the status endpoint's JSON.
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+/// The status of the updates, as served by the status endpoint.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct UpdateStatus {
+    /// The addresses found by the latest discovery.
+    pub(crate) discovered: BTreeSet<IpAddr>,
+
+    /// When the latest update finished, in RFC 3339 format.
+    pub(crate) last_update: Option<String>,
+
+    /// When the latest update that brought every zone up-to-date finished, in RFC 3339 format.
+    pub(crate) last_sync: Option<String>,
+
+    /// What went wrong in the latest update, if anything did.
+    pub(crate) last_error: Option<String>,
+
+    /// The addresses last published for each hostname.
+    pub(crate) published: BTreeMap<String, BTreeSet<IpAddr>>,
+
+    /// When each hostname's records were last changed, in RFC 3339 format.
+    pub(crate) last_changed: BTreeMap<String, String>,
+
+    /// The most recent changes to the discovered addresses, oldest first.
+    pub(crate) history: VecDeque<AddressChange>,
+
+    /// The most recent errors, oldest first.
+    pub(crate) recent_errors: VecDeque<RecentError>,
+}
+
+/// A change to the discovered addresses.
+#[derive(Debug, Serialize)]
+pub(crate) struct AddressChange {
+    /// When the change was discovered, in RFC 3339 format.
+    pub(crate) time: String,
+
+    /// The addresses discovered.
+    pub(crate) addresses: BTreeSet<IpAddr>,
+}
+
+/// An error from an update.
+#[derive(Debug, Serialize)]
+pub(crate) struct RecentError {
+    /// When the update finished, in RFC 3339 format.
+    pub(crate) time: String,
+
+    /// What went wrong.
+    pub(crate) message: String,
+}
+
+impl UpdateStatus {
+    /// Records the addresses found by a discovery, adding them to the history if they changed.
+    pub(crate) fn record_discovered(&mut self, addresses: BTreeSet<IpAddr>) {
+        if self.history.back().is_none_or(|change| change.addresses != addresses) {
+            push_bounded(
+                &mut self.history,
+                AddressChange {
+                    time: now(),
+                    addresses: addresses.clone(),
+                },
+            );
+        }
+
+        self.discovered = addresses;
+    }
+
+    /// Records that hostnames' records were changed.
+    pub(crate) fn record_changes(&mut self, hostnames: &[String]) {
+        let now = now();
+        for hostname in hostnames {
+            self.last_changed.insert(hostname.trim_end_matches('.').to_string(), now.clone());
+        }
+    }
+
+    /// Records the end of an update, along with its errors, if any.
+    pub(crate) fn finish_update(&mut self, errors: &[String]) {
+        let now = now();
+        if errors.is_empty() {
+            self.last_sync = Some(now.clone());
+            self.last_error = None;
+        } else {
+            self.last_error = Some(errors.join("; "));
+            for message in errors {
+                push_bounded(
+                    &mut self.recent_errors,
+                    RecentError {
+                        time: now.clone(),
+                        message: message.clone(),
+                    },
+                );
+            }
+        }
+
+        self.last_update = Some(now);
+    }
+}
+
+/// Returns the current time in RFC 3339 format.
+fn now() -> String {
+    humantime::format_rfc3339_seconds(SystemTime::now()).to_string()
+}
+
+/// Appends an item, dropping the oldest ones beyond the history length.
+fn push_bounded<T>(items: &mut VecDeque<T>, item: T) {
+    items.push_back(item);
+    while items.len() > HISTORY_LEN {
+        items.pop_front();
+    }
+}
+
+/// The status of the updates, shared between the updates and the status endpoint.
+pub(crate) type SharedStatus = Arc<Mutex<UpdateStatus>>;
+
+/// Serves the status of the updates as JSON, along with a dashboard showing it, on the address until the process is
+/// stopped. If a token is given, requests must include it.
+pub(crate) async fn serve_status(
+    listen: SocketAddr,
+    status: SharedStatus,
+    token: Option<String>,
+) -> Result<(), BoxError> {
+    let token = Arc::new(token);
+    let make_service = make_service_fn(move |_| {
+        let status = status.clone();
+        let token = token.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let status = status.clone();
+                let token = token.clone();
+                async move { Ok::<_, Infallible>(handle(&request, &status, token.as_deref())) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&listen)?.serve(make_service);
+    info!("Serving the update status on http://{}/status", server.local_addr());
+    server.await?;
+    Ok(())
+}
+
+/// Responds to a request for the status or the dashboard.
+fn handle(request: &Request<Body>, status: &SharedStatus, token: Option<&str>) -> Response<Body> {
+    let (code, content_type, body) = match (request.method(), request.uri().path()) {
+        (&Method::GET, _) if !is_authorized(request, token) => {
+            (StatusCode::UNAUTHORIZED, "application/json", br#"{"error": "unauthorized"}"#.to_vec())
+        }
+        (&Method::GET, "/" | "/status") => {
+            let body = serde_json::to_vec_pretty(&*status.lock().unwrap()).unwrap_or_default();
+            (StatusCode::OK, "application/json", body)
+        }
+        (&Method::GET, "/dashboard") => {
+            (StatusCode::OK, "text/html; charset=utf-8", DASHBOARD_HTML.as_bytes().to_vec())
+        }
+        (&Method::GET, _) => (StatusCode::NOT_FOUND, "application/json", br#"{"error": "not found"}"#.to_vec()),
+        _ => (StatusCode::METHOD_NOT_ALLOWED, "application/json", br#"{"error": "method not allowed"}"#.to_vec()),
+    };
+
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = code;
+    response.headers_mut().insert(CONTENT_TYPE, content_type.parse().unwrap());
+    response
+}
+
+/// Indicates whether a request carries the token, either as a bearer token or, so the dashboard can be bookmarked, in
+/// the `token` query parameter. Any request is authorized if there's no token.
+fn is_authorized(request: &Request<Body>, token: Option<&str>) -> bool {
+    let Some(token) = token else {
+        return true;
+    };
+
+    let bearer = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let query = request.uri().query().and_then(|query| {
+        form_urlencoded::parse(query.as_bytes()).find(|(key, _)| key == "token").map(|(_, value)| value.into_owned())
+    });
+
+    bearer.into_iter().chain(query).any(|given| verify_slices_are_equal(given.as_bytes(), token.as_bytes()).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{handle, SharedStatus},
+        hyper::{body::to_bytes, Body, Request, StatusCode},
+        serde_json::Value,
+    };
+
+    #[tokio::test]
+    async fn serves_the_status_as_json() {
+        let status = SharedStatus::default();
+        {
+            let mut status = status.lock().unwrap();
+            status.record_discovered(["192.0.2.1".parse().unwrap()].into());
+            status.record_discovered(["192.0.2.1".parse().unwrap()].into());
+            status.published.entry("home.example.com".to_string()).or_default().insert("192.0.2.1".parse().unwrap());
+            status.record_changes(&["home.example.com.".to_string()]);
+            status.finish_update(&["zone Z2: AccessDenied".to_string()]);
+        }
+
+        let response = handle(&Request::get("/status").body(Body::empty()).unwrap(), &status, None);
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(&to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["discovered"], serde_json::json!(["192.0.2.1"]));
+        assert_eq!(body["published"]["home.example.com"], serde_json::json!(["192.0.2.1"]));
+        assert_eq!(body["last-error"], "zone Z2: AccessDenied");
+        assert!(body["last-update"].is_string());
+        assert!(body["last-sync"].is_null());
+        assert!(body["last-changed"]["home.example.com"].is_string());
+        assert_eq!(body["history"].as_array().unwrap().len(), 1);
+        assert_eq!(body["history"][0]["addresses"], serde_json::json!(["192.0.2.1"]));
+        assert_eq!(body["recent-errors"][0]["message"], "zone Z2: AccessDenied");
+
+        let response = handle(&Request::get("/other").body(Body::empty()).unwrap(), &status, None);
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn requires_the_token_if_one_is_set() {
+        let status = SharedStatus::default();
+        let token = Some("s3cret");
+        let get = |uri: &str, authorization: Option<&str>| {
+            let mut request = Request::get(uri);
+            if let Some(authorization) = authorization {
+                request = request.header("Authorization", authorization);
+            }
+
+            handle(&request.body(Body::empty()).unwrap(), &status, token).status()
+        };
+
+        assert_eq!(get("/dashboard", None), StatusCode::UNAUTHORIZED);
+        assert_eq!(get("/status?token=wrong", None), StatusCode::UNAUTHORIZED);
+        assert_eq!(get("/status", Some("Bearer wrong")), StatusCode::UNAUTHORIZED);
+        assert_eq!(get("/dashboard?token=s3cret", None), StatusCode::OK);
+        assert_eq!(get("/status", Some("Bearer s3cret")), StatusCode::OK);
+    }
+}
@@ -0,0 +1,354 @@
+use {
+    crate::error::Route53IpUpdateError,
+    log::debug,
+    rand::random,
+    schemars::JsonSchema,
+    serde::{Deserialize, Serialize},
+    std::{
+        fmt::{Display, Formatter, Result as FmtResult},
+        net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+        time::Duration,
+    },
+    tokio::{
+        net::{lookup_host, UdpSocket},
+        time::{timeout, Instant},
+    },
+    tower::BoxError,
+};
+
+/// The STUN magic cookie (RFC 5389).
+const MAGIC_COOKIE: u32 = 0x2112_a442;
+
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_RESPONSE: u16 = 0x0101;
+
+const MAPPED_ADDRESS: u16 = 0x0001;
+const CHANGE_REQUEST: u16 = 0x0003;
+const CHANGED_ADDRESS: u16 = 0x0005;
+const XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const OTHER_ADDRESS: u16 = 0x802c;
+
+/// The CHANGE-REQUEST flags asking the server to reply from its other address and port.
+const CHANGE_IP_AND_PORT: u32 = 0x06;
+
+/// How often an unanswered request is sent again.
+const RETRANSMIT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The shared address space used by carrier-grade NAT, 100.64.0.0/10 (RFC 6598).
+const SHARED_ADDRESS_SPACE: (Ipv4Addr, u32) = (Ipv4Addr::new(100, 64, 0, 0), 0xffc0_0000);
+
+/// The kind of NAT between this host and the internet, as far as STUN can tell.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum NatType {
+    /// No NAT: the interface address is the public address.
+    Open,
+
+    /// Any host can reach a port once it's been mapped.
+    FullCone,
+
+    /// A mapped port is only reachable from hosts it's been used to contact.
+    RestrictedCone,
+
+    /// The same mapping is used for every destination, but the STUN server can't tell how incoming packets are
+    /// filtered.
+    Cone,
+
+    /// A different mapping is used for each destination.
+    Symmetric,
+
+    /// The interface address is in the carrier-grade NAT shared address space, so the public address is shared with
+    /// the ISP's other customers.
+    Cgnat,
+}
+
+impl NatType {
+    /// Returns whether hosts on the internet can usually connect to the published address without port forwarding.
+    pub(crate) fn is_reachable(&self) -> bool {
+        matches!(self, Self::Open | Self::FullCone)
+    }
+
+    /// Explains what the NAT means for the published address.
+    pub(crate) fn explanation(&self) -> &'static str {
+        match self {
+            Self::Open => "the published address reaches this host directly",
+            Self::FullCone => "the published address reaches this host on ports it has opened",
+            Self::RestrictedCone | Self::Cone => {
+                "the published address only reaches this host on ports forwarded by the router"
+            }
+            Self::Symmetric => {
+                "the NAT maps each connection differently, so the published address only reaches this host on ports \
+                 forwarded by the router"
+            }
+            Self::Cgnat => {
+                "the published address is shared with the ISP's other customers and likely won't reach this host at all"
+            }
+        }
+    }
+}
+
+impl Display for NatType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Open => write!(f, "no NAT"),
+            Self::FullCone => write!(f, "full-cone NAT"),
+            Self::RestrictedCone => write!(f, "restricted-cone NAT"),
+            Self::Cone => write!(f, "cone NAT"),
+            Self::Symmetric => write!(f, "symmetric NAT"),
+            Self::Cgnat => write!(f, "carrier-grade NAT"),
+        }
+    }
+}
+
+/// What the STUN servers observed about this host's IPv4 traffic.
+#[derive(Debug)]
+pub(crate) struct NatObservation {
+    /// The interface address the traffic was sent from.
+    pub(crate) local: IpAddr,
+
+    /// The address and port the first server saw.
+    pub(crate) mapped: SocketAddr,
+
+    /// The address and port the second server saw, if a second server answered.
+    pub(crate) mapped_elsewhere: Option<SocketAddr>,
+
+    /// Whether the first server managed to reply from a different address and port, if it supports that.
+    pub(crate) unsolicited_reply_received: Option<bool>,
+}
+
+impl NatObservation {
+    /// Classifies the NAT from the observation.
+    pub(crate) fn nat_type(&self) -> NatType {
+        if self.local == self.mapped.ip() {
+            return NatType::Open;
+        }
+
+        if let IpAddr::V4(local) = self.local {
+            let (network, mask) = SHARED_ADDRESS_SPACE;
+            if u32::from(local) & mask == u32::from(network) {
+                return NatType::Cgnat;
+            }
+        }
+
+        if self.mapped_elsewhere.is_some_and(|mapped| mapped != self.mapped) {
+            return NatType::Symmetric;
+        }
+
+        match self.unsolicited_reply_received {
+            Some(true) => NatType::FullCone,
+            Some(false) => NatType::RestrictedCone,
+            None => NatType::Cone,
+        }
+    }
+}
+
+/// Finds out how this host's IPv4 traffic is translated by asking the STUN servers (`host:port`) what address they see
+/// it coming from. At least one server is needed; with two or more, symmetric NAT can be told apart from cone NAT.
+pub(crate) async fn observe_nat(servers: &[String], timeout: Duration) -> Result<NatObservation, BoxError> {
+    let mut addrs = Vec::with_capacity(servers.len());
+    for server in servers {
+        match lookup_host(server.as_str()).await {
+            Ok(found) => addrs.extend(found.filter(SocketAddr::is_ipv4).take(1)),
+            Err(e) => debug!("Unable to look up STUN server {server}: {e}"),
+        }
+    }
+
+    let Some(&first) = addrs.first() else {
+        return Err(Route53IpUpdateError::StunFailed("none of the STUN servers could be found".to_string()).into());
+    };
+
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+
+    // The interface address the traffic goes out of; connecting a UDP socket doesn't send anything.
+    let route = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    route.connect(first).await?;
+    let local = route.local_addr()?.ip();
+
+    let reply = binding(&socket, first, false, timeout)
+        .await?
+        .ok_or_else(|| Route53IpUpdateError::StunFailed(format!("no reply from STUN server {first}")))?;
+
+    let mut mapped_elsewhere = None;
+    for &server in addrs.iter().skip(1).filter(|&&server| server.ip() != first.ip()) {
+        if let Some(other) = binding(&socket, server, false, timeout).await? {
+            mapped_elsewhere = Some(other.mapped);
+            break;
+        }
+    }
+
+    // Only servers with a second address (RFC 5780) can test whether unsolicited packets get through.
+    let unsolicited_reply_received = match reply.other {
+        Some(_) => Some(binding(&socket, first, true, timeout).await?.is_some()),
+        None => None,
+    };
+
+    Ok(NatObservation {
+        local,
+        mapped: reply.mapped,
+        mapped_elsewhere,
+        unsolicited_reply_received,
+    })
+}
+
+/// A STUN server's reply to a binding request.
+#[derive(Debug, Eq, PartialEq)]
+struct BindingReply {
+    /// The address and port the server saw the request come from.
+    mapped: SocketAddr,
+
+    /// The server's other address, if it has one.
+    other: Option<SocketAddr>,
+}
+
+/// Sends a binding request to the server, returning its reply or `None` if it doesn't reply in time. With
+/// `change_address`, the server is asked to reply from its other address and port.
+async fn binding(
+    socket: &UdpSocket,
+    server: SocketAddr,
+    change_address: bool,
+    wait: Duration,
+) -> Result<Option<BindingReply>, BoxError> {
+    let transaction_id: [u8; 12] = random();
+    let request = binding_request(&transaction_id, change_address);
+    let deadline = Instant::now() + wait;
+    let mut buf = [0; 1024];
+
+    while Instant::now() < deadline {
+        socket.send_to(&request, server).await?;
+        let retransmit_at = (Instant::now() + RETRANSMIT_INTERVAL).min(deadline);
+        while let Ok(received) = timeout(retransmit_at - Instant::now(), socket.recv_from(&mut buf)).await {
+            let (len, _) = received?;
+            if let Some(reply) = parse_binding_reply(&buf[..len], &transaction_id) {
+                return Ok(Some(reply));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn binding_request(transaction_id: &[u8; 12], change_address: bool) -> Vec<u8> {
+    let attributes_len: u16 = if change_address {
+        8
+    } else {
+        0
+    };
+    let mut request = Vec::with_capacity(20 + usize::from(attributes_len));
+    request.extend(BINDING_REQUEST.to_be_bytes());
+    request.extend(attributes_len.to_be_bytes());
+    request.extend(MAGIC_COOKIE.to_be_bytes());
+    request.extend(transaction_id);
+    if change_address {
+        request.extend(CHANGE_REQUEST.to_be_bytes());
+        request.extend(4u16.to_be_bytes());
+        request.extend(CHANGE_IP_AND_PORT.to_be_bytes());
+    }
+
+    request
+}
+
+/// Parses a binding response to the transaction, ignoring anything else.
+fn parse_binding_reply(message: &[u8], transaction_id: &[u8; 12]) -> Option<BindingReply> {
+    let header = message.get(..20)?;
+    if u16::from_be_bytes([header[0], header[1]]) != BINDING_RESPONSE || &header[8..20] != transaction_id {
+        return None;
+    }
+
+    let mut mapped = None;
+    let mut xor_mapped = None;
+    let mut other = None;
+    let mut attributes = message.get(20..)?;
+    while attributes.len() >= 4 {
+        let kind = u16::from_be_bytes([attributes[0], attributes[1]]);
+        let len = usize::from(u16::from_be_bytes([attributes[2], attributes[3]]));
+        let value = attributes.get(4..4 + len)?;
+        match kind {
+            MAPPED_ADDRESS => mapped = parse_address(value, None),
+            XOR_MAPPED_ADDRESS => xor_mapped = parse_address(value, Some(transaction_id)),
+            OTHER_ADDRESS | CHANGED_ADDRESS => other = parse_address(value, None),
+            _ => (),
+        }
+
+        // Attributes are padded to a multiple of four bytes.
+        attributes = attributes.get((4 + len).next_multiple_of(4)..).unwrap_or_default();
+    }
+
+    Some(BindingReply {
+        mapped: xor_mapped.or(mapped)?,
+        other,
+    })
+}
+
+/// Parses an address attribute, undoing the XOR with the magic cookie and transaction ID for XOR-MAPPED-ADDRESS.
+fn parse_address(value: &[u8], xor_with: Option<&[u8; 12]>) -> Option<SocketAddr> {
+    let mut mask = [0; 16];
+    if let Some(transaction_id) = xor_with {
+        mask[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        mask[4..].copy_from_slice(transaction_id);
+    }
+
+    let port = u16::from_be_bytes([value.get(2)? ^ mask[0], value.get(3)? ^ mask[1]]);
+    let addr = match value.get(1)? {
+        1 => {
+            let octets: [u8; 4] = value.get(4..8)?.try_into().ok()?;
+            IpAddr::V4(Ipv4Addr::from(u32::from_be_bytes(octets) ^ u32::from_be_bytes(mask[..4].try_into().ok()?)))
+        }
+        2 => {
+            let octets: [u8; 16] = value.get(4..20)?.try_into().ok()?;
+            IpAddr::V6(Ipv6Addr::from(u128::from_be_bytes(octets) ^ u128::from_be_bytes(mask)))
+        }
+        _ => return None,
+    };
+
+    Some(SocketAddr::new(addr, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{binding_request, parse_binding_reply, BindingReply, NatObservation, NatType},
+        std::net::SocketAddr,
+    };
+
+    #[test]
+    fn parses_xor_mapped_and_other_addresses() {
+        let transaction_id = [7; 12];
+        let request = binding_request(&transaction_id, true);
+        assert_eq!(request.len(), 28);
+        assert_eq!(&request[..4], [0, 1, 0, 8]);
+
+        let mut reply = vec![0x01, 0x01, 0, 24, 0x21, 0x12, 0xa4, 0x42];
+        reply.extend(transaction_id);
+        // XOR-MAPPED-ADDRESS 1.1.1.1:54321
+        reply.extend([0, 0x20, 0, 8, 0, 1, 0xd4 ^ 0x21, 0x31 ^ 0x12, 1 ^ 0x21, 1 ^ 0x12, 1 ^ 0xa4, 1 ^ 0x42]);
+        // OTHER-ADDRESS 8.8.4.4:3479
+        reply.extend([0x80, 0x2c, 0, 8, 0, 1, 0x0d, 0x97, 8, 8, 4, 4]);
+
+        assert_eq!(
+            parse_binding_reply(&reply, &transaction_id),
+            Some(BindingReply {
+                mapped: "1.1.1.1:54321".parse().unwrap(),
+                other: Some("8.8.4.4:3479".parse().unwrap()),
+            })
+        );
+        assert_eq!(parse_binding_reply(&reply, &[8; 12]), None);
+    }
+
+    #[test]
+    fn classifies_nat_types() {
+        let addr = |addr: &str| -> SocketAddr { addr.parse().unwrap() };
+        let observation = |local: &str, elsewhere: Option<&str>, unsolicited: Option<bool>| NatObservation {
+            local: local.parse().unwrap(),
+            mapped: addr("1.1.1.1:40000"),
+            mapped_elsewhere: elsewhere.map(addr),
+            unsolicited_reply_received: unsolicited,
+        };
+
+        assert_eq!(observation("1.1.1.1", None, None).nat_type(), NatType::Open);
+        assert_eq!(observation("100.72.1.9", None, None).nat_type(), NatType::Cgnat);
+        assert_eq!(observation("192.168.1.9", Some("1.1.1.1:40001"), Some(true)).nat_type(), NatType::Symmetric);
+        assert_eq!(observation("192.168.1.9", Some("1.1.1.1:40000"), Some(true)).nat_type(), NatType::FullCone);
+        assert_eq!(observation("192.168.1.9", None, Some(false)).nat_type(), NatType::RestrictedCone);
+        assert_eq!(observation("192.168.1.9", None, None).nat_type(), NatType::Cone);
+    }
+}
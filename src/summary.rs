@@ -0,0 +1,190 @@
+use {
+    crate::{
+        config::Route53ZoneConfig,
+        diff::{RecordSetDiff, RecordSetSummary},
+    },
+    std::{
+        fmt::Write as _,
+        io::{stdout, Write},
+        time::Duration,
+    },
+};
+
+/// The column headings of the run summary.
+const HEADINGS: [&str; 8] = ["HOSTNAME", "ZONE", "TYPE", "ACTION", "VALUES", "TTL", "STATUS", "DURATION"];
+
+/// What happened to each hostname during a run, for printing as a table once the run is over.
+#[derive(Debug, Default)]
+pub(crate) struct RunSummary {
+    rows: Vec<[String; 8]>,
+}
+
+impl RunSummary {
+    /// Adds the outcome of updating a zone: a row for each record set changed, and one for each of the zone's hostnames
+    /// left unchanged. If the update failed, `diffs` is `None` and each of the zone's hostnames is marked as failed.
+    pub(crate) fn add_zone(&mut self, zone: &Route53ZoneConfig, diffs: Option<&[RecordSetDiff]>, duration: Duration) {
+        let duration = format!("{:.1}s", duration.as_secs_f64());
+        let hostnames = zone.hostnames.iter().flat_map(|hc| hc.get_hostnames()).map(|h| h.trim_end_matches('.'));
+        let (diffs, status) = match diffs {
+            Some(diffs) => (diffs, "ok"),
+            None => (&[][..], "failed"),
+        };
+
+        for diff in diffs {
+            let old = diff.old.as_ref();
+            let new = diff.new.as_ref();
+            let action = match (old, new) {
+                (None, _) => "create",
+                (_, None) => "delete",
+                _ => "update",
+            };
+            let ttl = match (old.and_then(|old| old.ttl), new.and_then(|new| new.ttl)) {
+                (Some(old), Some(new)) if old != new => format!("{old} → {new}"),
+                (_, Some(ttl)) | (Some(ttl), None) => ttl.to_string(),
+                (None, None) => "-".to_string(),
+            };
+            self.rows.push([
+                diff.hostname.trim_end_matches('.').to_string(),
+                zone.zone_id.clone(),
+                diff.record_type.clone(),
+                action.to_string(),
+                format!("{} → {}", values(old), values(new)),
+                ttl,
+                status.to_string(),
+                duration.clone(),
+            ]);
+        }
+
+        for hostname in hostnames {
+            if diffs.iter().any(|diff| diff.hostname.trim_end_matches('.') == hostname) {
+                continue;
+            }
+
+            let action = if status == "ok" {
+                "none"
+            } else {
+                "-"
+            };
+            self.rows.push([
+                hostname.to_string(),
+                zone.zone_id.clone(),
+                "-".to_string(),
+                action.to_string(),
+                "-".to_string(),
+                "-".to_string(),
+                status.to_string(),
+                duration.clone(),
+            ]);
+        }
+    }
+
+    /// Adds a row for each of a disabled zone's hostnames, in the zone and its mirrors, noting that they were skipped.
+    pub(crate) fn add_disabled_zone(&mut self, zone: &Route53ZoneConfig) {
+        let zone_ids = [&zone.zone_id].into_iter().chain(zone.mirrors.iter().map(|mirror| &mirror.zone_id));
+        for zone_id in zone_ids {
+            for hostname in zone.hostnames.iter().flat_map(|hc| hc.get_hostnames()) {
+                self.rows.push([
+                    hostname.trim_end_matches('.').to_string(),
+                    zone_id.clone(),
+                    "-".to_string(),
+                    "-".to_string(),
+                    "-".to_string(),
+                    "-".to_string(),
+                    "disabled".to_string(),
+                    "-".to_string(),
+                ]);
+            }
+        }
+    }
+
+    /// Indicates whether any hostname was changed or failed to update.
+    pub(crate) fn is_eventful(&self) -> bool {
+        self.rows.iter().any(|row| row[3] != "none" && row[6] != "disabled")
+    }
+
+    /// Formats the summary as a table, sorted by hostname and zone.
+    pub(crate) fn format(&self) -> String {
+        let mut rows: Vec<&[String; 8]> = self.rows.iter().collect();
+        rows.sort_by(|a, b| (&a[0], &a[1]).cmp(&(&b[0], &b[1])));
+
+        let mut widths = HEADINGS.map(|heading| heading.chars().count());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let mut out = String::new();
+        let headings = HEADINGS.map(String::from);
+        for row in [&headings].into_iter().chain(rows) {
+            let cells: Vec<String> = row.iter().zip(widths).map(|(cell, width)| format!("{cell:<width$}")).collect();
+            let _ = writeln!(out, "{}", cells.join("  ").trim_end());
+        }
+
+        out
+    }
+
+    /// Prints the summary to stdout, if there's anything in it.
+    pub(crate) fn print(&self) {
+        if !self.rows.is_empty() {
+            let _ = stdout().lock().write_all(self.format().as_bytes());
+        }
+    }
+}
+
+/// Returns a record set's values for the summary, or `-` if there's no record set.
+fn values(summary: Option<&RecordSetSummary>) -> String {
+    match summary {
+        Some(summary) if !summary.values.is_empty() => summary.values.join(", "),
+        _ => "-".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::RunSummary,
+        crate::{
+            config::Route53ZoneConfig,
+            diff::{RecordSetDiff, RecordSetSummary},
+        },
+        std::time::Duration,
+    };
+
+    fn zone(zone_id: &str, hostnames: &[&str]) -> Route53ZoneConfig {
+        serde_yaml::from_str(&format!("zone-id: {zone_id}\nhostnames: [{}]", hostnames.join(", "))).unwrap()
+    }
+
+    #[test]
+    fn tabulates_changes_unchanged_hostnames_and_failures() {
+        let diffs = [RecordSetDiff {
+            hostname: "home.example.com".to_string(),
+            record_type: "A".to_string(),
+            old: Some(RecordSetSummary::new(Some(300), ["192.0.2.1"])),
+            new: Some(RecordSetSummary::new(Some(60), ["192.0.2.2"])),
+        }];
+
+        let mut summary = RunSummary::default();
+        summary.add_zone(
+            &zone("Z1", &["home.example.com", "vpn.example.com"]),
+            Some(&diffs),
+            Duration::from_millis(1250),
+        );
+        summary.add_zone(&zone("Z2", &["home.example.org"]), None, Duration::from_millis(300));
+        assert!(summary.is_eventful());
+
+        let expected = "\
+HOSTNAME          ZONE  TYPE  ACTION  VALUES                 TTL       STATUS  DURATION
+home.example.com  Z1    A     update  192.0.2.1 → 192.0.2.2  300 → 60  ok      1.2s
+home.example.org  Z2    -     -       -                      -         failed  0.3s
+vpn.example.com   Z1    -     none    -                      -         ok      1.2s
+";
+        assert_eq!(summary.format(), expected);
+
+        let mut unchanged = RunSummary::default();
+        unchanged.add_zone(&zone("Z1", &["home.example.com"]), Some(&[]), Duration::ZERO);
+        unchanged.add_disabled_zone(&zone("Z3", &["old.example.net"]));
+        assert!(!unchanged.is_eventful());
+        assert!(unchanged.format().contains("old.example.net   Z3    -     -       -       -    disabled  -\n"));
+    }
+}
@@ -1,6 +1,14 @@
 use {
     crate::error::Route53IpUpdateError,
-    serde::{Deserialize, Deserializer, Serialize, Serializer, de::{self, Visitor, Unexpected}},
+    schemars::{
+        gen::SchemaGenerator,
+        schema::{InstanceType, Schema, SchemaObject, SubschemaValidation},
+        JsonSchema,
+    },
+    serde::{
+        de::{self, Unexpected, Visitor},
+        Deserialize, Deserializer, Serialize, Serializer,
+    },
     std::{
         fmt::{Display, Formatter, Result as FmtResult},
         str::FromStr,
@@ -29,9 +37,24 @@ impl From<Ttl> for i64 {
 impl FromStr for Ttl {
     type Err = Route53IpUpdateError;
 
+    /// Parses a TTL, either as a raw number of seconds (`300`) or as a duration with units (`5m`, `1h 30m`).
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.parse::<i64>() {
-            Ok(ttl) if ttl > 0 => Ok(Self(ttl)),
+        if let Ok(ttl) = s.parse::<i64>() {
+            return if ttl > 0 {
+                Ok(Self(ttl))
+            } else {
+                Err(Route53IpUpdateError::InvalidTtl(s.to_string()))
+            };
+        }
+
+        // Route 53 TTLs have a granularity of one second; reject anything that can't be represented exactly.
+        match humantime::parse_duration(s) {
+            Ok(duration) if duration.subsec_nanos() == 0 && duration.as_secs() > 0 => {
+                match i64::try_from(duration.as_secs()) {
+                    Ok(seconds) => Ok(Self(seconds)),
+                    Err(_) => Err(Route53IpUpdateError::InvalidTtl(s.to_string())),
+                }
+            }
             _ => Err(Route53IpUpdateError::InvalidTtl(s.to_string())),
         }
     }
@@ -45,7 +68,7 @@ impl Display for Ttl {
 
 impl<'de> Deserialize<'de> for Ttl {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        deserializer.deserialize_i64(TtlVisitor)
+        deserializer.deserialize_any(TtlVisitor)
     }
 }
 
@@ -54,7 +77,7 @@ impl<'de> Visitor<'de> for TtlVisitor {
     type Value = Ttl;
 
     fn expecting(&self, formatter: &mut Formatter) -> FmtResult {
-        formatter.write_str("positive integer representing the time-to-live in seconds")
+        formatter.write_str("positive integer representing the time-to-live in seconds, or a duration such as \"5m\"")
     }
 
     fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
@@ -64,10 +87,47 @@ impl<'de> Visitor<'de> for TtlVisitor {
             Ok(Ttl(value))
         }
     }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        match i64::try_from(value) {
+            Ok(value) => self.visit_i64(value),
+            Err(_) => Err(E::invalid_value(Unexpected::Unsigned(value), &"positive integer")),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        value.parse().map_err(|_| E::invalid_value(Unexpected::Str(value), &"positive duration in whole seconds"))
+    }
 }
 
 impl Serialize for Ttl {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         serializer.serialize_i64(self.0)
     }
-}
\ No newline at end of file
+}
+
+impl JsonSchema for Ttl {
+    fn schema_name() -> String {
+        "Ttl".to_string()
+    }
+
+    /// TTLs may be given either as a positive number of seconds or as a duration string such as "5m".
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let mut seconds = gen.subschema_for::<i64>().into_object();
+        seconds.number().minimum = Some(1.0);
+
+        let duration = SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            ..Default::default()
+        };
+
+        SchemaObject {
+            subschemas: Some(Box::new(SubschemaValidation {
+                any_of: Some(vec![seconds.into(), duration.into()]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
@@ -1,131 +1,402 @@
 use {
     crate::{
-        config::{HostnameConfig, Route53ZoneConfig},
+        config::{Config, HostnameConfig, Route53ZoneConfig, VerifyConfig},
+        diff::{RecordSetDiff, RecordSetSummary},
         error::Route53IpUpdateError,
+        provider::{
+            publish_targets, timestamp_record_hostnames, timestamp_record_name, timestamp_record_text, DnsProvider,
+        },
+        route53_api::Route53Api,
+        shutdown::{shutdown_requested, shutdown_signal},
+        snapshot::{roll_back, save_snapshot, take_snapshot, Snapshot},
+        statsd,
         ttl::Ttl,
+        verify::verify_changes,
     },
+    async_trait::async_trait,
     aws_sdk_route53::{
-        model::{Change, ChangeAction, ChangeBatch, ChangeStatus, ResourceRecord, ResourceRecordSet, RrType},
+        model::{
+            resource_record_set, Change, ChangeAction, ChangeBatch, ChangeInfo, ChangeStatus, ResourceRecord,
+            ResourceRecordSet, RrType,
+        },
         Client as Route53Client,
     },
     futures::stream::{FuturesUnordered, StreamExt},
     log::{debug, error, info},
-    std::{collections::HashSet, net::IpAddr, time::Duration},
+    std::{
+        cmp::max,
+        collections::{HashMap, HashSet},
+        net::IpAddr,
+        path::PathBuf,
+        time::{Duration, Instant, SystemTime},
+    },
     tokio::time::sleep,
     tower::BoxError,
 };
 
 const DEFAULT_TTL: Ttl = Ttl::from_seconds(300);
 
-pub(crate) async fn update_zone(
-    route53: Route53Client,
-    zone_config: &Route53ZoneConfig,
-    default_ttl: Option<Ttl>,
-    desired_ipv4: &HashSet<IpAddr>,
-    desired_ipv6: &HashSet<IpAddr>,
-) -> Result<(), BoxError> {
-    let mut f = FuturesUnordered::new();
-    let mut all_changes = Vec::new();
+/// The maximum length of a ChangeBatch comment accepted by Route 53.
+const MAX_CHANGE_COMMENT_LEN: usize = 256;
 
-    let default_ttl = match zone_config.ttl {
-        Some(ttl) => Some(ttl),
-        None => default_ttl,
-    };
+/// The most resource records Route 53 accepts in a ChangeBatch. The records of an UPSERT count twice.
+const MAX_CHANGE_BATCH_RECORDS: usize = 1000;
 
-    for hostname_config in &zone_config.hostnames {
-        debug!(
-            "Getting changes in Route 53 zone {} for hostname {}",
-            zone_config.zone_id,
-            hostname_config.get_hostname()
-        );
-        f.push(get_changes_for_hostname(
-            route53.clone(),
-            &zone_config.zone_id,
-            hostname_config,
-            desired_ipv4,
-            desired_ipv6,
-            default_ttl,
-        ));
-    }
-
-    while let Some(changes) = f.next().await {
-        match changes {
-            Ok(changes) => all_changes.extend(changes),
-            Err(e) => {
-                error!("Failed to get changes for hostname: {e}");
-                return Err(e);
+/// The most characters of record values Route 53 accepts in a ChangeBatch. The values of an UPSERT count twice.
+const MAX_CHANGE_BATCH_VALUE_LEN: usize = 32000;
+
+/// How long to wait for a Route 53 change to propagate. Changes normally propagate within a minute.
+const PROPAGATION_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// The changes needed to bring a hostname up-to-date.
+#[derive(Debug, Default)]
+pub(crate) struct HostnameChanges {
+    /// Changes that lower the TTL of existing records before they are replaced. These must be applied and allowed to
+    /// expire before `changes` is applied.
+    pub(crate) ttl_lowering: Vec<Change>,
+
+    /// The changes that publish the desired addresses.
+    pub(crate) changes: Vec<Change>,
+
+    /// The longest TTL (in seconds) of the records being lowered, i.e. how long to wait after lowering before
+    /// applying `changes`.
+    pub(crate) ttl_lowering_wait: i64,
+
+    /// A summary of `changes`, for display.
+    pub(crate) diffs: Vec<RecordSetDiff>,
+}
+
+/// The changes needed to bring a Route 53 zone up-to-date.
+pub(crate) struct Route53Plan {
+    /// The changes for all of the zone's hostnames.
+    changes: HostnameChanges,
+
+    /// The comment attached to the change batches.
+    comment: String,
+}
+
+/// Changes submitted to a Route 53 zone.
+pub(crate) struct Route53Pending {
+    /// The status of the change when it was submitted.
+    change: ChangeInfo,
+
+    /// A summary of the changes, to verify once they've propagated.
+    diffs: Vec<RecordSetDiff>,
+
+    /// The record sets as they were before the change, to roll back to if verification fails.
+    before: Option<Snapshot>,
+}
+
+/// Publishes hostnames to a Route 53 hosted zone.
+pub(crate) struct Route53Provider<R: Route53Api = Route53Client> {
+    route53: R,
+    zone_id: String,
+
+    /// The directory the record sets are saved to before they're changed, if any.
+    snapshot_dir: Option<PathBuf>,
+
+    /// How changes are checked once they've propagated, if they are.
+    verify: Option<VerifyConfig>,
+}
+
+impl<R: Route53Api> Route53Provider<R> {
+    pub(crate) fn new(route53: R, zone_id: &str) -> Self {
+        Self {
+            route53,
+            zone_id: zone_id.to_string(),
+            snapshot_dir: None,
+            verify: None,
+        }
+    }
+
+    /// Saves the record sets to a snapshot in the directory before changing them.
+    pub(crate) fn with_snapshot_dir(mut self, snapshot_dir: Option<PathBuf>) -> Self {
+        self.snapshot_dir = snapshot_dir;
+        self
+    }
+
+    /// Checks changes once they've propagated, rolling them back if the check fails and `rollback` is set.
+    pub(crate) fn with_verify(mut self, verify: Option<VerifyConfig>) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Returns the Route 53 client used for this zone.
+    pub(crate) fn client(&self) -> &R {
+        &self.route53
+    }
+}
+
+#[async_trait]
+impl<R: Route53Api> DnsProvider for Route53Provider<R> {
+    type Plan = Route53Plan;
+    type Pending = Route53Pending;
+
+    async fn list(&self, hostname: &str) -> Result<(HashSet<IpAddr>, HashSet<IpAddr>), BoxError> {
+        get_hostname_addresses(self.route53.clone(), &self.zone_id, hostname).await
+    }
+
+    async fn plan(
+        &self,
+        config: &Config,
+        zone_config: &Route53ZoneConfig,
+        desired_ipv4: &HashSet<IpAddr>,
+        desired_ipv6: &HashSet<IpAddr>,
+    ) -> Result<Option<Route53Plan>, BoxError> {
+        let targets = publish_targets(zone_config, desired_ipv4, desired_ipv6);
+        let mut f = FuturesUnordered::new();
+        let mut all_changes = HostnameChanges::default();
+
+        let default_ttl = match zone_config.ttl {
+            Some(ttl) => Some(ttl),
+            None => config.ttl,
+        };
+
+        for target in &targets {
+            debug!(
+                "Getting changes in Route 53 zone {} for hostname {}",
+                zone_config.zone_id,
+                target.hostname_config.get_hostname()
+            );
+            f.push(get_changes_for_hostname(
+                self.route53.clone(),
+                &zone_config.zone_id,
+                &target.hostname_config,
+                target.ipv4,
+                target.ipv6,
+                default_ttl,
+                config.prechange_ttl,
+            ));
+        }
+
+        while let Some(changes) = f.next().await {
+            match changes {
+                Ok(changes) => {
+                    all_changes.ttl_lowering.extend(changes.ttl_lowering);
+                    all_changes.changes.extend(changes.changes);
+                    all_changes.ttl_lowering_wait = max(all_changes.ttl_lowering_wait, changes.ttl_lowering_wait);
+                    all_changes.diffs.extend(changes.diffs);
+                }
+                Err(e) => {
+                    error!("Failed to get changes for hostname: {e}");
+                    return Err(e);
+                }
             }
         }
+
+        // Hostnames are examined concurrently; list them in a stable order.
+        all_changes.diffs.sort_by(|a, b| a.hostname.cmp(&b.hostname));
+
+        let timestamp_ttl = default_ttl.unwrap_or(DEFAULT_TTL).into();
+        for hostname in timestamp_record_hostnames(config, zone_config, &all_changes.diffs) {
+            all_changes.changes.push(timestamp_record_change(&hostname, timestamp_ttl));
+        }
+
+        if all_changes.changes.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Route53Plan {
+            changes: all_changes,
+            comment: format_change_comment(&config.change_comment, zone_config),
+        }))
+    }
+
+    fn describe<'a>(&self, plan: &'a Route53Plan) -> &'a [RecordSetDiff] {
+        &plan.changes.diffs
     }
 
-    if all_changes.is_empty() {
-        info!("All IP addresses are for zone {} up-to-date; no changes to make.", zone_config.zone_id);
-        return Ok(());
+    async fn apply(&self, plan: Route53Plan) -> Result<Route53Pending, BoxError> {
+        let Route53Plan {
+            changes,
+            comment,
+        } = plan;
+
+        let rollback = self.verify.as_ref().is_some_and(|verify| verify.rollback);
+        let before = if self.snapshot_dir.is_some() || rollback {
+            let all_changes: Vec<&Change> = changes.ttl_lowering.iter().chain(&changes.changes).collect();
+            let snapshot = take_snapshot(&self.route53, &self.zone_id, &all_changes).await?;
+            if let Some(snapshot_dir) = &self.snapshot_dir {
+                let path = save_snapshot(snapshot_dir, &snapshot).await?;
+                info!("Saved the records about to change in zone {} to {}", self.zone_id, path.display());
+            }
+            Some(snapshot)
+        } else {
+            None
+        };
+
+        if !changes.ttl_lowering.is_empty() {
+            info!("Lowering TTLs of records to be changed in zone {}", self.zone_id);
+            if let Err(e) =
+                update_route53_zone(self.route53.clone(), &self.zone_id, changes.ttl_lowering, &comment).await
+            {
+                eprintln!("Failed to lower TTLs of Route 53 records: {e}");
+                return Err(e);
+            }
+
+            info!("Waiting {} seconds for the old TTLs in zone {} to expire", changes.ttl_lowering_wait, self.zone_id);
+            tokio::select! {
+                _ = sleep(Duration::from_secs(changes.ttl_lowering_wait as u64)) => (),
+                _ = shutdown_signal() => {
+                    // Leaving the lowered TTLs in place without the new values would be worse than changing early.
+                    info!("Not waiting for the old TTLs in zone {} to expire; shutting down", self.zone_id);
+                }
+            }
+        }
+
+        match submit_route53_changes(self.route53.clone(), &self.zone_id, changes.changes, &comment).await {
+            Ok(ci) => Ok(Route53Pending {
+                change: ci,
+                diffs: changes.diffs,
+                before,
+            }),
+            Err(e) => {
+                eprintln!("Failed to update Route 53 hostnames: {e}");
+                Err(e)
+            }
+        }
     }
 
-    match update_route53_zone(route53, &zone_config.zone_id, all_changes, &zone_config.hostnames).await {
-        Ok(_) => {
-            info!("Route 53 hostnames updated successfully for zone {}", zone_config.zone_id);
-            Ok(())
+    async fn wait(&self, pending: Route53Pending) -> Result<(), BoxError> {
+        let start = Instant::now();
+        wait_for_route53_change(self.route53.clone(), pending.change).await?;
+        statsd::timing("propagation.latency", start.elapsed(), &[("zone", self.zone_id.as_str())]);
+
+        // When shutting down, the change may not have propagated yet, so there's nothing to verify.
+        let Some(verify) = self.verify.as_ref().filter(|_| !shutdown_requested()) else {
+            return Ok(());
+        };
+
+        let problems = verify_changes(&self.route53, &self.zone_id, verify, &pending.diffs).await?;
+        if problems.is_empty() {
+            debug!("Verified the changes to zone {}", self.zone_id);
+            return Ok(());
         }
-        Err(e) => {
-            eprintln!("Failed to update Route 53 hostnames: {e}");
-            Err(e)
+
+        for problem in &problems {
+            error!("Verification of the changes to zone {} failed: {problem}", self.zone_id);
         }
+
+        let rolled_back = match pending.before.filter(|_| verify.rollback) {
+            Some(before) => {
+                if let Err(e) = roll_back(self.route53.clone(), &before).await {
+                    error!("Unable to roll back the changes to zone {}: {e}", self.zone_id);
+                    return Err(e);
+                }
+                error!("Rolled back the changes to zone {} to the records from {}", self.zone_id, before.timestamp);
+                true
+            }
+            None => false,
+        };
+
+        Err(Route53IpUpdateError::VerificationFailed(self.zone_id.clone(), problems, rolled_back).into())
+    }
+
+    fn change_id(&self, pending: &Route53Pending) -> Option<String> {
+        pending.change.id().map(String::from)
     }
 }
 
-pub(crate) async fn get_changes_for_hostname(
-    route53: Route53Client,
+/// Returns the changes needed to bring a hostname up-to-date. If the desired addresses of a type are `None`, that type
+/// isn't managed and its record sets are left as they are.
+pub(crate) async fn get_changes_for_hostname<R: Route53Api>(
+    route53: R,
     route53_zone: &str,
     hostname_config: &HostnameConfig,
-    desired_ipv4: &HashSet<IpAddr>,
-    desired_ipv6: &HashSet<IpAddr>,
+    desired_ipv4: Option<&HashSet<IpAddr>>,
+    desired_ipv6: Option<&HashSet<IpAddr>>,
     default_ttl: Option<Ttl>,
-) -> Result<Vec<Change>, BoxError> {
+    prechange_ttl: Option<Ttl>,
+) -> Result<HostnameChanges, BoxError> {
     let hostname = hostname_config.get_hostname();
-    let desired_ttl: i64 = hostname_config.get_ttl().unwrap_or(default_ttl.unwrap_or(DEFAULT_TTL)).into();
+    let default_ttl = default_ttl.unwrap_or(DEFAULT_TTL);
+    let desired_ipv4_ttl: i64 = hostname_config.get_ipv4_ttl().unwrap_or(default_ttl).into();
+    let desired_ipv6_ttl: i64 = hostname_config.get_ipv6_ttl().unwrap_or(default_ttl).into();
 
     // Get a list of changes necessary for this hostname.
     let record_sets = get_hostname_record_sets(route53.clone(), route53_zone, hostname).await?;
 
-    debug!("Hostname {hostname} has record sets: {record_sets:?}");
+    debug!("Hostname {hostname} has {} record sets", record_sets.len());
+
+    let prechange_ttl: Option<i64> = prechange_ttl.map(Into::into);
+    let mut result = HostnameChanges::default();
+    let changes = &mut result.changes;
+    let diffs = &mut result.diffs;
 
-    let mut changes: Vec<Change> = Vec::new();
+    let (manage_ipv4, manage_ipv6) = (desired_ipv4.is_some(), desired_ipv6.is_some());
+    let no_addresses = HashSet::new();
+    let desired_ipv4 = desired_ipv4.unwrap_or(&no_addresses);
+    let desired_ipv6 = desired_ipv6.unwrap_or(&no_addresses);
 
     let mut desired_ipv4_rrs_seen = desired_ipv4.is_empty();
     let mut desired_ipv6_rrs_seen = desired_ipv6.is_empty();
 
-    for rrs in record_sets {
+    // With a routing policy, only the record sets with our set identifier are ours; the others belong to other
+    // updaters publishing the same hostname.
+    let routing = hostname_config.get_routing();
+    let set_identifier = routing.as_ref().map(|routing| routing.set_identifier.as_str());
+    let is_ours = |rrs: &ResourceRecordSet| rrs.set_identifier() == set_identifier;
+    let is_others = |rrs: &ResourceRecordSet| routing.is_some() && rrs.set_identifier().is_some() && !is_ours(rrs);
+    let is_routed = |rrs: &ResourceRecordSet| routing.as_ref().is_none_or(|routing| routing.matches(rrs));
+    let routed = |builder: resource_record_set::Builder| match &routing {
+        Some(routing) => routing.apply(builder),
+        None => builder,
+    };
+
+    for mut rrs in record_sets {
         match rrs.r#type() {
             None => Err(Route53IpUpdateError::MissingExpectedAwsReplyField("Type".to_string()))?,
+            Some(&RrType::A) if !manage_ipv4 => {
+                debug!("Leaving A record set for {hostname} alone; IPv4 addresses aren't managed here");
+            }
+            Some(&RrType::Aaaa) if !manage_ipv6 => {
+                debug!("Leaving AAAA record set for {hostname} alone; IPv6 addresses aren't managed here");
+            }
+            Some(&RrType::A) if is_others(&rrs) => {
+                debug!("Leaving A record set for {hostname} alone; it belongs to another updater");
+            }
+            Some(&RrType::Aaaa) if is_others(&rrs) => {
+                debug!("Leaving AAAA record set for {hostname} alone; it belongs to another updater");
+            }
             Some(&RrType::A) => {
                 let existing_ipv4 = get_ipaddrs_from_rrs(&rrs)?;
-                debug!("Examining existing A record set: {rrs:?}");
 
-                if rrs.set_identifier.is_none() && &existing_ipv4 == desired_ipv4 && rrs.ttl() == Some(desired_ttl) {
-                    debug!("Existing A record set is up-to-date: {rrs:?}");
+                if is_ours(&rrs)
+                    && is_routed(&rrs)
+                    && &existing_ipv4 == desired_ipv4
+                    && rrs.ttl() == Some(desired_ipv4_ttl)
+                {
+                    debug!("Existing A record set for {hostname} is up-to-date");
                     desired_ipv4_rrs_seen = true;
                 } else {
-                    let change = if desired_ipv4_rrs_seen || desired_ipv4.is_empty() || rrs.set_identifier.is_some() {
-                        debug!("Deleting existing A record set: {rrs:?}");
+                    let old = summarize(&rrs);
+                    if let Some(lowered) = lower_ttl(&rrs, prechange_ttl) {
+                        debug!("Lowering TTL of existing A record set for {hostname} before changing it");
+                        result.ttl_lowering_wait = max(result.ttl_lowering_wait, rrs.ttl().unwrap_or_default());
+                        result.ttl_lowering.push(
+                            Change::builder().action(ChangeAction::Upsert).resource_record_set(lowered.clone()).build(),
+                        );
+                        rrs = lowered;
+                    }
+
+                    let change = if desired_ipv4_rrs_seen || desired_ipv4.is_empty() || !is_ours(&rrs) {
+                        diffs.push(record_set_diff(hostname, "A", Some(old), None));
                         Change::builder().action(ChangeAction::Delete).resource_record_set(rrs).build()
                     } else {
                         // We can upsert this record set to our desired values.
                         desired_ipv4_rrs_seen = true;
 
-                        debug!("Upserting existing A record set: {rrs:?}");
-
                         let records = desired_ipv4
                             .iter()
                             .map(|ip| ResourceRecord::builder().value(ip.to_string()).build())
                             .collect();
-                        let rrs = ResourceRecordSet::builder()
+                        let rrs = routed(ResourceRecordSet::builder())
                             .name(hostname)
                             .r#type(RrType::A)
-                            .ttl(desired_ttl)
+                            .ttl(desired_ipv4_ttl)
                             .set_resource_records(Some(records))
                             .build();
+                        diffs.push(record_set_diff(hostname, "A", Some(old), Some(summarize(&rrs))));
                         Change::builder().action(ChangeAction::Upsert).resource_record_set(rrs).build()
                     };
 
@@ -135,31 +406,43 @@ pub(crate) async fn get_changes_for_hostname(
 
             Some(&RrType::Aaaa) => {
                 let existing_ipv6 = get_ipaddrs_from_rrs(&rrs)?;
-                debug!("Examining existing AAAA record set: {rrs:?}");
-                if rrs.set_identifier.is_none() && &existing_ipv6 == desired_ipv6 && rrs.ttl() == Some(desired_ttl) {
-                    debug!("Existing AAAA record set is up-to-date: {rrs:?}");
+                if is_ours(&rrs)
+                    && is_routed(&rrs)
+                    && &existing_ipv6 == desired_ipv6
+                    && rrs.ttl() == Some(desired_ipv6_ttl)
+                {
+                    debug!("Existing AAAA record set for {hostname} is up-to-date");
                     desired_ipv6_rrs_seen = true;
                 } else {
-                    let change = if desired_ipv6_rrs_seen || desired_ipv6.is_empty() || rrs.set_identifier.is_some() {
+                    let old = summarize(&rrs);
+                    if let Some(lowered) = lower_ttl(&rrs, prechange_ttl) {
+                        debug!("Lowering TTL of existing AAAA record set for {hostname} before changing it");
+                        result.ttl_lowering_wait = max(result.ttl_lowering_wait, rrs.ttl().unwrap_or_default());
+                        result.ttl_lowering.push(
+                            Change::builder().action(ChangeAction::Upsert).resource_record_set(lowered.clone()).build(),
+                        );
+                        rrs = lowered;
+                    }
+
+                    let change = if desired_ipv6_rrs_seen || desired_ipv6.is_empty() || !is_ours(&rrs) {
                         // We need to delete this record set.
-                        debug!("Deleting existing AAAA record set: {rrs:?}");
+                        diffs.push(record_set_diff(hostname, "AAAA", Some(old), None));
                         Change::builder().action(ChangeAction::Delete).resource_record_set(rrs).build()
                     } else {
                         // We can upsert this record set to our desired values.
                         desired_ipv6_rrs_seen = true;
 
-                        debug!("Upserting existing AAAA record set: {rrs:?}");
-
                         let records = desired_ipv6
                             .iter()
                             .map(|ip| ResourceRecord::builder().value(ip.to_string()).build())
                             .collect();
-                        let rrs = ResourceRecordSet::builder()
+                        let rrs = routed(ResourceRecordSet::builder())
                             .name(hostname)
                             .r#type(RrType::Aaaa)
-                            .ttl(desired_ttl)
+                            .ttl(desired_ipv6_ttl)
                             .set_resource_records(Some(records))
                             .build();
+                        diffs.push(record_set_diff(hostname, "AAAA", Some(old), Some(summarize(&rrs))));
                         Change::builder().action(ChangeAction::Upsert).resource_record_set(rrs).build()
                     };
 
@@ -169,73 +452,234 @@ pub(crate) async fn get_changes_for_hostname(
 
             Some(&RrType::Cname) => {
                 // Don't allow CNAMEs. Delete them.
-                debug!("Deleting CNAME record set: {rrs:?}");
+                diffs.push(record_set_diff(hostname, "CNAME", Some(summarize(&rrs)), None));
                 changes.push(Change::builder().action(ChangeAction::Delete).resource_record_set(rrs).build());
             }
 
             _ => {
                 // Allow other records to be preserved.
-                debug!("Ignoring {:?} record set: {rrs:?}", rrs.r#type());
+                debug!("Ignoring {:?} record set for {hostname}", rrs.r#type());
             }
         }
     }
 
     if !desired_ipv4_rrs_seen {
         let records = desired_ipv4.iter().map(|ip| ResourceRecord::builder().value(ip.to_string()).build()).collect();
-        let rrs = ResourceRecordSet::builder()
+        let rrs = routed(ResourceRecordSet::builder())
             .r#type(RrType::A)
             .name(hostname)
-            .ttl(desired_ttl)
+            .ttl(desired_ipv4_ttl)
             .set_resource_records(Some(records))
             .build();
 
-        debug!("Creating new A record set: {rrs:?}");
+        diffs.push(record_set_diff(hostname, "A", None, Some(summarize(&rrs))));
 
         changes.push(Change::builder().action(ChangeAction::Upsert).resource_record_set(rrs).build());
     }
 
     if !desired_ipv6_rrs_seen {
         let records = desired_ipv6.iter().map(|ip| ResourceRecord::builder().value(ip.to_string()).build()).collect();
-        let rrs = ResourceRecordSet::builder()
+        let rrs = routed(ResourceRecordSet::builder())
             .r#type(RrType::Aaaa)
             .name(hostname)
-            .ttl(desired_ttl)
+            .ttl(desired_ipv6_ttl)
             .set_resource_records(Some(records))
             .build();
 
-        debug!("Creating new AAAA record set: {rrs:?}");
+        diffs.push(record_set_diff(hostname, "AAAA", None, Some(summarize(&rrs))));
 
         changes.push(Change::builder().action(ChangeAction::Upsert).resource_record_set(rrs).build());
     }
 
-    Ok(changes)
+    Ok(result)
+}
+
+/// Returns the values, TTL, and weight of a record set, for display.
+fn summarize(rrs: &ResourceRecordSet) -> RecordSetSummary {
+    RecordSetSummary::new(rrs.ttl(), rrs.resource_records().unwrap_or_default().iter().filter_map(|rr| rr.value()))
+        .with_weight(rrs.weight())
+}
+
+fn record_set_diff(
+    hostname: &str,
+    record_type: &str,
+    old: Option<RecordSetSummary>,
+    new: Option<RecordSetSummary>,
+) -> RecordSetDiff {
+    RecordSetDiff {
+        hostname: hostname.to_string(),
+        record_type: record_type.to_string(),
+        old,
+        new,
+    }
+}
+
+/// Returns a copy of the record set with its TTL lowered to `prechange_ttl`, if the TTL is currently higher.
+fn lower_ttl(rrs: &ResourceRecordSet, prechange_ttl: Option<i64>) -> Option<ResourceRecordSet> {
+    let prechange_ttl = prechange_ttl?;
+    match rrs.ttl() {
+        Some(ttl) if ttl > prechange_ttl => {
+            let mut lowered = rrs.clone();
+            lowered.ttl = Some(prechange_ttl);
+            Some(lowered)
+        }
+        _ => None,
+    }
 }
 
 /// Updates Route 53 with the specificed changes and waits for them to propagate.
-pub(crate) async fn update_route53_zone(
-    route53: Route53Client,
+pub(crate) async fn update_route53_zone<R: Route53Api>(
+    route53: R,
     zone_id: &str,
     changes: Vec<Change>,
-    hostnames: &[HostnameConfig],
+    comment: &str,
 ) -> Result<(), BoxError> {
-    let hostnames_str = hostnames.iter().map(|h| h.get_hostname()).collect::<Vec<_>>().join(" ");
+    let ci = submit_route53_changes(route53.clone(), zone_id, changes, comment).await?;
+    wait_for_route53_change(route53, ci).await
+}
 
-    let cb = ChangeBatch::builder()
-        .set_changes(Some(changes))
-        .comment(format!("Route 53 update for {hostnames_str}"))
-        .build();
+/// Submits the specified changes to Route 53, returning the status of the change.
+async fn submit_route53_changes<R: Route53Api>(
+    route53: R,
+    zone_id: &str,
+    changes: Vec<Change>,
+    comment: &str,
+) -> Result<ChangeInfo, BoxError> {
+    validate_change_batch(&changes)?;
+    let cb = ChangeBatch::builder().set_changes(Some(changes.clone())).comment(comment).build();
 
     debug!("Submitting changes to Route 53 zone {zone_id}");
 
-    let result = route53.change_resource_record_sets().hosted_zone_id(zone_id).change_batch(cb).send().await?;
-    let mut ci = result
-        .change_info
-        .ok_or_else(|| Route53IpUpdateError::MissingExpectedAwsReplyField("ChangeInfo".to_string()))?;
+    route53.change_resource_record_sets(zone_id, cb).await.map_err(|e| match e.downcast_ref() {
+        Some(Route53IpUpdateError::ChangeBatchRejected(messages)) => Route53IpUpdateError::ChangeBatchRejected(
+            messages.iter().map(|message| attribute_rejection(&changes, message)).collect(),
+        )
+        .into(),
+        _ => e,
+    })
+}
+
+/// Prefixes a message from an InvalidChangeBatch error with the hostname, record type, and action of the change it
+/// refers to, if that can be determined from the record set name, type, or value quoted in the message.
+fn attribute_rejection(changes: &[Change], message: &str) -> String {
+    let message = message.trim_start_matches('[').trim_end_matches(']');
+
+    // E.g. "Tried to create resource record set [name='host.example.com.', type='A'] but it already exists" or
+    // "RRSet of type CNAME with DNS name host.example.com. is not permitted ...".
+    let name = quoted_after(message, "name='").or_else(|| word_after(message, "DNS name "));
+    let rr_type = quoted_after(message, "type='").or_else(|| word_after(message, "of type "));
+
+    // E.g. "Invalid Resource Record: FATAL problem: ARRDATAIllegalIPv4Address (Value is not a valid IPv4 address)
+    // encountered with '192.0.2'".
+    let value = quoted_after(message, "encountered with '");
+
+    let change = changes.iter().find(|change| {
+        let Some(rrs) = change.resource_record_set() else {
+            return false;
+        };
+
+        match (name, value) {
+            (Some(name), _) => {
+                same_name(rrs.name().unwrap_or_default(), name)
+                    && rr_type.is_none_or(|rr_type| rrs.r#type().map(RrType::as_str) == Some(rr_type))
+            }
+            (None, Some(value)) => {
+                rrs.resource_records().unwrap_or_default().iter().any(|rr| rr.value() == Some(value))
+            }
+            (None, None) => false,
+        }
+    });
+
+    match change.and_then(|change| Some((change.action()?, change.resource_record_set()?))) {
+        Some((action, rrs)) => {
+            let hostname = rrs.name().unwrap_or_default();
+            let hostname = hostname.strip_suffix('.').unwrap_or(hostname);
+            let rr_type = rrs.r#type().map(RrType::as_str).unwrap_or_default();
+            format!("{hostname} {rr_type} ({}): {message}", action.as_str())
+        }
+        None => message.to_string(),
+    }
+}
+
+/// Returns the text between `prefix` and the next single quote.
+fn quoted_after<'a>(message: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = &message[message.find(prefix)? + prefix.len()..];
+    rest.split('\'').next()
+}
+
+/// Returns the word following `prefix`.
+fn word_after<'a>(message: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = &message[message.find(prefix)? + prefix.len()..];
+    rest.split_whitespace().next()
+}
+
+/// Compares DNS names, ignoring case and a trailing dot.
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim_end_matches('.').eq_ignore_ascii_case(b.trim_end_matches('.'))
+}
+
+/// Checks changes against the limits Route 53 places on a ChangeBatch, so a batch it would reject is reported with
+/// the hostname at fault instead of failing as a whole.
+fn validate_change_batch(changes: &[Change]) -> Result<(), Route53IpUpdateError> {
+    let mut records = 0;
+    let mut value_len = 0;
+    let mut seen = HashMap::new();
+
+    for change in changes {
+        let (Some(action), Some(rrs)) = (change.action(), change.resource_record_set()) else {
+            continue;
+        };
+
+        let name = rrs.name().unwrap_or_default();
+        let hostname = name.strip_suffix('.').unwrap_or(name);
+        let rr_type = rrs.r#type().map(RrType::as_str).unwrap_or_default();
+        let invalid = |message: String| Route53IpUpdateError::InvalidChangeBatch(hostname.to_string(), message);
+
+        let weight = if action == &ChangeAction::Upsert {
+            2
+        } else {
+            1
+        };
+        let values = rrs.resource_records().unwrap_or_default();
+        records += weight * values.len();
+        value_len += weight * values.iter().filter_map(|rr| rr.value()).map(str::len).sum::<usize>();
+
+        if records > MAX_CHANGE_BATCH_RECORDS {
+            return Err(invalid(format!(
+                "the batch would exceed the limit of {MAX_CHANGE_BATCH_RECORDS} resource records per request"
+            )));
+        }
+
+        if value_len > MAX_CHANGE_BATCH_VALUE_LEN {
+            return Err(invalid(format!(
+                "the batch would exceed the limit of {MAX_CHANGE_BATCH_VALUE_LEN} characters of record values per \
+                 request"
+            )));
+        }
+
+        // A record set may only appear twice in a batch if it's deleted and then created again.
+        let key = (hostname.to_ascii_lowercase(), rr_type, rrs.set_identifier());
+        if let Some(previous) = seen.insert(key, action) {
+            if !(previous == &ChangeAction::Delete && action == &ChangeAction::Create) {
+                return Err(invalid(format!(
+                    "the {rr_type} record set is changed twice ({} and {})",
+                    previous.as_str(),
+                    action.as_str()
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
 
+/// Waits for a submitted Route 53 change to propagate.
+async fn wait_for_route53_change<R: Route53Api>(route53: R, mut ci: ChangeInfo) -> Result<(), BoxError> {
     let change_id =
         ci.id().ok_or_else(|| Route53IpUpdateError::MissingExpectedAwsReplyField("Id".to_string()))?.to_string();
 
     debug!("Waiting for Route 53 to propagate changes (change ID {change_id})");
+    let deadline = Instant::now() + PROPAGATION_TIMEOUT;
 
     loop {
         if let Some(status) = ci.status() {
@@ -247,20 +691,68 @@ pub(crate) async fn update_route53_zone(
         match ci.status() {
             None => Err(Route53IpUpdateError::MissingExpectedAwsReplyField("Status".to_string()))?,
             Some(&ChangeStatus::Insync) => return Ok(()),
-            Some(&ChangeStatus::Pending) => sleep(Duration::from_millis(500)).await,
+            Some(&ChangeStatus::Pending) if Instant::now() >= deadline => {
+                Err(Route53IpUpdateError::PropagationTimedOut(change_id.clone()))?
+            }
+            Some(&ChangeStatus::Pending) => {
+                tokio::select! {
+                    _ = sleep(Duration::from_millis(500)) => (),
+                    _ = shutdown_signal() => {
+                        // The change has been accepted; Route 53 propagates it whether or not anyone is waiting.
+                        info!("Not waiting for Route 53 change {change_id} to propagate; shutting down");
+                        return Ok(());
+                    }
+                }
+            }
             Some(ChangeStatus::Unknown(status)) => Err(Route53IpUpdateError::UnexpectedRoute53Status(status.clone()))?,
             _ => Err(Route53IpUpdateError::UnexpectedRoute53Status(ci.status().unwrap().as_str().to_string()))?,
         }
 
-        let result = route53.get_change().id(change_id.clone()).send().await?;
-        ci = result
-            .change_info
-            .ok_or_else(|| Route53IpUpdateError::MissingExpectedAwsReplyField("ChangeInfo".to_string()))?
+        ci = route53.get_change(&change_id).await?;
     }
 }
 
-async fn get_hostname_record_sets(
-    route53: Route53Client,
+/// Returns the change that replaces a hostname's timestamp record.
+fn timestamp_record_change(hostname: &str, ttl: i64) -> Change {
+    // TXT record values are quoted strings in Route 53.
+    let record = ResourceRecord::builder().value(format!("\"{}\"", timestamp_record_text())).build();
+    let rrs = ResourceRecordSet::builder()
+        .name(timestamp_record_name(hostname))
+        .r#type(RrType::Txt)
+        .ttl(ttl)
+        .resource_records(record)
+        .build();
+    Change::builder().action(ChangeAction::Upsert).resource_record_set(rrs).build()
+}
+
+/// Expands the placeholders in a ChangeBatch comment template, truncating the result to the length allowed by Route 53.
+///
+/// Supported placeholders are `{hostnames}`, `{zone}`, `{timestamp}` (RFC 3339, UTC), `{machine}` (the hostname of this
+/// machine), and `{version}` (the name and version of this tool).
+pub(crate) fn format_change_comment(template: &str, zone_config: &Route53ZoneConfig) -> String {
+    let hostnames = zone_config.hostnames.iter().map(|h| h.get_hostname()).collect::<Vec<_>>().join(" ");
+    let machine = gethostname::gethostname();
+
+    let mut comment = template
+        .replace("{hostnames}", &hostnames)
+        .replace("{zone}", &zone_config.zone_id)
+        .replace("{timestamp}", &humantime::format_rfc3339_seconds(SystemTime::now()).to_string())
+        .replace("{machine}", &machine.to_string_lossy())
+        .replace("{version}", concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")));
+
+    if comment.len() > MAX_CHANGE_COMMENT_LEN {
+        let mut end = MAX_CHANGE_COMMENT_LEN;
+        while !comment.is_char_boundary(end) {
+            end -= 1;
+        }
+        comment.truncate(end);
+    }
+
+    comment
+}
+
+pub(crate) async fn get_hostname_record_sets<R: Route53Api>(
+    route53: R,
     route53_zone: &str,
     hostname: &str,
 ) -> Result<Vec<ResourceRecordSet>, BoxError> {
@@ -275,36 +767,58 @@ async fn get_hostname_record_sets(
     };
 
     loop {
-        let query = route53
-            .list_resource_record_sets()
-            .hosted_zone_id(route53_zone)
-            .start_record_name(start_record_name.clone());
-        let query = query.start_record_type(start_record_type.clone());
         debug!("get_hostname_record_sets: hosted_zone_id={route53_zone} start_record_name={start_record_name}, start_record_type={start_record_type:?}");
-        let query_results = query.send().await?;
+        let page = route53
+            .list_resource_record_sets(route53_zone, Some((&start_record_name, start_record_type.clone())))
+            .await?;
 
-        if let Some(records) = query_results.resource_record_sets() {
-            for record in records {
-                if record.name() == Some(hostname_dot.as_str()) {
-                    // This record is ok.
-                    results.push(record.clone());
-                } else {
-                    // We've hit the next record. Stop processing.
-                    debug!("Hit next record: {record:?} name={:?} expected {hostname}", record.name());
-                    return Ok(results);
-                }
-            }
-        } else {
+        if page.record_sets.is_empty() {
             error!("No records returned for {hostname} in {route53_zone}")
         }
 
-        if !query_results.is_truncated() {
-            return Ok(results);
+        for record in page.record_sets {
+            if record.name() == Some(hostname_dot.as_str()) {
+                // This record is ok.
+                results.push(record);
+            } else {
+                // We've hit the next record. Stop processing.
+                debug!("Hit next record: {record:?} name={:?} expected {hostname}", record.name());
+                return Ok(results);
+            }
+        }
+
+        match page.next {
+            None => return Ok(results),
+            Some((name, rr_type)) => {
+                start_record_name = name;
+                start_record_type = rr_type;
+            }
+        }
+    }
+}
+
+/// Returns the IPv4 and IPv6 addresses currently published for a hostname in simple (non-routing-policy) records.
+async fn get_hostname_addresses<R: Route53Api>(
+    route53: R,
+    route53_zone: &str,
+    hostname: &str,
+) -> Result<(HashSet<IpAddr>, HashSet<IpAddr>), BoxError> {
+    let mut ipv4 = HashSet::new();
+    let mut ipv6 = HashSet::new();
+
+    for rrs in get_hostname_record_sets(route53, route53_zone, hostname).await? {
+        if rrs.set_identifier().is_some() {
+            continue;
         }
 
-        start_record_name = query_results.next_record_name().unwrap().to_string();
-        start_record_type = query_results.next_record_type().unwrap().clone();
+        match rrs.r#type() {
+            Some(&RrType::A) => ipv4.extend(get_ipaddrs_from_rrs(&rrs)?),
+            Some(&RrType::Aaaa) => ipv6.extend(get_ipaddrs_from_rrs(&rrs)?),
+            _ => (),
+        }
     }
+
+    Ok((ipv4, ipv6))
 }
 
 fn get_ipaddrs_from_rrs(rrs: &ResourceRecordSet) -> Result<HashSet<IpAddr>, BoxError> {
@@ -323,3 +837,402 @@ fn get_ipaddrs_from_rrs(rrs: &ResourceRecordSet) -> Result<HashSet<IpAddr>, BoxE
 
     Ok(ipaddrs)
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            diff::changed_hostnames, provider::update_zone_with, query_address_type::QueryAddressType,
+            route53_api::fake::FakeRoute53,
+        },
+    };
+
+    fn rrs(name: &str, rr_type: RrType, ttl: i64, values: &[&str]) -> ResourceRecordSet {
+        let records = values.iter().map(|v| ResourceRecord::builder().value(*v).build()).collect();
+        ResourceRecordSet::builder().name(name).r#type(rr_type).ttl(ttl).set_resource_records(Some(records)).build()
+    }
+
+    fn zone(hostnames: &[&str]) -> Route53ZoneConfig {
+        serde_yaml::from_str(&format!("zone-id: Z1\nhostnames: [{}]", hostnames.join(", "))).unwrap()
+    }
+
+    fn addrs(addresses: &[&str]) -> HashSet<IpAddr> {
+        addresses.iter().map(|a| a.parse().unwrap()).collect()
+    }
+
+    fn values(rrs: &ResourceRecordSet) -> HashSet<IpAddr> {
+        get_ipaddrs_from_rrs(rrs).unwrap()
+    }
+
+    async fn plan(
+        fake: &FakeRoute53,
+        config: &Config,
+        hostnames: &[&str],
+        ipv4: &[&str],
+        ipv6: &[&str],
+    ) -> Option<Route53Plan> {
+        let provider = Route53Provider::new(fake.clone(), "Z1");
+        provider.plan(config, &zone(hostnames), &addrs(ipv4), &addrs(ipv6)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn plan_creates_missing_records() {
+        let fake = FakeRoute53::default();
+        let plan = plan(&fake, &Config::default(), &["host.example.com"], &["192.0.2.1"], &["2001:db8::1"]).await;
+        let changes = plan.unwrap().changes;
+
+        assert!(changes.ttl_lowering.is_empty());
+        assert_eq!(changes.changes.len(), 2);
+        for change in &changes.changes {
+            assert_eq!(change.action(), Some(&ChangeAction::Upsert));
+            assert_eq!(change.resource_record_set().unwrap().ttl(), Some(300));
+        }
+    }
+
+    #[tokio::test]
+    async fn plan_leaves_other_updaters_routed_records_alone() {
+        let routed = |set_identifier: &str, weight: i64| {
+            let mut rrs = rrs("w.example.com", RrType::A, 300, &["192.0.2.9"]);
+            rrs.set_identifier = Some(set_identifier.to_string());
+            rrs.weight = Some(weight);
+            rrs
+        };
+        let fake = FakeRoute53::with_record_sets([
+            rrs("w.example.com", RrType::A, 300, &["192.0.2.1"]),
+            routed("home", 10),
+            routed("office", 10),
+        ]);
+        let hostname = "{hostname: w.example.com, routing-policy: weighted, set-identifier: home, weight: 20}";
+
+        let plan = plan(&fake, &Config::default(), &[hostname], &["192.0.2.9"], &[]).await;
+        let changes = plan.unwrap().changes.changes;
+        let summary: Vec<_> = changes
+            .iter()
+            .map(|change| {
+                let rrs = change.resource_record_set().unwrap();
+                (change.action().unwrap().as_str(), rrs.set_identifier(), rrs.weight())
+            })
+            .collect();
+        assert_eq!(summary, [("DELETE", None, None), ("UPSERT", Some("home"), Some(20))]);
+    }
+
+    #[tokio::test]
+    async fn plan_updates_timestamp_records_of_changed_hostnames() {
+        let fake = FakeRoute53::with_record_sets([rrs("same.example.com", RrType::A, 300, &["192.0.2.1"])]);
+        let config = Config {
+            timestamp_records: true,
+            ..Config::default()
+        };
+
+        let plan = plan(&fake, &config, &["same.example.com", "new.example.com"], &["192.0.2.1"], &[]).await;
+        let changes = plan.unwrap().changes.changes;
+        let txt: Vec<_> = changes
+            .iter()
+            .filter_map(|c| c.resource_record_set())
+            .filter(|rrs| rrs.r#type() == Some(&RrType::Txt))
+            .collect();
+
+        assert_eq!(txt.len(), 1);
+        assert_eq!(txt[0].name(), Some("_r53ipupdate.new.example.com"));
+        let value = txt[0].resource_records().unwrap()[0].value().unwrap();
+        assert!(value.starts_with("\"updated=") && value.contains(" version=route53-ip-update/"), "{value}");
+    }
+
+    #[tokio::test]
+    async fn plan_refreshes_all_timestamp_records_with_heartbeat() {
+        let fake = FakeRoute53::with_record_sets([rrs("same.example.com", RrType::A, 300, &["192.0.2.1"])]);
+        let config = Config {
+            heartbeat: true,
+            ..Config::default()
+        };
+
+        let plan = plan(&fake, &config, &["same.example.com"], &["192.0.2.1"], &[]).await.unwrap();
+        assert!(plan.changes.diffs.is_empty());
+        let names: Vec<_> = plan.changes.changes.iter().filter_map(|c| c.resource_record_set()?.name()).collect();
+        assert_eq!(names, ["_r53ipupdate.same.example.com"]);
+    }
+
+    #[tokio::test]
+    async fn plan_is_empty_when_up_to_date() {
+        let fake = FakeRoute53::with_record_sets([
+            rrs("host.example.com", RrType::A, 300, &["192.0.2.1"]),
+            rrs("host.example.com", RrType::Aaaa, 300, &["2001:db8::1"]),
+        ]);
+
+        let plan = plan(&fake, &Config::default(), &["host.example.com"], &["192.0.2.1"], &["2001:db8::1"]).await;
+        assert!(plan.is_none());
+    }
+
+    #[tokio::test]
+    async fn plan_replaces_changed_records_and_removes_cnames() {
+        let fake = FakeRoute53::with_record_sets([
+            rrs("a.example.com", RrType::A, 300, &["192.0.2.1"]),
+            rrs("b.example.com", RrType::Cname, 300, &["elsewhere.example.net"]),
+            rrs("b.example.com", RrType::Txt, 300, &["\"keep me\""]),
+        ]);
+
+        let changes = plan(&fake, &Config::default(), &["a.example.com", "b.example.com"], &["192.0.2.2"], &[])
+            .await
+            .unwrap()
+            .changes
+            .changes;
+
+        let summary: HashSet<_> = changes
+            .iter()
+            .map(|c| {
+                let rrs = c.resource_record_set().unwrap();
+                (
+                    c.action().unwrap().as_str(),
+                    rrs.name().unwrap().trim_end_matches('.'),
+                    rrs.r#type().unwrap().as_str(),
+                )
+            })
+            .collect();
+        let expected: HashSet<_> =
+            [("UPSERT", "a.example.com", "A"), ("DELETE", "b.example.com", "CNAME"), ("UPSERT", "b.example.com", "A")]
+                .into_iter()
+                .collect();
+        assert_eq!(summary, expected);
+    }
+
+    #[tokio::test]
+    async fn plan_summarizes_changes_by_hostname() {
+        let fake = FakeRoute53::with_record_sets([
+            rrs("a.example.com", RrType::A, 3600, &["192.0.2.1"]),
+            rrs("b.example.com", RrType::Cname, 300, &["elsewhere.example.net"]),
+        ]);
+
+        let diffs = plan(&fake, &Config::default(), &["b.example.com", "a.example.com"], &["192.0.2.2"], &[])
+            .await
+            .unwrap()
+            .changes
+            .diffs;
+
+        let summary: Vec<_> =
+            diffs.iter().map(|d| (d.hostname.as_str(), d.record_type.as_str(), d.old.clone(), d.new.clone())).collect();
+        let new = Some(RecordSetSummary::new(Some(300), ["192.0.2.2"]));
+        assert_eq!(
+            summary,
+            [
+                ("a.example.com", "A", Some(RecordSetSummary::new(Some(3600), ["192.0.2.1"])), new.clone()),
+                ("b.example.com", "CNAME", Some(RecordSetSummary::new(Some(300), ["elsewhere.example.net"])), None),
+                ("b.example.com", "A", None, new),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn plan_deletes_records_for_unwanted_family() {
+        let fake = FakeRoute53::with_record_sets([
+            rrs("host.example.com", RrType::A, 300, &["192.0.2.1"]),
+            rrs("host.example.com", RrType::Aaaa, 300, &["2001:db8::1"]),
+        ]);
+
+        let changes = plan(&fake, &Config::default(), &["host.example.com"], &["192.0.2.1"], &[]).await.unwrap();
+        assert_eq!(changes.changes.changes.len(), 1);
+        let change = &changes.changes.changes[0];
+        assert_eq!(change.action(), Some(&ChangeAction::Delete));
+        assert_eq!(change.resource_record_set().unwrap().r#type(), Some(&RrType::Aaaa));
+    }
+
+    #[tokio::test]
+    async fn plan_leaves_records_for_unmanaged_family() {
+        let fake = FakeRoute53::with_record_sets([
+            rrs("host.example.com", RrType::A, 300, &["192.0.2.1"]),
+            rrs("host.example.com", RrType::Aaaa, 300, &["2001:db8::1"]),
+        ]);
+
+        let mut zone = zone(&["host.example.com"]);
+        zone.address_type = Some(QueryAddressType::Ipv4);
+        let provider = Route53Provider::new(fake.clone(), "Z1");
+        let plan = provider.plan(&Config::default(), &zone, &addrs(&["192.0.2.2"]), &addrs(&[])).await.unwrap();
+
+        let changes = plan.unwrap().changes.changes;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].action(), Some(&ChangeAction::Upsert));
+        assert_eq!(changes[0].resource_record_set().unwrap().r#type(), Some(&RrType::A));
+    }
+
+    #[tokio::test]
+    async fn plan_publishes_single_family_hostnames() {
+        let fake = FakeRoute53::with_record_sets([rrs("v4.example.com", RrType::Aaaa, 300, &["2001:db8::9"])]);
+        let zone: Route53ZoneConfig = serde_yaml::from_str(
+            "zone-id: Z1
+hostnames:
+  - hostname: host.example.com
+    ipv4-hostname: v4.example.com
+    ipv6-hostname: v6.example.com",
+        )
+        .unwrap();
+
+        let provider = Route53Provider::new(fake.clone(), "Z1");
+        let plan =
+            provider.plan(&Config::default(), &zone, &addrs(&["192.0.2.1"]), &addrs(&["2001:db8::1"])).await.unwrap();
+
+        let plan = plan.unwrap();
+        let mut summary: Vec<_> = plan
+            .changes
+            .changes
+            .iter()
+            .map(|c| {
+                let rrs = c.resource_record_set().unwrap();
+                (c.action().unwrap().as_str(), rrs.name().unwrap().to_string(), rrs.r#type().unwrap().as_str())
+            })
+            .collect();
+        summary.sort();
+
+        let expected = [
+            ("DELETE", "v4.example.com.", "AAAA"),
+            ("UPSERT", "host.example.com", "A"),
+            ("UPSERT", "host.example.com", "AAAA"),
+            ("UPSERT", "v4.example.com", "A"),
+            ("UPSERT", "v6.example.com", "AAAA"),
+        ];
+        assert_eq!(summary, expected.map(|(action, name, rr_type)| (action, name.to_string(), rr_type)));
+    }
+
+    #[tokio::test]
+    async fn plan_replaces_weighted_record_sets() {
+        let mut weighted = rrs("host.example.com", RrType::A, 300, &["192.0.2.1"]);
+        weighted.set_identifier = Some("primary".to_string());
+        weighted.weight = Some(10);
+        let fake = FakeRoute53::with_record_sets([weighted]);
+
+        let changes = plan(&fake, &Config::default(), &["host.example.com"], &["192.0.2.1"], &[]).await.unwrap();
+        let actions: Vec<_> = changes.changes.changes.iter().map(|c| c.action().unwrap().as_str()).collect();
+        assert_eq!(actions, ["DELETE", "UPSERT"]);
+    }
+
+    #[tokio::test]
+    async fn plan_lowers_ttl_before_changing_records() {
+        let fake = FakeRoute53::with_record_sets([rrs("host.example.com", RrType::A, 3600, &["192.0.2.1"])]);
+        let config = Config {
+            prechange_ttl: Some(Ttl::from_seconds(60)),
+            ..Config::default()
+        };
+
+        let changes = plan(&fake, &config, &["host.example.com"], &["192.0.2.2"], &[]).await.unwrap().changes;
+        assert_eq!(changes.ttl_lowering_wait, 3600);
+        assert_eq!(changes.ttl_lowering.len(), 1);
+        let lowered = changes.ttl_lowering[0].resource_record_set().unwrap();
+        assert_eq!(lowered.ttl(), Some(60));
+        assert_eq!(values(lowered), addrs(&["192.0.2.1"]));
+    }
+
+    #[tokio::test]
+    async fn listing_follows_pagination() {
+        let fake = FakeRoute53::with_record_sets([
+            rrs("a.example.com", RrType::A, 300, &["192.0.2.9"]),
+            rrs("host.example.com", RrType::A, 300, &["192.0.2.1"]),
+            rrs("host.example.com", RrType::Aaaa, 300, &["2001:db8::1"]),
+            rrs("host.example.com", RrType::Txt, 300, &["\"text\""]),
+            rrs("z.example.com", RrType::A, 300, &["192.0.2.9"]),
+        ]);
+        fake.zone.lock().unwrap().page_size = 1;
+
+        let record_sets = get_hostname_record_sets(fake, "Z1", "host.example.com").await.unwrap();
+        let types: Vec<_> = record_sets.iter().map(|rrs| rrs.r#type().unwrap().as_str()).collect();
+        assert_eq!(types, ["A", "AAAA", "TXT"]);
+    }
+
+    #[test]
+    fn change_batch_validation_names_offending_hostname() {
+        let upsert = |rrs| Change::builder().action(ChangeAction::Upsert).resource_record_set(rrs).build();
+        let delete = |rrs| Change::builder().action(ChangeAction::Delete).resource_record_set(rrs).build();
+
+        let changes = [
+            upsert(rrs("a.example.com", RrType::A, 300, &["192.0.2.1"])),
+            delete(rrs("a.example.com.", RrType::Aaaa, 300, &["2001:db8::1"])),
+        ];
+        assert!(validate_change_batch(&changes).is_ok());
+
+        let changes = [
+            upsert(rrs("a.example.com", RrType::Aaaa, 300, &["2001:db8::2"])),
+            delete(rrs("A.example.com.", RrType::Aaaa, 300, &["2001:db8::1"])),
+        ];
+        let e = validate_change_batch(&changes).unwrap_err().to_string();
+        assert!(e.contains("A.example.com") && e.contains("UPSERT and DELETE"), "{e}");
+
+        let addresses: Vec<String> = (0..=255).map(|i| format!("10.0.0.{i}")).collect();
+        let addresses: Vec<&str> = addresses.iter().map(String::as_str).collect();
+        let changes = [
+            upsert(rrs("a.example.com", RrType::A, 300, &addresses)),
+            upsert(rrs("b.example.com", RrType::A, 300, &addresses)),
+        ];
+        let e = validate_change_batch(&changes).unwrap_err().to_string();
+        assert!(e.contains("b.example.com") && e.contains("1000 resource records"), "{e}");
+    }
+
+    #[tokio::test]
+    async fn rejected_changes_name_their_records() {
+        let fake = FakeRoute53::with_record_sets([rrs("host.example.com", RrType::A, 300, &["192.0.2.1"])]);
+        let stale = rrs("host.example.com", RrType::A, 300, &["192.0.2.9"]);
+        let changes = vec![Change::builder().action(ChangeAction::Delete).resource_record_set(stale).build()];
+
+        let e = submit_route53_changes(fake, "Z1", changes.clone(), "").await.unwrap_err().to_string();
+        assert!(e.starts_with("Route 53 rejected the changes: host.example.com A (DELETE): Tried to delete"), "{e}");
+
+        let message = "[Invalid Resource Record: FATAL problem: ARRDATAIllegalIPv4Address (Value is not a valid IPv4 \
+                       address) encountered with '192.0.2.9']";
+        assert!(attribute_rejection(&changes, message).starts_with("host.example.com A (DELETE): Invalid Resource"));
+
+        let message = "Unrecognized problem with other.example.com";
+        assert_eq!(attribute_rejection(&changes, message), message);
+    }
+
+    #[tokio::test]
+    async fn update_applies_changes_and_waits_for_propagation() {
+        let fake = FakeRoute53::with_record_sets([
+            rrs("host.example.com", RrType::A, 300, &["192.0.2.1"]),
+            rrs("host.example.com", RrType::Aaaa, 300, &["2001:db8::1"]),
+        ]);
+        fake.zone.lock().unwrap().pending_polls = 2;
+
+        let provider = Route53Provider::new(fake.clone(), "Z1");
+        let config = Config::default();
+        let zone = zone(&["host.example.com"]);
+        let ipv4 = addrs(&["192.0.2.2", "192.0.2.3"]);
+        let changed = update_zone_with(&provider, &config, &zone, &ipv4, &HashSet::new()).await.unwrap();
+        assert_eq!(changed_hostnames(&changed), ["host.example.com"]);
+
+        let record_sets = fake.record_sets();
+        assert_eq!(record_sets.len(), 1);
+        assert_eq!(values(&record_sets[0]), ipv4);
+
+        {
+            let state = fake.zone.lock().unwrap();
+            assert_eq!(state.batches.len(), 1);
+            assert_eq!(state.get_change_calls, 2);
+        }
+
+        // A second run finds nothing to do.
+        let changed = update_zone_with(&provider, &config, &zone, &ipv4, &HashSet::new()).await.unwrap();
+        assert!(changed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_is_rolled_back_when_verification_fails() {
+        let fake = FakeRoute53::with_record_sets([rrs("host.example.com", RrType::A, 300, &["192.0.2.1"])]);
+        fake.zone.lock().unwrap().test_dns_answers = Some(vec!["192.0.2.9".to_string()]);
+
+        let verify: VerifyConfig = serde_yaml::from_str("{}").unwrap();
+        let provider = Route53Provider::new(fake.clone(), "Z1").with_verify(Some(verify));
+        let zone = zone(&["host.example.com"]);
+        let ipv4 = addrs(&["192.0.2.2"]);
+        let e = update_zone_with(&provider, &Config::default(), &zone, &ipv4, &HashSet::new()).await.unwrap_err();
+        match e.downcast_ref() {
+            Some(Route53IpUpdateError::VerificationFailed(zone_id, problems, rolled_back)) => {
+                assert_eq!(zone_id, "Z1");
+                assert_eq!(problems, &["Route 53 answers 192.0.2.9 for host.example.com A instead of 192.0.2.2"]);
+                assert!(rolled_back);
+            }
+            _ => panic!("unexpected error: {e}"),
+        }
+
+        let record_sets = fake.record_sets();
+        assert_eq!(record_sets.len(), 1);
+        assert_eq!(values(&record_sets[0]), addrs(&["192.0.2.1"]));
+        assert_eq!(fake.zone.lock().unwrap().batches.len(), 2);
+    }
+}
@@ -0,0 +1,173 @@
+use {
+    crate::{
+        config::{Config, HostnameConfig},
+        error::Route53IpUpdateError,
+        ttl::Ttl,
+        zones::{is_zone_name, normalize_domain_name},
+    },
+    log::warn,
+    std::collections::BTreeMap,
+};
+
+/// The longest domain name allowed by DNS, excluding the trailing dot.
+const MAX_DOMAIN_NAME_LEN: usize = 253;
+
+/// The longest label allowed by DNS.
+const MAX_LABEL_LEN: usize = 63;
+
+/// The domain substituted for `{zone}` when checking hostnames in zones whose names aren't known yet.
+const PLACEHOLDER_ZONE: &str = "zone.example";
+
+/// Checks the configuration without any network access: everything checked by [Config::check], plus hostname syntax,
+/// TTL policy, and hostnames that are configured more than once.
+pub(crate) fn validate_config(config: &Config) -> Result<(), Route53IpUpdateError> {
+    let mut messages = match config.check() {
+        Ok(()) => Vec::new(),
+        Err(Route53IpUpdateError::InvalidConfig(messages)) => messages,
+        Err(e) => return Err(e),
+    };
+
+    // Where each hostname is configured, for finding duplicates.
+    let mut locations: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for hostname in &config.hostnames {
+        if hostname.get_zones().is_empty() {
+            check_hostname(config, hostname.get_hostname(), hostname, config.ttl, &mut messages);
+            locations.entry(normalize_domain_name(hostname.get_hostname())).or_default().push("hostnames".to_string());
+            continue;
+        }
+
+        // The zones' names aren't known without looking them up; only zones given by name are checked for duplicates.
+        for zone in hostname.get_zones() {
+            let zone_name = if is_zone_name(zone) {
+                zone.as_str()
+            } else {
+                PLACEHOLDER_ZONE
+            };
+            let expanded = hostname.for_zone(zone_name);
+            check_hostname(config, hostname.get_hostname(), &expanded, config.ttl, &mut messages);
+            if is_zone_name(zone) {
+                locations
+                    .entry(normalize_domain_name(expanded.get_hostname()))
+                    .or_default()
+                    .push("hostnames".to_string());
+            }
+        }
+    }
+
+    for r53_zc in &config.route53_zones {
+        for hostname in &r53_zc.hostnames {
+            // {zone} is left in the hostnames of zones configured by ID until their names are looked up.
+            let hostname = &hostname.for_zone(PLACEHOLDER_ZONE);
+            check_hostname(config, hostname.get_hostname(), hostname, r53_zc.ttl.or(config.ttl), &mut messages);
+            locations
+                .entry(normalize_domain_name(hostname.get_hostname()))
+                .or_default()
+                .push(format!("zone {}", r53_zc.zone_id));
+        }
+    }
+
+    for tagged_zones in &config.tagged_zones {
+        check_ttl_bounds(config, tagged_zones.ttl, "a tagged zone selector", &mut messages);
+        for hostname in &tagged_zones.hostnames {
+            let location = format!("hostname {}", hostname.get_hostname());
+            check_ttl_bounds(config, hostname.get_ttl(), &location, &mut messages);
+            check_ttl_bounds(config, hostname.get_ipv4_ttl(), &location, &mut messages);
+            check_ttl_bounds(config, hostname.get_ipv6_ttl(), &location, &mut messages);
+
+            // Check the syntax with a stand-in for the zone each selected hosted zone will supply.
+            let expanded = hostname.with_hostname(hostname.get_hostname().replace("{zone}", PLACEHOLDER_ZONE));
+            check_hostname(config, hostname.get_hostname(), &expanded, tagged_zones.ttl.or(config.ttl), &mut messages);
+        }
+    }
+
+    for (hostname, locations) in locations {
+        if locations.len() > 1 {
+            messages.push(format!("Hostname {hostname} is configured more than once ({}).", locations.join(", ")));
+        }
+    }
+
+    if messages.is_empty() {
+        Ok(())
+    } else {
+        Err(Route53IpUpdateError::InvalidConfig(messages))
+    }
+}
+
+/// Checks the syntax of a hostname, and warns if the pre-change TTL isn't lower than its TTLs: lowering them first is
+/// pointless then, but harmless. Messages refer to the hostname as `name`.
+fn check_hostname(
+    config: &Config,
+    name: &str,
+    hostname: &HostnameConfig,
+    default_ttl: Option<Ttl>,
+    messages: &mut Vec<String>,
+) {
+    if let Err(reason) = check_hostname_syntax(hostname.get_hostname()) {
+        messages.push(format!("Hostname {name} is invalid: {reason}."));
+        return;
+    }
+
+    if let Some(prechange_ttl) = config.prechange_ttl {
+        let ttl = hostname.get_ttl().or(default_ttl);
+        for ttl in [hostname.get_ipv4_ttl().or(ttl), hostname.get_ipv6_ttl().or(ttl)].into_iter().flatten() {
+            if prechange_ttl >= ttl {
+                warn!(
+                    "The pre-change TTL ({prechange_ttl}) is not lower than the TTL for hostname {name} ({ttl}), so \
+                     lowering it first has no effect"
+                );
+                break;
+            }
+        }
+    }
+}
+
+fn check_ttl_bounds(config: &Config, ttl: Option<Ttl>, location: &str, messages: &mut Vec<String>) {
+    if let Some(ttl) = ttl {
+        if config.min_ttl <= config.max_ttl && (ttl < config.min_ttl || ttl > config.max_ttl) {
+            messages.push(format!(
+                "TTL {ttl} for {location} is outside of the allowed range {}-{}.",
+                config.min_ttl, config.max_ttl
+            ));
+        }
+    }
+}
+
+/// Checks that a hostname is a syntactically valid DNS name. A leading `*` label is allowed for wildcard records.
+fn check_hostname_syntax(name: &str) -> Result<(), String> {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    if name.is_empty() {
+        return Err("it is empty".to_string());
+    }
+
+    if name.len() > MAX_DOMAIN_NAME_LEN {
+        return Err(format!("it is longer than {MAX_DOMAIN_NAME_LEN} characters"));
+    }
+
+    for (i, label) in name.split('.').enumerate() {
+        if label.is_empty() {
+            return Err("it contains an empty label".to_string());
+        }
+
+        if label.len() > MAX_LABEL_LEN {
+            return Err(format!("label {label} is longer than {MAX_LABEL_LEN} characters"));
+        }
+
+        if label == "*" {
+            if i == 0 {
+                continue;
+            }
+            return Err("a wildcard may only be the first label".to_string());
+        }
+
+        if label.starts_with('-') || label.ends_with('-') {
+            return Err(format!("label {label} starts or ends with a hyphen"));
+        }
+
+        if let Some(c) = label.chars().find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_')) {
+            return Err(format!("label {label} contains the character {c:?}"));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use {
+    crate::{config::VerifyConfig, diff::RecordSetDiff, route53_api::Route53Api},
+    aws_sdk_route53::model::RrType,
+    std::{
+        net::{IpAddr, SocketAddr},
+        time::Duration,
+    },
+    tokio::{net::TcpStream, time::timeout},
+    tower::BoxError,
+};
+
+/// Checks that changes to a zone took effect once they've propagated: that Route 53's name servers answer with the new
+/// addresses, and that each new address accepts connections on the probe port. Returns a description of each problem
+/// found; an error means the checks themselves couldn't be made.
+pub(crate) async fn verify_changes<R: Route53Api>(
+    route53: &R,
+    zone_id: &str,
+    verify: &VerifyConfig,
+    diffs: &[RecordSetDiff],
+) -> Result<Vec<String>, BoxError> {
+    let mut problems = Vec::new();
+    for diff in diffs {
+        let Some(new) = diff.new.as_ref().filter(|new| !new.values.is_empty()) else {
+            continue;
+        };
+
+        let rr_type = match diff.record_type.as_str() {
+            "A" => RrType::A,
+            "AAAA" => RrType::Aaaa,
+            _ => continue,
+        };
+
+        // TestDNSAnswer picks one of a name's weighted record sets, so it can't be expected to answer with this one.
+        if verify.test_dns_answer && new.weight.is_none() {
+            let mut answers = route53.test_dns_answer(zone_id, &diff.hostname, rr_type).await?;
+            answers.sort();
+            if answers != new.values {
+                problems.push(format!(
+                    "Route 53 answers {} for {} {} instead of {}",
+                    if answers.is_empty() {
+                        "nothing".to_string()
+                    } else {
+                        answers.join(", ")
+                    },
+                    diff.hostname,
+                    diff.record_type,
+                    new.values.join(", ")
+                ));
+            }
+        }
+
+        if let Some(port) = verify.probe_port {
+            for addr in new.values.iter().filter_map(|value| value.parse::<IpAddr>().ok()) {
+                if let Err(e) = probe(SocketAddr::new(addr, port), verify.probe_timeout).await {
+                    problems.push(format!("{addr} ({}) is unreachable on TCP port {port}: {e}", diff.hostname));
+                }
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Connects to an address, failing if the connection isn't accepted within the timeout.
+async fn probe(addr: SocketAddr, probe_timeout: Duration) -> Result<(), BoxError> {
+    match timeout(probe_timeout, TcpStream::connect(addr)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(e.into()),
+        Err(_) => Err(format!("no connection within {}", humantime::format_duration(probe_timeout)).into()),
+    }
+}
@@ -0,0 +1,129 @@
+use {
+    crate::config::{Config, TimeWindow},
+    chrono::{Datelike, Duration as ChronoDuration, Local, NaiveDateTime, NaiveTime, Weekday},
+    std::future::pending,
+    tokio::time::sleep,
+};
+
+impl TimeWindow {
+    /// Returns a description of each problem with the window.
+    pub(crate) fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for time in [&self.start, &self.end] {
+            if parse_time(time).is_none() {
+                problems.push(format!("{time:?} is not a time of day; use HH:MM, e.g. 09:00"));
+            }
+        }
+
+        for day in &self.days {
+            if day.parse::<Weekday>().is_err() {
+                problems.push(format!("{day:?} is not a day of the week; use e.g. mon or monday"));
+            }
+        }
+
+        problems
+    }
+
+    /// Indicates whether the window includes the given local time. A window whose end is before its start runs past
+    /// midnight, and its days are the days it starts on.
+    fn contains(&self, at: NaiveDateTime) -> bool {
+        let (Some(start), Some(end)) = (parse_time(&self.start), parse_time(&self.end)) else {
+            return false;
+        };
+
+        let time = at.time();
+        if start <= end {
+            self.on_day(at.weekday()) && start <= time && time < end
+        } else {
+            (self.on_day(at.weekday()) && start <= time) || (self.on_day(at.weekday().pred()) && time < end)
+        }
+    }
+
+    /// Indicates whether the window starts on the given day of the week. A window without days starts every day.
+    fn on_day(&self, weekday: Weekday) -> bool {
+        self.days.is_empty() || self.days.iter().any(|day| day.parse::<Weekday>() == Ok(weekday))
+    }
+}
+
+/// Parses a time of day given as HH:MM.
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M").ok()
+}
+
+/// Indicates whether changes must be held at the given local time: during a blackout window, or outside every change
+/// window if any are configured.
+pub(crate) fn changes_held_at(config: &Config, at: NaiveDateTime) -> bool {
+    config.blackout_windows.iter().any(|window| window.contains(at))
+        || (!config.change_windows.is_empty() && !config.change_windows.iter().any(|window| window.contains(at)))
+}
+
+/// Indicates whether changes must be held now.
+pub(crate) fn changes_held(config: &Config) -> bool {
+    changes_held_at(config, Local::now().naive_local())
+}
+
+/// Returns the next local time after `at` when changes are no longer held: when a change window starts or a blackout
+/// window ends. Returns `None` if that doesn't happen within a week.
+pub(crate) fn next_opening(config: &Config, at: NaiveDateTime) -> Option<NaiveDateTime> {
+    let times: Vec<NaiveTime> = config
+        .change_windows
+        .iter()
+        .map(|window| &window.start)
+        .chain(config.blackout_windows.iter().map(|window| &window.end))
+        .filter_map(|time| parse_time(time))
+        .collect();
+
+    let mut openings: Vec<NaiveDateTime> = (0..=7)
+        .flat_map(|days| times.iter().map(move |time| (at.date() + ChronoDuration::days(days)).and_time(*time)))
+        .filter(|opening| *opening > at && !changes_held_at(config, *opening))
+        .collect();
+    openings.sort();
+    openings.into_iter().next()
+}
+
+/// Waits until changes are no longer held, if any are waiting to be made. Otherwise, or if changes stay held for more
+/// than a week, this never returns.
+pub(crate) async fn window_opened(config: &Config, changes_waiting: bool) {
+    let now = Local::now().naive_local();
+    match next_opening(config, now).filter(|_| changes_waiting) {
+        Some(opening) => sleep((opening - now).to_std().unwrap_or_default()).await,
+        None => pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{changes_held_at, next_opening},
+        crate::config::Config,
+        chrono::NaiveDateTime,
+    };
+
+    fn at(time: &str) -> NaiveDateTime {
+        // 2026-01-05 is a Monday.
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn holds_changes_outside_change_windows_and_during_blackouts() {
+        let config: Config = serde_yaml::from_str(
+            "
+change-windows:
+  - {start: '22:00', end: '06:00'}
+blackout-windows:
+  - {days: [sat, sun], start: '21:00', end: '23:30'}
+",
+        )
+        .unwrap();
+
+        assert!(changes_held_at(&config, at("2026-01-05 12:00")));
+        assert!(!changes_held_at(&config, at("2026-01-05 23:00")));
+        assert!(!changes_held_at(&config, at("2026-01-06 02:00")));
+        assert!(changes_held_at(&config, at("2026-01-10 23:00")));
+        assert!(!changes_held_at(&config, at("2026-01-11 01:00")));
+
+        assert_eq!(next_opening(&config, at("2026-01-05 12:00")), Some(at("2026-01-05 22:00")));
+        // On Saturdays, the change window opens when the blackout ends rather than at 22:00.
+        assert_eq!(next_opening(&config, at("2026-01-10 12:00")), Some(at("2026-01-10 23:30")));
+    }
+}
@@ -0,0 +1,627 @@
+use {
+    crate::{
+        aws::Route53Clients,
+        config::{Config, HostnameConfig, Route53ZoneConfig},
+        error::{ClassifiedError, Route53IpUpdateError},
+        rate_limit::ROUTE53_RATE_LIMITER,
+        route53_api::list_zone_record_sets,
+    },
+    aws_sdk_route53::{
+        model::{HostedZone, ResourceRecordSet, RrType, TagResourceType},
+        types::SdkError,
+        Client as Route53Client,
+    },
+    log::{debug, info},
+    std::collections::HashMap,
+    tower::BoxError,
+};
+
+/// The prefix Route 53 puts in front of hosted zone IDs in API replies.
+const HOSTED_ZONE_ID_PREFIX: &str = "/hostedzone/";
+
+/// The maximum number of resources that can be passed to a single ListTagsForResources call.
+const MAX_TAG_RESOURCE_IDS: usize = 10;
+
+/// Indicates whether the zone specified in the configuration is a domain name (e.g. `example.com.`) rather than a
+/// hosted zone ID. Hosted zone IDs never contain dots.
+pub(crate) fn is_zone_name(zone: &str) -> bool {
+    zone.contains('.')
+}
+
+/// Returns the domain name in the form Route 53 uses: lowercase and with a trailing dot.
+pub(crate) fn normalize_domain_name(name: &str) -> String {
+    let name = name.to_ascii_lowercase();
+    if name.ends_with('.') {
+        name
+    } else {
+        format!("{name}.")
+    }
+}
+
+/// Indicates whether a hostname is the zone's apex or a subdomain of it.
+pub(crate) fn is_hostname_in_zone(hostname: &str, zone_name: &str) -> bool {
+    let (hostname, zone_name) = (normalize_domain_name(hostname), normalize_domain_name(zone_name));
+    hostname == zone_name || hostname.ends_with(&format!(".{zone_name}"))
+}
+
+/// Returns the hosted zone ID without the `/hostedzone/` prefix.
+pub(crate) fn strip_hosted_zone_id_prefix(id: &str) -> &str {
+    id.strip_prefix(HOSTED_ZONE_ID_PREFIX).unwrap_or(id)
+}
+
+/// Replaces any enabled zones (and mirrors) configured by domain name with their hosted zone IDs.
+pub(crate) async fn resolve_zone_ids(clients: &mut Route53Clients, config: &mut Config) -> Result<(), BoxError> {
+    for r53_zc in config.route53_zones.iter_mut().filter(|zone| zone.enabled) {
+        if let Some((zone_id, zone_name)) = resolve_zone_id(clients, r53_zc).await? {
+            r53_zc.zone_id = zone_id;
+            r53_zc.zone_name = Some(zone_name);
+        }
+
+        for i in 0..r53_zc.mirrors.len() {
+            let mirror_zone = r53_zc.mirror_zone(&r53_zc.mirrors[i]);
+            if let Some((zone_id, zone_name)) = resolve_zone_id(clients, &mirror_zone).await? {
+                r53_zc.mirrors[i].zone_id = zone_id;
+                r53_zc.mirrors[i].zone_name = Some(zone_name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the zone ID and normalized name of a zone configured by domain name, or `None` if it was configured by ID.
+async fn resolve_zone_id(
+    clients: &mut Route53Clients,
+    r53_zc: &Route53ZoneConfig,
+) -> Result<Option<(String, String)>, BoxError> {
+    if !is_zone_name(&r53_zc.zone_id) {
+        return Ok(None);
+    }
+
+    let zone_name = normalize_domain_name(&r53_zc.zone_id);
+    if !r53_zc.is_route53() {
+        // The zone isn't on Route 53; its ID is already its name.
+        return Ok(Some((r53_zc.zone_id.clone(), zone_name)));
+    }
+
+    let route53 = clients.for_zone(r53_zc).await?;
+    let zone_id = find_zone_id_by_name(&route53, &zone_name, r53_zc.private_zone).await?;
+    debug!("Resolved Route 53 zone {zone_name} to hosted zone ID {zone_id}");
+    Ok(Some((zone_id, zone_name)))
+}
+
+/// Checks that each Route 53 zone (and mirror) can be accessed, logging what it is and learning the names of zones
+/// configured by hosted zone ID, then checks that every hostname is in the zone it's published to. This catches
+/// permission problems before any records are listed or changed, the zones [`Config::check`] couldn't check because
+/// their names weren't known yet, and hostnames added to zones since.
+pub(crate) async fn check_hosted_zones(clients: &mut Route53Clients, config: &mut Config) -> Result<(), BoxError> {
+    let mut messages = Vec::new();
+    for r53_zc in config.route53_zones.iter_mut().filter(|zone| zone.enabled) {
+        if r53_zc.is_route53() {
+            r53_zc.zone_name = Some(get_zone_name(clients, r53_zc).await?);
+            r53_zc.expand_zone_placeholder();
+        }
+
+        messages.extend(r53_zc.check_hostnames_in_zone());
+
+        for i in 0..r53_zc.mirrors.len() {
+            let mirror_zone = r53_zc.mirror_zone(&r53_zc.mirrors[i]);
+            if mirror_zone.is_route53() {
+                r53_zc.mirrors[i].zone_name = Some(get_zone_name(clients, &mirror_zone).await?);
+            }
+
+            messages.extend(r53_zc.mirror_zone(&r53_zc.mirrors[i]).check_hostnames_in_zone());
+        }
+    }
+
+    if messages.is_empty() {
+        Ok(())
+    } else {
+        Err(Route53IpUpdateError::InvalidConfig(messages).into())
+    }
+}
+
+/// Gets a Route 53 hosted zone, logging its name, whether it's private, and how many record sets it has. Returns its
+/// normalized name.
+async fn get_zone_name(clients: &mut Route53Clients, r53_zc: &Route53ZoneConfig) -> Result<String, BoxError> {
+    let route53 = clients.for_zone(r53_zc).await?;
+    ROUTE53_RATE_LIMITER.acquire().await;
+    let result = match route53.get_hosted_zone().id(&r53_zc.zone_id).send().await {
+        Ok(result) => result,
+        Err(SdkError::ServiceError {
+            err,
+            ..
+        }) if err.is_no_such_hosted_zone() => {
+            let error = Route53IpUpdateError::NoSuchHostedZone(r53_zc.zone_id.clone(), Vec::new()).into();
+            return Err(suggest_hosted_zones(&route53, r53_zc, error).await);
+        }
+        Err(e) => {
+            let context = format!("Unable to get hosted zone {}", r53_zc.zone_id);
+            return Err(ClassifiedError::from_sdk(e).context(context).into());
+        }
+    };
+
+    let hosted_zone = result
+        .hosted_zone()
+        .ok_or_else(|| Route53IpUpdateError::MissingExpectedAwsReplyField("HostedZone".to_string()))?;
+    let zone_name = hosted_zone
+        .name()
+        .map(normalize_domain_name)
+        .ok_or_else(|| Route53IpUpdateError::MissingExpectedAwsReplyField("HostedZone.Name".to_string()))?;
+    let visibility = if is_private_zone(hosted_zone) {
+        "private"
+    } else {
+        "public"
+    };
+
+    match hosted_zone.resource_record_set_count() {
+        Some(count) => {
+            info!("Hosted zone {} is {zone_name}, a {visibility} zone with {count} record sets", r53_zc.zone_id)
+        }
+        None => info!("Hosted zone {} is {zone_name}, a {visibility} zone", r53_zc.zone_id),
+    }
+
+    Ok(zone_name)
+}
+
+/// Finds the ID of the hosted zone with the given (normalized) name. If `private_zone` is specified, only private
+/// (`true`) or public (`false`) zones are considered.
+async fn find_zone_id_by_name(
+    route53: &Route53Client,
+    zone_name: &str,
+    private_zone: Option<bool>,
+) -> Result<String, BoxError> {
+    let mut candidates = Vec::new();
+    let mut next_zone_id: Option<String> = None;
+
+    'pages: loop {
+        let query = route53.list_hosted_zones_by_name().dns_name(zone_name).set_hosted_zone_id(next_zone_id.clone());
+        ROUTE53_RATE_LIMITER.acquire().await;
+        let query_results = query.send().await.map_err(ClassifiedError::from_sdk)?;
+
+        for hosted_zone in query_results.hosted_zones().unwrap_or_default() {
+            if hosted_zone.name().map(normalize_domain_name).as_deref() != Some(zone_name) {
+                // Zones are returned in name order, so we've passed all zones with this name.
+                break 'pages;
+            }
+
+            if private_zone.is_none() || private_zone == Some(is_private_zone(hosted_zone)) {
+                candidates.push(hosted_zone.clone());
+            }
+        }
+
+        if !query_results.is_truncated()
+            || query_results.next_dns_name().map(normalize_domain_name).as_deref() != Some(zone_name)
+        {
+            break;
+        }
+
+        next_zone_id = query_results.next_hosted_zone_id().map(ToString::to_string);
+    }
+
+    let mut ids: Vec<String> =
+        candidates.iter().filter_map(|hz| hz.id()).map(|id| strip_hosted_zone_id_prefix(id).to_string()).collect();
+
+    match ids.len() {
+        0 => Err(Route53IpUpdateError::ZoneNotFound(zone_name.to_string()).into()),
+        1 => Ok(ids.remove(0)),
+        _ => Err(Route53IpUpdateError::AmbiguousZoneName(zone_name.to_string(), ids).into()),
+    }
+}
+
+/// If the error is for a hosted zone that doesn't exist, lists the hosted zones the credentials can see and adds the
+/// ones that could hold the zone's hostnames to the error as suggestions. Other errors are returned as they are.
+pub(crate) async fn suggest_hosted_zones(
+    route53: &Route53Client,
+    r53_zc: &Route53ZoneConfig,
+    error: BoxError,
+) -> BoxError {
+    let Some(Route53IpUpdateError::NoSuchHostedZone(zone_id, _)) = error.downcast_ref() else {
+        return error;
+    };
+
+    match list_all_hosted_zones(route53).await {
+        Ok(hosted_zones) => {
+            let hostnames = r53_zc.hostnames.iter().flat_map(|hc| hc.get_hostnames());
+            Route53IpUpdateError::NoSuchHostedZone(
+                zone_id.clone(),
+                hosted_zones_for_hostnames(&hosted_zones, hostnames),
+            )
+            .into()
+        }
+        Err(e) => {
+            debug!("Unable to list hosted zones to suggest replacements for {zone_id}: {e}");
+            error
+        }
+    }
+}
+
+/// Returns the hosted zones (as `ID (name)`) that are the best match for any of the hostnames.
+fn hosted_zones_for_hostnames<'a>(
+    hosted_zones: &[HostedZone],
+    hostnames: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    let mut suggestions = Vec::new();
+    for hostname in hostnames {
+        let Some(hosted_zone) = find_best_zone_for_hostname(hosted_zones, hostname) else {
+            continue;
+        };
+
+        let zone_id = strip_hosted_zone_id_prefix(hosted_zone.id().unwrap_or_default());
+        let suggestion = format!("{zone_id} ({})", hosted_zone.name().unwrap_or_default());
+        if !suggestions.contains(&suggestion) {
+            suggestions.push(suggestion);
+        }
+    }
+
+    suggestions
+}
+
+/// Indicates whether the hosted zone is a private zone.
+pub(crate) fn is_private_zone(hosted_zone: &HostedZone) -> bool {
+    hosted_zone.config().map(|c| c.private_zone()).unwrap_or(false)
+}
+
+/// Lists every hosted zone visible to the credentials.
+pub(crate) async fn list_all_hosted_zones(route53: &Route53Client) -> Result<Vec<HostedZone>, BoxError> {
+    let mut results = Vec::new();
+    let mut marker: Option<String> = None;
+
+    loop {
+        ROUTE53_RATE_LIMITER.acquire().await;
+        let query_results =
+            route53.list_hosted_zones().set_marker(marker.clone()).send().await.map_err(ClassifiedError::from_sdk)?;
+        results.extend(query_results.hosted_zones().unwrap_or_default().iter().cloned());
+
+        if !query_results.is_truncated() {
+            return Ok(results);
+        }
+
+        marker = query_results.next_marker().map(ToString::to_string);
+    }
+}
+
+/// Finds the hosted zone whose name is the longest suffix of the hostname. Public zones are preferred over private
+/// zones with the same name.
+pub(crate) fn find_best_zone_for_hostname<'a>(
+    hosted_zones: &'a [HostedZone],
+    hostname: &str,
+) -> Option<&'a HostedZone> {
+    let hostname = normalize_domain_name(hostname);
+    let mut best: Option<(&HostedZone, usize, bool)> = None;
+
+    for hosted_zone in hosted_zones {
+        let Some(zone_name) = hosted_zone.name().map(normalize_domain_name) else {
+            continue;
+        };
+
+        if hostname != zone_name && !hostname.ends_with(&format!(".{zone_name}")) {
+            continue;
+        }
+
+        let private = is_private_zone(hosted_zone);
+        let better = match best {
+            None => true,
+            Some((_, best_len, best_private)) => {
+                zone_name.len() > best_len || (zone_name.len() == best_len && best_private && !private)
+            }
+        };
+
+        if better {
+            best = Some((hosted_zone, zone_name.len(), private));
+        }
+    }
+
+    best.map(|(hosted_zone, _, _)| hosted_zone)
+}
+
+/// Moves the top-level hostnames into the zone configurations of their best-matching hosted zones.
+pub(crate) async fn assign_hostnames_to_zones(route53: &Route53Client, config: &mut Config) -> Result<(), BoxError> {
+    if config.hostnames.is_empty() {
+        return Ok(());
+    }
+
+    // List the zones once and match every hostname against this list, rather than querying per hostname. This isn't
+    // needed if every hostname lists zones that are already configured.
+    let needs_listing = config.hostnames.iter().any(|hostname_config| {
+        hostname_config.get_zones().is_empty()
+            || hostname_config.get_zones().iter().any(|zone| find_listed_zone(config, &[], zone).is_err())
+    });
+    let hosted_zones = if needs_listing {
+        list_all_hosted_zones(route53).await?
+    } else {
+        Vec::new()
+    };
+
+    for hostname_config in std::mem::take(&mut config.hostnames) {
+        if !hostname_config.get_zones().is_empty() {
+            for zone in hostname_config.get_zones() {
+                let (zone_id, zone_name) = find_listed_zone(config, &hosted_zones, zone)?;
+                let zone_hostname_config = hostname_config.for_zone(&zone_name);
+                info!("Using zone {zone_id} ({zone_name}) for hostname {}", zone_hostname_config.get_hostname());
+
+                let r53_zc = config.get_or_create_zone_config(&zone_id);
+                r53_zc.zone_name = Some(zone_name);
+                r53_zc.add_hostname_config(zone_hostname_config);
+            }
+
+            continue;
+        }
+
+        let hostname = normalize_domain_name(hostname_config.get_hostname());
+        let Some(hosted_zone) = find_best_zone_for_hostname(&hosted_zones, &hostname) else {
+            return Err(Route53IpUpdateError::NoZoneForHostname(hostname).into());
+        };
+
+        let zone_id = strip_hosted_zone_id_prefix(hosted_zone.id().unwrap_or_default()).to_string();
+        let zone_name = hosted_zone.name().map(normalize_domain_name).unwrap_or_default();
+
+        info!("Using hosted zone {zone_id} ({zone_name}) for hostname {}", hostname_config.get_hostname());
+        let r53_zc = config.get_or_create_zone_config(&zone_id);
+        r53_zc.zone_name = Some(zone_name);
+        r53_zc.add_hostname_config(hostname_config);
+    }
+
+    Ok(())
+}
+
+/// Returns the ID and normalized name of a zone listed in a hostname's `zones`, given by hosted zone ID or domain name.
+/// Configured zones are matched first, so a hostname can also be published to zones hosted elsewhere than Route 53.
+fn find_listed_zone(
+    config: &Config,
+    hosted_zones: &[HostedZone],
+    zone: &str,
+) -> Result<(String, String), Route53IpUpdateError> {
+    let by_name = is_zone_name(zone).then(|| normalize_domain_name(zone));
+    for r53_zc in &config.route53_zones {
+        let Some(zone_name) = r53_zc.known_zone_name().map(normalize_domain_name) else {
+            continue;
+        };
+
+        if r53_zc.zone_id == zone || by_name.as_ref() == Some(&zone_name) {
+            return Ok((r53_zc.zone_id.clone(), zone_name));
+        }
+    }
+
+    let hosted_zone = match &by_name {
+        // Of a public and private zone with the same name, the public one is preferred.
+        Some(zone_name) => find_best_zone_for_hostname(hosted_zones, zone_name)
+            .filter(|hz| hz.name().map(normalize_domain_name).as_ref() == Some(zone_name)),
+        None => hosted_zones.iter().find(|hz| hz.id().map(strip_hosted_zone_id_prefix) == Some(zone)),
+    };
+
+    match (hosted_zone, by_name) {
+        (Some(hosted_zone), _) => Ok((
+            strip_hosted_zone_id_prefix(hosted_zone.id().unwrap_or_default()).to_string(),
+            hosted_zone.name().map(normalize_domain_name).unwrap_or_default(),
+        )),
+        (None, Some(zone_name)) => Err(Route53IpUpdateError::ZoneNotFound(zone_name)),
+        (None, None) => Err(Route53IpUpdateError::NoSuchHostedZone(zone.to_string(), Vec::new())),
+    }
+}
+
+/// Adds the hostnames from each tagged zone selector to every hosted zone carrying its tags.
+pub(crate) async fn assign_tagged_zones(route53: &Route53Client, config: &mut Config) -> Result<(), BoxError> {
+    if config.tagged_zones.is_empty() {
+        return Ok(());
+    }
+
+    let hosted_zones = list_all_hosted_zones(route53).await?;
+    let zone_ids: Vec<&str> = hosted_zones.iter().filter_map(|hz| hz.id()).map(strip_hosted_zone_id_prefix).collect();
+    let mut zone_tags: HashMap<String, HashMap<String, String>> = HashMap::new();
+
+    for chunk in zone_ids.chunks(MAX_TAG_RESOURCE_IDS) {
+        ROUTE53_RATE_LIMITER.acquire().await;
+        let query_results = route53
+            .list_tags_for_resources()
+            .resource_type(TagResourceType::Hostedzone)
+            .set_resource_ids(Some(chunk.iter().map(ToString::to_string).collect()))
+            .send()
+            .await
+            .map_err(ClassifiedError::from_sdk)?;
+
+        for tag_set in query_results.resource_tag_sets().unwrap_or_default() {
+            let Some(zone_id) = tag_set.resource_id() else {
+                continue;
+            };
+
+            let tags = tag_set
+                .tags()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|tag| Some((tag.key()?.to_string(), tag.value().unwrap_or_default().to_string())))
+                .collect();
+            zone_tags.insert(zone_id.to_string(), tags);
+        }
+    }
+
+    for tagged_zones in config.tagged_zones.clone() {
+        for hosted_zone in &hosted_zones {
+            let zone_id = strip_hosted_zone_id_prefix(hosted_zone.id().unwrap_or_default());
+            let Some(tags) = zone_tags.get(zone_id) else {
+                continue;
+            };
+
+            if !tagged_zones.tags.iter().all(|(key, value)| tags.get(key) == Some(value)) {
+                continue;
+            }
+
+            let zone_name = hosted_zone.name().map(normalize_domain_name).unwrap_or_default();
+            info!("Hosted zone {zone_id} ({zone_name}) matches tags {:?}", tagged_zones.tags);
+
+            let r53_zc = config.get_or_create_zone_config(zone_id);
+            r53_zc.zone_name = Some(zone_name.clone());
+            if tagged_zones.ttl.is_some() {
+                r53_zc.ttl = tagged_zones.ttl;
+            }
+
+            for hostname_config in &tagged_zones.hostnames {
+                let hostname = hostname_config.get_hostname().replace("{zone}", zone_name.trim_end_matches('.'));
+                r53_zc.add_hostname_config(hostname_config.with_hostname(hostname));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Adds the existing A and AAAA records in the zone matching its `manage-matching` pattern, if it has one, to its
+/// hostnames.
+pub(crate) async fn adopt_matching_records(
+    clients: &mut Route53Clients,
+    zone: &mut Route53ZoneConfig,
+) -> Result<(), BoxError> {
+    let Some(pattern) = zone.manage_matching.clone() else {
+        return Ok(());
+    };
+
+    let route53 = clients.for_zone(zone).await?;
+    let record_sets = list_zone_record_sets(&route53, &zone.zone_id).await?;
+    for hostname in matching_hostnames(&pattern, &record_sets) {
+        if !zone.hostnames.iter().any(|h| normalize_domain_name(h.get_hostname()) == normalize_domain_name(&hostname)) {
+            debug!("Managing existing record {hostname} in zone {}; it matches {pattern}", zone.zone_id);
+            zone.hostnames.push(HostnameConfig::HostnameOnly(hostname));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the names of the A and AAAA records matching the pattern, without their trailing dots.
+///
+/// Records with a routing policy (a set identifier) are left out, since there's no telling which updater they belong
+/// to, as are names Route 53 has escaped, such as wildcard records.
+fn matching_hostnames(pattern: &str, record_sets: &[ResourceRecordSet]) -> Vec<String> {
+    let mut hostnames: Vec<String> = record_sets
+        .iter()
+        .filter(|rrs| matches!(rrs.r#type(), Some(RrType::A | RrType::Aaaa)) && rrs.set_identifier().is_none())
+        .filter_map(|rrs| rrs.name())
+        .filter(|name| !name.contains('\\') && matches_pattern(pattern, name))
+        .map(|name| name.trim_end_matches('.').to_string())
+        .collect();
+    hostnames.dedup();
+    hostnames
+}
+
+/// Indicates whether a domain name matches a pattern in which `*` matches any run of characters, ignoring case and
+/// trailing dots.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (normalize_domain_name(pattern), normalize_domain_name(name));
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = name.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            find_listed_zone, hosted_zones_for_hostnames, is_hostname_in_zone, matches_pattern, matching_hostnames,
+        },
+        crate::config::{Config, HostnameConfig, Route53ZoneConfig},
+        aws_sdk_route53::model::{HostedZone, ResourceRecordSet, RrType},
+    };
+
+    fn hosted_zone(id: &str, name: &str) -> HostedZone {
+        HostedZone::builder().id(format!("/hostedzone/{id}")).name(name).build()
+    }
+
+    #[test]
+    fn finds_zones_listed_by_hostnames() {
+        let config: Config = serde_yaml::from_str(
+            "route53-zones:
+  - zone-id: example.org.
+    cloudflare: {api-token: secret}
+    hostnames: [gw.example.org]",
+        )
+        .unwrap();
+        let hosted_zones = [hosted_zone("Z1", "example.com."), hosted_zone("Z2", "example.net.")];
+
+        let found = |zone| find_listed_zone(&config, &hosted_zones, zone).map_err(|e| e.to_string());
+        assert_eq!(found("example.org"), Ok(("example.org.".to_string(), "example.org.".to_string())));
+        assert_eq!(found("example.net"), Ok(("Z2".to_string(), "example.net.".to_string())));
+        assert_eq!(found("Z1"), Ok(("Z1".to_string(), "example.com.".to_string())));
+        assert!(found("example.edu").is_err());
+        assert!(found("Z3").is_err());
+
+        let hostname: HostnameConfig =
+            serde_yaml::from_str(r#"{hostname: "home.{zone}", ipv6-hostname: "v6.{zone}", zones: [Z1, Z2]}"#).unwrap();
+        let for_zone = hostname.for_zone("example.net.");
+        assert_eq!(for_zone.get_hostnames().collect::<Vec<_>>(), ["home.example.net", "v6.example.net"]);
+        assert!(for_zone.get_zones().is_empty());
+    }
+
+    #[test]
+    fn suggests_zones_containing_hostnames() {
+        let hosted_zones = [
+            hosted_zone("Z1", "example.com."),
+            hosted_zone("Z2", "home.example.com."),
+            hosted_zone("Z3", "example.org."),
+        ];
+
+        let suggestions = hosted_zones_for_hostnames(
+            &hosted_zones,
+            ["gw.home.example.com", "nas.home.example.com", "www.example.com"],
+        );
+        assert_eq!(suggestions, ["Z2 (home.example.com.)", "Z1 (example.com.)"]);
+
+        assert!(hosted_zones_for_hostnames(&hosted_zones, ["example.net"]).is_empty());
+    }
+
+    #[test]
+    fn checks_hostnames_are_in_their_zone() {
+        assert!(is_hostname_in_zone("Home.Example.com", "example.com."));
+        assert!(is_hostname_in_zone("example.com.", "example.com"));
+        assert!(!is_hostname_in_zone("badexample.com", "example.com."));
+
+        let zone: Route53ZoneConfig = serde_yaml::from_str(
+            "zone-id: example.com
+hostnames:
+  - home.example.com
+  - hostname: dual.example.com
+    ipv6-hostname: v6.example.org",
+        )
+        .unwrap();
+        assert_eq!(zone.check_hostnames_in_zone(), ["Zone example.com. does not contain hostname v6.example.org."]);
+    }
+
+    #[test]
+    fn adopts_records_matching_patterns() {
+        assert!(matches_pattern("*.dyn.example.com", "home.dyn.example.com."));
+        assert!(matches_pattern("*.DYN.example.com.", "a.b.dyn.example.com"));
+        assert!(matches_pattern("host-*.example.com", "host-1.example.com."));
+        assert!(!matches_pattern("*.dyn.example.com", "dyn.example.com."));
+        assert!(!matches_pattern("*.dyn.example.com", "home.example.com."));
+        assert!(!matches_pattern("a*a.example.com", "a.example.com."));
+
+        let record = |name: &str, rr_type: RrType| ResourceRecordSet::builder().name(name).r#type(rr_type).build();
+        let record_sets = [
+            record("dyn.example.com.", RrType::A),
+            record("home.dyn.example.com.", RrType::A),
+            record("home.dyn.example.com.", RrType::Aaaa),
+            record("mail.dyn.example.com.", RrType::Mx),
+            record("\\052.dyn.example.com.", RrType::A),
+            ResourceRecordSet::builder().name("lb.dyn.example.com.").r#type(RrType::A).set_identifier("east").build(),
+            record("vpn.dyn.example.com.", RrType::Aaaa),
+        ];
+        assert_eq!(
+            matching_hostnames("*.dyn.example.com", &record_sets),
+            ["home.dyn.example.com", "vpn.dyn.example.com"]
+        );
+    }
+}
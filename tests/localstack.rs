@@ -0,0 +1,177 @@
+//! Integration tests that run route53-ip-update against LocalStack's Route 53 emulation.
+//!
+//! These are skipped unless `LOCALSTACK_ENDPOINT` is set, e.g.:
+//!
+//! ```sh
+//! docker run --rm -d -p 4566:4566 localstack/localstack
+//! LOCALSTACK_ENDPOINT=http://localhost:4566 cargo test --test localstack
+//! ```
+
+use {
+    aws_sdk_route53::{
+        model::{Change, ChangeAction, ChangeBatch, RrType},
+        Client, Config, Credentials, Endpoint, Region,
+    },
+    hyper::Uri,
+    std::{
+        env,
+        io::{Read, Write},
+        net::TcpListener,
+        process::Command,
+        thread,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+const REGION: &str = "us-east-1";
+
+/// Returns the LocalStack endpoint, or `None` if these tests should be skipped.
+fn localstack_endpoint() -> Option<String> {
+    match env::var("LOCALSTACK_ENDPOINT") {
+        Ok(endpoint) if !endpoint.is_empty() => Some(endpoint),
+        _ => {
+            eprintln!("LOCALSTACK_ENDPOINT is not set; skipping LocalStack tests");
+            None
+        }
+    }
+}
+
+fn route53_client(endpoint: &str) -> Client {
+    let uri: Uri = endpoint.parse().expect("LOCALSTACK_ENDPOINT must be a valid URL");
+    let config = Config::builder()
+        .region(Region::new(REGION))
+        .credentials_provider(Credentials::new("test", "test", None, None, "localstack"))
+        .endpoint_resolver(Endpoint::immutable(uri))
+        .build();
+    Client::from_conf(config)
+}
+
+/// Runs a minimal IP service on a local port that always reports the given address, returning its URL.
+fn serve_address(address: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request);
+            let response =
+                format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{address}", address.len());
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    url
+}
+
+/// Runs route53-ip-update against LocalStack, asserting that it succeeds.
+fn run_update(endpoint: &str, ip_service: &str, zone_id: &str, hostname: &str) {
+    let output = Command::new(env!("CARGO_BIN_EXE_route53-ip-update"))
+        .env("AWS_ACCESS_KEY_ID", "test")
+        .env("AWS_SECRET_ACCESS_KEY", "test")
+        .env_remove("AWS_PROFILE")
+        .args(["--aws-endpoint-url", endpoint, "--region", REGION, "--address-type", "ipv4"])
+        // The test addresses are in a documentation range, which isn't globally routable.
+        .args(["--allow-nonroutable", "true"])
+        .args(["--ip-service", ip_service, "--route53-zone", zone_id, hostname])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "route53-ip-update failed: {}", String::from_utf8_lossy(&output.stderr));
+}
+
+/// Returns the values of the A record set for a hostname, sorted.
+async fn a_record_values(route53: &Client, zone_id: &str, hostname: &str) -> Vec<String> {
+    let result = route53
+        .list_resource_record_sets()
+        .hosted_zone_id(zone_id)
+        .start_record_name(hostname)
+        .start_record_type(RrType::A)
+        .send()
+        .await
+        .unwrap();
+
+    let mut values: Vec<String> = result
+        .resource_record_sets()
+        .unwrap_or_default()
+        .iter()
+        .filter(|rrs| rrs.name() == Some(&format!("{hostname}.")) && rrs.r#type() == Some(&RrType::A))
+        .flat_map(|rrs| rrs.resource_records().unwrap_or_default())
+        .filter_map(|rr| rr.value().map(String::from))
+        .collect();
+    values.sort();
+    values
+}
+
+/// Deletes a hosted zone and the record sets created in it.
+async fn delete_zone(route53: &Client, zone_id: &str) {
+    let record_sets = route53.list_resource_record_sets().hosted_zone_id(zone_id).send().await.unwrap();
+    let changes: Vec<Change> = record_sets
+        .resource_record_sets()
+        .unwrap_or_default()
+        .iter()
+        .filter(|rrs| !matches!(rrs.r#type(), Some(&RrType::Soa) | Some(&RrType::Ns)))
+        .map(|rrs| Change::builder().action(ChangeAction::Delete).resource_record_set(rrs.clone()).build())
+        .collect();
+
+    if !changes.is_empty() {
+        let batch = ChangeBatch::builder().set_changes(Some(changes)).build();
+        let _ = route53.change_resource_record_sets().hosted_zone_id(zone_id).change_batch(batch).send().await;
+    }
+
+    let _ = route53.delete_hosted_zone().id(zone_id).send().await;
+}
+
+#[tokio::test]
+async fn creates_and_updates_records() {
+    let Some(endpoint) = localstack_endpoint() else {
+        return;
+    };
+
+    let route53 = route53_client(&endpoint);
+    let nonce = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let zone_name = format!("route53-ip-update-{nonce}.test");
+    let hostname = format!("home.{zone_name}");
+
+    let created =
+        route53.create_hosted_zone().name(&zone_name).caller_reference(nonce.to_string()).send().await.unwrap();
+    let zone_id = created.hosted_zone().and_then(|hz| hz.id()).unwrap().trim_start_matches("/hostedzone/").to_string();
+
+    // Publish an address to a hostname with no records.
+    let first = serve_address("192.0.2.10");
+    run_update(&endpoint, &first, &zone_id, &hostname);
+    assert_eq!(a_record_values(&route53, &zone_id, &hostname).await, ["192.0.2.10"]);
+
+    // Replace it when the address changes.
+    let second = serve_address("192.0.2.11");
+    run_update(&endpoint, &second, &zone_id, &hostname);
+    assert_eq!(a_record_values(&route53, &zone_id, &hostname).await, ["192.0.2.11"]);
+
+    // Running again with the same address leaves the record alone.
+    run_update(&endpoint, &second, &zone_id, &hostname);
+    assert_eq!(a_record_values(&route53, &zone_id, &hostname).await, ["192.0.2.11"]);
+
+    delete_zone(&route53, &zone_id).await;
+}
+
+#[tokio::test]
+async fn resolves_zones_by_name() {
+    let Some(endpoint) = localstack_endpoint() else {
+        return;
+    };
+
+    let route53 = route53_client(&endpoint);
+    let nonce = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let zone_name = format!("route53-ip-update-byname-{nonce}.test");
+    let hostname = format!("home.{zone_name}");
+
+    let created =
+        route53.create_hosted_zone().name(&zone_name).caller_reference(nonce.to_string()).send().await.unwrap();
+    let zone_id = created.hosted_zone().and_then(|hz| hz.id()).unwrap().trim_start_matches("/hostedzone/").to_string();
+
+    let ip_service = serve_address("192.0.2.20");
+    run_update(&endpoint, &ip_service, &format!("{zone_name}."), &hostname);
+    assert_eq!(a_record_values(&route53, &zone_id, &hostname).await, ["192.0.2.20"]);
+
+    delete_zone(&route53, &zone_id).await;
+}